@@ -0,0 +1,99 @@
+//! Compares the transition selection of a wide parallel state with and without [Fsm::cache_conditions].\
+//! Run with `cargo bench --bench condition_cache --no-default-features --features ECMAScriptModel,xml`.
+//! With the default features, the feature "Debug" prints each evaluated condition.
+//!
+//! The chart consists of a parallel state with some regions. The parallel state has a conditional targetless
+//! transition for the "tick" event, that is examined for each region. Without the cache, the condition is
+//! evaluated once per region, with the cache once per event.
+//!
+//! Parsing and the setup of the datamodel are not measured, only the interpretation of the events.
+
+use std::ops::DerefMut;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rufsm::datamodel::{create_global_data_arc, Datamodel};
+use rufsm::fsm::{create_datamodel, run_blocking, Event, EventSource, Fsm};
+use rufsm::scxml_reader;
+
+const REGIONS: usize = 50;
+const EVENTS: usize = 500;
+
+fn create_fsm(cache_conditions: bool) -> Box<Fsm> {
+    let mut regions = String::new();
+    for idx in 0..REGIONS {
+        regions.push_str(format!("<state id='r{}'/>", idx).as_str());
+    }
+    let mut fsm = scxml_reader::parse_from_xml(format!(
+        r#"<scxml initial='p' datamodel='ecmascript'>
+              <datamodel><data id='limit' expr='1000000'/></datamodel>
+              <parallel id='p'>
+                <transition event='tick' cond='Math.sqrt(limit) &lt; limit'/>
+                <transition event='stop' target='end'/>
+                {}
+              </parallel>
+              <final id='end'/>
+            </scxml>"#,
+        regions
+    ))
+    .unwrap();
+    fsm.cache_conditions = cache_conditions;
+    fsm
+}
+
+fn create_events() -> Vec<Event> {
+    let mut events: Vec<Event> = (0..EVENTS).map(|_| Event::new_simple("tick")).collect();
+    events.push(Event::new_simple("stop"));
+    events
+}
+
+/// The events of one run.
+struct Events(std::vec::IntoIter<Event>);
+
+impl EventSource for Events {
+    fn next_event(&mut self) -> Option<Box<Event>> {
+        self.0.next().map(Box::new)
+    }
+}
+
+fn condition_cache(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("condition_cache ({} regions)", REGIONS));
+    // One run without the cache takes some 300ms, the minimal number of samples keeps the duration acceptable.
+    group.sample_size(10);
+    for (name, cache_conditions) in [("uncached", false), ("cached", true)] {
+        let template = create_fsm(cache_conditions);
+
+        // Check the chart once, outside of the measurement.
+        let global = run_blocking(template.instantiate(), create_events());
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["end".to_string()])
+        );
+
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    let datamodel: Box<dyn Datamodel> = create_datamodel(
+                        template.datamodel.as_str(),
+                        create_global_data_arc(),
+                        &Default::default(),
+                    );
+                    (
+                        template.instantiate(),
+                        datamodel,
+                        Events(create_events().into_iter()),
+                    )
+                },
+                |(mut fsm, mut datamodel, mut events)| {
+                    fsm.interpret_with_event_source(datamodel.deref_mut(), &mut events);
+                    // Dropped outside of the measurement.
+                    (fsm, datamodel)
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, condition_cache);
+criterion_main!(benches);
@@ -0,0 +1,152 @@
+//! Measures the core algorithm of the interpreter (transition selection, conflict resolution, exit and entry sets)
+//! on synthetic charts of different size.\
+//! Run with `cargo bench --bench selection --no-default-features --features xml`.
+//! With the default features, the feature "Debug" prints each enabled transition.
+//!
+//! The charts consist of a parallel state with some regions. Each region is a chain of nested compound states
+//! with some leaf states at the end. The null datamodel is used, so the time is spent in the interpreter and not
+//! in the evaluation of scripts. Each chart size is a benchmark group, each scenario sends one kind of event:
+//! + "tick": Targetless transitions in all regions. Mainly `selectTransitions` and `selectEventlessTransitions`.
+//! + "toggle": Transitions between the leaf states of all regions. Mainly `removeConflictingTransitions`,
+//!   `computeExitSet` and `computeEntrySet`.
+//! + "step": Transitions to states that are left again by eventless transitions. Mainly `selectEventlessTransitions`.
+//! + "reset": One transition that exits and enters the complete parallel state. Mainly `computeExitSet`
+//!   and `computeEntrySet` on large sets.
+//! + "conflict": Like "toggle", but every 7th region tries to leave the parallel state. These transitions
+//!   are preempted by the regions before them. Mainly `removeConflictingTransitions`.
+//!
+//! Parsing and the setup of the datamodel are not measured, only the interpretation of the events.
+
+use std::ops::DerefMut;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rufsm::datamodel::{create_global_data_arc, Datamodel, NULL_DATAMODEL};
+use rufsm::fsm::{create_datamodel, run_blocking, Event, EventSource, Fsm};
+use rufsm::scxml_reader;
+
+const EVENTS: usize = 200;
+
+const SCENARIOS: &[&str] = &["tick", "toggle", "step", "reset", "conflict"];
+
+fn create_fsm(regions: usize, depth: usize) -> Box<Fsm> {
+    let mut xml = String::new();
+    for r in 0..regions {
+        // Every 7th region conflicts with all other regions.
+        let (conflict_a, conflict_b) = if r % 7 == 3 {
+            ("end".to_string(), "end".to_string())
+        } else {
+            (format!("r{}_b", r), format!("r{}_a", r))
+        };
+        for d in 0..depth {
+            xml.push_str(format!("<state id='r{}_{}'>", r, d).as_str());
+        }
+        xml.push_str(
+            format!(
+                "<state id='r{r}_a'>\
+                   <transition event='tick'/>\
+                   <transition event='toggle' target='r{r}_b'/>\
+                   <transition event='step' target='r{r}_c'/>\
+                   <transition event='conflict' target='{conflict_a}'/>\
+                   <transition cond=\"In('end')\" target='r{r}_b'/>\
+                 </state>\
+                 <state id='r{r}_b'>\
+                   <transition event='tick'/>\
+                   <transition event='toggle' target='r{r}_a'/>\
+                   <transition event='step' target='r{r}_c'/>\
+                   <transition event='conflict' target='{conflict_b}'/>\
+                 </state>\
+                 <state id='r{r}_c'>\
+                   <transition target='r{r}_a'/>\
+                 </state>",
+                r = r,
+                conflict_a = conflict_a,
+                conflict_b = conflict_b
+            )
+            .as_str(),
+        );
+        for _ in 0..depth {
+            xml.push_str("</state>");
+        }
+    }
+    scxml_reader::parse_from_xml(format!(
+        r#"<scxml initial='p' datamodel='null'>
+              <parallel id='p'>
+                <transition event='reset' target='p'/>
+                <transition event='stop' target='end'/>
+                {}
+              </parallel>
+              <final id='end'/>
+            </scxml>"#,
+        xml
+    ))
+    .unwrap()
+}
+
+fn create_events(event: &str) -> Vec<Event> {
+    let mut events: Vec<Event> = (0..EVENTS).map(|_| Event::new_simple(event)).collect();
+    events.push(Event::new_simple("stop"));
+    events
+}
+
+/// The events of one run.
+struct Events(std::vec::IntoIter<Event>);
+
+impl EventSource for Events {
+    fn next_event(&mut self) -> Option<Box<Event>> {
+        self.0.next().map(Box::new)
+    }
+}
+
+fn bench_size(c: &mut Criterion, name: &str, regions: usize, depth: usize, sample_size: usize) {
+    let template = create_fsm(regions, depth);
+    let mut group = c.benchmark_group(format!("{} ({} regions, depth {})", name, regions, depth));
+    group.sample_size(sample_size);
+    for event in SCENARIOS {
+        // Check the scenario once, outside of the measurement.
+        let global = run_blocking(template.instantiate(), create_events(event));
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["end".to_string()])
+        );
+
+        group.bench_function(*event, |b| {
+            b.iter_batched(
+                || {
+                    let datamodel: Box<dyn Datamodel> = create_datamodel(
+                        NULL_DATAMODEL,
+                        create_global_data_arc(),
+                        &Default::default(),
+                    );
+                    (
+                        template.instantiate(),
+                        datamodel,
+                        Events(create_events(event).into_iter()),
+                    )
+                },
+                |(mut fsm, mut datamodel, mut events)| {
+                    fsm.interpret_with_event_source(datamodel.deref_mut(), &mut events);
+                    // Dropped outside of the measurement.
+                    (fsm, datamodel)
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn small(c: &mut Criterion) {
+    bench_size(c, "small", 2, 1, 100);
+}
+
+fn medium(c: &mut Criterion) {
+    bench_size(c, "medium", 10, 3, 50);
+}
+
+fn large(c: &mut Criterion) {
+    // One run takes some 100ms, the minimal number of samples keeps the duration acceptable.
+    bench_size(c, "large", 40, 6, 10);
+}
+
+criterion_group!(benches, small, medium, large);
+criterion_main!(benches);
@@ -5,7 +5,10 @@ extern crate core;
 
 use log::error;
 #[cfg(feature = "ECMAScriptModel")]
-use rufsm::datamodel::ecma_script::ECMA_STRICT_ARGUMENT;
+use rufsm::datamodel::ecma_script::{
+    ECMA_CLOCK_ARGUMENT, ECMA_LOOP_LIMIT_ARGUMENT, ECMA_RANDOM_SEED_ARGUMENT, ECMA_RECURSION_LIMIT_ARGUMENT,
+    ECMA_STACK_LIMIT_ARGUMENT, ECMA_STRICT_ARGUMENT,
+};
 use std::io::{stdout, Write};
 use std::sync::mpsc::Sender;
 use std::{io, process, thread, time};
@@ -110,6 +113,16 @@ async fn main_internal() {
         &INCLUDE_PATH_ARGUMENT_OPTION,
         #[cfg(feature = "ECMAScriptModel")]
         &ECMA_STRICT_ARGUMENT,
+        #[cfg(feature = "ECMAScriptModel")]
+        &ECMA_RANDOM_SEED_ARGUMENT,
+        #[cfg(feature = "ECMAScriptModel")]
+        &ECMA_CLOCK_ARGUMENT,
+        #[cfg(feature = "ECMAScriptModel")]
+        &ECMA_LOOP_LIMIT_ARGUMENT,
+        #[cfg(feature = "ECMAScriptModel")]
+        &ECMA_RECURSION_LIMIT_ARGUMENT,
+        #[cfg(feature = "ECMAScriptModel")]
+        &ECMA_STACK_LIMIT_ARGUMENT,
     ]);
 
     #[cfg(feature = "Trace")]
@@ -108,7 +108,7 @@ async fn main() {
                     ));
                 }
             }
-            "rfsm" | "scxml" | "xml" => match load_fsm(arg.as_str(), &include_paths) {
+            "rfsm" | "scxml" | "xml" | "txml" => match load_fsm(arg.as_str(), &include_paths) {
                 Ok(fsm_loaded) => {
                     fsm = Some(fsm_loaded);
                 }
@@ -0,0 +1,414 @@
+//! Compact representation of a parsed FSM, e.g. for servers that hold a large number of FSM templates.\
+//! A [CompactFsm] stores names, child lists, transitions and references to executable content in a few
+//! contiguous arenas instead of separate allocations per state and transition.
+//! The executable content itself is shared with the parsed FSM.\
+//! Create it via [Fsm::compile]. To run it, create a new instance via [CompactFsm::instantiate]:
+//! ```ignore
+//! let template = scxml_reader::parse_from_uri(uri)?.compile();
+//! ...
+//! let fsm = template.instantiate();
+//! ```
+//! The interpreter still works on [Fsm], so each instance expands the template into the usual per-state
+//! and per-transition allocations. Memory is only saved for templates that are not running; a running
+//! session needs as much memory as a parsed FSM.
+
+use std::ops::Range;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use crate::datamodel::{create_data_arc, Data, LogSink};
+use crate::fsm::{
+    normalize_event_descriptor, BindingType, DocumentId, DoneData, Event, ExecutableContentId, ExecutableContentMap,
+    Fsm, HistoryType, Invoke, InvokeId, List, SessionId, State, StateId, Transition, TransitionId, TransitionType,
+    UnhandledEventHandler,
+};
+#[cfg(feature = "Trace")]
+use crate::tracer::TraceMode;
+
+/// Range of elements inside one of the arenas.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Span {
+    start: u32,
+    len: u32,
+}
+
+impl Span {
+    fn range(&self) -> Range<usize> {
+        self.start as usize..(self.start + self.len) as usize
+    }
+}
+
+/// Appends the elements to the arena and returns the range of them.
+fn push_all<T: Clone>(arena: &mut Vec<T>, elements: &[T]) -> Span {
+    let span = Span {
+        start: arena.len() as u32,
+        len: elements.len() as u32,
+    };
+    arena.extend_from_slice(elements);
+    span
+}
+
+fn push_str(arena: &mut String, s: &str) -> Span {
+    let span = Span {
+        start: arena.len() as u32,
+        len: s.len() as u32,
+    };
+    arena.push_str(s);
+    span
+}
+
+#[derive(Debug, Clone)]
+struct CompactState {
+    doc_id: DocumentId,
+    name: Span,
+    initial: TransitionId,
+    parent: StateId,
+    is_parallel: bool,
+    is_final: bool,
+    history_type: HistoryType,
+    states: Span,
+    history: Span,
+    onentry: Span,
+    onexit: Span,
+    transitions: Span,
+    invoke: Span,
+    data: Span,
+    /// Index of the done data + 1, 0 if the state has none.
+    donedata: u32,
+}
+
+#[derive(Debug, Clone)]
+struct CompactTransition {
+    id: TransitionId,
+    doc_id: DocumentId,
+    events: Span,
+    wildcard: bool,
+    cond: Data,
+    source: StateId,
+    target: Span,
+    transition_type: TransitionType,
+    content: ExecutableContentId,
+}
+
+/// Read-only, compact form of a FSM. See the [module documentation](self).
+pub struct CompactFsm {
+    pub name: String,
+    pub datamodel: String,
+    pub binding: BindingType,
+    pub version: String,
+    pub file: Option<String>,
+    pub pseudo_root: StateId,
+    pub script: ExecutableContentId,
+    pub unhandled_event_handler: Option<UnhandledEventHandler>,
+    /// Settings of the FSM that are copied to each instance, see the fields of [Fsm] with the same names.
+    pub caller_invoke_id: Option<InvokeId>,
+    pub parent_session_id: Option<SessionId>,
+    pub record_transitions: bool,
+    pub cache_conditions: bool,
+    pub terminate_on_parallel_completion: bool,
+    pub reverse_onexit_order: bool,
+    pub log_sink: Option<Arc<dyn LogSink>>,
+    pub event_observer: Option<Sender<Event>>,
+    /// Trace mode of the tracer of the FSM, enabled for the tracer of each instance.
+    #[cfg(feature = "Trace")]
+    pub trace_mode: TraceMode,
+    executable_content: Arc<ExecutableContentMap>,
+    /// State names and event names.
+    strings: String,
+    /// States in the order of their ids.
+    states: Vec<CompactState>,
+    /// Transitions sorted by id.
+    transitions: Vec<CompactTransition>,
+    /// Child states, history states and transition targets.
+    state_ids: Vec<StateId>,
+    transition_ids: Vec<TransitionId>,
+    content_ids: Vec<ExecutableContentId>,
+    events: Vec<Span>,
+    invokes: Vec<Invoke>,
+    data: Vec<(Span, Data)>,
+    donedata: Vec<DoneData>,
+}
+
+impl CompactFsm {
+    /// Converts a parsed FSM. The runtime state of the FSM is not copied.
+    pub fn from_fsm(fsm: &Fsm) -> CompactFsm {
+        let mut compact = CompactFsm {
+            name: fsm.name.clone(),
+            datamodel: fsm.datamodel.clone(),
+            binding: fsm.binding,
+            version: fsm.version.clone(),
+            file: fsm.file.clone(),
+            pseudo_root: fsm.pseudo_root,
+            script: fsm.script,
+            unhandled_event_handler: fsm.unhandled_event_handler.clone(),
+            caller_invoke_id: fsm.caller_invoke_id.clone(),
+            parent_session_id: fsm.parent_session_id,
+            record_transitions: fsm.record_transitions,
+            cache_conditions: fsm.cache_conditions,
+            terminate_on_parallel_completion: fsm.terminate_on_parallel_completion,
+            reverse_onexit_order: fsm.reverse_onexit_order,
+            log_sink: fsm.log_sink.clone(),
+            event_observer: fsm.event_observer.clone(),
+            #[cfg(feature = "Trace")]
+            trace_mode: fsm.tracer.trace_mode(),
+            executable_content: fsm.executableContent.clone(),
+            strings: String::new(),
+            states: Vec::with_capacity(fsm.states.len()),
+            transitions: Vec::with_capacity(fsm.transitions.len()),
+            state_ids: Vec::new(),
+            transition_ids: Vec::new(),
+            content_ids: Vec::new(),
+            events: Vec::new(),
+            invokes: Vec::new(),
+            data: Vec::new(),
+            donedata: Vec::new(),
+        };
+        for state in &fsm.states {
+            let mut data: Vec<(&String, Data)> = state
+                .data
+                .iter()
+                .map(|(name, value)| (name, value.lock().unwrap().clone()))
+                .collect();
+            data.sort_by(|a, b| a.0.cmp(b.0));
+            let data_start = compact.data.len() as u32;
+            for (name, value) in data {
+                let name = push_str(&mut compact.strings, name);
+                compact.data.push((name, value));
+            }
+            let donedata = match &state.donedata {
+                None => 0,
+                Some(donedata) => {
+                    compact.donedata.push(donedata.clone());
+                    compact.donedata.len() as u32
+                }
+            };
+            let transitions: Vec<TransitionId> = state.transitions.iterator().cloned().collect();
+            let invoke: Vec<Invoke> = state.invoke.iterator().cloned().collect();
+            let history: Vec<StateId> = state.history.iterator().cloned().collect();
+            let compact_state = CompactState {
+                doc_id: state.doc_id,
+                name: push_str(&mut compact.strings, &state.name),
+                initial: state.initial,
+                parent: state.parent,
+                is_parallel: state.is_parallel,
+                is_final: state.is_final,
+                history_type: state.history_type,
+                states: push_all(&mut compact.state_ids, &state.states),
+                history: push_all(&mut compact.state_ids, &history),
+                onentry: push_all(&mut compact.content_ids, &state.onentry),
+                onexit: push_all(&mut compact.content_ids, &state.onexit),
+                transitions: push_all(&mut compact.transition_ids, &transitions),
+                invoke: push_all(&mut compact.invokes, &invoke),
+                data: Span {
+                    start: data_start,
+                    len: compact.data.len() as u32 - data_start,
+                },
+                donedata,
+            };
+            compact.states.push(compact_state);
+        }
+
+        let mut transitions: Vec<&Transition> = fsm.transitions.values().collect();
+        transitions.sort_by_key(|transition| transition.id);
+        for transition in transitions {
+            let events: Vec<Span> = transition
+                .source_event_descriptors()
+                .iter()
+                .map(|event| push_str(&mut compact.strings, event))
+                .collect();
+            let compact_transition = CompactTransition {
+                id: transition.id,
+                doc_id: transition.doc_id,
+                events: push_all(&mut compact.events, &events),
+                wildcard: transition.wildcard,
+                cond: transition.cond.clone(),
+                source: transition.source,
+                target: push_all(&mut compact.state_ids, &transition.target),
+                transition_type: transition.transition_type,
+                content: transition.content,
+            };
+            compact.transitions.push(compact_transition);
+        }
+        compact.strings.shrink_to_fit();
+        compact.state_ids.shrink_to_fit();
+        compact.transition_ids.shrink_to_fit();
+        compact.content_ids.shrink_to_fit();
+        compact.events.shrink_to_fit();
+        compact.invokes.shrink_to_fit();
+        compact.data.shrink_to_fit();
+        compact.donedata.shrink_to_fit();
+        compact
+    }
+
+    /// Number of states, including the pseudo root.
+    pub fn state_count(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Gets the name of the state or None if the id is invalid.
+    pub fn state_name(&self, state: StateId) -> Option<&str> {
+        if state == 0 {
+            None
+        } else {
+            self.states
+                .get(state as usize - 1)
+                .map(|compact_state| self.string(compact_state.name))
+        }
+    }
+
+    fn string(&self, span: Span) -> &str {
+        &self.strings[span.range()]
+    }
+
+    /// Creates a new runnable FSM from this template. The executable content is shared.
+    pub fn instantiate(&self) -> Box<Fsm> {
+        let mut fsm = Box::new(Fsm::new());
+        fsm.name = self.name.clone();
+        fsm.datamodel = self.datamodel.clone();
+        fsm.binding = self.binding;
+        fsm.version = self.version.clone();
+        fsm.file = self.file.clone();
+        fsm.pseudo_root = self.pseudo_root;
+        fsm.script = self.script;
+        fsm.unhandled_event_handler = self.unhandled_event_handler.clone();
+        fsm.caller_invoke_id = self.caller_invoke_id.clone();
+        fsm.parent_session_id = self.parent_session_id;
+        fsm.record_transitions = self.record_transitions;
+        fsm.cache_conditions = self.cache_conditions;
+        fsm.terminate_on_parallel_completion = self.terminate_on_parallel_completion;
+        fsm.reverse_onexit_order = self.reverse_onexit_order;
+        fsm.log_sink = self.log_sink.clone();
+        fsm.event_observer = self.event_observer.clone();
+        #[cfg(feature = "Trace")]
+        fsm.tracer.enable_trace(self.trace_mode);
+        fsm.executableContent = self.executable_content.clone();
+
+        fsm.states.reserve_exact(self.states.len());
+        for (idx, compact_state) in self.states.iter().enumerate() {
+            let mut state = State::new(self.string(compact_state.name));
+            state.id = (idx + 1) as StateId;
+            state.doc_id = compact_state.doc_id;
+            state.initial = compact_state.initial;
+            state.parent = compact_state.parent;
+            state.is_parallel = compact_state.is_parallel;
+            state.is_final = compact_state.is_final;
+            state.history_type = compact_state.history_type;
+            state.states = self.state_ids[compact_state.states.range()].to_vec();
+            state.history = List::from_array(&self.state_ids[compact_state.history.range()]);
+            state.onentry = self.content_ids[compact_state.onentry.range()].to_vec();
+            state.onexit = self.content_ids[compact_state.onexit.range()].to_vec();
+            state.transitions = List::from_array(&self.transition_ids[compact_state.transitions.range()]);
+            state.invoke = List::from_array(&self.invokes[compact_state.invoke.range()]);
+            state.data = self.data[compact_state.data.range()]
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        self.string(*name).to_string(),
+                        create_data_arc(value.clone()),
+                    )
+                })
+                .collect();
+            if compact_state.donedata > 0 {
+                state.donedata = Some(self.donedata[compact_state.donedata as usize - 1].clone());
+            }
+            fsm.statesNames.insert(state.name.clone(), state.id);
+            fsm.states.push(state);
+        }
+
+        fsm.transitions.reserve(self.transitions.len());
+        for compact_transition in &self.transitions {
+            let mut transition = Transition::with_id(compact_transition.id);
+            transition.doc_id = compact_transition.doc_id;
+            transition.event_descriptors = self.events[compact_transition.events.range()]
+                .iter()
+                .map(|event| self.string(*event).to_string())
+                .collect();
+            transition.events = transition
+                .event_descriptors
+                .iter()
+                .map(|event| normalize_event_descriptor(event))
+                .collect();
+            transition.wildcard = compact_transition.wildcard;
+            transition.cond = compact_transition.cond.clone();
+            transition.source = compact_transition.source;
+            transition.target = self.state_ids[compact_transition.target.range()].to_vec();
+            transition.transition_type = compact_transition.transition_type;
+            transition.content = compact_transition.content;
+            fsm.transitions.insert(transition.id, transition);
+        }
+        fsm
+    }
+}
+
+impl Fsm {
+    /// Creates the compact form of this FSM, see [CompactFsm].
+    pub fn compile(&self) -> CompactFsm {
+        CompactFsm::from_fsm(self)
+    }
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+mod tests {
+    use crate::fsm::{run_blocking, Event};
+    use crate::scxml_reader;
+
+    #[test]
+    fn compact_fsm_behaves_like_parsed_fsm() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript' name='compact'>
+              <datamodel><data id='count' expr='0'/></datamodel>
+              <state id='s0'>
+                <onentry><assign location='count' expr='count + 1'/></onentry>
+                <transition event='go' target='p'/>
+              </state>
+              <parallel id='p'>
+                <state id='a'>
+                  <state id='a1'><transition event='next' target='a2'/></state>
+                  <state id='a2'/>
+                  <history id='h' type='deep'><transition target='a1'/></history>
+                </state>
+                <state id='b'>
+                  <transition event='next' cond='count == 1' target='b1'/>
+                  <state id='b0'/>
+                  <state id='b1'/>
+                </state>
+                <transition event='done' target='pass'/>
+              </parallel>
+              <final id='pass'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        // Settings of the FSM are part of the template.
+        fsm.record_transitions = true;
+        fsm.reverse_onexit_order = true;
+
+        let compact = fsm.compile();
+        assert_eq!(compact.state_count(), fsm.states.len());
+        let b1 = fsm.get_state_by_name(&"b1".to_string()).id;
+        assert_eq!(compact.state_name(b1), Some("b1"));
+
+        let run = |fsm: Box<crate::fsm::Fsm>| {
+            assert!(fsm.reverse_onexit_order);
+            let global = run_blocking(
+                fsm,
+                vec![
+                    Event::new_simple("go"),
+                    Event::new_simple("next"),
+                    Event::new_simple("done"),
+                ],
+            );
+            let global = global.lock().unwrap();
+            (
+                global.recorded_transitions().to_vec(),
+                global.final_configuration.clone(),
+            )
+        };
+        let expected = run(fsm.instantiate());
+        assert_eq!(expected.0.len(), 3);
+        assert_eq!(run(compact.instantiate()), expected);
+        // Templates can be instantiated multiple times.
+        assert_eq!(run(compact.instantiate()), expected);
+    }
+}
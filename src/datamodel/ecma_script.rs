@@ -3,23 +3,31 @@
 //! See [W3C:The ECMAScript Data Model](/doc/W3C_SCXML_2024_07_13/index.html#ecma-profile).\
 //! See [GitHub:Boa Engine](https://github.com/boa-dev/boa).
 
+#[cfg(feature = "Debug")]
+use crate::common::debug;
 use crate::common::ArgOption;
-use crate::common::{debug, error, info, warn};
+use crate::common::{error, info, warn};
 use boa_engine::context::ContextBuilder;
 use boa_engine::object::builtins::{JsArray, JsMap};
 use boa_engine::object::ObjectInitializer;
 use boa_engine::property::{Attribute, PropertyDescriptor, PropertyKey};
 use boa_engine::value::Type;
 use boa_engine::{js_string, native_function::NativeFunction, Context, JsBigInt, JsError, JsValue, Source};
-use boa_engine::{JsArgs, JsData, JsResult};
+use boa_engine::{JsArgs, JsData, JsNativeError, JsResult, Script};
 use boa_gc::{empty_trace, Finalize, Trace};
 use std::collections::HashMap;
+use std::future::Future;
 use std::ops::Deref;
+use std::pin::pin;
 use std::string::ToString;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::task::{Poll, Waker};
 
 use crate::datamodel::{
-    create_data_arc, str_to_source, Data, DataArc, Datamodel, DatamodelFactory, GlobalDataArc,
-    EVENT_VARIABLE_FIELD_DATA, EVENT_VARIABLE_FIELD_INVOKE_ID, EVENT_VARIABLE_FIELD_NAME, EVENT_VARIABLE_FIELD_ORIGIN,
+    convert_event_data_default, create_data_arc, str_to_source, Clock, Data, DataArc, Datamodel, DatamodelFactory,
+    FixedClock, GlobalDataArc, RandomGenerator, SeededRandom, EVENT_VARIABLE_FIELD_DATA,
+    EVENT_VARIABLE_FIELD_INVOKE_ID, EVENT_VARIABLE_FIELD_NAME, EVENT_VARIABLE_FIELD_ORIGIN,
     EVENT_VARIABLE_FIELD_ORIGIN_TYPE, EVENT_VARIABLE_FIELD_SEND_ID, EVENT_VARIABLE_FIELD_TYPE, EVENT_VARIABLE_NAME,
 };
 use crate::event_io_processor::SYS_IO_PROCESSORS;
@@ -27,28 +35,89 @@ use crate::event_io_processor::SYS_IO_PROCESSORS;
 #[cfg(feature = "Trace")]
 use crate::executable_content::DefaultExecutableContentTracer;
 
-use crate::executable_content::{ExecutableContent, ExecutableContentTracer};
-use crate::fsm::{ExecutableContentId, Fsm, StateId};
+use crate::executable_content::{execute_element, ExecutableContent, ExecutableContentTracer};
+use crate::fsm::{ExecutableContentId, Fsm, ParamPair, StateId};
 
 pub const ECMA_SCRIPT: &str = "ECMAScript";
 pub const ECMA_SCRIPT_LC: &str = "ecmascript";
 
 pub const ECMA_OPTION_INFIX: &str = "ecma:";
 pub const ECMA_OPTION_STRICT_POSTFIX: &str = "strict";
+pub const ECMA_OPTION_RANDOM_SEED_POSTFIX: &str = "random_seed";
+pub const ECMA_OPTION_CLOCK_POSTFIX: &str = "clock";
+pub const ECMA_OPTION_LOOP_LIMIT_POSTFIX: &str = "loop_limit";
+pub const ECMA_OPTION_RECURSION_LIMIT_POSTFIX: &str = "recursion_limit";
+pub const ECMA_OPTION_STACK_LIMIT_POSTFIX: &str = "stack_limit";
 
 pub const ECMA_STRICT_OPTION: &str = "datamodel:ecma:strict";
 
+/// Seed for "Math.random()". If set, the random numbers are reproducible.
+pub const ECMA_RANDOM_SEED_OPTION: &str = "datamodel:ecma:random_seed";
+
+/// Fixed time in milliseconds since the Unix epoch for "Date.now()".
+pub const ECMA_CLOCK_OPTION: &str = "datamodel:ecma:clock";
+
+/// Maximal number of iterations of one loop in a script or expression.\
+/// Scripts that exceed the limit are aborted and "error.execution" is raised. Default is no limit.
+pub const ECMA_LOOP_LIMIT_OPTION: &str = "datamodel:ecma:loop_limit";
+
+/// Maximal depth of nested function calls. Scripts that exceed the limit are aborted and
+/// "error.execution" is raised.
+pub const ECMA_RECURSION_LIMIT_OPTION: &str = "datamodel:ecma:recursion_limit";
+
+/// Maximal number of values on the stack of the script engine. Scripts that exceed the limit are aborted and
+/// "error.execution" is raised.\
+/// Note: The script engine has no limit for the heap, the memory of a script is only limited by these options.
+pub const ECMA_STACK_LIMIT_OPTION: &str = "datamodel:ecma:stack_limit";
+
+/// Number of "clock cycles" of the script engine between two checks if the session was cancelled.
+pub const ECMA_CANCEL_CHECK_BUDGET: u32 = 10000;
+
 pub static ECMA_STRICT_ARGUMENT: ArgOption = ArgOption {
     name: ECMA_STRICT_OPTION,
     with_value: false,
     required: false,
 };
 
+pub static ECMA_RANDOM_SEED_ARGUMENT: ArgOption = ArgOption {
+    name: ECMA_RANDOM_SEED_OPTION,
+    with_value: true,
+    required: false,
+};
+
+pub static ECMA_CLOCK_ARGUMENT: ArgOption = ArgOption {
+    name: ECMA_CLOCK_OPTION,
+    with_value: true,
+    required: false,
+};
+
+pub static ECMA_LOOP_LIMIT_ARGUMENT: ArgOption = ArgOption {
+    name: ECMA_LOOP_LIMIT_OPTION,
+    with_value: true,
+    required: false,
+};
+
+pub static ECMA_RECURSION_LIMIT_ARGUMENT: ArgOption = ArgOption {
+    name: ECMA_RECURSION_LIMIT_OPTION,
+    with_value: true,
+    required: false,
+};
+
+pub static ECMA_STACK_LIMIT_ARGUMENT: ArgOption = ArgOption {
+    name: ECMA_STACK_LIMIT_OPTION,
+    with_value: true,
+    required: false,
+};
+
 pub struct ECMAScriptDatamodel {
     pub global_data: GlobalDataArc,
     pub context: Context,
     pub tracer: Option<Box<dyn ExecutableContentTracer>>,
     pub strict_mode: bool,
+    /// Set if a script was interrupted because the session was cancelled or if a panic unwound through
+    /// the script engine, see [Datamodel::abort].
+    /// The state of the script engine is undefined afterwards, so all further scripts fail.
+    pub aborted: bool,
 }
 
 pub struct ECMAScriptDatamodelFactory {}
@@ -63,6 +132,9 @@ impl DatamodelFactory for ECMAScriptDatamodelFactory {
     }
 }
 
+/// Largest integer that ECMAScript numbers can represent exactly (Number.MAX_SAFE_INTEGER).
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
 fn js_to_string(jv: &JsValue, ctx: &mut Context) -> String {
     match jv.to_string(ctx) {
         Ok(s) => s.to_std_string().unwrap().clone(),
@@ -130,6 +202,18 @@ impl FsmJSWrapper {
     }
 }
 
+/// Injected clock and random generator, used by "Date.now()" and "Math.random()".
+#[derive(JsData, Finalize, Default)]
+struct HostSeams {
+    pub clock: Option<Mutex<Box<dyn Clock>>>,
+    pub random: Option<Mutex<Box<dyn RandomGenerator>>>,
+}
+
+/// Safety: Nothing in this struct needs tracing, so this is safe.
+unsafe impl Trace for HostSeams {
+    empty_trace!();
+}
+
 impl ECMAScriptDatamodel {
     pub fn new(global_data: GlobalDataArc) -> ECMAScriptDatamodel {
         ECMAScriptDatamodel {
@@ -140,6 +224,7 @@ impl ECMAScriptDatamodel {
             #[cfg(not(feature = "Trace"))]
             tracer: None,
             strict_mode: false,
+            aborted: false,
         }
     }
 
@@ -209,7 +294,7 @@ impl ECMAScriptDatamodel {
         }
     }
 
-    pub fn set_option(&mut self, name: &str, _value: &str) {
+    pub fn set_option(&mut self, name: &str, value: &str) {
         if let Some(ecma_option) = name.strip_prefix(ECMA_OPTION_INFIX) {
             match ecma_option {
                 ECMA_OPTION_STRICT_POSTFIX => {
@@ -218,11 +303,85 @@ impl ECMAScriptDatamodel {
                     self.strict_mode = true;
                     self.context.strict(true);
                 }
+                ECMA_OPTION_RANDOM_SEED_POSTFIX => match value.trim().parse::<u64>() {
+                    Ok(seed) => self.set_random(Box::new(SeededRandom::new(seed))),
+                    Err(_) => error!("Illegal value for {}: '{}'", name, value),
+                },
+                ECMA_OPTION_CLOCK_POSTFIX => match value.trim().parse::<f64>() {
+                    Ok(millis) => self.set_clock(Box::new(FixedClock::new(millis))),
+                    Err(_) => error!("Illegal value for {}: '{}'", name, value),
+                },
+                ECMA_OPTION_LOOP_LIMIT_POSTFIX => match value.trim().parse::<u64>() {
+                    Ok(limit) => self
+                        .context
+                        .runtime_limits_mut()
+                        .set_loop_iteration_limit(limit),
+                    Err(_) => error!("Illegal value for {}: '{}'", name, value),
+                },
+                ECMA_OPTION_RECURSION_LIMIT_POSTFIX => match value.trim().parse::<usize>() {
+                    Ok(limit) => self.context.runtime_limits_mut().set_recursion_limit(limit),
+                    Err(_) => error!("Illegal value for {}: '{}'", name, value),
+                },
+                ECMA_OPTION_STACK_LIMIT_POSTFIX => match value.trim().parse::<usize>() {
+                    Ok(limit) => self
+                        .context
+                        .runtime_limits_mut()
+                        .set_stack_size_limit(limit),
+                    Err(_) => error!("Illegal value for {}: '{}'", name, value),
+                },
                 &_ => {}
             }
         }
     }
 
+    /// Implements "Date.now()" with the injected clock.
+    fn now_js(_this: &JsValue, _args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+        let now = match ctx
+            .get_data::<HostSeams>()
+            .and_then(|seams| seams.clock.as_ref())
+        {
+            Some(clock) => clock.lock().unwrap().now_millis(),
+            None => 0f64,
+        };
+        Ok(JsValue::Rational(now))
+    }
+
+    /// Implements "Math.random()" with the injected random generator.
+    fn random_js(_this: &JsValue, _args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+        let r = match ctx
+            .get_data::<HostSeams>()
+            .and_then(|seams| seams.random.as_ref())
+        {
+            Some(random) => random.lock().unwrap().next_f64(),
+            None => 0f64,
+        };
+        Ok(JsValue::Rational(r))
+    }
+
+    /// Replaces a function of a builtin object, e.g. "Date.now", by a native function.
+    fn replace_builtin(&mut self, object: &str, function: &str, native: NativeFunction) {
+        let ctx = &mut self.context;
+        let helper = format!("__{}_{}", object, function);
+        let _ = ctx.register_global_callable(js_string!(helper.as_str()), 0, native);
+        let r = ctx.eval(Source::from_bytes(
+            format!(
+                "{}.{} = function() {{ return {}(); }};",
+                object, function, helper
+            )
+            .as_str(),
+        ));
+        if let Err(err) = r {
+            error!("Failed to replace {}.{}: {}", object, function, err);
+        }
+    }
+
+    fn host_seams(&mut self) -> HostSeams {
+        self.context
+            .remove_data::<HostSeams>()
+            .map(|seams| *seams)
+            .unwrap_or_default()
+    }
+
     fn execute_internal(&mut self, script: &Data, handle_error: bool) -> Result<DataArc, String> {
         let result = self.eval(script);
         match result {
@@ -254,6 +413,9 @@ impl ECMAScriptDatamodel {
                 // Pretty print the error
                 let msg = format!("Script Error:  {} => {} ", script, e);
                 error!("{}", msg);
+                if handle_error {
+                    self.internal_error_execution();
+                }
                 Err(msg)
             }
         }
@@ -266,11 +428,44 @@ impl ECMAScriptDatamodel {
             }
             None => {}
         }
-        e.execute(self, fsm)
+        execute_element(e, self, fsm)
     }
 
+    /// Evaluates a script.\
+    /// The script engine runs in the thread of the session. To react on [crate::fsm::ScxmlSession::cancel]
+    /// while a long-running script is executed, the script is executed in slices of
+    /// [ECMA_CANCEL_CHECK_BUDGET] "clock cycles". If the session is cancelled, the script is aborted.
     fn eval(&mut self, source: &Data) -> JsResult<JsValue> {
-        self.context.eval(Source::from_bytes(&source.as_script()))
+        if self.aborted {
+            return Err(JsNativeError::error()
+                .with_message("Script engine was aborted")
+                .into());
+        }
+        let cancelled = self
+            .global_data
+            .lock()
+            .unwrap()
+            .externalQueue
+            .cancelled
+            .clone();
+        let script = Script::parse(
+            Source::from_bytes(&source.as_script()),
+            None,
+            &mut self.context,
+        )?;
+        let mut evaluation = pin!(script.evaluate_async_with_budget(&mut self.context, ECMA_CANCEL_CHECK_BUDGET));
+        let mut task_context = std::task::Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(result) = evaluation.as_mut().poll(&mut task_context) {
+                return result;
+            }
+            if cancelled.load(Ordering::Acquire) {
+                self.aborted = true;
+                return Err(JsNativeError::error()
+                    .with_message("Script aborted by a cancel of the session")
+                    .into());
+            }
+        }
     }
 
     fn set_js_property<V>(&mut self, name: &str, value: V)
@@ -520,6 +715,10 @@ impl Datamodel for ECMAScriptDatamodel {
                     } else {
                         self.set_js_property(name.as_str(), JsValue::Null);
                     };
+                } else if let Data::Error(err) = data_guard.deref() {
+                    error!("Error on Initialize '{}': {}", name, err);
+                    self.set_js_property(name.as_str(), JsValue::Undefined);
+                    self.internal_error_execution();
                 } else {
                     let ds = self.data_value_to_js(data_guard.deref());
                     self.set_js_property(name.as_str(), ds);
@@ -563,6 +762,15 @@ impl Datamodel for ECMAScriptDatamodel {
         }
     }
 
+    /// ECMAScript numbers are doubles. Integers that can be represented exactly are converted to numbers,
+    /// so that they compare like values created by scripts. Larger integers are kept as BigInt.
+    fn convert_event_data(&mut self, data: &Data) -> Result<Data, String> {
+        match data {
+            Data::Integer(v) if v.unsigned_abs() <= MAX_SAFE_INTEGER => Ok(Data::Double(*v as f64)),
+            _ => convert_event_data_default(self, data),
+        }
+    }
+
     fn set_event(&mut self, event: &crate::fsm::Event) {
         let data_value = match &event.param_values {
             None => match &event.content {
@@ -570,12 +778,24 @@ impl Datamodel for ECMAScriptDatamodel {
                 Some(c) => self.data_value_to_js(c),
             },
             Some(pv) => {
-                let mut data = Vec::with_capacity(pv.len());
+                let groups = ParamPair::group_by_name(pv);
+                let mut data = Vec::with_capacity(groups.len());
 
-                for pair in pv.iter() {
+                for (name, values) in groups {
+                    // Duplicate names are given as array of all values.
+                    let value = if values.len() == 1 {
+                        values[0].clone()
+                    } else {
+                        Data::Array(
+                            values
+                                .into_iter()
+                                .map(|v| create_data_arc(v.clone()))
+                                .collect(),
+                        )
+                    };
                     data.push((
-                        js_string!(pair.name.clone()),
-                        self.data_arc_to_js(&create_data_arc(pair.value.clone())),
+                        js_string!(name),
+                        self.data_arc_to_js(&create_data_arc(value)),
                     ));
                 }
                 let ctx = &mut self.context;
@@ -652,6 +872,9 @@ impl Datamodel for ECMAScriptDatamodel {
     }
 
     fn assign(self: &mut ECMAScriptDatamodel, left_expr: &Data, right_expr: &Data) -> bool {
+        if !self.check_assign_location(left_expr) {
+            return false;
+        }
         self.assign_internal(
             left_expr.as_script().as_str(),
             right_expr.as_script().as_str(),
@@ -669,8 +892,36 @@ impl Datamodel for ECMAScriptDatamodel {
         }
     }
 
+    /// Replaces "Date.now()". Other time functions, e.g. "new Date()", still use the system time.
+    fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        let mut seams = self.host_seams();
+        if seams.clock.is_none() {
+            self.replace_builtin("Date", "now", NativeFunction::from_fn_ptr(Self::now_js));
+        }
+        seams.clock = Some(Mutex::new(clock));
+        self.context.insert_data(seams);
+    }
+
+    /// Replaces "Math.random()".
+    fn set_random(&mut self, random: Box<dyn RandomGenerator>) {
+        let mut seams = self.host_seams();
+        if seams.random.is_none() {
+            self.replace_builtin(
+                "Math",
+                "random",
+                NativeFunction::from_fn_ptr(Self::random_js),
+            );
+        }
+        seams.random = Some(Mutex::new(random));
+        self.context.insert_data(seams);
+    }
+
     fn clear(self: &mut ECMAScriptDatamodel) {}
 
+    fn abort(&mut self) {
+        self.aborted = true;
+    }
+
     fn execute(&mut self, script: &Data) -> Result<DataArc, String> {
         self.execute_internal(script, true)
     }
@@ -684,10 +935,7 @@ impl Datamodel for ECMAScriptDatamodel {
     ) -> bool {
         #[cfg(feature = "Debug")]
         debug!("ForEach: array: {}", array_expression);
-        match self
-            .context
-            .eval(Source::from_bytes(&array_expression.as_script()))
-        {
+        match self.eval(array_expression) {
             Ok(r) => {
                 match r.get_type() {
                     Type::Object => {
@@ -728,8 +976,10 @@ impl Datamodel for ECMAScriptDatamodel {
                         }
                     }
                     _ => {
+                        // W3C: Terminate the <foreach> and the block that contains it.
                         self.log("Resulting value is not a supported collection.");
                         self.internal_error_execution();
+                        return false;
                     }
                 }
                 true
@@ -769,6 +1019,7 @@ impl Datamodel for ECMAScriptDatamodel {
 }
 
 #[cfg(test)]
+#[cfg(feature = "xml")]
 mod tests {
     use log::info;
     use std::collections::HashMap;
@@ -833,4 +1084,267 @@ mod tests {
             &final_expected_configuration,
         ));
     }
+
+    #[test]
+    fn assign_to_system_variable_raises_error() {
+        let fsm = scxml_reader::parse_from_xml(
+            r##"<scxml initial='s0' datamodel='ecmascript' name='sys'>
+              <datamodel><data id='before'/></datamodel>
+              <state id='s0'>
+                <onentry>
+                  <assign location='before' expr='_sessionid'/>
+                  <assign location='_sessionid' expr='1'/>
+                </onentry>
+                <transition event='error.execution' cond='_sessionid === before' target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <onentry><assign location='_event.name' expr='"changed"'/></onentry>
+                <onentry><assign location='_name' expr='"other"'/></onentry>
+                <transition event='error.execution' target='s2'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s2'>
+                <transition event='error.execution' cond='_name === "sys"' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"##
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual(
+            "assign_to_system_variable_raises_error",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000u64,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    fn injected_clock_and_random_are_reproducible() {
+        use crate::datamodel::ecma_script::ECMAScriptDatamodel;
+        use crate::datamodel::{create_global_data_arc, str_to_source, Datamodel, FixedClock, SeededRandom};
+
+        let run = |seed: u64| {
+            let mut ecma = ECMAScriptDatamodel::new(create_global_data_arc());
+            ecma.set_clock(Box::new(FixedClock::new(1000.0)));
+            ecma.set_random(Box::new(SeededRandom::new(seed)));
+            (0..5)
+                .map(|_| {
+                    let r = ecma
+                        .execute(&str_to_source("Date.now() + ':' + Math.random()"))
+                        .unwrap();
+                    let v = r.lock().unwrap().to_string();
+                    v
+                })
+                .collect::<Vec<String>>()
+        };
+
+        let first = run(42);
+        assert_eq!(first, run(42));
+        assert_ne!(first, run(43));
+        for v in &first {
+            let (now, random) = v.split_once(':').unwrap();
+            assert_eq!(now, "1000");
+            let random: f64 = random.parse().unwrap();
+            assert!((0.0..1.0).contains(&random), "{}", v);
+        }
+        // Subsequent calls give different numbers.
+        assert_ne!(first[0], first[1]);
+    }
+
+    #[test]
+    fn clock_and_random_options() {
+        let fsm = scxml_reader::parse_from_xml(
+            r##"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='r' expr='Math.random()'/></datamodel>
+              <state id='s0'>
+                <transition cond='Date.now() === 5000 &amp;&amp; r >= 0 &amp;&amp; r &lt; 1' target='pass'/>
+                <transition target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"##
+                .to_string(),
+        )
+        .unwrap();
+
+        let mut options = HashMap::new();
+        options.insert(
+            crate::datamodel::ecma_script::ECMA_CLOCK_OPTION,
+            "5000".to_string(),
+        );
+        options.insert(
+            crate::datamodel::ecma_script::ECMA_RANDOM_SEED_OPTION,
+            "7".to_string(),
+        );
+        assert!(run_test_manual(
+            "clock_and_random_options",
+            &options,
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000u64,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    fn runaway_scripts_abort_with_error() {
+        let fsm = scxml_reader::parse_from_xml(
+            r##"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry>
+                  <script>while (true) {}</script>
+                  <raise event='not_reached'/>
+                </onentry>
+                <transition event='error.execution' target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <onentry>
+                  <script>function f(n) { return f(n + 1); } f(0);</script>
+                </onentry>
+                <transition event='error.execution' target='s2'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s2'>
+                <transition cond='(function() { for (var i = 0; ; i++) {} })()' target='fail'/>
+                <transition event='error.execution' target='pass'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"##
+                .to_string(),
+        )
+        .unwrap();
+
+        let mut options = HashMap::new();
+        options.insert(
+            crate::datamodel::ecma_script::ECMA_LOOP_LIMIT_OPTION,
+            "10000".to_string(),
+        );
+        options.insert(
+            crate::datamodel::ecma_script::ECMA_RECURSION_LIMIT_OPTION,
+            "100".to_string(),
+        );
+        assert!(run_test_manual(
+            "runaway_scripts_abort_with_error",
+            &options,
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000u64,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    fn event_fields_in_condition() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <transition event='go' cond="_event.name == 'go' &amp;&amp; _event.type == 'external'" target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <onentry><send event='go' id='s1.go'/><raise event='go'/></onentry>
+                <transition event='go' cond="_event.type == 'external'" target='fail'/>
+                <transition event='go' cond="_event.type == 'internal' &amp;&amp; _event.sendid === undefined" target='s2'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s2'>
+                <transition event='go' cond="_event.type == 'external' &amp;&amp; _event.sendid == 's1.go' &amp;&amp; _event.origin == _ioprocessors['scxml'].location" target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        let global = crate::fsm::run_blocking(fsm, vec![crate::fsm::Event::new_simple("go")]);
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+    }
+
+    #[test]
+    fn cancel_interrupts_long_running_script() {
+        use std::time::{Duration, Instant};
+
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry><script>while (true) {}</script></onentry>
+              </state>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let executor = crate::fsm_executor::FsmExecutor::new_without_io_processor();
+        let mut session = crate::fsm::start_fsm_with_data(
+            fsm,
+            crate::actions::ActionWrapper::new(),
+            Box::new(executor),
+            &Vec::new(),
+        );
+        let thread = session.thread.take().unwrap();
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!thread.is_finished());
+        let cancelled_at = Instant::now();
+        session.cancel();
+        while !thread.is_finished() {
+            assert!(
+                cancelled_at.elapsed() < Duration::from_secs(5),
+                "Script was not interrupted"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = thread.join();
+    }
+
+    #[test]
+    fn inbound_integers_become_numbers() {
+        use crate::datamodel::ecma_script::ECMAScriptDatamodel;
+        use crate::datamodel::{create_data_arc, create_global_data_arc, Data, Datamodel};
+
+        let mut dm = ECMAScriptDatamodel::new(create_global_data_arc());
+        assert_eq!(
+            dm.convert_event_data(&Data::Integer(42)),
+            Ok(Data::Double(42.0))
+        );
+        // Can't be represented exactly as number, stays a BigInt.
+        assert_eq!(
+            dm.convert_event_data(&Data::Integer(1 << 60)),
+            Ok(Data::Integer(1 << 60))
+        );
+
+        let mut map = HashMap::new();
+        map.insert("v".to_string(), create_data_arc(Data::Integer(-7)));
+        match dm.convert_event_data(&Data::Map(map)) {
+            Ok(Data::Map(converted)) => {
+                assert_eq!(*converted["v"].lock().unwrap(), Data::Double(-7.0))
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        assert!(dm
+            .convert_event_data(&Data::Array(vec![create_data_arc(Data::Error(
+                "broken".to_string()
+            ))]))
+            .is_err());
+    }
 }
@@ -16,11 +16,12 @@ use crate::datamodel::{
     EVENT_VARIABLE_FIELD_TYPE, EVENT_VARIABLE_NAME,
 };
 use crate::event_io_processor::SYS_IO_PROCESSORS;
+use crate::executable_content::execute_element;
 use crate::expression_engine::expressions::{
     Expression, ExpressionAssign, ExpressionAssignUndefined, ExpressionConstant,
 };
 use crate::expression_engine::parser::ExpressionParser;
-use crate::fsm::{Event, ExecutableContentId, Fsm, GlobalData, StateId};
+use crate::fsm::{Event, ExecutableContentId, Fsm, GlobalData, ParamPair, StateId};
 
 pub const RFSM_EXPRESSION_DATAMODEL: &str = "RFSM-EXPRESSION";
 pub const RFSM_EXPRESSION_DATAMODEL_LC: &str = "rfsm-expression";
@@ -144,11 +145,19 @@ impl RFsmExpressionDatamodel {
                         // Pretty print the error
                         let msg = format!("Script Error:  {} => {} ", source, e);
                         error!("{}", msg);
+                        if handle_error {
+                            self.internal_error_execution();
+                        }
                         Err(msg)
                     }
                 }
             }
-            Err(err) => Err(err),
+            Err(err) => {
+                if handle_error {
+                    self.internal_error_execution();
+                }
+                Err(err)
+            }
         }
     }
 
@@ -410,7 +419,17 @@ impl Datamodel for RFsmExpressionDatamodel {
     fn set_from_state_data(&mut self, data: &HashMap<String, DataArc>, set_data: bool) {
         for (name, value) in data {
             if set_data {
-                if let Data::Source(src) = value.lock().unwrap().deref() {
+                let load_error = match value.lock().unwrap().deref() {
+                    Data::Error(err) => Some(err.clone()),
+                    _ => None,
+                };
+                if let Some(err) = load_error {
+                    // Data that couldn't be loaded, e.g. by 'src'. See W3C comment below.
+                    error!("Error on Initialize '{}': {}", name, err);
+                    let data_lock = &mut self.global_data.lock().unwrap();
+                    data_lock.data.set_undefined(name.clone(), Data::None());
+                    data_lock.enqueue_internal(Event::error_execution(&None, &None));
+                } else if let Data::Source(src) = value.lock().unwrap().deref() {
                     if !src.is_empty() {
                         // The data from state-data needs to be evaluated
                         let rs = self.execute_internal_source(src, false);
@@ -481,18 +500,22 @@ impl Datamodel for RFsmExpressionDatamodel {
             },
             Some(pv) => {
                 let mut data = HashMap::with_capacity(pv.len());
-                for pair in pv.iter() {
-                    match self.resolve_source_data(&pair.value) {
-                        Ok(val) => {
-                            data.insert(pair.name.clone(), val);
-                        }
-                        Err(err) => {
-                            error!(
-                                "Can set event data '{} = {}': {}",
-                                pair.name, pair.value, err
-                            )
+                for (name, values) in ParamPair::group_by_name(pv) {
+                    let mut resolved = Vec::with_capacity(values.len());
+                    for value in values {
+                        match self.resolve_source_data(value) {
+                            Ok(val) => resolved.push(val),
+                            Err(err) => {
+                                error!("Can set event data '{} = {}': {}", name, value, err)
+                            }
                         }
                     }
+                    // Duplicate names are given as array of all values.
+                    if resolved.len() > 1 {
+                        data.insert(name.to_string(), create_data_arc(Data::Array(resolved)));
+                    } else if let Some(val) = resolved.pop() {
+                        data.insert(name.to_string(), val);
+                    }
                 }
                 create_data_arc(Data::Map(data))
             }
@@ -536,6 +559,9 @@ impl Datamodel for RFsmExpressionDatamodel {
     }
 
     fn assign(&mut self, left_expr: &Data, right_expr: &Data) -> bool {
+        if !self.check_assign_location(left_expr) {
+            return false;
+        }
         self.assign_internal(left_expr, right_expr, false)
     }
 
@@ -622,8 +648,10 @@ impl Datamodel for RFsmExpressionDatamodel {
                         }
                     }
                     _ => {
+                        // W3C: Terminate the <foreach> and the block that contains it.
                         self.log("Resulting value is not a supported collection.");
                         self.internal_error_execution();
+                        return false;
                     }
                 }
                 true
@@ -674,7 +702,7 @@ impl Datamodel for RFsmExpressionDatamodel {
     fn executeContent(&mut self, fsm: &Fsm, content_id: ExecutableContentId) -> bool {
         let ec = fsm.executableContent.get(&content_id);
         for e in ec.unwrap().iter() {
-            if !e.execute(self, fsm) {
+            if !execute_element(e.as_ref(), self, fsm) {
                 return false;
             }
         }
@@ -796,4 +824,37 @@ mod tests {
         let rs = ExpressionParser::execute("'abcdef'.toString()".to_string(), &mut gd.lock().unwrap());
         assert_eq!(rs, Ok(create_data_arc(Data::String("abcdef".to_string()))));
     }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn event_fields_in_condition() {
+        let fsm = crate::scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='rfsm-expression'>
+              <state id='s0'>
+                <transition event='go' cond="_event.type == 'external'" target='s0b'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s0b'>
+                <onentry><raise event='check'/></onentry>
+                <transition event='check' cond="_event.name == 'check'" target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <onentry><raise event='go'/></onentry>
+                <transition event='go' cond="_event.type == 'internal'" target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        let global = crate::fsm::run_blocking(fsm, vec![crate::fsm::Event::new_simple("go")]);
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+    }
 }
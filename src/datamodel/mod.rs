@@ -1,6 +1,8 @@
 //! Defines the API used to access the data models.
 
-use crate::common::{debug, error, info, warn};
+#[cfg(feature = "Debug")]
+use crate::common::{debug, warn};
+use crate::common::{error, info};
 use crate::expression_engine::lexer::{ExpressionLexer, Token};
 use crate::fsm::{
     vec_to_string, CommonContent, Event, ExecutableContentId, Fsm, GlobalData, InvokeId, ParamPair, Parameter, State,
@@ -15,7 +17,7 @@ use std::ops::Deref;
 use std::sync::{Arc, LockResult, Mutex, MutexGuard};
 
 use crate::actions::ActionMap;
-use crate::event_io_processor::EventIOProcessor;
+use crate::event_io_processor::{unknown_io_processor_message, EventIOProcessor, SYS_IO_PROCESSORS};
 
 #[cfg(feature = "ECMAScriptModel")]
 pub mod ecma_script;
@@ -23,6 +25,9 @@ pub mod ecma_script;
 #[cfg(feature = "RfsmExpressionModel")]
 pub mod expression_engine;
 
+#[cfg(feature = "xpath")]
+pub mod xpath;
+
 /// Gets the global data store from datamodel.
 macro_rules! get_global {
     ($x:expr) => {
@@ -43,9 +48,12 @@ pub const SCXML_INVOKE_TYPE_SHORT: &str = "scxml";
 
 pub const SCXML_EVENT_PROCESSOR: &str = "http://www.w3.org/TR/scxml/#SCXMLEventProcessor";
 
-#[cfg(feature = "BasicHttpEventIOProcessor")]
+/// Type of the BasicHTTPEventIOProcessor. The processor is only available with feature "BasicHttpEventIOProcessor".
 pub const BASIC_HTTP_EVENT_PROCESSOR: &str = "http://www.w3.org/TR/scxml/#BasicHTTPEventProcessor";
 
+/// Short forms of [BASIC_HTTP_EVENT_PROCESSOR].
+pub const BASIC_HTTP_EVENT_PROCESSOR_SHORT: &[&str] = &["basichttp", "http"];
+
 /// Name of system variable "_sessionid".\
 /// *W3C says*:\
 /// The SCXML Processor MUST bind the variable _sessionid at load time to the system-generated id
@@ -63,6 +71,30 @@ pub const SESSION_NAME_VARIABLE_NAME: &str = "_name";
 /// Name of system variable "_event" for events
 pub const EVENT_VARIABLE_NAME: &str = "_event";
 
+/// Name of system variable "_x".\
+/// *W3C says*:\
+/// The SCXML Processor MAY define other system variables, but their names MUST begin with '_x.'.
+pub const PLATFORM_VARIABLE_NAME: &str = "_x";
+
+/// Names of the read-only system variables.
+pub const SYSTEM_VARIABLE_NAMES: [&str; 5] = [
+    EVENT_VARIABLE_NAME,
+    SESSION_ID_VARIABLE_NAME,
+    SESSION_NAME_VARIABLE_NAME,
+    SYS_IO_PROCESSORS,
+    PLATFORM_VARIABLE_NAME,
+];
+
+/// Checks if a location expression addresses a system variable or a member of it.
+pub fn is_system_variable_location(location: &str) -> bool {
+    let location = location.trim();
+    let root = match location.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')) {
+        Some(end) => &location[..end],
+        None => location,
+    };
+    SYSTEM_VARIABLE_NAMES.contains(&root)
+}
+
 /// Name of field "name" of system variable "_event"
 pub const EVENT_VARIABLE_FIELD_NAME: &str = "name";
 
@@ -90,12 +122,109 @@ pub trait DatamodelFactory: Send {
     fn create(&mut self, global_data: GlobalDataArc, options: &HashMap<String, String>) -> Box<dyn Datamodel>;
 }
 
+/// Source of the current time for a datamodel, in milliseconds since the Unix epoch.\
+/// Can be injected via [Datamodel::set_clock], e.g. to get reproducible runs.
+pub trait Clock: Send {
+    fn now_millis(&mut self) -> f64;
+}
+
+/// Receives the messages of \<log\> elements, see [Fsm::log_sink].
+pub trait LogSink: Send + Sync {
+    fn log(&self, message: &str);
+}
+
+/// Forwards the messages to a channel, e.g. to check them in tests.
+impl LogSink for std::sync::mpsc::Sender<String> {
+    fn log(&self, message: &str) {
+        let _ = self.send(message.to_string());
+    }
+}
+
+/// Source of random numbers in the range \[0, 1) for a datamodel.\
+/// Can be injected via [Datamodel::set_random], e.g. to get reproducible runs.
+pub trait RandomGenerator: Send {
+    fn next_f64(&mut self) -> f64;
+}
+
+/// Clock that always returns the same time.
+#[derive(Debug, Clone)]
+pub struct FixedClock {
+    pub millis: f64,
+}
+
+impl FixedClock {
+    pub fn new(millis: f64) -> FixedClock {
+        FixedClock { millis }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now_millis(&mut self) -> f64 {
+        self.millis
+    }
+}
+
+/// Deterministic random generator (SplitMix64). The same seed gives the same sequence.
+#[derive(Debug, Clone)]
+pub struct SeededRandom {
+    state: u64,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> SeededRandom {
+        SeededRandom { state: seed }
+    }
+}
+
+impl RandomGenerator for SeededRandom {
+    fn next_f64(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+        // Use the upper 53 bits, the precision of f64.
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
 pub type GlobalDataLock<'a> = MutexGuard<'a, GlobalData>;
 
 /// Currently we assume that we need access to the global-data via a mutex as RUST doesn't allow access to it
 /// from callbacks as used in ECMA-implementations and timers. If not, change this type to "GlobalData" and adapt implementation.
 pub type GlobalDataArc = Arc<Mutex<GlobalData>>;
 
+/// Default of [Datamodel::convert_event_data].\
+/// Rejects [Data::Error], that marks data an I/O processor failed to decode, and passes script
+/// source as plain string, as it must not be evaluated by the receiver.
+/// The members of arrays and maps are converted via [Datamodel::convert_event_data] of the data model.
+pub fn convert_event_data_default<D: Datamodel + ?Sized>(datamodel: &mut D, data: &Data) -> Result<Data, String> {
+    match data {
+        Data::Error(msg) => Err(msg.clone()),
+        Data::Source(src) => Ok(Data::String(src.source.clone())),
+        Data::Array(values) => {
+            let mut converted = Vec::with_capacity(values.len());
+            for value in values {
+                let value = value.lock().unwrap().clone();
+                converted.push(create_data_arc(datamodel.convert_event_data(&value)?));
+            }
+            Ok(Data::Array(converted))
+        }
+        Data::Map(values) => {
+            let mut converted = HashMap::with_capacity(values.len());
+            for (key, value) in values {
+                let value = value.lock().unwrap().clone();
+                converted.insert(
+                    key.clone(),
+                    create_data_arc(datamodel.convert_event_data(&value)?),
+                );
+            }
+            Ok(Data::Map(converted))
+        }
+        _ => Ok(data.clone()),
+    }
+}
+
 /// Helper to create the global data instance. Should be used to minimize dependencies.
 pub fn create_global_data_arc() -> GlobalDataArc {
     GlobalDataArc::new(Mutex::from(crate::fsm::GlobalData::new()))
@@ -165,10 +294,35 @@ pub trait Datamodel {
     // Sets system variable "_event"
     fn set_event(&mut self, event: &Event);
 
+    /// Converts the data of an inbound external event to the representation of this data model.\
+    /// *W3C says*:\
+    /// If the receiving processor is unable to reformat the data so that it can be stored in the data model,
+    /// it must leave the corresponding field of the event blank and place error.execution in the internal event queue.\
+    /// The default is [convert_event_data_default]. Data models override it to check or convert single values.
+    fn convert_event_data(&mut self, data: &Data) -> Result<Data, String> {
+        convert_event_data_default(self, data)
+    }
+
     /// Execute an assign expression.
     /// Returns true if the assignment was correct.
     fn assign(&mut self, left_expr: &Data, right_expr: &Data) -> bool;
 
+    /// Checks that the location of an assignment is not a read-only system variable.\
+    /// *W3C says*:\
+    /// If the location expression denotes a system variable, the SCXML Processor must place the
+    /// error 'error.execution' on the internal event queue.\
+    /// Returns false if the location is a system variable.
+    fn check_assign_location(&mut self, location: &Data) -> bool {
+        let location = location.as_script();
+        if is_system_variable_location(&location) {
+            self.log(format!("Could not assign to system variable '{}'.", location).as_str());
+            self.internal_error_execution();
+            false
+        } else {
+            true
+        }
+    }
+
     /// Gets a global variable by a location expression.\
     /// If the location is undefined or the location expression is invalid,
     /// "error.execute" shall be put inside the internal event queue.\
@@ -211,16 +365,36 @@ pub trait Datamodel {
             let mut icg = ic.lock().unwrap();
             icg.send(self.global(), target.to_string().as_str(), event)
         } else {
+            error!("{}", unknown_io_processor_message(ioc_processor));
             false
         }
     }
 
+    /// Replaces the clock that is used by the scripts of the datamodel.\
+    /// The default ignores it, for datamodels that have no access to the time.
+    fn set_clock(&mut self, _clock: Box<dyn Clock>) {}
+
+    /// Replaces the random generator that is used by the scripts of the datamodel.\
+    /// The default ignores it, for datamodels that have no random numbers.
+    fn set_random(&mut self, _random: Box<dyn RandomGenerator>) {}
+
     /// Clear all data.
     fn clear(&mut self);
 
-    /// "log" function, use for \<log\> content.
+    /// Extension: Called if a panic unwound through the datamodel, e.g. from a custom action that was called
+    /// by a script. The state of a script engine is undefined afterwards, so datamodels with such a state
+    /// shall refuse to evaluate any further expression.\
+    /// The default does nothing, for datamodels without such a state.
+    fn abort(&mut self) {}
+
+    /// "log" function, use for \<log\> content.\
+    /// Writes to the log sink of the session, if one is set. See [Fsm::log_sink].
     fn log(&mut self, msg: &str) {
-        info!("{}", msg);
+        let sink = get_global!(self).log_sink.clone();
+        match sink {
+            Some(sink) => sink.log(msg),
+            None => info!("{}", msg),
+        }
     }
 
     /// Executes a script.\
@@ -297,6 +471,7 @@ pub trait Datamodel {
                             Err(_) => create_data_arc(Data::String(ct_content.clone())),
                         }),
                     Some(expr) => {
+                        let error_mark = get_global!(self).internal_queue_size();
                         match self.execute(&str_to_source(expr.as_str())) {
                             Err(msg) => {
                                 // W3C:\
@@ -304,7 +479,9 @@ pub trait Datamodel {
                                 // error.execution in the internal event queue and use the empty string as
                                 // the value of the <content> element.
                                 error!("content expr '{}' is invalid ({})", expr, msg);
-                                self.internal_error_execution();
+                                if get_global!(self).errors_since(error_mark) == 0 {
+                                    self.internal_error_execution();
+                                }
                                 None
                             }
                             Ok(value) => Some(value),
@@ -341,6 +518,7 @@ pub trait Datamodel {
                             }
                         }
                     } else if !param.expr.is_empty() {
+                        let error_mark = get_global!(self).internal_queue_size();
                         match self.execute(&str_to_source(param.expr.as_str())) {
                             Err(msg) => {
                                 //  W3C:\
@@ -348,7 +526,9 @@ pub trait Datamodel {
                                 // Processor must place the error 'error.execution' on the internal event
                                 // queue and must ignore the name and value.
                                 error!("expr of param {} is invalid ({})", param, msg);
-                                self.internal_error_execution();
+                                if get_global!(self).errors_since(error_mark) == 0 {
+                                    self.internal_error_execution();
+                                }
                             }
                             Ok(value) => {
                                 values.push(ParamPair::new_moved(
@@ -463,9 +643,9 @@ impl Datamodel for NullDatamodel {
         // nothing to do
     }
 
-    fn assign(&mut self, _left_expr: &Data, _right_expr: &Data) -> bool {
+    fn assign(&mut self, left_expr: &Data, _right_expr: &Data) -> bool {
         // nothing to do
-        true
+        self.check_assign_location(left_expr)
     }
 
     fn get_by_location(&mut self, _name: &str) -> Result<DataArc, String> {
@@ -475,7 +655,11 @@ impl Datamodel for NullDatamodel {
     fn clear(self: &mut NullDatamodel) {}
 
     fn log(self: &mut NullDatamodel, msg: &str) {
-        println!("{}", msg);
+        let sink = get_global!(self).log_sink.clone();
+        match sink {
+            Some(sink) => sink.log(msg),
+            None => println!("{}", msg),
+        }
     }
 
     fn execute(&mut self, _script: &Data) -> Result<DataArc, String> {
@@ -693,9 +877,11 @@ pub fn operation_multiply(left: &Data, right: &Data) -> Data {
 pub fn operation_divide(left: &Data, right: &Data) -> Data {
     if left.is_numeric() && right.is_numeric() {
         let right_value = right.as_number();
+        if right_value == 0f64 {
+            return Data::Error("Division by zero".to_string());
+        }
         let r = left.as_number() / right_value;
         if r.is_nan() {
-            // This covers also division by 0.
             Data::Error("Result of '/' is NaN".to_string())
         } else {
             Data::Double(r)
@@ -708,6 +894,9 @@ pub fn operation_divide(left: &Data, right: &Data) -> Data {
 /// Implements a "%" modulus (remainder) operation on Data items.
 pub fn operation_modulus(left: &Data, right: &Data) -> Data {
     if left.is_numeric() && right.is_numeric() {
+        if right.as_number() == 0f64 {
+            return Data::Error("Division by zero in '%'".to_string());
+        }
         match (left, right) {
             (Data::Double(d1), Data::Double(d2)) => Data::Double(d1 % d2),
             (Data::Integer(d1), Data::Double(d2)) => Data::Double((*d1 as f64) % d2),
@@ -0,0 +1,783 @@
+//! Implements the SCXML XPath Data model.\
+//! Included if feature "xpath" is enabled.\
+//! The data is kept in a XML document tree. Each \<data\> element is stored as
+//! element "\<data id='name'\>" and can be accessed in expressions via the variable "$name".\
+//! Location expressions are XPath location paths, values are XPath expressions.\
+//! See [GitHub:sxd-xpath](https://github.com/shepmaster/sxd-xpath).
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::sync::Arc;
+
+use sxd_document::dom::{ChildOfElement, Document, Element};
+use sxd_document::Package;
+use sxd_xpath::context::Evaluation;
+use sxd_xpath::function::{Args, Error as FunctionError, Function};
+use sxd_xpath::nodeset::{Node, Nodeset};
+use sxd_xpath::{Context, Factory, Value};
+
+#[cfg(feature = "Debug")]
+use crate::common::debug;
+use crate::common::error;
+use crate::datamodel::{
+    convert_event_data_default, create_data_arc, Data, DataArc, Datamodel, DatamodelFactory, GlobalDataArc,
+    EVENT_VARIABLE_FIELD_DATA, EVENT_VARIABLE_FIELD_INVOKE_ID, EVENT_VARIABLE_FIELD_NAME, EVENT_VARIABLE_FIELD_ORIGIN,
+    EVENT_VARIABLE_FIELD_ORIGIN_TYPE, EVENT_VARIABLE_FIELD_SEND_ID, EVENT_VARIABLE_FIELD_TYPE, EVENT_VARIABLE_NAME,
+};
+use crate::event_io_processor::SYS_IO_PROCESSORS;
+use crate::executable_content::execute_element;
+use crate::fsm::{Event, ExecutableContentId, Fsm, StateId};
+
+pub const XPATH_DATAMODEL: &str = "XPATH";
+pub const XPATH_DATAMODEL_LC: &str = "xpath";
+
+const TAG_DATAMODEL: &str = "datamodel";
+const TAG_DATA: &str = "data";
+const TAG_ITEM: &str = "item";
+const ATTR_ID: &str = "id";
+
+/// Owned copy of a XML node, independent of the document.
+#[derive(Debug, Clone, PartialEq)]
+enum XmlNode {
+    Element {
+        name: String,
+        attributes: Vec<(String, String)>,
+        children: Vec<XmlNode>,
+    },
+    Text(String),
+}
+
+impl XmlNode {
+    fn from_element(element: Element) -> XmlNode {
+        XmlNode::Element {
+            name: element.name().local_part().to_string(),
+            attributes: element
+                .attributes()
+                .iter()
+                .map(|a| (a.name().local_part().to_string(), a.value().to_string()))
+                .collect(),
+            children: element
+                .children()
+                .into_iter()
+                .filter_map(|child| match child {
+                    ChildOfElement::Element(e) => Some(XmlNode::from_element(e)),
+                    ChildOfElement::Text(t) => Some(XmlNode::Text(t.text().to_string())),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Copies the nodes of a node-set. The "\<data\>" elements of variables are replaced by their children,
+    /// so that "$a" copies the value of "a".
+    fn from_nodeset(nodeset: &Nodeset) -> Vec<XmlNode> {
+        let mut nodes = Vec::new();
+        for node in nodeset.document_order() {
+            match node {
+                Node::Element(e) => {
+                    if is_variable_element(e) {
+                        if let XmlNode::Element { children, .. } = XmlNode::from_element(e) {
+                            nodes.extend(children);
+                        }
+                    } else {
+                        nodes.push(XmlNode::from_element(e))
+                    }
+                }
+                Node::Root(_) => {}
+                other => nodes.push(XmlNode::Text(other.string_value())),
+            }
+        }
+        nodes
+    }
+
+    /// Converts data to nodes. Maps are converted to elements with the keys as name,
+    /// array members to "\<item\>" elements.
+    fn from_data(data: &Data) -> Vec<XmlNode> {
+        match data {
+            Data::Map(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                keys.iter()
+                    .map(|key| XmlNode::Element {
+                        name: key.to_string(),
+                        attributes: Vec::new(),
+                        children: XmlNode::from_data(map.get(*key).unwrap().lock().unwrap().deref()),
+                    })
+                    .collect()
+            }
+            Data::Array(array) => array
+                .iter()
+                .map(|item| XmlNode::Element {
+                    name: TAG_ITEM.to_string(),
+                    attributes: Vec::new(),
+                    children: XmlNode::from_data(item.lock().unwrap().deref()),
+                })
+                .collect(),
+            Data::Null() | Data::None() | Data::Error(_) => Vec::new(),
+            Data::Source(source) => vec![XmlNode::Text(source.source.clone())],
+            _ => vec![XmlNode::Text(data.to_string())],
+        }
+    }
+
+    /// Parses a XML fragment, e.g. the content of a \<data\> element.
+    fn parse_fragment(xml: &str) -> Result<Vec<XmlNode>, String> {
+        let package = sxd_document::parser::parse(format!("<{}>{}</{}>", TAG_DATA, xml, TAG_DATA).as_str())
+            .map_err(|err| format!("Can't parse XML '{}': {:?}", xml, err))?;
+        let document = package.as_document();
+        match document.root().children().first().and_then(|c| c.element()) {
+            Some(root) => match XmlNode::from_element(root) {
+                XmlNode::Element { children, .. } => Ok(children),
+                XmlNode::Text(_) => Ok(Vec::new()),
+            },
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn append_to(&self, document: Document, parent: Element) {
+        match self {
+            XmlNode::Element {
+                name,
+                attributes,
+                children,
+            } => {
+                let element = document.create_element(name.as_str());
+                for (name, value) in attributes {
+                    element.set_attribute_value(name.as_str(), value.as_str());
+                }
+                for child in children {
+                    child.append_to(document, element);
+                }
+                parent.append_child(element);
+            }
+            XmlNode::Text(text) => parent.append_child(document.create_text(text.as_str())),
+        }
+    }
+}
+
+/// Result of an evaluation, independent of the document.
+#[derive(Debug, Clone, PartialEq)]
+enum XPathValue {
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    Nodes(Vec<XmlNode>, String),
+}
+
+impl XPathValue {
+    fn from_value(value: &Value) -> XPathValue {
+        match value {
+            Value::Boolean(b) => XPathValue::Boolean(*b),
+            Value::Number(n) => XPathValue::Number(*n),
+            Value::String(s) => XPathValue::String(s.clone()),
+            Value::Nodeset(nodeset) => XPathValue::Nodes(XmlNode::from_nodeset(nodeset), value.string()),
+        }
+    }
+
+    fn to_nodes(&self) -> Vec<XmlNode> {
+        match self {
+            XPathValue::Nodes(nodes, _) => nodes.clone(),
+            XPathValue::Boolean(b) => vec![XmlNode::Text(b.to_string())],
+            XPathValue::Number(n) => vec![XmlNode::Text(Value::Number(*n).string())],
+            XPathValue::String(s) => vec![XmlNode::Text(s.clone())],
+        }
+    }
+
+    fn to_data(&self) -> Data {
+        match self {
+            XPathValue::Boolean(b) => Data::Boolean(*b),
+            XPathValue::Number(n) => Data::Double(*n),
+            XPathValue::String(s) => Data::String(s.clone()),
+            XPathValue::Nodes(_, string_value) => Data::String(string_value.clone()),
+        }
+    }
+}
+
+/// Checks if the name can be used as name of an element, see [XmlNode::from_data].\
+/// Namespace prefixes are not supported.
+fn is_element_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+        }
+        _ => false,
+    }
+}
+
+fn is_variable_element(element: Element) -> bool {
+    element.name().local_part() == TAG_DATA
+        && match element.parent() {
+            Some(sxd_document::dom::ParentOfChild::Element(parent)) => parent.name().local_part() == TAG_DATAMODEL,
+            _ => false,
+        }
+}
+
+/// Implements the mandatory SCXML-Datamodel function "In".
+struct InFunction {
+    global_data: GlobalDataArc,
+    state_name_to_id: Arc<HashMap<String, StateId>>,
+}
+
+impl Function for InFunction {
+    fn evaluate<'c, 'd>(
+        &self,
+        _context: &Evaluation<'c, 'd>,
+        args: Vec<Value<'d>>,
+    ) -> Result<Value<'d>, FunctionError> {
+        let mut args = Args(args);
+        args.exactly(1)?;
+        let state_name = args.pop_string()?;
+        let r = match self.state_name_to_id.get(&state_name) {
+            None => false,
+            Some(state_id) => self
+                .global_data
+                .lock()
+                .unwrap()
+                .configuration
+                .data
+                .contains(state_id),
+        };
+        Ok(Value::Boolean(r))
+    }
+}
+
+pub struct XPathDatamodel {
+    pub global_data: GlobalDataArc,
+    package: Package,
+    factory: Factory,
+    read_only: HashSet<String>,
+    state_name_to_id: Arc<HashMap<String, StateId>>,
+}
+
+pub struct XPathDatamodelFactory {}
+
+impl DatamodelFactory for XPathDatamodelFactory {
+    fn create(&mut self, global_data: GlobalDataArc, _options: &HashMap<String, String>) -> Box<dyn Datamodel> {
+        Box::new(XPathDatamodel::new(global_data))
+    }
+}
+
+impl XPathDatamodel {
+    pub fn new(global_data: GlobalDataArc) -> XPathDatamodel {
+        let package = Package::new();
+        {
+            let document = package.as_document();
+            let datamodel = document.create_element(TAG_DATAMODEL);
+            document.root().append_child(datamodel);
+        }
+        XPathDatamodel {
+            global_data,
+            package,
+            factory: Factory::new(),
+            read_only: HashSet::new(),
+            state_name_to_id: Arc::new(HashMap::new()),
+        }
+    }
+
+    fn datamodel_element(document: Document) -> Element {
+        document.root().children()[0].element().unwrap()
+    }
+
+    fn find_variable<'d>(document: Document<'d>, name: &str) -> Option<Element<'d>> {
+        Self::datamodel_element(document)
+            .children()
+            .into_iter()
+            .filter_map(|c| c.element())
+            .find(|e| e.attribute_value(ATTR_ID) == Some(name))
+    }
+
+    /// Sets the content of a variable. Creates the variable if "allow_undefined" is true.\
+    /// Returns false if the variable doesn't exist or is read-only.
+    fn set_variable(&mut self, name: &str, nodes: &[XmlNode], allow_undefined: bool) -> bool {
+        if self.read_only.contains(name) {
+            return false;
+        }
+        let document = self.package.as_document();
+        let element = match Self::find_variable(document, name) {
+            Some(element) => element,
+            None => {
+                if !allow_undefined {
+                    return false;
+                }
+                let element = document.create_element(TAG_DATA);
+                element.set_attribute_value(ATTR_ID, name);
+                Self::datamodel_element(document).append_child(element);
+                element
+            }
+        };
+        element.clear_children();
+        for node in nodes {
+            node.append_to(document, element);
+        }
+        true
+    }
+
+    fn create_context<'d>(&self, document: Document<'d>) -> Context<'d> {
+        let mut context = Context::new();
+        for element in Self::datamodel_element(document)
+            .children()
+            .into_iter()
+            .filter_map(|c| c.element())
+        {
+            if let Some(id) = element.attribute_value(ATTR_ID) {
+                let mut nodeset = Nodeset::new();
+                nodeset.add(element);
+                context.set_variable(id, Value::Nodeset(nodeset));
+            }
+        }
+        context.set_function(
+            "In",
+            InFunction {
+                global_data: self.global_data.clone(),
+                state_name_to_id: self.state_name_to_id.clone(),
+            },
+        );
+        context
+    }
+
+    /// Evaluates an expression. An empty expression results in an empty node-set.
+    fn evaluate(&self, expression: &str) -> Result<XPathValue, String> {
+        let xpath = match self.factory.build(expression) {
+            Ok(Some(xpath)) => xpath,
+            Ok(None) => return Ok(XPathValue::Nodes(Vec::new(), String::new())),
+            Err(err) => return Err(format!("Can't parse '{}': {}", expression, err)),
+        };
+        let document = self.package.as_document();
+        let context = self.create_context(document);
+        match xpath.evaluate(&context, document.root()) {
+            Ok(value) => Ok(XPathValue::from_value(&value)),
+            Err(err) => Err(format!("Can't evaluate '{}': {}", expression, err)),
+        }
+    }
+
+    /// Gets the value of some data item. Sources that start with "<" are XML content, other sources are expressions.
+    fn value_of(&self, data: &Data) -> Result<XPathValue, String> {
+        match data {
+            Data::Source(source) => {
+                let src = source.source.trim();
+                // The reader gives the content of <assign> as quoted string.
+                let src = match src.strip_prefix("\"<").and_then(|s| s.strip_suffix('"')) {
+                    Some(content) => format!("<{}", content.replace("\\\"", "\"")),
+                    None => src.to_string(),
+                };
+                if src.starts_with('<') {
+                    let nodes = XmlNode::parse_fragment(src.as_str())?;
+                    Ok(XPathValue::Nodes(nodes, String::new()))
+                } else {
+                    self.evaluate(src.as_str())
+                }
+            }
+            Data::Error(err) => Err(err.clone()),
+            _ => Ok(XPathValue::Nodes(
+                XmlNode::from_data(data),
+                data.to_string(),
+            )),
+        }
+    }
+
+    /// Replaces the children of the node that is selected by the location.
+    fn assign_internal(&mut self, location: &str, value: &XPathValue) -> Result<(), String> {
+        let xpath = match self.factory.build(location) {
+            Ok(Some(xpath)) => xpath,
+            Ok(None) => return Err("Empty location".to_string()),
+            Err(err) => return Err(format!("Can't parse location '{}': {}", location, err)),
+        };
+        let document = self.package.as_document();
+        let context = self.create_context(document);
+        let target = match xpath.evaluate(&context, document.root()) {
+            Ok(Value::Nodeset(nodeset)) => nodeset.document_order_first(),
+            Ok(_) => None,
+            Err(err) => return Err(format!("Can't evaluate location '{}': {}", location, err)),
+        };
+        match target {
+            Some(Node::Element(element)) => {
+                if is_variable_element(element)
+                    && self
+                        .read_only
+                        .contains(element.attribute_value(ATTR_ID).unwrap_or_default())
+                {
+                    return Err(format!("Location '{}' is read-only", location));
+                }
+                element.clear_children();
+                for node in value.to_nodes() {
+                    node.append_to(document, element);
+                }
+                Ok(())
+            }
+            Some(Node::Attribute(attribute)) => {
+                if let Some(element) = attribute.parent() {
+                    element.set_attribute_value(attribute.name(), value.to_data().to_string().as_str());
+                }
+                Ok(())
+            }
+            _ => Err(format!("Location '{}' doesn't select an element", location)),
+        }
+    }
+
+    fn execute_internal(&mut self, script: &Data, handle_error: bool) -> Result<DataArc, String> {
+        match self.value_of(script) {
+            Ok(value) => Ok(create_data_arc(value.to_data())),
+            Err(err) => {
+                error!("Script Error: {} => {}", script, err);
+                if handle_error {
+                    self.internal_error_execution();
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Datamodel for XPathDatamodel {
+    fn global(&mut self) -> &mut GlobalDataArc {
+        &mut self.global_data
+    }
+    fn global_s(&self) -> &GlobalDataArc {
+        &self.global_data
+    }
+
+    fn get_name(&self) -> &str {
+        XPATH_DATAMODEL
+    }
+
+    fn add_functions(&mut self, fsm: &mut Fsm) {
+        let mut state_name_to_id = HashMap::new();
+        for state in fsm.states.as_slice() {
+            state_name_to_id.insert(state.name.clone(), state.id);
+        }
+        self.state_name_to_id = Arc::new(state_name_to_id);
+    }
+
+    fn set_ioprocessors(&mut self) {
+        let session_id = self.global_s().lock().unwrap().session_id;
+        let mut io_processors = HashMap::new();
+        for (name, processor) in &self.global_data.lock().unwrap().io_processors {
+            let mut processor_data = HashMap::new();
+            processor_data.insert(
+                "location".to_string(),
+                create_data_arc(Data::String(
+                    processor.lock().unwrap().get_location(session_id),
+                )),
+            );
+            io_processors.insert(name.clone(), create_data_arc(Data::Map(processor_data)));
+        }
+        self.initialize_read_only(SYS_IO_PROCESSORS, Data::Map(io_processors));
+    }
+
+    fn set_from_state_data(&mut self, data: &HashMap<String, DataArc>, set_data: bool) {
+        for (name, value) in data {
+            if set_data {
+                let value = value.lock().unwrap().clone();
+                match self.value_of(&value) {
+                    Ok(v) => {
+                        self.set_variable(name, &v.to_nodes(), true);
+                    }
+                    Err(err) => {
+                        error!("Error on Initialize '{}': {}", name, err);
+                        // W3C says:
+                        // If the value specified for a <data> element (by 'src', children, or
+                        // the environment) is not a legal data value, the SCXML Processor MUST
+                        // raise place error.execution in the internal event queue and MUST
+                        // create an empty data element in the data model with the specified id.
+                        self.set_variable(name, &[], true);
+                        self.internal_error_execution();
+                    }
+                }
+            } else {
+                self.set_variable(name, &[], true);
+            }
+        }
+    }
+
+    fn initialize_read_only_arc(&mut self, name: &str, value: DataArc) {
+        self.read_only.remove(name);
+        let nodes = XmlNode::from_data(value.lock().unwrap().deref());
+        self.set_variable(name, &nodes, true);
+        self.read_only.insert(name.to_string());
+    }
+
+    fn set_arc(&mut self, name: &str, data: DataArc, allow_undefined: bool) {
+        let nodes = XmlNode::from_data(data.lock().unwrap().deref());
+        self.set_variable(name, &nodes, allow_undefined);
+    }
+
+    /// W3C: The data of the event is available as "$_event/data". Each parameter is a
+    /// "\<data id='name'\>" element, content is parsed as XML if possible.
+    /// Event data is stored as XML. Keys of maps have to be valid element names and strings that
+    /// start with "<" have to be well-formed XML.
+    fn convert_event_data(&mut self, data: &Data) -> Result<Data, String> {
+        match data {
+            Data::String(s) if s.trim_start().starts_with('<') => {
+                XmlNode::parse_fragment(s)?;
+                Ok(data.clone())
+            }
+            Data::Map(map) => match map.keys().find(|key| !is_element_name(key)) {
+                Some(key) => Err(format!("'{}' is not a valid element name", key)),
+                None => convert_event_data_default(self, data),
+            },
+            _ => convert_event_data_default(self, data),
+        }
+    }
+
+    fn set_event(&mut self, event: &Event) {
+        let mut data_nodes = Vec::new();
+        if let Some(params) = &event.param_values {
+            for param in params {
+                let value = match self.value_of(&param.value) {
+                    Ok(value) => value.to_nodes(),
+                    Err(err) => {
+                        error!(
+                            "Can't set event data '{} = {}': {}",
+                            param.name, param.value, err
+                        );
+                        Vec::new()
+                    }
+                };
+                data_nodes.push(XmlNode::Element {
+                    name: TAG_DATA.to_string(),
+                    attributes: vec![(ATTR_ID.to_string(), param.name.clone())],
+                    children: value,
+                });
+            }
+        } else if let Some(content) = &event.content {
+            data_nodes = match content {
+                Data::String(s) if s.trim_start().starts_with('<') => {
+                    XmlNode::parse_fragment(s).unwrap_or_else(|_| vec![XmlNode::Text(s.clone())])
+                }
+                _ => match self.value_of(content) {
+                    Ok(value) => value.to_nodes(),
+                    Err(_) => XmlNode::from_data(content),
+                },
+            };
+        }
+
+        let field = |name: &str, value: &Option<String>| XmlNode::Element {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            children: value.iter().map(|v| XmlNode::Text(v.clone())).collect(),
+        };
+        let event_nodes = vec![
+            field(EVENT_VARIABLE_FIELD_NAME, &Some(event.name.clone())),
+            field(
+                EVENT_VARIABLE_FIELD_TYPE,
+                &Some(event.etype.name().to_string()),
+            ),
+            field(EVENT_VARIABLE_FIELD_SEND_ID, &event.sendid),
+            field(EVENT_VARIABLE_FIELD_ORIGIN, &event.origin),
+            field(EVENT_VARIABLE_FIELD_ORIGIN_TYPE, &event.origin_type),
+            field(EVENT_VARIABLE_FIELD_INVOKE_ID, &event.invoke_id),
+            XmlNode::Element {
+                name: EVENT_VARIABLE_FIELD_DATA.to_string(),
+                attributes: Vec::new(),
+                children: data_nodes,
+            },
+        ];
+        self.read_only.remove(EVENT_VARIABLE_NAME);
+        self.set_variable(EVENT_VARIABLE_NAME, &event_nodes, true);
+        self.read_only.insert(EVENT_VARIABLE_NAME.to_string());
+    }
+
+    fn assign(&mut self, left_expr: &Data, right_expr: &Data) -> bool {
+        let location = left_expr.as_script();
+        // Check the location without the "$" of the variable.
+        if !self.check_assign_location(&Data::String(
+            location.trim().trim_start_matches('$').to_string(),
+        )) {
+            return false;
+        }
+        let r = match self.value_of(right_expr) {
+            Ok(value) => self.assign_internal(location.as_str(), &value),
+            Err(err) => Err(err),
+        };
+        match r {
+            Ok(_) => true,
+            Err(err) => {
+                // W3C says:\
+                // If the location expression does not denote a valid location in the data model or
+                // if the value specified (by 'expr' or children) is not a legal value for the
+                // location specified, the SCXML Processor must place the error 'error.execution'
+                // in the internal event queue.
+                self.log(format!("Can't assign {}={}: {}", location, right_expr, err).as_str());
+                self.internal_error_execution();
+                false
+            }
+        }
+    }
+
+    fn get_by_location(&mut self, location: &str) -> Result<DataArc, String> {
+        match self.evaluate(location) {
+            Ok(value) => Ok(create_data_arc(value.to_data())),
+            Err(err) => {
+                self.internal_error_execution();
+                Err(err)
+            }
+        }
+    }
+
+    fn clear(&mut self) {}
+
+    fn execute(&mut self, script: &Data) -> Result<DataArc, String> {
+        self.execute_internal(script, true)
+    }
+
+    /// Iterates over the nodes selected by the array expression. The item variable gets a copy of the node.
+    fn execute_for_each(
+        &mut self,
+        array_expression: &Data,
+        item_name: &str,
+        index: &str,
+        execute_body: &mut dyn FnMut(&mut dyn Datamodel) -> bool,
+    ) -> bool {
+        #[cfg(feature = "Debug")]
+        debug!("ForEach: array: {}", array_expression);
+        match self.value_of(array_expression) {
+            Ok(XPathValue::Nodes(nodes, _)) => {
+                for (idx, node) in nodes.iter().enumerate() {
+                    self.set_variable(item_name, std::slice::from_ref(node), true);
+                    if !index.is_empty() {
+                        self.set_variable(index, &[XmlNode::Text(idx.to_string())], true);
+                    }
+                    if !execute_body(self) {
+                        return false;
+                    }
+                }
+                true
+            }
+            Ok(_) => {
+                self.log("Resulting value is not a node-set.");
+                self.internal_error_execution();
+                false
+            }
+            Err(e) => {
+                self.log(&e);
+                self.internal_error_execution();
+                false
+            }
+        }
+    }
+
+    /// W3C: The result of the expression is converted to a boolean, as by the XPath function "boolean()".
+    fn execute_condition(&mut self, script: &Data) -> Result<bool, String> {
+        let r = match self.value_of(script)? {
+            XPathValue::Boolean(b) => b,
+            XPathValue::Number(n) => n != 0.0 && !n.is_nan(),
+            XPathValue::String(s) => !s.is_empty(),
+            XPathValue::Nodes(nodes, _) => !nodes.is_empty(),
+        };
+        #[cfg(feature = "Debug")]
+        debug!("execute_condition: {} => {:?}", script, r);
+        Ok(r)
+    }
+
+    #[allow(non_snake_case)]
+    fn executeContent(&mut self, fsm: &Fsm, content_id: ExecutableContentId) -> bool {
+        let ec = fsm.executableContent.get(&content_id);
+        for e in ec.unwrap().iter() {
+            if !execute_element(e.as_ref(), self, fsm) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::datamodel::xpath::XPathDatamodel;
+    use crate::datamodel::{create_data_arc, create_global_data_arc, Data, Datamodel, SourceCode};
+    #[cfg(feature = "xml")]
+    use crate::scxml_reader;
+    #[cfg(all(feature = "xml", feature = "Trace"))]
+    use crate::tracer::TraceMode;
+    use std::collections::HashMap;
+
+    fn source(s: &str) -> Data {
+        Data::Source(SourceCode::new(s, 0))
+    }
+
+    #[test]
+    fn location_and_value_expressions() {
+        let mut dm = XPathDatamodel::new(create_global_data_arc());
+        let mut data = HashMap::new();
+        data.insert(
+            "cart".to_string(),
+            create_data_arc(source("<items><item>1</item><item>2</item></items>")),
+        );
+        dm.set_from_state_data(&data, true);
+
+        assert!(dm.assign(&source("$cart/items/item[1]"), &source("40")));
+        let sum = dm.execute(&source("sum($cart/items/item)")).unwrap();
+        assert_eq!(*sum.lock().unwrap(), Data::Double(42.0));
+        assert!(dm
+            .execute_condition(&source("count($cart/items/item) = 2"))
+            .unwrap());
+        assert!(!dm.assign(&source("$unknown"), &source("1")));
+    }
+
+    #[test]
+    fn inbound_data_must_be_representable_as_xml() {
+        let mut dm = XPathDatamodel::new(create_global_data_arc());
+
+        let xml = Data::String("<a><b>1</b></a>".to_string());
+        assert_eq!(dm.convert_event_data(&xml), Ok(xml));
+        assert!(dm
+            .convert_event_data(&Data::String("<a><b>1</a>".to_string()))
+            .is_err());
+        // Plain text is not parsed.
+        let text = Data::String("1 < 2".to_string());
+        assert_eq!(dm.convert_event_data(&text), Ok(text));
+
+        let mut map = HashMap::new();
+        map.insert("item-1".to_string(), create_data_arc(Data::Integer(1)));
+        assert!(dm.convert_event_data(&Data::Map(map.clone())).is_ok());
+
+        map.insert("1st item".to_string(), create_data_arc(Data::Integer(2)));
+        let nested = Data::Array(vec![create_data_arc(Data::Map(map))]);
+        let err = dm.convert_event_data(&nested).err().unwrap();
+        assert!(
+            err.contains("'1st item' is not a valid element name"),
+            "{}",
+            err
+        );
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn xpath_datamodel_runs_fsm() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s1' datamodel='xpath'>
+              <datamodel>
+                <data id='count' expr='1'/>
+                <data id='list'><v>a</v><v>b</v></data>
+                <data id='joined'/>
+              </datamodel>
+              <state id='s1'>
+                <onentry>
+                  <assign location='$count' expr='$count + 1'/>
+                  <foreach array='$list/v' item='v'>
+                    <assign location='$joined' expr='concat($joined, $v)'/>
+                  </foreach>
+                  <send event='go'><param name='p' expr='$count * 10'/></send>
+                </onentry>
+                <transition event='go' cond="In('s1') and $count = 2 and $joined = 'ab' and $_event/data/data[@id='p'] = 20" target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(crate::test::run_test_manual(
+            "xpath_datamodel_runs_fsm",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        ));
+    }
+}
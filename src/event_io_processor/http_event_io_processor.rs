@@ -2,7 +2,12 @@
 //! See [W3C:SCXML - Basic HTTP Event I/O Processor](/doc/W3C_SCXML_2024_07_13/index.html#BasicHTTPEventProcessor).\
 //! Included if feature "BasicHttpEventIOProcessor" is enabled.\
 //! This implementation is based on Rocket, but can be used as template for implementations based on other frameworks.\
-//! The event input form is only added for test & de debugging and should not be used in production.
+//! The event input form is only added for test & de debugging and should not be used in production.\
+//! Sessions get the processors of their executor. To send via HTTP, create the executor with
+//! `FsmExecutor::new_with_io_processor` or add a processor via `FsmExecutor::add_processor`
+//! (`BasicHTTPEventIOProcessor::new_send_only` creates one without http server).
+//! In \<send\> the processor can be selected by the full type URI, "basichttp" or "http".\
+//! As extension, events can also be sent and received as JSON, see [event_to_json].
 
 use rocket::response::content::RawHtml;
 use rocket::{post, Shutdown};
@@ -15,8 +20,9 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::net::IpAddr;
 
+use crate::common::warn;
 use crate::common::{debug, error, info};
-use crate::datamodel::{Data, GlobalDataArc, BASIC_HTTP_EVENT_PROCESSOR};
+use crate::datamodel::{create_data_arc, Data, GlobalDataArc, BASIC_HTTP_EVENT_PROCESSOR};
 use crate::event_io_processor::{EventIOProcessor, ExternalQueueContainer};
 use crate::fsm::{Event, ParamPair, SessionId};
 use crate::fsm_executor::ExecutorStateArc;
@@ -24,15 +30,32 @@ use crate::fsm_executor::ExecutorStateArc;
 pub const SCXML_EVENT_NAME: &str = "_scxmleventname";
 pub const SCXML_EVENT_CONTENT: &str = "_content";
 
+pub const JSON_EVENT_NAME: &str = "name";
+pub const JSON_EVENT_DATA: &str = "data";
+pub const JSON_EVENT_CONTENT: &str = "content";
+
+/// Format of the body of requests that are sent by the processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HttpBodyFormat {
+    /// "application/x-www-form-urlencoded", as described by the W3C recommendation.
+    #[default]
+    Form,
+    /// Extension: "application/json", see [event_to_json].
+    Json,
+}
+
 /// IO Processor to server basic http request. \
 /// See /doc/W3C_SCXML_2024_07_13/index.html#BasicHTTPEventProcessor \
 /// If the feature is active, this IO Processor is automatically added by FsmExecutor.
 #[derive(Debug, Clone)]
 pub struct BasicHTTPEventIOProcessor {
-    pub shutdown_guard: Shutdown,
+    /// Shutdown of the http server, None if the processor can only send.
+    pub shutdown_guard: Option<Shutdown>,
     pub location: String,
     pub queues: ExternalQueueContainer,
     pub executor_state: ExecutorStateArc,
+    /// The format of the requests that are sent. Received requests can use any format.
+    pub send_format: HttpBodyFormat,
 }
 
 /// The parsed payload of a http request
@@ -43,7 +66,7 @@ struct Message {
     pub session: SessionId,
 }
 
-#[post("/scxml/<sessionid>", data = "<params>")]
+#[post("/scxml/<sessionid>", data = "<params>", rank = 2)]
 fn rocket_receive_event(
     sessionid: u32,
     params: rocket::form::Form<HashMap<String, String>>,
@@ -51,56 +74,84 @@ fn rocket_receive_event(
 ) -> (rocket::http::Status, String) {
     let form_data = params.into_inner();
 
+    let mut event = Event::new_external();
+    let mut event_name: Option<String> = None;
+
+    for (name, value) in form_data {
+        match name.as_str() {
+            SCXML_EVENT_NAME => {
+                event_name = Some(value);
+            }
+            SCXML_EVENT_CONTENT => {
+                event.content = Some(Data::String(value));
+            }
+            _ => {
+                if event.param_values.is_none() {
+                    event.param_values = Some(Vec::new());
+                }
+                let pair = ParamPair {
+                    name,
+                    value: Data::String(value),
+                };
+                event.param_values.as_mut().unwrap().push(pair);
+            }
+        }
+    }
+    match event_name {
+        None => (
+            rocket::http::Status::BadRequest,
+            format!("Missing argument '{}'", SCXML_EVENT_NAME),
+        ),
+        Some(name) => {
+            event.name = name;
+            deliver_event(sessionid, event, executor_state.inner())
+        }
+    }
+}
+
+/// Extension: Receives events in the format of [event_to_json].\
+/// If the body can't be decoded, "error.communication" is sent to the session, with the reason as content.
+#[post("/scxml/<sessionid>", format = "json", data = "<body>")]
+fn rocket_receive_json_event(
+    sessionid: u32,
+    body: String,
+    executor_state: &rocket::State<ExecutorStateArc>,
+) -> (rocket::http::Status, String) {
+    receive_json_event(sessionid, &body, executor_state.inner())
+}
+
+fn receive_json_event(sessionid: u32, body: &str, executor_state: &ExecutorStateArc) -> (rocket::http::Status, String) {
+    match event_from_json(body) {
+        Ok(event) => deliver_event(sessionid, event, executor_state),
+        Err(err) => {
+            error!("Failed to decode JSON event: {}", err);
+            let mut event = Event::error_communication_for(&None, &None);
+            event.origin_type = Some(BASIC_HTTP_EVENT_PROCESSOR.to_string());
+            event.content = Some(Data::String(err.clone()));
+            let _ = deliver_event(sessionid, event, executor_state);
+            (rocket::http::Status::BadRequest, err)
+        }
+    }
+}
+
+/// Puts the event in the external queue of the session.
+fn deliver_event(sessionid: u32, event: Event, executor_state: &ExecutorStateArc) -> (rocket::http::Status, String) {
     match executor_state.arc.lock() {
-        Ok(state) => match state.sessions.get(&sessionid) {
+        Ok(state) => match state.sessions.get(&SessionId(sessionid)) {
             None => (
                 rocket::http::Status::BadRequest,
                 format!("Session {} not found", sessionid),
             ),
             Some(scxml_session) => {
-                let mut event = Event::new_external();
-
-                let mut event_name: Option<String> = None;
-
-                for (name, value) in form_data {
-                    match name.as_str() {
-                        SCXML_EVENT_NAME => {
-                            event_name = Some(value);
-                        }
-                        SCXML_EVENT_CONTENT => {
-                            event.content = Some(Data::String(value));
-                        }
-                        _ => {
-                            if event.param_values.is_none() {
-                                event.param_values = Some(Vec::new());
-                            }
-                            let pair = ParamPair {
-                                name,
-                                value: Data::String(value),
-                            };
-                            event.param_values.as_mut().unwrap().push(pair);
-                        }
-                    }
-                }
-                match event_name {
-                    None => (
-                        rocket::http::Status::BadRequest,
-                        format!("Missing argument '{}'", SCXML_EVENT_NAME),
-                    ),
-                    Some(name) => {
-                        event.name = name;
-
-                        debug!("Sending HTTP Event '{}' [{:?}]", event, event.param_values);
-                        match scxml_session.sender.send(Box::new(event)) {
-                            Ok(_) => (rocket::http::Status::Ok, "Event send".to_string()),
-                            Err(err) => {
-                                error!("Failed to Send Event: {}", err);
-                                (
-                                    rocket::http::Status::InternalServerError,
-                                    "Can't send".to_string(),
-                                )
-                            }
-                        }
+                debug!("Sending HTTP Event '{}' [{:?}]", event, event.param_values);
+                match scxml_session.sender.send(Box::new(event)) {
+                    Ok(_) => (rocket::http::Status::Ok, "Event send".to_string()),
+                    Err(err) => {
+                        error!("Failed to Send Event: {}", err);
+                        (
+                            rocket::http::Status::InternalServerError,
+                            "Can't send".to_string(),
+                        )
                     }
                 }
             }
@@ -115,6 +166,128 @@ fn rocket_receive_event(
     }
 }
 
+/// Extension: Serializes the name, the parameters and the content of the event as JSON:\
+/// `{"name":"<event name>","data":{"<parameter name>":<value>,...},"content":<content>}`\
+/// "data" is always written, "content" only if the event has content. If a parameter name is used more than
+/// once, all values are written as array in document order, as "_event.data" of the datamodels shows them.
+/// Values without JSON representation (e.g. errors) are written as null.
+pub fn event_to_json(event: &Event) -> String {
+    let mut data = serde_json::Map::new();
+    if let Some(parameters) = &event.param_values {
+        for (name, values) in ParamPair::group_by_name(parameters) {
+            let value = if values.len() == 1 {
+                data_to_json(values[0])
+            } else {
+                serde_json::Value::Array(values.into_iter().map(data_to_json).collect())
+            };
+            data.insert(name.to_string(), value);
+        }
+    }
+    let mut json = serde_json::Map::new();
+    json.insert(
+        JSON_EVENT_NAME.to_string(),
+        serde_json::Value::String(event.name.clone()),
+    );
+    json.insert(JSON_EVENT_DATA.to_string(), serde_json::Value::Object(data));
+    if let Some(content) = &event.content {
+        json.insert(JSON_EVENT_CONTENT.to_string(), data_to_json(content));
+    }
+    serde_json::Value::Object(json).to_string()
+}
+
+/// Extension: Creates an external event from JSON in the format of [event_to_json].\
+/// The members of "data" are the parameters of the event, "data" and "content" are optional.
+pub fn event_from_json(json: &str) -> Result<Event, String> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|err| format!("Illegal JSON. {}", err))?;
+    let serde_json::Value::Object(mut members) = value else {
+        return Err("JSON event is not an object".to_string());
+    };
+    let mut event = Event::new_external();
+    event.name = match members.remove(JSON_EVENT_NAME) {
+        Some(serde_json::Value::String(name)) if !name.is_empty() => name,
+        _ => {
+            return Err(format!(
+                "Missing member '{}' of JSON event",
+                JSON_EVENT_NAME
+            ))
+        }
+    };
+    match members.remove(JSON_EVENT_DATA) {
+        None | Some(serde_json::Value::Null) => {}
+        Some(serde_json::Value::Object(data)) => {
+            if !data.is_empty() {
+                event.param_values = Some(
+                    data.into_iter()
+                        .map(|(name, value)| ParamPair::new_moved(name, json_to_data(value)))
+                        .collect(),
+                );
+            }
+        }
+        Some(_) => {
+            return Err(format!(
+                "Member '{}' of JSON event is not an object",
+                JSON_EVENT_DATA
+            ))
+        }
+    }
+    if let Some(content) = members.remove(JSON_EVENT_CONTENT) {
+        event.content = Some(json_to_data(content));
+    }
+    Ok(event)
+}
+
+fn data_to_json(data: &Data) -> serde_json::Value {
+    match data {
+        Data::Integer(value) => serde_json::Value::from(*value),
+        Data::Double(value) => serde_json::Number::from_f64(*value)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Data::String(value) => serde_json::Value::String(value.clone()),
+        Data::Boolean(value) => serde_json::Value::Bool(*value),
+        Data::Array(values) => serde_json::Value::Array(
+            values
+                .iter()
+                .map(|value| data_to_json(&value.lock().unwrap()))
+                .collect(),
+        ),
+        Data::Map(values) => serde_json::Value::Object(
+            values
+                .iter()
+                .map(|(key, value)| (key.clone(), data_to_json(&value.lock().unwrap())))
+                .collect(),
+        ),
+        Data::Null() | Data::None() => serde_json::Value::Null,
+        Data::Error(_) | Data::Source(_) => {
+            warn!("Value {} can't be serialized to JSON", data);
+            serde_json::Value::Null
+        }
+    }
+}
+
+fn json_to_data(value: serde_json::Value) -> Data {
+    match value {
+        serde_json::Value::Null => Data::Null(),
+        serde_json::Value::Bool(value) => Data::Boolean(value),
+        serde_json::Value::Number(value) => match value.as_i64() {
+            Some(value) => Data::Integer(value),
+            None => Data::Double(value.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(value) => Data::String(value),
+        serde_json::Value::Array(values) => Data::Array(
+            values
+                .into_iter()
+                .map(|value| create_data_arc(json_to_data(value)))
+                .collect(),
+        ),
+        serde_json::Value::Object(values) => Data::Map(
+            values
+                .into_iter()
+                .map(|(key, value)| (key, create_data_arc(json_to_data(value))))
+                .collect(),
+        ),
+    }
+}
+
 fn escape_html(text: &str) -> String {
     // Possibly not the optimized way, but easy to understand and without any dependencies
 
@@ -262,14 +435,19 @@ impl BasicHTTPEventIOProcessor {
         let figment = figment.merge(Config::release_default());
 
         let figment = figment
-            .merge(("port", 5555))
+            .merge(("port", port))
             .merge(("shutdown.ctrlc", false));
 
         let server = rocket::custom(figment)
             .manage(es_clone)
             .mount(
                 "/",
-                routes![rocket_welcome, rocket_receive_event, rocket_get_favicon],
+                routes![
+                    rocket_welcome,
+                    rocket_receive_event,
+                    rocket_receive_json_event,
+                    rocket_get_favicon
+                ],
             )
             .ignite()
             .await
@@ -280,19 +458,57 @@ impl BasicHTTPEventIOProcessor {
         info!("HTTP server started at {}:{}", ip_addr, port);
 
         BasicHTTPEventIOProcessor {
-            shutdown_guard: shutdown,
+            shutdown_guard: Some(shutdown),
             // The base uri for requests.
             location: format!("http://{}:{}/scxml/", location_name, port),
             queues: ExternalQueueContainer::new(),
             executor_state: execute_state,
+            send_format: HttpBodyFormat::Form,
+        }
+    }
+
+    /// Creates a processor that only sends events, without http server. Sessions can't receive events via http
+    /// and have no location.
+    pub fn new_send_only(execute_state: ExecutorStateArc) -> BasicHTTPEventIOProcessor {
+        BasicHTTPEventIOProcessor {
+            shutdown_guard: None,
+            location: String::new(),
+            queues: ExternalQueueContainer::new(),
+            executor_state: execute_state,
+            send_format: HttpBodyFormat::Form,
+        }
+    }
+
+    /// Puts "error.communication" in the internal queue if the request failed.
+    fn check_result(
+        &self,
+        global: &GlobalDataArc,
+        target: &str,
+        event: &Event,
+        r: Result<ureq::Response, ureq::Error>,
+    ) -> bool {
+        match r {
+            Ok(_) => true,
+            Err(err) => {
+                error!("Failed to send to {}. {}", target, err);
+                global
+                    .lock()
+                    .unwrap()
+                    .enqueue_internal(Event::error_communication(event));
+                false
+            }
         }
     }
 }
 
-const TYPES: &[&str] = &[BASIC_HTTP_EVENT_PROCESSOR, "basichttp"];
+const TYPES: &[&str] = &[BASIC_HTTP_EVENT_PROCESSOR, "basichttp", "http"];
 
 impl EventIOProcessor for BasicHTTPEventIOProcessor {
     fn get_location(&self, id: SessionId) -> String {
+        if self.location.is_empty() {
+            // Send-only processor.
+            return String::new();
+        }
         format!("{}{}", self.location, id)
     }
 
@@ -311,6 +527,7 @@ impl EventIOProcessor for BasicHTTPEventIOProcessor {
             location: self.location.clone(),
             queues: self.queues.clone(),
             executor_state: self.executor_state.clone(),
+            send_format: self.send_format,
         };
         Box::new(b)
     }
@@ -323,12 +540,31 @@ impl EventIOProcessor for BasicHTTPEventIOProcessor {
     /// reconstruction, the Processor MUST serialize the data to JSON. Otherwise, the Processor MAY use platform-specific
     /// methods (including JSON despite the loss of information) to serialize the data.\
     /// The Processor SHOULD provide a warning if the serialization entails loss of information or if it is unable to serialize at all.
-    fn send(&mut self, _global: &GlobalDataArc, target: &str, event: Event) -> bool {
+    /// If no target is given or the message can't be delivered, "error.communication" is
+    /// placed in the internal queue of the sending session.\
+    /// The body is written in the format given by [send_format](BasicHTTPEventIOProcessor::send_format).
+    fn send(&mut self, global: &GlobalDataArc, target: &str, event: Event) -> bool {
         #[cfg(feature = "Debug")]
         debug!("Send HTTP Event {}", event.name);
 
+        if target.is_empty() {
+            error!("Send HTTP Event {}: no target", event.name);
+            global
+                .lock()
+                .unwrap()
+                .enqueue_internal(Event::error_communication(&event));
+            return false;
+        }
+
+        if self.send_format == HttpBodyFormat::Json {
+            let r = ureq::post(target)
+                .set("Content-Type", "application/json")
+                .send_string(&event_to_json(&event));
+            return self.check_result(global, target, &event, r);
+        }
+
         let mut data = Vec::new();
-        data.push((SCXML_EVENT_NAME, event.name));
+        data.push((SCXML_EVENT_NAME, event.name.clone()));
         if let Some(parameters) = &event.param_values {
             for e in parameters {
                 data.push((e.name.as_str(), e.value.to_string()));
@@ -344,20 +580,240 @@ impl EventIOProcessor for BasicHTTPEventIOProcessor {
             .collect();
 
         let r = ureq::post(target).send_form(form_data.as_slice());
-
-        match r {
-            Ok(_) => {}
-            Err(err) => {
-                error!("Failed to send to {}. {}", target, err);
-            }
-        }
-        true
+        self.check_result(global, target, &event, r)
     }
 
     fn shutdown(&mut self) {
         info!("HTTP Event IO Processor shutdown...");
-        self.shutdown_guard.clone().notify();
+        if let Some(shutdown) = &self.shutdown_guard {
+            shutdown.clone().notify();
+        }
         // Shutdown all FSMs
         self.queues.shutdown();
     }
 }
+
+#[cfg(test)]
+#[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::time::Duration;
+
+    use crate::datamodel::Data;
+    use crate::event_io_processor::http_event_io_processor::{
+        event_from_json, event_to_json, BasicHTTPEventIOProcessor, HttpBodyFormat,
+    };
+    use crate::fsm::{Event, ParamPair};
+    use crate::fsm_executor::FsmExecutor;
+    use crate::scxml_reader;
+
+    /// Loopback endpoint that answers one request. Returns the port and a receiver for the request.
+    fn start_endpoint() -> (u16, Receiver<String>) {
+        let endpoint = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint_port = endpoint.local_addr().unwrap().port();
+        let (request_sender, request_receiver) = channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = endpoint.accept().unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut request = String::new();
+            let mut buffer = [0u8; 1024];
+            loop {
+                // Complete if the header and the body with the announced length is read.
+                if let Some(header_end) = request.find("\r\n\r\n") {
+                    let content_length = request[..header_end]
+                        .lines()
+                        .find_map(|line| {
+                            line.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|length| length.trim().parse::<usize>().unwrap_or(0))
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= header_end + 4 + content_length {
+                        break;
+                    }
+                }
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.push_str(&String::from_utf8_lossy(&buffer[..n])),
+                }
+            }
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+            let _ = request_sender.send(request);
+        });
+        (endpoint_port, request_receiver)
+    }
+
+    /// Runs a FSM that sends "hello" with some parameters to the endpoint.
+    fn send_to_endpoint(send_format: HttpBodyFormat) -> String {
+        let (endpoint_port, request_receiver) = start_endpoint();
+
+        let mut executor = FsmExecutor::new_without_io_processor();
+        let mut processor = BasicHTTPEventIOProcessor::new_send_only(executor.state.clone());
+        processor.send_format = send_format;
+        executor.add_processor(Box::new(processor));
+
+        let fsm = scxml_reader::parse_from_xml(format!(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry>
+                  <send type='http' target='http://127.0.0.1:{}/events' event='hello'>
+                    <param name='value' expr='42'/>
+                    <param name='list' expr='[1, "two"]'/>
+                  </send>
+                  <raise event='sent'/>
+                </onentry>
+                <transition event='sent' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#,
+            endpoint_port
+        ))
+        .unwrap();
+        let mut session = crate::fsm::start_fsm_with_data_and_finish_mode(
+            fsm,
+            crate::actions::ActionWrapper::new(),
+            Box::new(executor.clone()),
+            &Vec::new(),
+            crate::fsm::FinishMode::KEEP_CONFIGURATION,
+        );
+        let _ = session.thread.take().unwrap().join();
+        executor.shutdown();
+
+        assert_eq!(
+            session.global_data.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+        let request = request_receiver
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        assert!(request.starts_with("POST /events"), "{}", request);
+        request
+    }
+
+    #[test]
+    fn send_type_http_reaches_endpoint() {
+        let request = send_to_endpoint(HttpBodyFormat::Form);
+        assert!(request.contains("_scxmleventname=hello"), "{}", request);
+        assert!(request.contains("value=42"), "{}", request);
+    }
+
+    #[test]
+    fn send_json_round_trip() {
+        let request = send_to_endpoint(HttpBodyFormat::Json);
+        let (header, body) = request.split_once("\r\n\r\n").unwrap();
+        assert!(
+            header
+                .to_ascii_lowercase()
+                .contains("content-type: application/json"),
+            "{}",
+            header
+        );
+
+        let event = event_from_json(body).unwrap();
+        assert_eq!(event.name, "hello");
+        let params = event.param_values.as_ref().unwrap();
+        assert_eq!(params.len(), 2);
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|p| p.name == name)
+                .unwrap()
+                .value
+                .clone()
+        };
+        assert_eq!(param("value"), Data::Integer(42));
+        assert_eq!(param("list").to_string(), "[1,two]");
+        assert_eq!(event_to_json(&event), body);
+
+        let mut event = Event::new_simple("content");
+        event.content = Some(Data::String("text".to_string()));
+        assert_eq!(
+            event_to_json(&event),
+            r#"{"content":"text","data":{},"name":"content"}"#
+        );
+        let mut event = event_from_json(&event_to_json(&event)).unwrap();
+        assert_eq!(event.content.take(), Some(Data::String("text".to_string())));
+        assert!(event.param_values.is_none());
+        event.param_values = Some(vec![ParamPair::new_moved(
+            "x".to_string(),
+            Data::Double(0.5),
+        )]);
+        assert_eq!(
+            event_from_json(&event_to_json(&event))
+                .unwrap()
+                .param_values
+                .unwrap()[0]
+                .value,
+            Data::Double(0.5)
+        );
+
+        for illegal in ["{", "[]", r#"{"data":{}}"#, r#"{"name":"a","data":1}"#] {
+            assert!(event_from_json(illegal).is_err(), "{}", illegal);
+        }
+    }
+
+    #[test]
+    fn json_keeps_duplicate_params() {
+        let mut event = Event::new_simple("dup");
+        event.param_values = Some(vec![
+            ParamPair::new_moved("x".to_string(), Data::Integer(1)),
+            ParamPair::new_moved("y".to_string(), Data::String("a".to_string())),
+            ParamPair::new_moved("x".to_string(), Data::Integer(2)),
+        ]);
+        assert_eq!(
+            event_to_json(&event),
+            r#"{"data":{"x":[1,2],"y":"a"},"name":"dup"}"#
+        );
+    }
+
+    #[test]
+    fn receive_json_event() {
+        let mut executor = FsmExecutor::new_without_io_processor();
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <transition event='update' cond='_event.data.value == 42 &amp;&amp; _event.data.list[1] === "two"' target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <transition event='error.communication' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let mut session = crate::fsm::start_fsm_with_data_and_finish_mode(
+            fsm,
+            crate::actions::ActionWrapper::new(),
+            Box::new(executor.clone()),
+            &Vec::new(),
+            crate::fsm::FinishMode::KEEP_CONFIGURATION,
+        );
+
+        // The handler of the route, without http server.
+        let (status, _) = super::receive_json_event(
+            session.session_id.0,
+            r#"{"name":"update","data":{"value":42,"list":[1,"two"]}}"#,
+            &executor.state,
+        );
+        assert_eq!(status, rocket::http::Status::Ok);
+        let (status, _) = super::receive_json_event(session.session_id.0, r#"{"data":{}}"#, &executor.state);
+        assert_eq!(status, rocket::http::Status::BadRequest);
+
+        let _ = session.thread.take().unwrap().join();
+        executor.shutdown();
+        assert_eq!(
+            session.global_data.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+    }
+}
@@ -9,7 +9,7 @@ use std::sync::mpsc::Sender;
 #[cfg(feature = "Debug")]
 use crate::common::debug;
 
-use crate::datamodel::{Datamodel, GlobalDataArc, ToAny};
+use crate::datamodel::{Datamodel, GlobalDataArc, ToAny, BASIC_HTTP_EVENT_PROCESSOR, BASIC_HTTP_EVENT_PROCESSOR_SHORT};
 use crate::fsm::SessionId;
 use crate::fsm::{Event, Fsm, EVENT_CANCEL_SESSION};
 
@@ -20,10 +20,29 @@ pub mod scxml_event_io_processor;
 
 pub const SYS_IO_PROCESSORS: &str = "_ioprocessors";
 
+/// Creates the error message for a send type without registered I/O processor.
+/// Gives a hint how to enable the BasicHTTPEventIOProcessor if this type is requested.
+pub fn unknown_io_processor_message(type_name: &str) -> String {
+    if type_name != BASIC_HTTP_EVENT_PROCESSOR && !BASIC_HTTP_EVENT_PROCESSOR_SHORT.contains(&type_name) {
+        format!("Unknown io-processor {}", type_name)
+    } else if cfg!(feature = "BasicHttpEventIOProcessor") {
+        format!(
+            "No io-processor registered for type {}. Use FsmExecutor::new_with_io_processor or add a \
+             BasicHTTPEventIOProcessor via FsmExecutor::add_processor",
+            type_name
+        )
+    } else {
+        format!(
+            "Io-processor type {} requires feature 'BasicHttpEventIOProcessor'",
+            type_name
+        )
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct ExternalQueueContainer {
     /// The FSMs that are connected to this IO Processor
-    pub fsms: HashMap<u32, Sender<Box<Event>>>,
+    pub fsms: HashMap<SessionId, Sender<Box<Event>>>,
 }
 
 impl ExternalQueueContainer {
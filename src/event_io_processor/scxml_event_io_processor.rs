@@ -2,19 +2,24 @@
 //! I/O Processor implementation for type "<http://www.w3.org/TR/scxml/#SCXMLEventProcessor>" (or short-cut "scxml").
 //! See [W3C:SCXML - SCXML Event I/O Processor](/doc/W3C_SCXML_2024_07_13/index.html#/#SCXMLEventProcessor).
 
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 
 #[cfg(feature = "Debug")]
 use crate::common::debug;
 use crate::common::error;
 use crate::datamodel::{GlobalDataArc, GlobalDataLock, SCXML_EVENT_PROCESSOR};
 use crate::event_io_processor::{EventIOProcessor, ExternalQueueContainer};
-use crate::fsm::{Event, EventType, SessionId};
+use crate::fsm::{Event, EventType, InvokeId, SessionId};
 
 /// SCXML Processors specific target:\
 /// If the target is the special term '#_internal', the Processor must add the event to the internal event queue of the sending session.
 pub const SCXML_TARGET_INTERNAL: &str = "#_internal";
 
+/// Checks for the special target '#_internal'. The form without '#' is accepted as well.
+pub fn is_internal_target(target: &str) -> bool {
+    target == SCXML_TARGET_INTERNAL || target == &SCXML_TARGET_INTERNAL[1..]
+}
+
 /// SCXML Processors specific target:\
 /// If the target is the special term '#_scxml_sessionid', where sessionid is the id of an SCXML session that is accessible to the Processor,
 /// the Processor must add the event to the external queue of that session. The set of SCXML sessions that are accessible to a given SCXML Processor is platform-dependent.
@@ -25,6 +30,13 @@ pub const SCXML_TARGET_SESSION_ID_PREFIX: &str = "#_scxml_";
 /// invoked the sending session, if there is one.
 pub const SCXML_TARGET_PARENT: &str = "#_parent";
 
+/// Extension to the SCXML Processors specific targets:\
+/// If the target is the special term '#_all', the Processor adds the event to the external queues of all sessions
+/// that are connected to the processor, see [ExternalQueueContainer::fsms].
+/// The sending session itself gets the event only if [ScxmlEventIOProcessor::broadcast_to_self] is set.\
+/// Note: As '#_all' is checked first, an invoke with id "all" can't be addressed as target.
+pub const SCXML_TARGET_ALL: &str = "#_all";
+
 /// SCXML Processors specific target:\
 /// If the target is the special term '#_invokeid', where invokeid is the invokeid of an SCXML session that the sending session has created by &lt;invoke\>,
 /// the Processor must add the event to the external queue of that session.\
@@ -34,10 +46,67 @@ pub const SCXML_TARGET_INVOKE_ID_PREFIX: &str = "#_";
 /// Shortcut for SCXML I/O Processors type
 pub const SCXML_EVENT_PROCESSOR_SHORT_TYPE: &str = "scxml";
 
+/// A parsed target of the SCXML I/O Processor. See [parse_target].\
+/// The string representation (via [Display]) is the target that is parsed to the same value, so the location
+/// of a session ("#_scxml_sessionid") can be used as target to reply to the session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScxmlTarget {
+    /// No target, the external queue of the sending session.
+    External,
+    /// '#_internal', the internal queue of the sending session.
+    Internal,
+    /// '#_parent', the session that invoked the sending session.
+    Parent,
+    /// '#_scxml_sessionid', any session of the executor.
+    Session(SessionId),
+    /// '#_invokeid', a session that was invoked by the sending session.
+    Invoke(InvokeId),
+    /// '#_all', all sessions that are connected to the processor.
+    All,
+}
+
+impl Display for ScxmlTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScxmlTarget::External => Ok(()),
+            ScxmlTarget::Internal => write!(f, "{}", SCXML_TARGET_INTERNAL),
+            ScxmlTarget::Parent => write!(f, "{}", SCXML_TARGET_PARENT),
+            ScxmlTarget::Session(session_id) => write!(f, "{}{}", SCXML_TARGET_SESSION_ID_PREFIX, session_id),
+            ScxmlTarget::Invoke(invoke_id) => write!(f, "{}{}", SCXML_TARGET_INVOKE_ID_PREFIX, invoke_id),
+            ScxmlTarget::All => write!(f, "{}", SCXML_TARGET_ALL),
+        }
+    }
+}
+
+/// Parses the target of a \<send\> with the SCXML I/O Processor.\
+/// Returns None if the target is not supported, or if the session id of a '#_scxml_' target is not a number.
+pub fn parse_target(target: &str) -> Option<ScxmlTarget> {
+    match target {
+        "" => Some(ScxmlTarget::External),
+        SCXML_TARGET_INTERNAL => Some(ScxmlTarget::Internal),
+        SCXML_TARGET_PARENT => Some(ScxmlTarget::Parent),
+        SCXML_TARGET_ALL => Some(ScxmlTarget::All),
+        _ => {
+            if let Some(session_id) = target.strip_prefix(SCXML_TARGET_SESSION_ID_PREFIX) {
+                session_id
+                    .parse::<SessionId>()
+                    .ok()
+                    .map(ScxmlTarget::Session)
+            } else {
+                target
+                    .strip_prefix(SCXML_TARGET_INVOKE_ID_PREFIX)
+                    .map(|invoke_id| ScxmlTarget::Invoke(invoke_id.to_string()))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct ScxmlEventIOProcessor {
     pub location: String,
     pub handle: ExternalQueueContainer,
+    /// If set, events to [SCXML_TARGET_ALL] are also added to the queue of the sending session.
+    pub broadcast_to_self: bool,
 }
 
 impl ScxmlEventIOProcessor {
@@ -48,9 +117,26 @@ impl ScxmlEventIOProcessor {
         ScxmlEventIOProcessor {
             location: SCXML_TARGET_SESSION_ID_PREFIX.to_string(),
             handle: ExternalQueueContainer::new(),
+            broadcast_to_self: false,
         }
     }
 
+    /// Adds the event to the queues of all connected sessions, see [SCXML_TARGET_ALL].\
+    /// Sessions that have terminated are removed.
+    fn send_to_all(&mut self, sender_session_id: SessionId, event: Event) -> bool {
+        let broadcast_to_self = self.broadcast_to_self;
+        self.handle.fsms.retain(|session_id, sender| {
+            if *session_id == sender_session_id && !broadcast_to_self {
+                true
+            } else {
+                #[cfg(feature = "Debug")]
+                debug!("Send '{}' to Session #{}", event, session_id);
+                sender.send(event.get_copy()).is_ok()
+            }
+        });
+        true
+    }
+
     fn send_to_session(&mut self, global_data_lock: &mut GlobalDataLock, session_id: SessionId, event: Event) -> bool {
         match &global_data_lock.executor {
             None => {
@@ -78,6 +164,8 @@ impl ScxmlEventIOProcessor {
 const TYPES: &[&str] = &[SCXML_EVENT_PROCESSOR, SCXML_EVENT_PROCESSOR_SHORT_TYPE];
 
 impl EventIOProcessor for ScxmlEventIOProcessor {
+    /// Returns "#_scxml_sessionid" (for the default location prefix), that can be used as target to
+    /// send events to the session. See [ScxmlTarget].
     fn get_location(&self, id: SessionId) -> String {
         format!("{}{}", self.location, id)
     }
@@ -95,6 +183,7 @@ impl EventIOProcessor for ScxmlEventIOProcessor {
         let b = ScxmlEventIOProcessor {
             location: self.location.clone(),
             handle: self.handle.clone(),
+            broadcast_to_self: self.broadcast_to_self,
         };
         Box::new(b)
     }
@@ -113,6 +202,7 @@ impl EventIOProcessor for ScxmlEventIOProcessor {
     /// <li>#_invokeid. If the target is the special term '#_invokeid', where invokeid is the invokeid of an SCXML session that the sending session has created by &lt;invoke\>, the Processor must add the event to the external queue of that session. See 6.4 &lt;invoke\> for details.</li>
     /// <li>If neither the 'target' nor the 'targetexpr' attribute is specified, the SCXML Processor must add the event to the external event queue of the sending session.</li>
     /// </ul>
+    /// As extension, the target '#_all' broadcasts the event, see [SCXML_TARGET_ALL].
     fn send(&mut self, global: &GlobalDataArc, target: &str, mut event: Event) -> bool {
         let mut global_lock = global.lock().unwrap();
         event.origin_type = Some(SCXML_EVENT_PROCESSOR.to_string());
@@ -121,68 +211,57 @@ impl EventIOProcessor for ScxmlEventIOProcessor {
         }
         // For SCXMLEventProcessor: Target is an SCXML session.
 
-        match target {
-            "" => {
+        match parse_target(target) {
+            Some(ScxmlTarget::External) => {
                 global_lock.externalQueue.enqueue(Box::new(event));
                 true
             }
-            SCXML_TARGET_INTERNAL => {
+            Some(ScxmlTarget::Internal) => {
                 event.etype = EventType::internal;
                 global_lock.enqueue_internal(event);
                 true
             }
-            SCXML_TARGET_PARENT => {
-                let sid = global_lock.parent_session_id.unwrap();
-                self.send_to_session(&mut global_lock, sid, event)
+            Some(ScxmlTarget::Parent) => match global_lock.parent_session_id {
+                Some(sid) => self.send_to_session(&mut global_lock, sid, event),
+                None => {
+                    error!(
+                        "Send target '{}' not available, session was not invoked.",
+                        target
+                    );
+                    global_lock.enqueue_internal(Event::error_communication(&event));
+                    false
+                }
+            },
+            // W3C: If the sending SCXML session specifies a session that does not exist or is inaccessible,
+            //      the SCXML Processor must place the error "error.communication" on the internal event queue of the sending session.
+            Some(ScxmlTarget::Session(session_id)) => self.send_to_session(&mut global_lock, session_id, event),
+            Some(ScxmlTarget::Invoke(invokeid)) => {
+                let session_id = match global_lock.child_sessions.get(&invokeid) {
+                    None => {
+                        error!(
+                            "InvokeId of target {} '{}' is not available.",
+                            invokeid, target
+                        );
+                        global_lock.enqueue_internal(Event::error_communication(&event));
+                        return false;
+                    }
+                    Some(session) => session.session_id,
+                };
+                self.send_to_session(&mut global_lock, session_id, event)
             }
-            _ => {
-                // W3C: If the sending SCXML session specifies a session that does not exist or is inaccessible,
-                //      the SCXML Processor must place the error "error.communication" on the internal event queue of the sending session.
+            Some(ScxmlTarget::All) => self.send_to_all(global_lock.session_id, event),
+            None => {
                 if target.starts_with(SCXML_TARGET_SESSION_ID_PREFIX) {
-                    match target.get(SCXML_TARGET_SESSION_ID_PREFIX.len()..) {
-                        None => {
-                            error!("Send target '{}' has wrong format.", target);
-                            global_lock.enqueue_internal(Event::error_communication(&event));
-                            false
-                        }
-                        Some(session_id_s) => match session_id_s.parse::<SessionId>() {
-                            Ok(session_id) => self.send_to_session(&mut global_lock, session_id, event),
-                            Err(_err) => {
-                                error!("Send target '{}' has wrong format.", target);
-                                global_lock.enqueue_internal(Event::error_communication(&event));
-                                false
-                            }
-                        },
-                    }
-                } else if target.starts_with(SCXML_TARGET_INVOKE_ID_PREFIX) {
-                    match target.get(SCXML_TARGET_INVOKE_ID_PREFIX.len()..) {
-                        None => {
-                            error!("Send target '{}' has wrong format.", target);
-                            global_lock.enqueue_internal(Event::error_communication(&event));
-                            false
-                        }
-                        Some(invokeid) => {
-                            let session_id = match global_lock.child_sessions.get(invokeid) {
-                                None => {
-                                    error!(
-                                        "InvokeId of target {} '{}' is not available.",
-                                        invokeid, target
-                                    );
-                                    global_lock.enqueue_internal(Event::error_communication(&event));
-                                    return false;
-                                }
-                                Some(session) => session.session_id,
-                            };
-                            self.send_to_session(&mut global_lock, session_id, event)
-                        }
-                    }
+                    // Not accessible, as the session id is not valid.
+                    error!("Send target '{}' has wrong format.", target);
+                    global_lock.enqueue_internal(Event::error_communication(&event));
                 } else {
                     // W3C says:
                     // If the value ... is not supported or invalid, the Processor MUST place the
                     // error error.execution on the internal event queue.
                     global_lock.enqueue_internal(Event::error_execution(&event.sendid, &event.invoke_id));
-                    false
                 }
+                false
             }
         }
     }
@@ -195,3 +274,249 @@ impl EventIOProcessor for ScxmlEventIOProcessor {
         self.handle.shutdown();
     }
 }
+
+#[cfg(test)]
+#[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+mod tests {
+    use crate::event_io_processor::scxml_event_io_processor::{parse_target, ScxmlEventIOProcessor, ScxmlTarget};
+    use crate::event_io_processor::EventIOProcessor;
+    use crate::fsm::SessionId;
+    use crate::scxml_reader;
+    #[cfg(feature = "Trace")]
+    use crate::tracer::TraceMode;
+    use std::collections::HashMap;
+
+    fn run(name: &str, xml: &str) -> bool {
+        let fsm = scxml_reader::parse_from_xml(xml.to_string()).unwrap();
+        crate::test::run_test_manual(
+            name,
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        )
+    }
+
+    #[test]
+    fn send_to_parent() {
+        assert!(run(
+            "send_to_parent",
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <invoke type='scxml'>
+                  <content>
+                    <scxml initial='c0' datamodel='ecmascript'>
+                      <state id='c0'>
+                        <onentry><send target='#_parent' event='fromChild'/></onentry>
+                      </state>
+                    </scxml>
+                  </content>
+                </invoke>
+                <transition event='fromChild' target='pass'/>
+                <transition event='error.*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+        ));
+    }
+
+    #[test]
+    fn send_to_session_id() {
+        assert!(run(
+            "send_to_session_id",
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <invoke type='scxml'>
+                  <param name='parentSession' expr='_sessionid'/>
+                  <content>
+                    <scxml initial='c0' datamodel='ecmascript'>
+                      <datamodel><data id='parentSession'/></datamodel>
+                      <state id='c0'>
+                        <onentry>
+                          <send targetexpr="'#_scxml_' + parentSession" event='fromChild'/>
+                        </onentry>
+                      </state>
+                    </scxml>
+                  </content>
+                </invoke>
+                <transition event='fromChild' target='pass'/>
+                <transition event='error.*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+        ));
+    }
+
+    #[test]
+    fn location_round_trips_through_target_parser() {
+        let processor = ScxmlEventIOProcessor::new();
+        let location = processor.get_location(SessionId(42));
+        assert_eq!(location, "#_scxml_42");
+        assert_eq!(
+            parse_target(&location),
+            Some(ScxmlTarget::Session(SessionId(42)))
+        );
+
+        for target in [
+            ScxmlTarget::External,
+            ScxmlTarget::Internal,
+            ScxmlTarget::Parent,
+            ScxmlTarget::Session(SessionId(7)),
+            ScxmlTarget::Invoke("child".to_string()),
+            ScxmlTarget::All,
+        ] {
+            assert_eq!(parse_target(&target.to_string()), Some(target));
+        }
+        assert_eq!(parse_target("#_scxml_foo"), None);
+        assert_eq!(parse_target("http://localhost"), None);
+    }
+
+    #[test]
+    fn reply_to_origin_reaches_sender() {
+        assert!(run(
+            "reply_to_origin_reaches_sender",
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <invoke type='scxml' id='b'>
+                  <param name='parentSession' expr='_sessionid'/>
+                  <content>
+                    <scxml initial='c0' datamodel='ecmascript'>
+                      <datamodel><data id='parentSession'/></datamodel>
+                      <state id='c0'>
+                        <onentry><send target='#_parent' event='ready'/></onentry>
+                        <transition event='request' cond="_event.origin == '#_scxml_' + parentSession">
+                          <send targetexpr='_event.origin' event='reply'/>
+                        </transition>
+                        <transition event='request'>
+                          <send targetexpr='_event.origin' event='wrongOrigin'/>
+                        </transition>
+                      </state>
+                    </scxml>
+                  </content>
+                </invoke>
+                <transition event='ready'>
+                  <send target='#_b' event='request'/>
+                </transition>
+                <transition event='reply' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+        ));
+    }
+
+    #[test]
+    fn send_to_unknown_targets_raises_error_communication() {
+        assert!(run(
+            "send_to_unknown_session",
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry><send target='#_scxml_999999' event='lost'/></onentry>
+                <transition event='error.communication' target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <onentry><send target='#_parent' event='lost'/></onentry>
+                <transition event='error.communication' target='s2'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s2'>
+                <onentry><send target='#_unknownInvoke' event='lost'/></onentry>
+                <transition event='error.communication' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+        ));
+    }
+
+    #[test]
+    fn send_errors_distinguish_communication_and_execution() {
+        assert!(run(
+            "send_errors_distinguish_communication_and_execution",
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry><send targetexpr='undefinedVariable.target' event='lost'/></onentry>
+                <transition event='error.execution' target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <onentry><send target='#_scxml_999999' event='lost'/></onentry>
+                <transition event='error.communication' target='s2'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s2'>
+                <onentry><send target='#_scxml_999999' event='lost' delay='10ms'/></onentry>
+                <transition event='error.communication' target='s3'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s3'>
+                <invoke src='file:does/not/exist.scxml'/>
+                <transition event='error.communication' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+        ));
+    }
+
+    #[test]
+    fn send_to_all_reaches_other_sessions() {
+        use crate::fsm::{start_fsm_with_data_and_finish_mode, FinishMode};
+
+        let mut executor = crate::fsm_executor::FsmExecutor::new_without_io_processor();
+        let start = |xml: &str| {
+            start_fsm_with_data_and_finish_mode(
+                scxml_reader::parse_from_xml(xml.to_string()).unwrap(),
+                crate::actions::ActionWrapper::new(),
+                Box::new(executor.clone()),
+                &Vec::new(),
+                FinishMode::KEEP_CONFIGURATION,
+            )
+        };
+        let receiver = r#"<scxml initial='wait' datamodel='ecmascript'>
+              <state id='wait'>
+                <transition event='reload' target='pass'/>
+              </state>
+              <final id='pass'/>
+            </scxml>"#;
+        let mut receivers = [start(receiver), start(receiver)];
+        for session in receivers.iter_mut() {
+            // Wait until the session is connected to the processor.
+            assert_eq!(session.fire_batch(Vec::new()), Ok(vec!["wait".to_string()]));
+        }
+
+        // The broadcast must not reach the sender, otherwise "reload" is received before "done".
+        let mut sender = start(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry>
+                  <send target='#_all' event='reload'/>
+                  <send event='done'/>
+                </onentry>
+                <transition event='done' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#,
+        );
+
+        for session in receivers.iter_mut().chain(std::iter::once(&mut sender)) {
+            let _ = session.thread.take().unwrap().join();
+            assert_eq!(
+                session.global_data.lock().unwrap().final_configuration,
+                Some(vec!["pass".to_string()])
+            );
+        }
+        executor.shutdown();
+    }
+}
@@ -9,11 +9,12 @@ use std::sync::atomic::Ordering;
 use crate::common::debug;
 use crate::common::{error, info, warn};
 use crate::datamodel::{str_to_source, Data, Datamodel, ToAny, SCXML_EVENT_PROCESSOR};
-use crate::event_io_processor::scxml_event_io_processor::SCXML_TARGET_INTERNAL;
+use crate::event_io_processor::scxml_event_io_processor::is_internal_target;
+use crate::event_io_processor::unknown_io_processor_message;
 use crate::expression_engine::lexer::ExpressionLexer;
 use crate::fsm::{
     opt_vec_to_string, vec_to_string, CommonContent, Event, EventType, ExecutableContentId, Fsm, ParamPair, Parameter,
-    PLATFORM_ID_COUNTER,
+    PendingSend, EVENT_WAKE_UP, PLATFORM_ID_COUNTER,
 };
 
 pub const TARGET_SCXML_EVENT_PROCESSOR: &str = "http://www.w3.org/TR/scxml/#SCXMLEventProcessor";
@@ -47,10 +48,92 @@ macro_rules! get_global {
     };
 }
 
-pub trait ExecutableContent: ToAny + Debug + Send {
+/// An element of executable content.\
+/// Implementations must be `Sync`, as the executable content is shared by all instances of a template
+/// (see [Fsm::instantiate]) and these instances may run in different threads.\
+/// **Breaking change**: The `Sync` bound was added with [Fsm::instantiate]. Implementations with interior
+/// mutability that is not thread-safe (e.g. `Cell` or `RefCell`) have to use `Mutex` or atomics instead.
+pub trait ExecutableContent: ToAny + Debug + Send + Sync {
     fn execute(&self, datamodel: &mut dyn Datamodel, fsm: &Fsm) -> bool;
     fn get_type(&self) -> u8;
     fn trace(&self, tracer: &mut dyn ExecutableContentTracer, fsm: &Fsm);
+
+    /// Describes the element without executing it, e.g. for editors or documentation.\
+    /// The default contains only the type.
+    fn describe(&self) -> ContentDescription {
+        ContentDescription::new(self.get_type())
+    }
+}
+
+/// Structural summary of an element of executable content. See [ExecutableContent::describe].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentDescription {
+    /// The name of the element, see [TYPE_NAMES].
+    pub type_name: &'static str,
+    /// The specified attributes, named as in SCXML. Attributes that are not specified are omitted.
+    pub fields: Vec<(&'static str, String)>,
+    /// Nested blocks of executable content, e.g. "then" and "else" of \<if\>.
+    /// The content can be retrieved via [Fsm::get_executable_content].
+    pub blocks: Vec<(&'static str, ExecutableContentId)>,
+}
+
+impl ContentDescription {
+    pub fn new(content_type: u8) -> ContentDescription {
+        ContentDescription {
+            type_name: TYPE_NAMES[content_type as usize],
+            fields: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Adds a field, if the value is specified.
+    fn field(mut self, name: &'static str, value: &Data) -> ContentDescription {
+        let value = match value {
+            Data::None() | Data::Null() => None,
+            Data::Source(src) => (!src.is_empty()).then(|| src.source.clone()),
+            other => Some(other.to_string()),
+        };
+        if let Some(value) = value {
+            self.fields.push((name, value));
+        }
+        self
+    }
+
+    /// Adds a field, if the value is not empty.
+    fn text_field(mut self, name: &'static str, value: &str) -> ContentDescription {
+        if !value.is_empty() {
+            self.fields.push((name, value.to_string()));
+        }
+        self
+    }
+
+    /// Adds a nested block, if it has content.
+    fn block(mut self, name: &'static str, content_id: ExecutableContentId) -> ContentDescription {
+        if content_id != 0 {
+            self.blocks.push((name, content_id));
+        }
+        self
+    }
+}
+
+/// Executes one element of a block of executable content.\
+/// *W3C says*:\
+/// If the processing of an element of executable content causes an error to be raised, the processor
+/// must not process the remaining elements of the block.\
+/// #Actual implementation:
+/// Each failing element results in exactly one error event, placed on the internal queue in execution order.
+/// If the element didn't report the error itself, "error.execution" is raised.
+/// Returns false if the element failed and the block shall be aborted.
+pub fn execute_element(ec: &dyn ExecutableContent, datamodel: &mut dyn Datamodel, fsm: &Fsm) -> bool {
+    let error_mark = get_global!(datamodel).internal_queue_size();
+    if ec.execute(datamodel, fsm) {
+        true
+    } else {
+        if get_global!(datamodel).errors_since(error_mark) == 0 {
+            datamodel.internal_error_execution();
+        }
+        false
+    }
 }
 
 pub fn get_safe_executable_content_as<T: 'static>(ec: &mut dyn ExecutableContent) -> &mut T {
@@ -249,6 +332,12 @@ impl ExecutableContent for Assign {
             ],
         );
     }
+
+    fn describe(&self) -> ContentDescription {
+        ContentDescription::new(TYPE_ASSIGN)
+            .field("location", &self.location)
+            .field("expr", &self.expr)
+    }
 }
 
 impl Raise {
@@ -279,6 +368,10 @@ impl ExecutableContent for Raise {
     fn trace(&self, tracer: &mut dyn ExecutableContentTracer, _fsm: &Fsm) {
         tracer.print_name_and_attributes(self, &[("event", &self.event)]);
     }
+
+    fn describe(&self) -> ContentDescription {
+        ContentDescription::new(TYPE_RAISE).text_field("event", &self.event)
+    }
 }
 
 impl Script {
@@ -342,14 +435,26 @@ impl Log {
 }
 
 impl ExecutableContent for Log {
+    /// Logs "label: value". If one of the attributes is missing, only the other one is logged.
     fn execute(&self, datamodel: &mut dyn Datamodel, _fsm: &Fsm) -> bool {
-        match &datamodel.execute(&self.expression) {
-            Ok(msg) => {
-                datamodel.log(msg.lock().unwrap().to_string().as_str());
-                true
+        let message = if self.expression.is_empty() {
+            self.label.clone()
+        } else {
+            match datamodel.execute(&self.expression) {
+                Ok(value) => {
+                    let value = value.lock().unwrap().to_string();
+                    if self.label.is_empty() {
+                        value
+                    } else {
+                        format!("{}: {}", self.label, value)
+                    }
+                }
+                // The caller raises error.execution, see [execute_element].
+                Err(_msg) => return false,
             }
-            Err(_msg) => false,
-        }
+        };
+        datamodel.log(message.as_str());
+        true
     }
 
     fn get_type(&self) -> u8 {
@@ -357,7 +462,19 @@ impl ExecutableContent for Log {
     }
 
     fn trace(&self, tracer: &mut dyn ExecutableContentTracer, _fsm: &Fsm) {
-        tracer.print_name_and_attributes(self, &[("expression", &self.expression.to_string())]);
+        tracer.print_name_and_attributes(
+            self,
+            &[
+                ("label", &self.label),
+                ("expression", &self.expression.to_string()),
+            ],
+        );
+    }
+
+    fn describe(&self) -> ContentDescription {
+        ContentDescription::new(TYPE_LOG)
+            .text_field("label", &self.label)
+            .field("expr", &self.expression)
     }
 }
 
@@ -373,23 +490,29 @@ impl If {
 
 impl ExecutableContent for If {
     fn execute(&self, datamodel: &mut dyn Datamodel, fsm: &Fsm) -> bool {
+        let error_mark = get_global!(datamodel).internal_queue_size();
         let r = datamodel
             .execute_condition(&self.condition)
             .unwrap_or_else(|e| {
+                // W3C: If a conditional expression cannot be evaluated, it is treated as 'false' and
+                // "error.execution" is placed in the internal event queue.
                 warn!("Condition {} can't be evaluated. {}", self.condition, e);
+                if get_global!(datamodel).errors_since(error_mark) == 0 {
+                    datamodel.internal_error_execution();
+                }
                 false
             });
         if r {
             if self.content != 0 {
                 for e in fsm.executableContent.get(&self.content).unwrap() {
-                    if !e.execute(datamodel, fsm) {
+                    if !execute_element(e.as_ref(), datamodel, fsm) {
                         return false;
                     }
                 }
             }
         } else if self.else_content != 0 {
             for e in fsm.executableContent.get(&self.else_content).unwrap() {
-                if !e.execute(datamodel, fsm) {
+                if !execute_element(e.as_ref(), datamodel, fsm) {
                     return false;
                 }
             }
@@ -406,6 +529,13 @@ impl ExecutableContent for If {
         tracer.print_sub_content("then", fsm, self.content);
         tracer.print_sub_content("else", fsm, self.else_content);
     }
+
+    fn describe(&self) -> ContentDescription {
+        ContentDescription::new(TYPE_IF)
+            .field("cond", &self.condition)
+            .block("then", self.content)
+            .block("else", self.else_content)
+    }
 }
 
 pub const INDEX_TEMP: &str = "__$index";
@@ -431,7 +561,7 @@ impl ExecutableContent for ForEach {
         datamodel.execute_for_each(&self.array, &self.item, &idx, &mut |datamodel| -> bool {
             if self.content != 0 {
                 for e in fsm.executableContent.get(&self.content).unwrap() {
-                    if !e.execute(datamodel, fsm) {
+                    if !execute_element(e.as_ref(), datamodel, fsm) {
                         return false;
                     }
                 }
@@ -455,6 +585,14 @@ impl ExecutableContent for ForEach {
         );
         tracer.print_sub_content("content", fsm, self.content);
     }
+
+    fn describe(&self) -> ContentDescription {
+        ContentDescription::new(TYPE_FOREACH)
+            .field("array", &self.array)
+            .text_field("item", &self.item)
+            .text_field("index", &self.index)
+            .block("content", self.content)
+    }
 }
 
 impl Parameter {
@@ -485,14 +623,31 @@ impl ExecutableContent for Cancel {
     /// the specified id. Note, however, that it can not be guaranteed to succeed, for example if
     /// the event has already been delivered by the time the \<cancel> tag executes.
     fn execute(&self, datamodel: &mut dyn Datamodel, _fsm: &Fsm) -> bool {
-        if let Ok(send_id) =
-            datamodel.get_expression_alternative_value(&str_to_source(self.send_id.as_str()), &self.send_id_expr)
-        {
-            get_global!(datamodel)
-                .delayed_send
-                .remove(&send_id.lock().unwrap().to_string());
-        };
-        true
+        let error_mark = get_global!(datamodel).internal_queue_size();
+        match datamodel.get_expression_alternative_value(&str_to_source(self.send_id.as_str()), &self.send_id_expr) {
+            Ok(send_id) => {
+                let send_id = send_id.lock().unwrap().to_string();
+                #[cfg(feature = "Debug")]
+                debug!("cancel '{}'", send_id);
+                let mut global = get_global!(datamodel);
+                global.delayed_send.remove(&send_id);
+                global
+                    .scheduled_sends
+                    .retain(|_, send| send.send_id.as_ref() != Some(&send_id));
+                true
+            }
+            Err(err) => {
+                // W3C: If the evaluation of 'sendidexpr' fails, error.execution is placed on the internal queue.
+                error!(
+                    "Cancel: failed to evaluate sendidexpr '{}'. {}",
+                    self.send_id_expr, err
+                );
+                if get_global!(datamodel).errors_since(error_mark) == 0 {
+                    datamodel.internal_error_execution();
+                }
+                false
+            }
+        }
     }
 
     fn get_type(&self) -> u8 {
@@ -508,6 +663,31 @@ impl ExecutableContent for Cancel {
             ],
         );
     }
+
+    fn describe(&self) -> ContentDescription {
+        ContentDescription::new(TYPE_CANCEL)
+            .text_field("sendid", &self.send_id)
+            .field("sendidexpr", &self.send_id_expr)
+    }
+}
+
+impl SendParameters {
+    /// Called if the send failed, e.g. if the evaluation of some argument failed.\
+    /// Errors that were already put into the internal queue get the send-id. If
+    /// there is none, "error.execution" is raised.
+    fn abort_send(
+        &self,
+        datamodel: &mut dyn Datamodel,
+        error_mark: usize,
+        send_id: &Option<String>,
+        fsm: &Fsm,
+    ) -> bool {
+        let mut global = get_global!(datamodel);
+        if global.set_send_id_of_errors(error_mark, send_id) == 0 {
+            global.enqueue_internal(Event::error_execution(send_id, &fsm.caller_invoke_id));
+        }
+        false
+    }
 }
 
 /// Implements the execution of \<send\> element.
@@ -515,22 +695,6 @@ impl ExecutableContent for SendParameters {
     /// If unable to dispatch, place "error.communication" in internal queue
     /// If target is not supported, place "error.execution" in internal queue
     fn execute(&self, datamodel: &mut dyn Datamodel, fsm: &Fsm) -> bool {
-        let target = match datamodel.get_expression_alternative_value(&self.target, &self.target_expr) {
-            Ok(value) => value,
-            Err(_) => {
-                // Error -> abort
-                return false;
-            }
-        };
-
-        let event_name = match datamodel.get_expression_alternative_value(&self.event, &self.event_expr) {
-            Ok(value) => value,
-            Err(_) => {
-                // Error -> abort
-                return false;
-            }
-        };
-
         let send_id = if self.name_location.is_empty() {
             if self.name.is_empty() {
                 None
@@ -561,6 +725,27 @@ impl ExecutableContent for SendParameters {
             Some(generated_id)
         };
 
+        // W3C says:
+        // ...in the case of error events triggered by a failed attempt to send an event, the Processor
+        // must set this field (sendid) to the send id of the triggering <send> element.
+        let error_mark = get_global!(datamodel).internal_queue_size();
+
+        let target = match datamodel.get_expression_alternative_value(&self.target, &self.target_expr) {
+            Ok(value) => value,
+            Err(_) => {
+                // Error -> abort
+                return self.abort_send(datamodel, error_mark, &send_id, fsm);
+            }
+        };
+
+        let event_name = match datamodel.get_expression_alternative_value(&self.event, &self.event_expr) {
+            Ok(value) => value,
+            Err(_) => {
+                // Error -> abort
+                return self.abort_send(datamodel, error_mark, &send_id, fsm);
+            }
+        };
+
         let mut data_vec = Vec::new();
 
         let mut content = None;
@@ -576,7 +761,7 @@ impl ExecutableContent for SendParameters {
                 match datamodel.get_by_location(name) {
                     Err(_msg) => {
                         // Error -> Abort
-                        return false;
+                        return self.abort_send(datamodel, error_mark, &send_id, fsm);
                     }
                     Ok(value) => {
                         data_vec.push(ParamPair::new(name.as_str(), &value.lock().unwrap()));
@@ -584,12 +769,14 @@ impl ExecutableContent for SendParameters {
                 }
             }
         }
+        // Failures in <param> or <content> doesn't abort the send, but the errors belong to it.
+        get_global!(datamodel).set_send_id_of_errors(error_mark, &send_id);
 
         let delay_ms = if !self.delay_expr.is_empty() {
             match datamodel.execute(&self.delay_expr) {
                 Err(_msg) => {
                     // Error -> Abort
-                    return false;
+                    return self.abort_send(datamodel, error_mark, &send_id, fsm);
                 }
                 Ok(delay) => parse_duration_to_milliseconds(&delay.lock().unwrap().to_string()),
             }
@@ -605,7 +792,8 @@ impl ExecutableContent for SendParameters {
         }
 
         let target_guard = target.lock().unwrap();
-        if delay_ms > 0 && target_guard.to_string().eq(SCXML_TARGET_INTERNAL) {
+        let internal_target = is_internal_target(target_guard.to_string().as_str());
+        if delay_ms > 0 && internal_target {
             // Can't send via internal queue
             error!("Send: illegal delay for target {}", target_guard);
             datamodel.internal_error_execution_for_event(&send_id, &fsm.caller_invoke_id);
@@ -617,8 +805,7 @@ impl ExecutableContent for SendParameters {
             Ok(val) => val,
             Err(err) => {
                 error!("Failed to evaluate send type: {}", err);
-                datamodel.internal_error_execution_for_event(&send_id, &fsm.caller_invoke_id);
-                return false;
+                return self.abort_send(datamodel, error_mark, &send_id, fsm);
             }
         };
 
@@ -644,7 +831,18 @@ impl ExecutableContent for SendParameters {
             content,
         };
 
-        let result = if delay_ms > 0 {
+        let result = if internal_target {
+            // W3C says:
+            // If the target is the special term '#_internal', the Processor must add the event to the
+            // internal event queue of the sending session.
+            // Handled like <raise>, independent of the type.
+            #[cfg(feature = "Debug")]
+            debug!("send '{}' to internal queue", event);
+            let mut event = event;
+            event.etype = EventType::internal;
+            get_global!(datamodel).enqueue_internal(event);
+            true
+        } else if delay_ms > 0 {
             let iop_opt = datamodel.get_io_processor(type_val_str);
             if let Some(iop) = iop_opt {
                 let iopc = iop.clone();
@@ -653,13 +851,33 @@ impl ExecutableContent for SendParameters {
                 let global_clone = datamodel.global_s().clone();
                 let send_id_clone = send_id.clone();
                 let target_str = target_guard.to_string();
+                let scheduled_key = get_global!(datamodel).add_scheduled_send(PendingSend {
+                    send_id: send_id.clone(),
+                    target: target_str.clone(),
+                    event: event.name.clone(),
+                    due: std::time::Instant::now() + std::time::Duration::from_millis(delay_ms as u64),
+                });
                 let tg = fsm.schedule(delay_ms, move || {
-                    if let Some(sid) = &send_id_clone {
-                        global_clone.lock().unwrap().delayed_send.remove(sid);
+                    {
+                        let mut global = global_clone.lock().unwrap();
+                        if let Some(sid) = &send_id_clone {
+                            global.delayed_send.remove(sid);
+                        }
+                        global.scheduled_sends.remove(&scheduled_key);
+                        global.notify_event_observer(&event);
                     }
-                    iopc.lock()
+                    let sent = iopc
+                        .lock()
                         .unwrap()
                         .send(&global_clone, target_str.as_str(), event.clone());
+                    if !sent {
+                        // The error is in the internal queue, but the session waits for external events.
+                        global_clone
+                            .lock()
+                            .unwrap()
+                            .externalQueue
+                            .enqueue(Box::new(Event::new_simple(EVENT_WAKE_UP)));
+                    }
                 });
                 if let Some(g) = tg {
                     if let Some(sid) = &send_id {
@@ -675,19 +893,21 @@ impl ExecutableContent for SendParameters {
                 };
                 true
             } else {
-                error!("Unknown io-processor {}", type_val_str);
+                error!("{}", unknown_io_processor_message(type_val_str));
                 false
             }
         } else {
             #[cfg(feature = "Debug")]
             debug!("send '{}' to '{}'", event, target_guard);
+            get_global!(datamodel).notify_event_observer(&event);
             datamodel.send(type_val_str, &target_guard, event.clone())
         };
 
         if !result {
             // W3C:  If the SCXML Processor does not support the type that is specified,
             // it must place the event error.execution on the internal event queue.
-            datamodel.internal_error_execution_for_event(&send_id, &fsm.caller_invoke_id);
+            // If the io-processor already reported the error, it is not raised twice.
+            self.abort_send(datamodel, error_mark, &send_id, fsm);
         };
         result
     }
@@ -716,11 +936,40 @@ impl ExecutableContent for SendParameters {
             ],
         );
     }
+
+    fn describe(&self) -> ContentDescription {
+        let description = ContentDescription::new(TYPE_SEND)
+            .field("event", &self.event)
+            .field("eventexpr", &self.event_expr)
+            .field("target", &self.target)
+            .field("targetexpr", &self.target_expr)
+            .field("type", &self.type_value)
+            .field("typeexpr", &self.type_expr)
+            .text_field("id", &self.name)
+            .text_field("idlocation", &self.name_location);
+        let delay = if self.delay_ms > 0 {
+            format!("{}ms", self.delay_ms)
+        } else {
+            String::new()
+        };
+        description
+            .text_field("delay", &delay)
+            .field("delayexpr", &self.delay_expr)
+            .text_field("namelist", &self.name_list.join(" "))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::executable_content::parse_duration_to_milliseconds;
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    use crate::scxml_reader;
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    use crate::test::run_test_manual;
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml", feature = "Trace"))]
+    use crate::tracer::TraceMode;
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    use std::collections::HashMap;
 
     #[test]
     fn delay_parse() {
@@ -736,38 +985,480 @@ mod tests {
         assert_eq!(parse_duration_to_milliseconds("x1S"), -1);
         assert_eq!(parse_duration_to_milliseconds("1Sx"), -1);
     }
+
+    #[test]
+    fn delay_parse_units() {
+        for (value, expected) in [
+            ("", 0),
+            ("250ms", 250),
+            ("250MS", 250),
+            ("2s", 2000),
+            ("1.5s", 1500),
+            (" 1.5s ", 1500),
+            (".5s", 500),
+            ("3m", 3 * 60 * 1000),
+            ("2h", 2 * 60 * 60 * 1000),
+            ("1d", 24 * 60 * 60 * 1000),
+            ("0s", 0),
+            ("abc", -1),
+            ("10", -1),
+            ("-1s", -1),
+            ("1.5 s x", -1),
+            ("1us", -1),
+        ] {
+            assert_eq!(
+                parse_duration_to_milliseconds(value),
+                expected,
+                "delay '{}'",
+                value
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn cancel_with_sendidexpr() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='sid'/></datamodel>
+              <state id='s0'>
+                <onentry>
+                  <send event='delayed' delay='200ms' idlocation='sid'/>
+                  <send event='timeout' delay='500ms'/>
+                  <cancel sendidexpr='sid'/>
+                </onentry>
+                <onentry>
+                  <cancel sendidexpr='noSuchVariable.x'/>
+                </onentry>
+                <transition event='error.execution' target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <transition event='timeout' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual(
+            "cancel_with_sendidexpr",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000u64,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn failed_send_error_has_send_id() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='sid1'/><data id='sid2'/></datamodel>
+              <state id='s0'>
+                <onentry>
+                  <send event='e1' target='unknown' idlocation='sid1'/>
+                </onentry>
+                <transition event='error.execution' cond='_event.sendid == sid1' target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <onentry>
+                  <send event='e2' targetexpr='noSuchVariable.x' idlocation='sid2'/>
+                </onentry>
+                <transition event='error.execution' cond='_event.sendid == sid2' target='s2'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s2'>
+                <onentry>
+                  <send event='e3' target='unknown' id='myId'/>
+                </onentry>
+                <transition event='error.execution' cond='_event.sendid == "myId"' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual(
+            "failed_send_error_has_send_id",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000u64,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn send_keeps_duplicate_params_in_order() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry>
+                  <send event='e1'>
+                    <param name='x' expr='1'/>
+                    <param name='y' expr='"a"'/>
+                    <param name='x' expr='2'/>
+                  </send>
+                </onentry>
+                <transition event='e1' cond='_event.data.x.length == 2 &amp;&amp; _event.data.x[0] == 1 &amp;&amp; _event.data.x[1] == 2 &amp;&amp; _event.data.y == "a"' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual(
+            "send_keeps_duplicate_params_in_order",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000u64,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn send_to_internal_target_raises_event() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry>
+                  <send event='external'/>
+                  <send target='_internal' event='go'/>
+                </onentry>
+                <transition event='go' cond='_event.type == "internal"' target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <onentry>
+                  <send target='#_internal' event='go2'/>
+                </onentry>
+                <transition event='go2' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual(
+            "send_to_internal_target_raises_event",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000u64,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn errors_are_raised_once_per_failure_in_execution_order() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry>
+                  <foreach array='[1, 2, 3]' item='i'>
+                    <send target='#_internal' event='ev'><param name='p' expr='undefined_var.x'/></send>
+                  </foreach>
+                  <if cond='undefined_var.y'><raise event='not_reached'/></if>
+                  <assign location='undefined_loc.x' expr='1'/>
+                  <raise event='not_reached'/>
+                </onentry>
+                <transition event='error.execution'/>
+                <transition event='ev'/>
+                <transition event='done' target='pass'/>
+              </state>
+              <final id='pass'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        fsm.record_transitions = true;
+
+        let global = crate::fsm::run_blocking(fsm, vec![crate::fsm::Event::new_simple("done")]);
+
+        let events: Vec<String> = global
+            .lock()
+            .unwrap()
+            .recorded_transitions()
+            .iter()
+            .map(|record| record.event.clone().unwrap_or_default())
+            .collect();
+        // One error per failing <param> (that doesn't abort the <send>), one for the failing <if> condition
+        // and one for the <assign> that aborts the block. All before the external event.
+        assert_eq!(
+            events,
+            vec![
+                "error.execution",
+                "ev",
+                "error.execution",
+                "ev",
+                "error.execution",
+                "ev",
+                "error.execution",
+                "error.execution",
+                "done"
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn assign_reads_event_data() {
+        use crate::datamodel::Data;
+        use crate::fsm::{Event, ParamPair};
+        use crate::test::run_test_manual_with_send;
+
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='x' expr='0'/></datamodel>
+              <state id='s0'>
+                <transition event='set' target='s1'>
+                  <assign location='x' expr='_event.data.value'/>
+                </transition>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <transition cond='x == 42' target='pass'/>
+                <transition target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual_with_send(
+            "assign_reads_event_data",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000u64,
+            &vec!["pass".to_string()],
+            |sender| {
+                let mut event = Event::new_simple("set");
+                event.param_values = Some(vec![ParamPair::new("value", &Data::Integer(42))]);
+                let _ = sender.send(Box::new(event));
+            },
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn log_evaluates_expression_and_prefixes_label() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry>
+                  <log label='n' expr='1+1'/>
+                  <log expr='"no label"'/>
+                  <log label='only label'/>
+                  <log label='bad' expr='undefined_var.x'/>
+                  <log label='not reached' expr='3'/>
+                </onentry>
+                <transition event='error.execution' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel::<String>();
+        fsm.log_sink = Some(std::sync::Arc::new(sender));
+
+        let global = crate::fsm::run_blocking(fsm, Vec::new());
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+        let messages: Vec<String> = receiver.try_iter().collect();
+        assert_eq!(messages, vec!["n: 2", "no label", "only label"]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn event_observer_receives_raised_and_sent_events() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry>
+                  <raise event='raised'/>
+                  <send event='sent'><param name='x' expr='"value"'/></send>
+                  <send event='internal' target='#_internal'><content>text</content></send>
+                </onentry>
+                <transition event='sent' target='pass'/>
+              </state>
+              <final id='pass'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        fsm.event_observer = Some(sender);
+
+        let global = crate::fsm::run_blocking(fsm, Vec::new());
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+        let events: Vec<crate::fsm::Event> = receiver.try_iter().collect();
+        assert_eq!(
+            events.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(),
+            vec!["raised", "sent", "internal"]
+        );
+        assert_eq!(events[0].etype, crate::fsm::EventType::internal);
+        let params = events[1].param_values.as_ref().unwrap();
+        assert_eq!(params[0].name, "x");
+        assert_eq!(
+            params[0].value,
+            crate::datamodel::Data::String("value".to_string())
+        );
+        assert_eq!(
+            events[2].content,
+            Some(crate::datamodel::Data::String("text".to_string()))
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn describe_onentry_block() {
+        use crate::executable_content::ContentDescription;
+
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='x' expr='0'/></datamodel>
+              <state id='s0'>
+                <onentry>
+                  <assign location='x' expr='x + 1'/>
+                  <log label='counter' expr='x'/>
+                  <send event='tick' delay='2s' id='timer'/>
+                  <if cond='x > 0'><raise event='positive'/><else/><cancel sendid='timer'/></if>
+                </onentry>
+              </state>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        let state = fsm.get_state_by_name(&"s0".to_string());
+        let onentry: Vec<ContentDescription> = fsm
+            .get_executable_content(state.onentry[0])
+            .iter()
+            .map(|ec| ec.describe())
+            .collect();
+        assert_eq!(onentry.len(), 4);
+
+        assert_eq!(onentry[0].type_name, "assign");
+        assert_eq!(
+            onentry[0].fields,
+            vec![("location", "x".to_string()), ("expr", "x + 1".to_string())]
+        );
+        assert_eq!(onentry[1].type_name, "log");
+        assert_eq!(
+            onentry[1].fields,
+            vec![("label", "counter".to_string()), ("expr", "x".to_string())]
+        );
+        assert_eq!(onentry[2].type_name, "send");
+        assert_eq!(
+            onentry[2].fields,
+            vec![
+                ("event", "tick".to_string()),
+                ("id", "timer".to_string()),
+                ("delay", "2000ms".to_string())
+            ]
+        );
+
+        let if_description = &onentry[3];
+        assert_eq!(if_description.type_name, "if");
+        assert_eq!(if_description.fields, vec![("cond", "x > 0".to_string())]);
+        assert_eq!(if_description.blocks.len(), 2);
+        assert_eq!(if_description.blocks[0].0, "then");
+        assert_eq!(if_description.blocks[1].0, "else");
+        let then_block = fsm.get_executable_content(if_description.blocks[0].1);
+        assert_eq!(
+            then_block[0].describe().fields,
+            vec![("event", "positive".to_string())]
+        );
+        let else_block = fsm.get_executable_content(if_description.blocks[1].1);
+        assert_eq!(else_block[0].describe().type_name, "cancel");
+        assert_eq!(
+            else_block[0].describe().fields,
+            vec![("sendid", "timer".to_string())]
+        );
+
+        assert!(fsm.get_executable_content(9999).is_empty());
+    }
 }
 
-/// a duration.
-/// RegExp: "\\d*(\\.\\d+)?(ms|s|m|h|d))").
+/// Parses a CSS2 time designation to milliseconds.\
+/// RegExp: "\\d*(\\.\\d+)?(ms|s|m|h|d))").\
+/// Units are case-insensitive. An empty string results in 0.
+/// Returns -1 if the value is malformed, negative or has no unit.
 pub fn parse_duration_to_milliseconds(d: &str) -> i64 {
+    let d = d.trim();
     if d.is_empty() {
         0
     } else {
         let mut exp = ExpressionLexer::new(d.to_string());
-        let value_result = exp.next_number();
-        if value_result.is_err() {
+        let Ok(value) = exp.next_number() else {
             return -1;
-        }
+        };
         let Ok(unit) = exp.next_name() else {
-            return 0;
+            return -1;
         };
+        if exp.has_next() {
+            return -1;
+        }
 
-        let mut v = value_result.unwrap().as_double();
-        match unit.as_str() {
-            "D" | "d" => {
+        let mut v = value.as_double();
+        if v < 0.0 {
+            return -1;
+        }
+        match unit.to_ascii_lowercase().as_str() {
+            "d" => {
                 v *= 24.0 * 60.0 * 60.0 * 1000.0;
             }
-            "H" | "h" => {
+            "h" => {
                 v *= 60.0 * 60.0 * 1000.0;
             }
-            "M" | "m" => {
+            "m" => {
                 v *= 60000.0;
             }
-            "S" | "s" => {
+            "s" => {
                 v *= 1000.0;
             }
-            "MS" | "ms" => {}
+            "ms" => {}
             _ => {
                 return -1;
             }
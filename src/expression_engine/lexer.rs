@@ -137,7 +137,7 @@ impl ExpressionLexer {
 
     /// Read a String.\
     /// delimiter - The delimiter\
-    /// Escape sequences see String state-chart on JSON.org.
+    /// Escape sequences see String state-chart on JSON.org, additionally "\\'" for strings in single quotes.
     fn read_string(&mut self, delimiter: char) -> Token {
         let mut escape = false;
         let mut c;
@@ -147,7 +147,7 @@ impl ExpressionLexer {
                 return Token::Error("Missing string delimiter".to_string());
             } else if escape {
                 match c {
-                    '"' | '\\' | '/' => {}
+                    '"' | '\'' | '\\' | '/' => {}
                     'b' => {
                         c = '\x08';
                     }
@@ -168,7 +168,7 @@ impl ExpressionLexer {
                         let mut codepoint = String::with_capacity(4);
                         for _i in 0..4 {
                             let c = self.next_char();
-                            if Self::is_digit(c) {
+                            if c.is_ascii_hexdigit() {
                                 codepoint.push(c);
                             } else {
                                 return Token::Error("Illegal \\u sequence in String".to_string());
@@ -706,4 +706,43 @@ mod tests {
         println!(" {:?}", n);
         assert_eq!(n, Token::EOE);
     }
+
+    #[test]
+    fn lexer_can_parse_string_escapes() {
+        let cases = [
+            (r#"'a\nb'"#, "a\nb"),
+            (r#"'a\tb'"#, "a\tb"),
+            (r#"'a\rb'"#, "a\rb"),
+            (r#"'a\bb'"#, "a\x08b"),
+            (r#"'a\fb'"#, "a\x0cb"),
+            (r#"'a\\b'"#, "a\\b"),
+            (r#"'a\/b'"#, "a/b"),
+            (r#"'a\'b'"#, "a'b"),
+            (r#"'a\"b'"#, "a\"b"),
+            (r#"'a"b'"#, "a\"b"),
+            (r#""a\nb""#, "a\nb"),
+            (r#""a\'b""#, "a'b"),
+            (r#""a\"b""#, "a\"b"),
+            (r#""a'b""#, "a'b"),
+            (r#"'\u00e9\u00C9'"#, "\u{e9}\u{c9}"),
+        ];
+        for (source, expected) in cases {
+            let mut l = ExpressionLexer::new(source.to_string());
+            assert_eq!(
+                l.next_token(),
+                Token::TString(expected.to_string()),
+                "{}",
+                source
+            );
+            assert_eq!(l.next_token(), Token::EOE, "{}", source);
+        }
+    }
+
+    #[test]
+    fn lexer_reports_unterminated_and_illegal_strings() {
+        for source in ["'abc", "\"abc", "'abc\"", "'abc\\'", "'a\\x'", "'\\u12'"] {
+            let mut l = ExpressionLexer::new(source.to_string());
+            assert!(matches!(l.next_token(), Token::Error(_)), "{}", source);
+        }
+    }
 }
@@ -15,10 +15,11 @@ use std::ops::DerefMut;
 use std::slice::Iter;
 use std::str::FromStr;
 use std::string::ToString;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvError, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use std::{fmt, thread};
 
 #[cfg(feature = "Debug")]
@@ -31,9 +32,9 @@ use crate::datamodel::ecma_script::ECMAScriptDatamodelFactory;
 #[cfg(feature = "ECMAScriptModel")]
 use crate::datamodel::ecma_script::ECMA_SCRIPT_LC;
 use crate::datamodel::{
-    create_data_arc, Data, DataArc, DataStore, Datamodel, DatamodelFactory, GlobalDataArc, NullDatamodelFactory,
-    NULL_DATAMODEL, NULL_DATAMODEL_LC, SCXML_INVOKE_TYPE, SCXML_INVOKE_TYPE_SHORT, SESSION_ID_VARIABLE_NAME,
-    SESSION_NAME_VARIABLE_NAME,
+    create_data_arc, str_to_source, Data, DataArc, DataStore, Datamodel, DatamodelFactory, GlobalDataArc, LogSink,
+    NullDatamodelFactory, NULL_DATAMODEL, NULL_DATAMODEL_LC, SCXML_INVOKE_TYPE, SCXML_INVOKE_TYPE_SHORT,
+    SESSION_ID_VARIABLE_NAME, SESSION_NAME_VARIABLE_NAME,
 };
 
 use crate::event_io_processor::EventIOProcessor;
@@ -41,12 +42,15 @@ use crate::executable_content::ExecutableContent;
 
 #[cfg(feature = "RfsmExpressionModel")]
 use crate::datamodel::expression_engine::{RFsmExpressionDatamodelFactory, RFSM_EXPRESSION_DATAMODEL_LC};
+#[cfg(feature = "xpath")]
+use crate::datamodel::xpath::{XPathDatamodelFactory, XPATH_DATAMODEL_LC};
 
 use crate::event_io_processor::scxml_event_io_processor::{
-    SCXML_EVENT_PROCESSOR_SHORT_TYPE, SCXML_TARGET_SESSION_ID_PREFIX,
+    ScxmlEventIOProcessor, ScxmlTarget, SCXML_EVENT_PROCESSOR_SHORT_TYPE,
 };
 use crate::fsm::BindingType::{Early, Late};
 use crate::fsm_executor::FsmExecutor;
+use crate::metrics::{Metrics, NoMetrics};
 
 /// Gets the global data store from datamodel.
 macro_rules! get_global {
@@ -64,14 +68,38 @@ use crate::tracer::{TraceMode, Tracer};
 pub const EVENT_CANCEL_SESSION: &str = "error.platform.cancel";
 pub const EVENT_DONE_INVOKE_PREFIX: &str = "done.invoke.";
 
+/// Platform specific event to let a session, waiting for external events, process its internal queue.\
+/// Sent e.g. if a delayed send failed and placed an error in the internal queue. Not visible to the FSM.
+pub const EVENT_WAKE_UP: &str = "platform.wakeup";
+
+/// Platform specific event to let a session process pending evaluations. See [ScxmlSession::evaluate].\
+/// Not visible to the FSM.
+pub const EVENT_EVALUATE: &str = "platform.evaluate";
+
+/// Platform specific event that marks the end of a batch of events. See [ScxmlSession::fire_batch].\
+/// Not visible to the FSM.
+pub const EVENT_BATCH: &str = "platform.batch";
+
+/// Platform specific event to let a session install a new tracer. See [ScxmlSession::swap_tracer].\
+/// Not visible to the FSM.
+#[cfg(feature = "Trace")]
+pub const EVENT_SWAP_TRACER: &str = "platform.tracer";
+
+/// Platform specific event to trace the content of the event queues. See [Fsm::trace_queues].\
+/// Not visible to the FSM.
+#[cfg(feature = "Debug_Queue")]
+pub const EVENT_DEBUG_QUEUES: &str = "platform.queues";
+
 pub static PLATFORM_ID_COUNTER: AtomicU32 = AtomicU32::new(1);
 
 pub static THREAD_ID_COUNTER: AtomicU32 = AtomicU32::new(1);
 
 /// Starts the FSM inside a worker thread.
-///
-pub fn start_fsm(sm: Box<Fsm>, actions: ActionWrapper, executor: Box<FsmExecutor>) -> ScxmlSession {
-    start_fsm_with_data(sm, actions, executor, &Vec::new())
+/// Returns the session and an [EventSink] to send events to the session.
+pub fn start_fsm(sm: Box<Fsm>, actions: ActionWrapper, executor: Box<FsmExecutor>) -> (ScxmlSession, EventSink) {
+    let session = start_fsm_with_data(sm, actions, executor, &Vec::new());
+    let sink = session.event_sink();
+    (session, sink)
 }
 
 pub fn start_fsm_with_data(
@@ -95,8 +123,9 @@ pub fn start_fsm_with_data_and_finish_mode(
     let sender = externalQueue.sender.clone();
 
     let data_copy = data.to_vec();
-    let session_id: SessionId = SESSION_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let session_id = SessionId(SESSION_ID_COUNTER.fetch_add(1, Ordering::Relaxed));
     let mut session = ScxmlSession::new_without_join_handle(session_id, sender.clone());
+    session.cancelled = externalQueue.cancelled.clone();
     session.global_data.lock().unwrap().source = Some(sm.name.clone());
 
     match finish_mode {
@@ -122,16 +151,23 @@ pub fn start_fsm_with_data_and_finish_mode(
     };
 
     let global_data = session.global_data.clone();
+    let session_processors: Vec<Arc<Mutex<Box<dyn EventIOProcessor>>>> = std::mem::take(&mut sm.io_processors)
+        .into_iter()
+        .map(|p| Arc::new(Mutex::new(p)))
+        .collect();
+    // The processors of the executor and of the FSM. The session is connected to them while it runs.
+    let mut connected_processors: Vec<Arc<Mutex<Box<dyn EventIOProcessor>>>> = Vec::new();
     {
         let mut gc = global_data.lock().unwrap();
         gc.actions = actions;
         let executor_state_lock = executor.state.lock();
         let guard = executor_state_lock.unwrap();
-        for p in &guard.processors {
+        for p in guard.processors.iter().chain(session_processors.iter()) {
             let pg = p.lock().unwrap();
             for t in pg.get_types() {
                 gc.io_processors.insert(t.to_string(), p.clone());
             }
+            connected_processors.push(p.clone());
         }
     }
 
@@ -152,6 +188,16 @@ pub fn start_fsm_with_data_and_finish_mode(
                     global.caller_invoke_id = Option::map(sm.caller_invoke_id.as_ref(), |x| x.clone());
                     global.parent_session_id = sm.parent_session_id;
                     global.executor = Some(executor);
+                    global.log_sink = sm.log_sink.clone();
+                    global.event_observer = sm.event_observer.clone();
+                    #[cfg(feature = "Trace")]
+                    {
+                        sm.tracer.set_session_id(session_id);
+                        // Traces of charts without a name keep their plain format.
+                        if sm.name != DEFAULT_FSM_NAME {
+                            sm.tracer.set_session_name(sm.name.as_str());
+                        }
+                    }
 
                     // W3C:
                     // If the value of a key ... matches the 'id' of a <data> element
@@ -169,7 +215,17 @@ pub fn start_fsm_with_data_and_finish_mode(
                         }
                     }
                 }
+                for p in &connected_processors {
+                    p.lock().unwrap().add_fsm(&sm, datamodel.deref_mut());
+                }
                 sm.interpret(datamodel.deref_mut());
+                for p in &connected_processors {
+                    p.lock()
+                        .unwrap()
+                        .get_external_queues()
+                        .fsms
+                        .remove(&session_id);
+                }
             }
             #[cfg(feature = "Debug")]
             debug!("SM finished");
@@ -179,6 +235,52 @@ pub fn start_fsm_with_data_and_finish_mode(
     session
 }
 
+/// Runs the FSM on the calling thread, without a session thread or executor.\
+/// The events are processed in order. The FSM stops if it reaches a top-level final state or if all events
+/// are processed, in the latter case the active states are exited as on a cancel event.\
+/// Events that are sent with delay or by other sessions are not received.
+/// Returns the global data of the session. The final configuration is kept.
+pub fn run_blocking(mut sm: Box<Fsm>, events: impl IntoIterator<Item = Event>) -> GlobalDataArc {
+    let session_id = SessionId(SESSION_ID_COUNTER.fetch_add(1, Ordering::Relaxed));
+    let global_data = GlobalDataArc::new(Mutex::new(GlobalData::new()));
+    let session_processors: Vec<Arc<Mutex<Box<dyn EventIOProcessor>>>> = std::mem::take(&mut sm.io_processors)
+        .into_iter()
+        .map(|p| Arc::new(Mutex::new(p)))
+        .collect();
+    {
+        let mut global = global_data.lock().unwrap();
+        global.session_id = session_id;
+        global.source = Some(sm.name.clone());
+        global.log_sink = sm.log_sink.clone();
+        global.event_observer = sm.event_observer.clone();
+        let _ = global.final_configuration.insert(Vec::new());
+        let processor: Arc<Mutex<Box<dyn EventIOProcessor>>> =
+            Arc::new(Mutex::new(Box::new(ScxmlEventIOProcessor::new())));
+        for t in processor.lock().unwrap().get_types() {
+            global
+                .io_processors
+                .insert(t.to_string(), processor.clone());
+        }
+        for p in &session_processors {
+            for t in p.lock().unwrap().get_types() {
+                global.io_processors.insert(t.to_string(), p.clone());
+            }
+        }
+    }
+    let mut datamodel = create_datamodel(sm.datamodel.as_str(), global_data.clone(), &HashMap::new());
+    for p in &session_processors {
+        p.lock().unwrap().add_fsm(&sm, datamodel.deref_mut());
+    }
+    #[cfg(feature = "Trace")]
+    sm.tracer.set_session_id(session_id);
+    let mut event_source = IteratorEventSource {
+        queue: get_global!(datamodel).externalQueue.receiver.clone(),
+        events: events.into_iter(),
+    };
+    sm.interpret_with_event_source(datamodel.deref_mut(), &mut event_source);
+    global_data
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // ## General Purpose Data types
 // Structs and methods are designed to match the signatures in the W3c-Pseudo-code.
@@ -510,12 +612,124 @@ impl<T> Queue<T> {
     pub fn isEmpty(&self) -> bool {
         self.data.is_empty()
     }
+
+    /// Extension to get the number of elements in the queue.
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Extension to iterate over the queued elements without removing them.
+    pub fn iterator(&self) -> std::collections::vec_deque::Iter<'_, T> {
+        self.data.iter()
+    }
 }
 
+/// Queue based on a channel, used for the external event queue of a session.\
+/// Threading model:
+/// + Any number of threads may enqueue concurrently via clones of [BlockingQueue::sender].
+///   No element is lost as long as the receiver is alive.
+/// + Elements sent by the same sender (or thread) are delivered in the order they were sent.
+///   Elements of different senders are interleaved in the order they arrived; there is no
+///   further ordering between them.
+/// + Only one consumer, the thread of the FSM, dequeues elements. The receiver is held in a
+///   mutex only to share it with the session (e.g. to estimate the size).
 #[derive(Debug)]
 pub struct BlockingQueue<T> {
     pub sender: Sender<T>,
-    pub receiver: Arc<Mutex<Receiver<T>>>,
+    pub receiver: Arc<Mutex<BlockingQueueReceiver<T>>>,
+    /// If set, [BlockingQueueReceiver::recv_unless_cancelled] returns immediately.
+    /// See [ScxmlSession::cancel].
+    pub cancelled: Arc<AtomicBool>,
+}
+
+/// Receiving side of a [BlockingQueue].\
+/// As a channel can't tell the number of pending elements, elements are moved to a
+/// look-ahead buffer to count them. [BlockingQueueReceiver::recv] delivers them first.
+#[derive(Debug)]
+pub struct BlockingQueueReceiver<T> {
+    receiver: Receiver<T>,
+    look_ahead: VecDeque<T>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<T> BlockingQueueReceiver<T> {
+    /// Removes and returns first element, blocks if no element is available.
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        match self.look_ahead.pop_front() {
+            Some(e) => Ok(e),
+            None => self.receiver.recv(),
+        }
+    }
+
+    /// Removes and returns first element, blocks if no element is available.\
+    /// Returns None if the queue was cancelled, also while blocking (the canceller has to send some
+    /// element to wake up the receiver). The received element is kept in the queue in this case, so no element is lost.
+    pub fn recv_unless_cancelled(&mut self) -> Option<T> {
+        if self.cancelled.load(Ordering::Acquire) {
+            return None;
+        }
+        let e = match self.look_ahead.pop_front() {
+            Some(e) => e,
+            None => self.receiver.recv().ok()?,
+        };
+        if self.cancelled.load(Ordering::Acquire) {
+            self.look_ahead.push_front(e);
+            None
+        } else {
+            Some(e)
+        }
+    }
+
+    /// Removes and returns first element, doesn't block.\
+    /// Returns None if no element is available or the queue was cancelled.
+    pub fn try_recv(&mut self) -> Option<T> {
+        if self.cancelled.load(Ordering::Acquire) {
+            return None;
+        }
+        match self.look_ahead.pop_front() {
+            Some(e) => Some(e),
+            None => self.receiver.try_recv().ok(),
+        }
+    }
+
+    /// Returns the number of elements that are currently pending.\
+    /// As other threads may send at any time, the result is only an estimate.
+    pub fn size_estimate(&mut self) -> usize {
+        self.look_ahead.extend(self.receiver.try_iter());
+        self.look_ahead.len()
+    }
+}
+
+/// Source of external events for [Fsm::interpret_with_event_source].
+pub trait EventSource {
+    /// Removes and returns the next external event. May block.\
+    /// Returns None if no more events will be available, e.g. if the session was cancelled.
+    fn next_event(&mut self) -> Option<Box<Event>>;
+}
+
+/// The external queue of a session, used by [Fsm::interpret]. Blocks until events are available.\
+/// If all senders are dropped, the FSM stops as on a cancel event. Note that the session itself holds a sender
+/// (to send events to itself), so dropping the handles of an embedder doesn't stop it, use [ScxmlSession::cancel].
+impl EventSource for Arc<Mutex<BlockingQueueReceiver<Box<Event>>>> {
+    fn next_event(&mut self) -> Option<Box<Event>> {
+        self.lock().unwrap().recv_unless_cancelled()
+    }
+}
+
+/// Source for [run_blocking]. Events in the external queue of the session (e.g. from \<send\> without target)
+/// are taken first, then the events of the iterator. Never blocks.
+struct IteratorEventSource<I: Iterator<Item = Event>> {
+    queue: Arc<Mutex<BlockingQueueReceiver<Box<Event>>>>,
+    events: I,
+}
+
+impl<I: Iterator<Item = Event>> EventSource for IteratorEventSource<I> {
+    fn next_event(&mut self) -> Option<Box<Event>> {
+        match self.queue.lock().unwrap().try_recv() {
+            Some(event) => Some(event),
+            None => self.events.next().map(Box::new),
+        }
+    }
 }
 
 impl<T> Default for BlockingQueue<T> {
@@ -527,9 +741,15 @@ impl<T> Default for BlockingQueue<T> {
 impl<T> BlockingQueue<T> {
     fn new() -> BlockingQueue<T> {
         let (sender, receiver) = channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
         BlockingQueue {
-            receiver: Arc::new(Mutex::new(receiver)),
+            receiver: Arc::new(Mutex::new(BlockingQueueReceiver {
+                receiver,
+                look_ahead: VecDeque::new(),
+                cancelled: cancelled.clone(),
+            })),
             sender,
+            cancelled,
         }
     }
 
@@ -544,6 +764,11 @@ impl<T> BlockingQueue<T> {
     pub fn dequeue(&mut self) -> T {
         self.receiver.lock().unwrap().recv().unwrap()
     }
+
+    /// Extension to get the number of pending elements. See [BlockingQueueReceiver::size_estimate].
+    pub fn size_estimate(&self) -> usize {
+        self.receiver.lock().unwrap().size_estimate()
+    }
 }
 
 /// *W3C says*:
@@ -600,6 +825,7 @@ pub type ExecutableContentId = u32;
 pub type StateVec = Vec<State>;
 pub type StateNameMap = HashMap<Name, StateId>;
 pub type TransitionMap = HashMap<TransitionId, Transition>;
+pub type ExecutableContentMap = HashMap<ExecutableContentId, Vec<Box<dyn ExecutableContent>>>;
 
 /// Datamodel binding type. See [W3C SCXML Data Binding](/doc/W3C_SCXML_2024_07_13/index.html#DataBinding)
 #[derive(Debug, Clone, PartialEq, Copy, Default)]
@@ -679,6 +905,22 @@ impl ParamPair {
             value: value.clone(),
         }
     }
+
+    /// Groups the values by name, in order of the first occurrence of each name.\
+    /// *W3C says*:
+    /// If the 'namelist' attribute or \<param\> elements contain duplicate names, the SCXML Processor
+    /// must include all attribute/value pairs, even if duplicates occur.\
+    /// The values of a name are kept in document order.
+    pub fn group_by_name(pairs: &[ParamPair]) -> Vec<(&str, Vec<&Data>)> {
+        let mut groups: Vec<(&str, Vec<&Data>)> = Vec::new();
+        for pair in pairs {
+            match groups.iter_mut().find(|(name, _)| *name == pair.name) {
+                Some((_, values)) => values.push(&pair.value),
+                None => groups.push((pair.name.as_str(), vec![&pair.value])),
+            }
+        }
+        groups
+    }
 }
 
 /// *W3C says*:
@@ -856,6 +1098,21 @@ impl Event {
         }
     }
 
+    /// W3C: Indicates that an error has occurred while trying to communicate with an external entity.\
+    /// Used if there is no event that failed, e.g. if an invoked session could not be started.
+    pub fn error_communication_for(send_id: &Option<String>, invoke_id: &Option<InvokeId>) -> Event {
+        Event {
+            name: "error.communication".to_string(),
+            etype: EventType::platform,
+            sendid: send_id.clone(),
+            origin: None,
+            param_values: None,
+            content: None,
+            invoke_id: invoke_id.clone(),
+            origin_type: None,
+        }
+    }
+
     pub fn get_copy(&self) -> Box<Event> {
         Box::new(Event {
             invoke_id: self.invoke_id.clone(),
@@ -874,6 +1131,50 @@ pub type InvokeId = String;
 
 pub type EventSender = Sender<Box<Event>>;
 
+/// Error of [EventSink] if the session no longer accepts events.\
+/// Contains the event that could not be delivered.
+#[derive(Debug)]
+pub struct SessionClosedError(pub Box<Event>);
+
+impl Display for SessionClosedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Session closed, event '{}' not delivered", self.0.name)
+    }
+}
+
+impl std::error::Error for SessionClosedError {}
+
+/// Convenience wrapper around the sender to the external queue of a session.\
+/// Can be cloned to feed one FSM from multiple producers.
+/// See [BlockingQueue] for the guarantees on concurrent senders.
+#[derive(Clone, Debug)]
+pub struct EventSink {
+    sender: EventSender,
+}
+
+impl EventSink {
+    pub fn new(sender: EventSender) -> EventSink {
+        EventSink { sender }
+    }
+
+    /// Sends an event to the external queue of the session.
+    pub fn send(&self, event: Event) -> Result<(), SessionClosedError> {
+        self.sender
+            .send(Box::new(event))
+            .map_err(|e| SessionClosedError(e.0))
+    }
+
+    /// Sends a simple event with the given name.
+    pub fn send_named(&self, name: &str) -> Result<(), SessionClosedError> {
+        self.send(Event::new_simple(name))
+    }
+
+    /// The wrapped sender.
+    pub fn sender(&self) -> &EventSender {
+        &self.sender
+    }
+}
+
 #[derive(Clone, PartialEq, Debug, Default)]
 pub struct CommonContent {
     /// content inside \<content\> child
@@ -944,7 +1245,9 @@ pub struct Invoke {
 
     /// *W3C says*:
     /// Boolean.\
-    /// A flag indicating whether to forward events to the invoked process.
+    /// A flag indicating whether to forward events to the invoked process.\
+    /// Events are forwarded when they are taken from the external queue. Delayed events that the session
+    /// sends to itself are therefore forwarded after the delay, when they arrive in the external queue.
     pub autoforward: bool,
 
     /// *W3C says*:
@@ -1043,6 +1346,8 @@ pub struct GlobalData {
 
     /// Will contain after execution the final configuration, if set before.
     pub final_configuration: Option<Vec<String>>,
+    /// Contains the executed microsteps if [Fsm::record_transitions] is set.
+    pub transition_records: Option<Vec<TransitionRecord>>,
     pub environment: HashMap<String, DataArc>,
 
     /// Stores any delayed send (with a "sendid"), Key: sendid
@@ -1050,6 +1355,52 @@ pub struct GlobalData {
     pub io_processors: HashMap<String, Arc<Mutex<Box<dyn EventIOProcessor>>>>,
 
     pub data: DataStore,
+
+    /// Pending evaluations, see [ScxmlSession::evaluate]. None after the FSM has finished.
+    pub evaluations: Option<Vec<Evaluation>>,
+
+    /// Callers waiting for the end of their batch, in the order of the batches.
+    /// See [ScxmlSession::fire_batch]. None after the FSM has finished.
+    pub batches: Option<VecDeque<Sender<Vec<String>>>>,
+    /// Pending tracer swaps, see [ScxmlSession::swap_tracer]. None after the FSM has finished.
+    #[cfg(feature = "Trace")]
+    pub tracer_swaps: Option<Vec<TracerSwap>>,
+    /// See [Fsm::log_sink].
+    pub log_sink: Option<Arc<dyn LogSink>>,
+    /// See [Fsm::event_observer].
+    pub event_observer: Option<Sender<Event>>,
+    /// Delayed sends that are not sent yet, by some internal key. See [GlobalData::pending_sends].
+    pub scheduled_sends: HashMap<u32, PendingSend>,
+    scheduled_send_count: u32,
+}
+
+/// A delayed \<send\> that is scheduled, but not sent yet. See [GlobalData::pending_sends].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingSend {
+    /// The id of the send. None if neither "id" nor "idlocation" was given.
+    pub send_id: Option<String>,
+    pub target: String,
+    pub event: String,
+    /// The time the event is due.
+    pub due: Instant,
+}
+
+impl PendingSend {
+    /// The time until the event is sent.
+    pub fn remaining(&self) -> Duration {
+        self.due.saturating_duration_since(Instant::now())
+    }
+}
+
+/// Record of one executed microstep. See [Fsm::record_transitions].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransitionRecord {
+    /// Name of the event that enabled the transitions. None for eventless transitions.
+    pub event: Option<String>,
+    /// The executed transitions in document order.
+    pub transitions: Vec<TransitionId>,
+    /// Names of the states of the configuration after the microstep.
+    pub configuration: Vec<String>,
 }
 
 impl GlobalData {
@@ -1067,18 +1418,96 @@ impl GlobalData {
             child_sessions: HashMap::new(),
             caller_invoke_id: None,
             parent_session_id: None,
-            session_id: 0,
+            session_id: SessionId(0),
             final_configuration: None,
+            transition_records: None,
             environment: HashMap::new(),
             delayed_send: HashMap::new(),
             io_processors: HashMap::new(),
             data: DataStore::new(),
+            evaluations: Some(Vec::new()),
+            batches: Some(VecDeque::new()),
+            #[cfg(feature = "Trace")]
+            tracer_swaps: Some(Vec::new()),
+            log_sink: None,
+            event_observer: None,
+            scheduled_sends: HashMap::new(),
+            scheduled_send_count: 0,
+        }
+    }
+
+    /// Adds a delayed send. Returns the key to remove it.
+    pub fn add_scheduled_send(&mut self, send: PendingSend) -> u32 {
+        self.scheduled_send_count += 1;
+        self.scheduled_sends.insert(self.scheduled_send_count, send);
+        self.scheduled_send_count
+    }
+
+    /// Gets a snapshot of the delayed sends that are not sent yet, the next one first.\
+    /// For diagnostics, e.g. to find out why some event was not received yet.
+    pub fn pending_sends(&self) -> Vec<PendingSend> {
+        let mut sends: Vec<PendingSend> = self.scheduled_sends.values().cloned().collect();
+        sends.sort_by_key(|send| send.due);
+        sends
+    }
+
+    /// Gets the recorded microsteps in execution order. Empty if recording was not enabled.
+    pub fn recorded_transitions(&self) -> &[TransitionRecord] {
+        match &self.transition_records {
+            None => &[],
+            Some(records) => records.as_slice(),
         }
     }
 
     pub fn enqueue_internal(&mut self, event: Event) {
+        self.notify_event_observer(&event);
         self.internalQueue.enqueue(event);
     }
+
+    /// Sends a copy of the event to the [event observer](Fsm::event_observer), if one is set.
+    pub fn notify_event_observer(&self, event: &Event) {
+        if let Some(observer) = &self.event_observer {
+            let _ = observer.send(event.clone());
+        }
+    }
+
+    /// Returns the number of events in the internal queue.\
+    /// Can be used as mark for [GlobalData::set_send_id_of_errors].
+    pub fn internal_queue_size(&self) -> usize {
+        self.internalQueue.size()
+    }
+
+    /// Removes all events that were put into the internal queue after the given mark.\
+    /// See [GlobalData::internal_queue_size].
+    pub fn discard_internal_since(&mut self, mark: usize) {
+        self.internalQueue.data.truncate(mark);
+    }
+
+    /// Returns the number of error events that were put into the internal queue after the given mark.\
+    /// See [GlobalData::internal_queue_size].
+    pub fn errors_since(&self, mark: usize) -> usize {
+        self.internalQueue
+            .data
+            .iter()
+            .skip(mark)
+            .filter(|event| event.name.starts_with("error."))
+            .count()
+    }
+
+    /// Sets the send-id of all error events that were put into the internal queue after the given mark
+    /// and that have no send-id yet. Returns the number of error events after the mark.
+    pub fn set_send_id_of_errors(&mut self, mark: usize, send_id: &Option<String>) -> usize {
+        let mut count = 0;
+        for event in self.internalQueue.data.iter_mut().skip(mark) {
+            if event.name.starts_with("error.") {
+                count += 1;
+                if event.sendid.is_none() {
+                    event.sendid.clone_from(send_id);
+                }
+            }
+        }
+        count
+    }
 }
 
 /// Mode how the executor handles the ScxmlSession
@@ -1104,6 +1533,8 @@ pub struct ScxmlSession {
     pub invoke_doc_id: DocumentId,
     /// State of the invoke or 0.
     pub state_id: Option<StateId>,
+    /// Shared with the external queue of the session. See [ScxmlSession::cancel].
+    pub cancelled: Arc<AtomicBool>,
 }
 
 impl Debug for ScxmlSession {
@@ -1125,10 +1556,131 @@ impl ScxmlSession {
             global_data: GlobalDataArc::new(Mutex::new(GlobalData::new())),
             invoke_doc_id: 0,
             state_id: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Stops the session immediately, even if the FSM is blocked in waiting for external events.\
+    /// Other than the cancel event, this doesn't wait until pending events are processed.
+    /// Pending events stay in the queue. The FSM exits the active states as on a cancel event.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+        // Wake up the FSM if blocked.
+        let _ = self.sender.send(Box::new(Event::new_simple(EVENT_WAKE_UP)));
+    }
+
+    /// Gets a sink to send events to this session.
+    pub fn event_sink(&self) -> EventSink {
+        EventSink::new(self.sender.clone())
+    }
+
+    /// Evaluates an expression against the data model of this session, e.g. for a debugger.\
+    /// The expression is evaluated after all events that were sent before, when the FSM waits for the next
+    /// external event. The result is sent through the returned channel.\
+    /// With "side_effects" not set, the expression is evaluated like a value expression. It should not modify
+    /// the data model, but the datamodel doesn't enforce this. With "side_effects" set, the expression is
+    /// executed as a script and may change data, e.g. "count = 0".\
+    /// In both cases, errors are only returned to the caller and don't raise error events in the FSM.
+    pub fn evaluate(&self, expression: &str, side_effects: bool) -> Receiver<Result<Data, String>> {
+        let (sender, receiver) = channel();
+        let evaluation = Evaluation {
+            expression: expression.to_string(),
+            side_effects,
+            reply: sender,
+        };
+        match &mut self.global_data.lock().unwrap().evaluations {
+            Some(evaluations) => evaluations.push(evaluation),
+            None => {
+                let _ = evaluation
+                    .reply
+                    .send(Err("Session is finished".to_string()));
+                return receiver;
+            }
+        }
+        let _ = self
+            .sender
+            .send(Box::new(Event::new_simple(EVENT_EVALUATE)));
+        receiver
+    }
+
+    /// Replaces the tracer of the running session, e.g. to record the activity of a session for a while.\
+    /// The tracer is installed after all events that were sent before, when the FSM waits for the next
+    /// external event. The previous tracer is sent through the returned channel, so it can be installed again later.
+    /// If the session is already finished or finishes before the tracer is installed, the given tracer is sent back.\
+    /// The trace mode of the new tracer is not changed.
+    #[cfg(feature = "Trace")]
+    pub fn swap_tracer(&self, tracer: Box<dyn Tracer>) -> Receiver<Box<dyn Tracer>> {
+        let (sender, receiver) = channel();
+        let swap = TracerSwap {
+            tracer,
+            reply: sender,
+        };
+        match &mut self.global_data.lock().unwrap().tracer_swaps {
+            Some(swaps) => swaps.push(swap),
+            None => {
+                let _ = swap.reply.send(swap.tracer);
+                return receiver;
+            }
+        }
+        let _ = self
+            .sender
+            .send(Box::new(Event::new_simple(EVENT_SWAP_TRACER)));
+        receiver
+    }
+
+    /// Gets a snapshot of the delayed sends of the session that are not sent yet.
+    /// See [GlobalData::pending_sends].\
+    /// The snapshot is taken at once, but may be taken in the middle of a macrostep. To get the state at the end of a
+    /// macrostep, call it after [ScxmlSession::fire_batch].
+    pub fn pending_sends(&self) -> Vec<PendingSend> {
+        self.global_data.lock().unwrap().pending_sends()
+    }
+
+    /// Sends a batch of events and waits until the FSM has processed all of them.\
+    /// Returns the configuration (in entry order) that is stable after the last event of the batch,
+    /// so the caller doesn't observe the intermediate configurations.\
+    /// Events of other senders may be processed between the events of the batch.
+    /// Returns an error if the session finishes before the end of the batch is reached.
+    pub fn fire_batch(&self, events: Vec<Event>) -> Result<Vec<String>, String> {
+        let (sender, receiver) = channel();
+        {
+            // The lock keeps the order of replies and end markers if multiple batches are sent concurrently.
+            let mut global = self.global_data.lock().unwrap();
+            match &mut global.batches {
+                Some(batches) => batches.push_back(sender),
+                None => return Err("Session is finished".to_string()),
+            }
+            for event in events {
+                self.sender
+                    .send(Box::new(event))
+                    .map_err(|_| "Session is finished".to_string())?;
+            }
+            self.sender
+                .send(Box::new(Event::new_simple(EVENT_BATCH)))
+                .map_err(|_| "Session is finished".to_string())?;
         }
+        receiver
+            .recv()
+            .map_err(|_| "Session finished during batch".to_string())
     }
 }
 
+/// Pending evaluation of an expression. See [ScxmlSession::evaluate].
+pub struct Evaluation {
+    pub expression: String,
+    /// If set, the expression is executed as script and may modify the data model.
+    pub side_effects: bool,
+    pub reply: Sender<Result<Data, String>>,
+}
+
+/// A pending [ScxmlSession::swap_tracer].
+#[cfg(feature = "Trace")]
+pub struct TracerSwap {
+    pub tracer: Box<dyn Tracer>,
+    /// Receives the replaced tracer.
+    pub reply: Sender<Box<dyn Tracer>>,
+}
+
 impl Clone for ScxmlSession {
     fn clone(&self) -> Self {
         ScxmlSession {
@@ -1138,6 +1690,7 @@ impl Clone for ScxmlSession {
             global_data: self.global_data.clone(),
             state_id: self.state_id,
             invoke_doc_id: self.invoke_doc_id,
+            cancelled: self.cancelled.clone(),
         }
     }
 
@@ -1147,10 +1700,58 @@ impl Clone for ScxmlSession {
         self.sender = source.sender.clone();
         self.state_id = source.state_id;
         self.invoke_doc_id = source.invoke_doc_id;
+        self.cancelled = source.cancelled.clone();
     }
 }
 
-pub type SessionId = u32;
+/// Unique id of a session.\
+/// A newtype and not an alias of u32, so it can't be mixed up with the other numeric ids, e.g. [StateId].
+/// ```compile_fail
+/// use rufsm::fsm::{SessionId, StateId};
+/// let state: StateId = 1;
+/// let session: SessionId = state;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SessionId(pub u32);
+
+impl Display for SessionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for SessionId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(SessionId(s.parse::<u32>()?))
+    }
+}
+
+impl From<u32> for SessionId {
+    fn from(value: u32) -> Self {
+        SessionId(value)
+    }
+}
+
+impl From<SessionId> for u32 {
+    fn from(value: SessionId) -> Self {
+        value.0
+    }
+}
+
+/// Callback for external events that didn't match any transition in the current configuration.\
+/// Gets the id of the session and the dropped event.
+pub type UnhandledEventHandler = Arc<dyn Fn(SessionId, &Event) + Send + Sync>;
+
+/// Change of the configuration during a macrostep. See [Fsm::config_change_sender].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    /// Names of the states that were entered, in entry order.
+    pub entered: Vec<String>,
+    /// Names of the states that were exited.
+    pub exited: Vec<String>,
+}
 
 /// The FSM implementation, according to W3C proposal.
 #[allow(non_snake_case)]
@@ -1161,7 +1762,9 @@ pub struct Fsm {
     pub binding: BindingType,
     pub version: String,
     pub statesNames: StateNameMap,
-    pub executableContent: HashMap<ExecutableContentId, Vec<Box<dyn ExecutableContent>>>,
+    /// The executable content is immutable after the FSM was read and is shared by all instances
+    /// created by [Fsm::instantiate].
+    pub executableContent: Arc<ExecutableContentMap>,
 
     pub name: String,
     pub file: Option<String>,
@@ -1187,6 +1790,53 @@ pub struct Fsm {
     pub timer: timer::Timer,
 
     pub generate_id_count: u32,
+
+    /// Optional handler, called for external events that enable no transition.
+    pub unhandled_event_handler: Option<UnhandledEventHandler>,
+
+    /// Collects runtime metrics. Does nothing by default.
+    pub metrics: Box<dyn Metrics>,
+
+    /// If set, all executed microsteps are recorded in [GlobalData::transition_records].
+    pub record_transitions: bool,
+
+    /// If set, the result of each transition condition is evaluated only once during one transition selection
+    /// ([Fsm::selectTransitions] or [Fsm::selectEventlessTransitions]), even if the transition is examined for
+    /// multiple atomic states of a parallel state.\
+    /// This is only correct if the conditions have no side effects, as W3C recommends. A condition that fails is
+    /// also reported only once per selection.
+    pub cache_conditions: bool,
+
+    /// If set, the FSM also terminates if all regions of a top-level \<parallel\> state reached a final state,
+    /// as if a top-level \<final\> state was entered. The "done.state" event of the parallel state is not
+    /// processed anymore.\
+    /// This is an extension, W3C only terminates the FSM if a top-level \<final\> state is entered.
+    pub terminate_on_parallel_completion: bool,
+
+    /// If set, the \<onexit\> handlers of a state are executed in reverse document order, so that a state
+    /// is torn down in the reverse order of its \<onentry\> handlers.\
+    /// This is not conformant, W3C executes the handlers in document order. The order in which the states
+    /// are exited is not changed.
+    pub reverse_onexit_order: bool,
+
+    /// If set, the FSM sends the changes of the configuration after each macrostep, so only
+    /// stable configurations are reported. States that were exited and entered again inside the same
+    /// macrostep are not reported. Macrosteps without changes are skipped.\
+    /// Has to be set before the FSM is started.
+    pub config_change_sender: Option<Sender<ConfigChange>>,
+
+    /// Additional I/O processors of the session, see [Fsm::add_io_processor].
+    pub io_processors: Vec<Box<dyn EventIOProcessor>>,
+
+    /// If set, the messages of \<log\> elements are written to this sink instead of the logging backend.\
+    /// Has to be set before the FSM is started.
+    pub log_sink: Option<Arc<dyn LogSink>>,
+
+    /// If set, a copy of each event that is put in the internal queue and of each event that is sent
+    /// by \<send\> to an I/O processor is sent to this channel, e.g. to check the events a chart produces in tests.
+    /// Delayed events are reported when they are sent.\
+    /// Has to be set before the FSM is started.
+    pub event_observer: Option<Sender<Event>>,
 }
 
 impl Default for Fsm {
@@ -1202,13 +1852,20 @@ impl Debug for Fsm {
             "Fsm{{v:{} root:{} states:",
             self.version, self.pseudo_root
         )?;
-        display_state_map(&self.states, f)?;
+        display_state_map(&self.states, self.pseudo_root, f)?;
         display_transition_map(&self.transitions, f)?;
         write!(f, "}}")
     }
 }
 
-fn display_state_map(sm: &StateVec, f: &mut Formatter<'_>) -> fmt::Result {
+/// Label of the artificial root state in diagnostic output.
+/// The root represents the \<scxml\> element and is not a state of the document.
+pub const DOCUMENT_ROOT_LABEL: &str = "<scxml>";
+
+/// Name of a FSM whose document declares no name.
+pub const DEFAULT_FSM_NAME: &str = "FSM";
+
+fn display_state_map(sm: &StateVec, root: StateId, f: &mut Formatter<'_>) -> fmt::Result {
     write!(f, "{{")?;
 
     let mut first = true;
@@ -1218,7 +1875,18 @@ fn display_state_map(sm: &StateVec, f: &mut Formatter<'_>) -> fmt::Result {
         } else {
             write!(f, ",")?;
         }
-        write!(f, "{}", *e)?;
+        if e.id == root {
+            write!(
+                f,
+                "{{#{} {} states:{} transitions: {}}}",
+                e.id,
+                DOCUMENT_ROOT_LABEL,
+                vec_to_string(&e.states),
+                vec_to_string(&e.transitions.data)
+            )?;
+        } else {
+            write!(f, "{}", *e)?;
+        }
     }
 
     write!(f, "}}")
@@ -1251,18 +1919,111 @@ impl Fsm {
             tracer: create_tracer(),
             caller_invoke_id: None,
             parent_session_id: None,
-            name: "FSM".to_string(),
+            name: DEFAULT_FSM_NAME.to_string(),
             file: None,
             script: 0,
             version: "1.0".to_string(),
             binding: BindingType::Early,
             statesNames: StateNameMap::new(),
-            executableContent: HashMap::new(),
+            executableContent: Arc::new(HashMap::new()),
+            timer: timer::Timer::new(),
+            generate_id_count: 0,
+            unhandled_event_handler: None,
+            metrics: Box::new(NoMetrics::default()),
+            record_transitions: false,
+            cache_conditions: false,
+            terminate_on_parallel_completion: false,
+            reverse_onexit_order: false,
+            config_change_sender: None,
+            io_processors: Vec::new(),
+            log_sink: None,
+            event_observer: None,
+        }
+    }
+
+    /// Creates a new runnable instance of this FSM.\
+    /// States and transitions are copied, the executable content is shared.
+    /// The runtime state of the new instance is reset. Session and datamodel are created if the
+    /// instance is started.
+    pub fn instantiate(&self) -> Box<Fsm> {
+        let mut fsm = Box::new(Fsm {
+            #[cfg(feature = "Trace")]
+            tracer: create_tracer(),
+            datamodel: self.datamodel.clone(),
+            binding: self.binding,
+            version: self.version.clone(),
+            statesNames: self.statesNames.clone(),
+            executableContent: self.executableContent.clone(),
+            name: self.name.clone(),
+            file: self.file.clone(),
+            pseudo_root: self.pseudo_root,
+            states: self.states.clone(),
+            transitions: self.transitions.clone(),
+            script: self.script,
+            caller_invoke_id: self.caller_invoke_id.clone(),
+            parent_session_id: self.parent_session_id,
             timer: timer::Timer::new(),
             generate_id_count: 0,
+            unhandled_event_handler: self.unhandled_event_handler.clone(),
+            metrics: Box::new(NoMetrics::default()),
+            record_transitions: self.record_transitions,
+            cache_conditions: self.cache_conditions,
+            terminate_on_parallel_completion: self.terminate_on_parallel_completion,
+            reverse_onexit_order: self.reverse_onexit_order,
+            config_change_sender: None,
+            io_processors: Vec::new(),
+            log_sink: self.log_sink.clone(),
+            event_observer: self.event_observer.clone(),
+        });
+        #[cfg(feature = "Trace")]
+        fsm.tracer.enable_trace(self.tracer.trace_mode());
+        for state in &mut fsm.states {
+            state.isFirstEntry = true;
+        }
+        fsm
+    }
+
+    /// Adds an I/O processor to the session of this FSM, e.g. for a custom transport.\
+    /// Has to be called before the FSM is started. On start, the processor is registered for its types,
+    /// in addition to the processors of the executor. If a type is handled by both, this processor is used.
+    /// The processor is bound to the session via [EventIOProcessor::add_fsm] and is available in
+    /// "_ioprocessors" and as "type" of \<send\>.\
+    /// The processors are not copied by [Fsm::instantiate].
+    pub fn add_io_processor(&mut self, processor: Box<dyn EventIOProcessor>) {
+        self.io_processors.push(processor);
+    }
+
+    /// Gets the executable content of a block, e.g. of an \<onentry\> element.\
+    /// Returns an empty slice for unknown ids.
+    pub fn get_executable_content(&self, content_id: ExecutableContentId) -> &[Box<dyn ExecutableContent>] {
+        match self.executableContent.get(&content_id) {
+            Some(content) => content.as_slice(),
+            None => &[],
         }
     }
 
+    /// Gets mutable access to the executable content.\
+    /// Returns None if the content is shared with other instances, see [Fsm::instantiate].
+    pub fn executable_content_mut(&mut self) -> Option<&mut ExecutableContentMap> {
+        Arc::get_mut(&mut self.executableContent)
+    }
+
+    /// The name of the FSM, as given by the "name" attribute of the \<scxml\> element.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// The data binding, as given by the "binding" attribute of the \<scxml\> element.
+    pub fn binding(&self) -> BindingType {
+        self.binding
+    }
+
+    /// Overrides the data binding of the document, e.g. to force late binding if the data is loaded lazily.\
+    /// Has to be called before the FSM is started.
+    pub fn set_binding(&mut self, binding: BindingType) {
+        self.binding = binding;
+    }
+
     pub fn get_state_by_name(&self, name: &Name) -> &State {
         self.get_state_by_id(*self.statesNames.get(name).unwrap())
     }
@@ -1291,6 +2052,99 @@ impl Fsm {
         self.transitions.get(&transition_id).unwrap()
     }
 
+    /// Gets a read-only view of a transition with all ids resolved to names.
+    /// The id MUST exist.
+    pub fn get_transition_view(&self, transition_id: TransitionId) -> TransitionView {
+        let transition = self.get_transition_by_id(transition_id);
+        TransitionView {
+            id: transition.id,
+            source: self.get_state_by_id(transition.source).name.clone(),
+            target: transition
+                .target
+                .iter()
+                .map(|sid| self.get_state_by_id(*sid).name.clone())
+                .collect(),
+            events: transition.events.clone(),
+            cond: if transition.cond.is_empty() {
+                None
+            } else {
+                Some(transition.cond.to_string())
+            },
+            transition_type: transition.transition_type.to_string(),
+        }
+    }
+
+    /// Gets the transitions of the state with the given name in document order.\
+    /// The initial transition of the state is not included.
+    /// Returns an empty list if the state doesn't exist.
+    pub fn transitions_from(&self, state_name: &str) -> Vec<TransitionView> {
+        match self.statesNames.get(state_name) {
+            None => Vec::new(),
+            Some(sid) => self
+                .get_state_by_id(*sid)
+                .transitions
+                .iterator()
+                .map(|tid| self.get_transition_view(*tid))
+                .collect(),
+        }
+    }
+
+    /// Gets all transitions that have the state with the given name as target.
+    /// Returns an empty list if the state doesn't exist.
+    pub fn transitions_to(&self, state_name: &str) -> Vec<TransitionView> {
+        match self.statesNames.get(state_name) {
+            None => Vec::new(),
+            Some(sid) => {
+                let mut views: Vec<TransitionView> = self
+                    .transitions
+                    .values()
+                    .filter(|transition| transition.target.contains(sid))
+                    .map(|transition| self.get_transition_view(transition.id))
+                    .collect();
+                views.sort_by_key(|view| self.get_transition_by_id(view.id).doc_id);
+                views
+            }
+        }
+    }
+
+    /// Gets the invoke descriptors of the state with the given name in document order.\
+    /// Returns an empty list if the state doesn't exist.
+    pub fn invokes_of(&self, state_name: &str) -> Vec<&Invoke> {
+        match self.statesNames.get(state_name) {
+            None => Vec::new(),
+            Some(sid) => self.get_state_by_id(*sid).invoke.iterator().collect(),
+        }
+    }
+
+    /// Gets the invoke descriptors of all states.
+    /// The invoking state is given by [Invoke::parent_state_name].
+    pub fn all_invokes(&self) -> Vec<&Invoke> {
+        let mut invokes: Vec<&Invoke> = self
+            .states
+            .iter()
+            .flat_map(|state| state.invoke.iterator())
+            .collect();
+        invokes.sort_by_key(|invoke| invoke.doc_id);
+        invokes
+    }
+
+    /// Gets the names of all events that can trigger a transition in the given configuration.\
+    /// The conditions of the transitions are not evaluated. The wildcard "*" is not included.
+    /// Descriptors are returned as declared, so "error" also stands for all "error.*" events.
+    pub fn enabled_events(&self, configuration: &OrderedSet<StateId>) -> HashSet<String> {
+        let mut events = HashSet::new();
+        for sid in configuration.iterator() {
+            for tid in self.get_state_by_id(*sid).transitions.iterator() {
+                for event in &self.get_transition_by_id(*tid).events {
+                    if event != "*" {
+                        events.insert(event.clone());
+                    }
+                }
+            }
+        }
+        events
+    }
+
     fn state_document_order(&self, sid1: &StateId, sid2: &StateId) -> std::cmp::Ordering {
         // TODO: Optimize! Do that state-ids == index in fsm.states.
         let s1 = self.get_state_by_id(*sid1);
@@ -1371,7 +2225,176 @@ impl Fsm {
     ///     enterStates([doc.initial.transition])
     ///     mainEventLoop()
     /// ```
+    /// #Actual implementation:
+    /// External events are taken from the external queue of the session.
     pub fn interpret(&mut self, datamodel: &mut dyn Datamodel) {
+        let mut events = get_global!(datamodel).externalQueue.receiver.clone();
+        self.interpret_with_event_source(datamodel, &mut events);
+    }
+
+    /// Extension of [Fsm::interpret] that takes the external events from some [EventSource].
+    pub fn interpret_with_event_source(&mut self, datamodel: &mut dyn Datamodel, events: &mut dyn EventSource) {
+        let initial = self.get_state_by_id(self.pseudo_root).initial;
+        self.interpret_with_initial_transition(datamodel, events, initial);
+    }
+
+    /// Extension of [Fsm::interpret] that starts the FSM in the given configuration instead of the initial one,
+    /// e.g. to resume a FSM or to test a deep state.\
+    /// The configuration is given by state names. Missing ancestors, the other regions of parallel states
+    /// and the initial children of compound states are entered as if the states were the target of a transition.\
+    /// Returns an error without starting the FSM if the configuration is not legal, see [Fsm::validate_configuration].
+    pub fn interpret_from(&mut self, datamodel: &mut dyn Datamodel, config: &[&str]) -> Result<(), String> {
+        let targets = self.validate_configuration(config)?;
+        let tid = self.add_initial_transition(targets);
+        let mut events = get_global!(datamodel).externalQueue.receiver.clone();
+        self.interpret_with_initial_transition(datamodel, &mut events, tid);
+        Ok(())
+    }
+
+    /// Extension to check that the named states can be active together.\
+    /// Returns the ids of the states to enter or an error if a state is unknown or if the configuration
+    /// that results from entering the states is not legal, see [Fsm::is_legal_configuration].
+    /// Ancestors of other given states are not returned, they are entered anyway.
+    pub fn validate_configuration(&mut self, config: &[&str]) -> Result<Vec<StateId>, String> {
+        let mut states = Vec::with_capacity(config.len());
+        for name in config {
+            match self.statesNames.get(*name) {
+                Some(sid) if *sid != self.pseudo_root => {
+                    if !states.contains(sid) {
+                        states.push(*sid);
+                    }
+                }
+                _ => return Err(format!("Unknown state '{}'", name)),
+            }
+        }
+        // Entering an ancestor as target would also enter its initial children.
+        let states: Vec<StateId> = states
+            .iter()
+            .filter(|sid| !states.iter().any(|other| self.isDescendant(*other, **sid)))
+            .cloned()
+            .collect();
+        let tid = self.add_initial_transition(states.clone());
+        let mut configuration = self.compute_entered_states(tid);
+        self.transitions.remove(&tid);
+        // History states are replaced by their default, add them to get them rejected.
+        for sid in &states {
+            if !configuration.contains(sid) {
+                configuration.push(*sid);
+            }
+        }
+        self.is_legal_configuration(&configuration)?;
+        Ok(states)
+    }
+
+    /// Adds a transition from the pseudo root to the targets, usable as initial transition.
+    fn add_initial_transition(&mut self, targets: Vec<StateId>) -> TransitionId {
+        let tid = self.transitions.keys().max().unwrap_or(&0) + 1;
+        let mut transition = Transition::with_id(tid);
+        transition.transition_type = TransitionType::Internal;
+        transition.source = self.pseudo_root;
+        transition.target = targets;
+        self.transitions.insert(tid, transition);
+        tid
+    }
+
+    /// Computes the states that are entered by the transition, in entry order. The pseudo root is not included.
+    fn compute_entered_states(&mut self, tid: TransitionId) -> Vec<StateId> {
+        // The entry set doesn't evaluate any expression, but needs a datamodel for the (empty) history.
+        let mut datamodel = create_datamodel(
+            NULL_DATAMODEL,
+            GlobalDataArc::new(Mutex::new(GlobalData::new())),
+            &HashMap::new(),
+        );
+        let mut states_to_enter = OrderedSet::new();
+        let mut states_for_default_entry = OrderedSet::new();
+        let mut default_history_content = HashTable::new();
+        self.computeEntrySet(
+            datamodel.deref_mut(),
+            &List::from_array(&[tid]),
+            &mut states_to_enter,
+            &mut states_for_default_entry,
+            &mut default_history_content,
+        );
+        states_to_enter
+            .sort(&|s1, s2| self.state_entry_order(s1, s2))
+            .iterator()
+            .filter(|sid| **sid != self.pseudo_root)
+            .cloned()
+            .collect()
+    }
+
+    /// Extension to check a complete configuration against the rules of section 3.11 "Legal State Configurations
+    /// and Specifications": each active state (besides the \<scxml\> element) has an active parent,
+    /// exactly one child of the \<scxml\> element and of each active compound state is active,
+    /// all children of active parallel states are active and no history state is active.\
+    /// The pseudo root may be part of the configuration, but doesn't need to.
+    pub fn is_legal_configuration(&self, config: &[StateId]) -> Result<(), String> {
+        let mut active = HashSet::with_capacity(config.len() + 1);
+        for sid in config {
+            if *sid == 0 || *sid as usize > self.states.len() {
+                return Err(format!("Unknown state id {}", sid));
+            }
+            if self.isHistoryState(*sid) {
+                return Err(format!(
+                    "History state '{}' can't be part of a configuration",
+                    self.get_state_by_id(*sid).name
+                ));
+            }
+            active.insert(*sid);
+        }
+        active.insert(self.pseudo_root);
+        for sid in std::iter::once(&self.pseudo_root).chain(config) {
+            let state = self.get_state_by_id(*sid);
+            if *sid != self.pseudo_root && !active.contains(&state.parent) {
+                return Err(format!(
+                    "State '{}' is active, but its parent '{}' is not",
+                    state.name,
+                    self.get_state_by_id(state.parent).name
+                ));
+            }
+            let children = self.getChildStates(*sid);
+            let active_children = children
+                .iterator()
+                .filter(|child| active.contains(*child))
+                .count();
+            if self.isParallelState(*sid) {
+                if active_children != children.size() {
+                    return Err(format!(
+                        "Not all regions of the parallel state '{}' are active",
+                        state.name
+                    ));
+                }
+            } else if self.isCompoundStateOrScxmlElement(*sid) && active_children != 1 {
+                return Err(format!(
+                    "State '{}' has {} active children, but needs exactly one",
+                    state.name, active_children
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Extension to compute the configuration after the initial transition was taken, without running the FSM,
+    /// e.g. to check the "initial" attributes and \<initial\> elements.\
+    /// Returns the names of the states in entry order. Eventless transitions and events raised on entry
+    /// are not considered, they may change the configuration before the FSM waits for the first event.
+    pub fn initial_configuration(&mut self) -> Vec<String> {
+        let initial = self.get_state_by_id(self.pseudo_root).initial;
+        if initial == 0 {
+            return Vec::new();
+        }
+        self.compute_entered_states(initial)
+            .iter()
+            .map(|sid| self.get_state_by_id(*sid).name.clone())
+            .collect()
+    }
+
+    fn interpret_with_initial_transition(
+        &mut self,
+        datamodel: &mut dyn Datamodel,
+        events: &mut dyn EventSource,
+        initial: TransitionId,
+    ) {
         #[cfg(feature = "Trace_Method")]
         self.tracer.enter_method("interpret");
         if !self.valid() {
@@ -1385,7 +2408,7 @@ impl Fsm {
             // Initialize session variables "_name" and "_sessionid"
 
             let session_id = datamodel.global_s().lock().unwrap().session_id;
-            datamodel.initialize_read_only(SESSION_ID_VARIABLE_NAME, Data::Integer(session_id as i64));
+            datamodel.initialize_read_only(SESSION_ID_VARIABLE_NAME, Data::Integer(session_id.0 as i64));
             // TODO :Escape name
             datamodel.initialize_read_only(SESSION_NAME_VARIABLE_NAME, Data::String(self.name.clone()));
 
@@ -1394,6 +2417,9 @@ impl Fsm {
                 gd.internalQueue.clear();
                 gd.historyValue.clear();
                 gd.running = true;
+                if self.record_transitions {
+                    gd.transition_records = Some(Vec::new());
+                }
             }
 
             datamodel.add_functions(self);
@@ -1408,12 +2434,11 @@ impl Fsm {
         self.executeGlobalScriptElement(datamodel);
 
         let mut inital_states = List::new();
-        let itid = self.get_state_by_id(self.pseudo_root).initial;
-        if itid != 0 {
-            inital_states.push(itid);
+        if initial != 0 {
+            inital_states.push(initial);
         }
         self.enterStates(datamodel, &inital_states);
-        self.mainEventLoop(datamodel);
+        self.mainEventLoop(datamodel, events);
         #[cfg(feature = "Trace_Method")]
         self.tracer.exit_method("interpret");
     }
@@ -1532,7 +2557,7 @@ impl Fsm {
     ///     exitInterpreter()
     /// ```
     #[allow(non_snake_case)]
-    fn mainEventLoop(&mut self, datamodel: &mut dyn Datamodel) {
+    fn mainEventLoop(&mut self, datamodel: &mut dyn Datamodel, events: &mut dyn EventSource) {
         #[cfg(feature = "Trace_Method")]
         self.tracer.enter_method("mainEventLoop");
 
@@ -1542,6 +2567,8 @@ impl Fsm {
                 Some(cid) => cid.clone(),
             }
         };
+        let loop_start = std::time::Instant::now();
+        let mut stableConfiguration: Vec<StateId> = Vec::new();
 
         while get_global!(datamodel).running {
             let mut enabledTransitions;
@@ -1550,6 +2577,7 @@ impl Fsm {
             // triggered by internal events until macrostep is complete
             while get_global!(datamodel).running && !macrostepDone {
                 enabledTransitions = self.selectEventlessTransitions(datamodel);
+                let mut internalEventName = None;
                 if enabledTransitions.isEmpty() {
                     if get_global!(datamodel).internalQueue.isEmpty() {
                         macrostepDone = true;
@@ -1558,6 +2586,7 @@ impl Fsm {
                         self.tracer.enter_method("internalQueue.dequeue");
 
                         let internalEvent = { get_global!(datamodel).internalQueue.dequeue() };
+                        self.metrics.internal_event();
                         #[cfg(feature = "Trace_Method")]
                         self.tracer.exit_method("internalQueue.dequeue");
                         #[cfg(feature = "Trace_Event")]
@@ -1565,12 +2594,16 @@ impl Fsm {
                         // TODO: Optimize it, set event only once
                         datamodel.set_event(&internalEvent);
                         enabledTransitions = self.selectTransitions(datamodel, &internalEvent);
+                        internalEventName = Some(internalEvent.name);
                     }
                 }
                 if !enabledTransitions.isEmpty() {
-                    self.microstep(datamodel, &enabledTransitions.toList())
+                    self.microstep(datamodel, &enabledTransitions.toList());
+                    self.record_microstep(datamodel, internalEventName, &enabledTransitions);
                 }
             }
+            self.metrics.macrostep();
+            self.notify_config_change(datamodel, &mut stableConfiguration);
             // either we're in a final state, and we break out of the loop
             if !get_global!(datamodel).running {
                 break;
@@ -1589,7 +2622,7 @@ impl Fsm {
 
             let externalEvent;
             {
-                let externalQueue_receiver = {
+                {
                     let mut global_lock = get_global!(datamodel);
 
                     // let gdb = datamodel.global();
@@ -1598,8 +2631,7 @@ impl Fsm {
                     if !global_lock.internalQueue.isEmpty() {
                         continue;
                     }
-                    global_lock.externalQueue.receiver.clone()
-                };
+                }
 
                 // W3C says:
                 //   A blocking wait for an external event.  Alternatively, if we have been invoked
@@ -1608,7 +2640,14 @@ impl Fsm {
                 #[cfg(feature = "Trace_Method")]
                 self.tracer.enter_method("externalQueue.dequeue");
                 loop {
-                    let externalEventTmp = externalQueue_receiver.lock().unwrap().recv().unwrap();
+                    let externalEventTmp = match events.next_event() {
+                        Some(event) => event,
+                        None => {
+                            // Session was cancelled or no more events, handle it like the cancel event.
+                            externalEvent = Box::new(Event::new_simple(EVENT_CANCEL_SESSION));
+                            break;
+                        }
+                    };
                     if externalEventTmp.name.starts_with(EVENT_DONE_INVOKE_PREFIX) {
                         externalEvent = externalEventTmp;
                         break;
@@ -1650,6 +2689,32 @@ impl Fsm {
                     get_global!(datamodel).running = false;
                     continue;
                 }
+                if externalEvent.name == EVENT_WAKE_UP {
+                    continue;
+                }
+                if externalEvent.name == EVENT_EVALUATE {
+                    // Control event, not visible to the FSM.
+                    self.evaluate_pending(datamodel);
+                    continue;
+                }
+                #[cfg(feature = "Trace")]
+                if externalEvent.name == EVENT_SWAP_TRACER {
+                    // Control event, not visible to the FSM.
+                    self.swap_pending_tracers(datamodel);
+                    continue;
+                }
+                if externalEvent.name == EVENT_BATCH {
+                    // Control event, not visible to the FSM. All events of the batch are processed
+                    // and the configuration is stable.
+                    self.reply_batch(datamodel);
+                    continue;
+                }
+                #[cfg(feature = "Debug_Queue")]
+                if externalEvent.name == EVENT_DEBUG_QUEUES {
+                    // Control event, not visible to the FSM.
+                    self.trace_queues(datamodel);
+                    continue;
+                }
 
                 if externalEvent.name.starts_with(EVENT_DONE_INVOKE_PREFIX) {
                     if let Some(invoke_id) = &externalEvent.invoke_id {
@@ -1658,38 +2723,33 @@ impl Fsm {
                 }
             }
             let mut toFinalize: Vec<ExecutableContentId> = Vec::new();
-            let mut toForward: Vec<InvokeId> = Vec::new();
+            let mut toForward: Vec<(DocumentId, InvokeId)> = Vec::new();
             {
-                match externalEvent.invoke_id {
-                    None => {}
-                    Some(ref invokeId) => {
-                        match get_global!(datamodel).child_sessions.get(invokeId) {
-                            None => {}
-                            Some(session) => {
-                                // Get state of invokeid
-                                if let Some(state_id) = session.state_id {
-                                    let invoke_doc_id = session.invoke_doc_id;
-                                    let state = self.get_state_by_id(state_id);
-                                    for inv in state.invoke.iterator() {
-                                        if inv.doc_id == invoke_doc_id {
-                                            toFinalize.push(inv.finalize);
-                                        }
-                                        if inv.autoforward {
-                                            toForward.push(invokeId.clone());
-                                        }
-                                    }
+                // The active invokes are the child sessions, each of them belongs to a state in the configuration.
+                for (invokeId, session) in get_global!(datamodel).child_sessions.iter() {
+                    if let Some(state_id) = session.state_id {
+                        let state = self.get_state_by_id(state_id);
+                        for inv in state.invoke.iterator() {
+                            if inv.doc_id == session.invoke_doc_id {
+                                if externalEvent.invoke_id.as_ref() == Some(invokeId) {
+                                    toFinalize.push(inv.finalize);
+                                }
+                                if inv.autoforward {
+                                    toForward.push((inv.doc_id, invokeId.clone()));
                                 }
                             }
                         }
                     }
-                };
+                }
+                toForward.sort();
             }
+            let externalEvent = self.convert_event_data(datamodel, externalEvent);
             datamodel.set_event(&externalEvent);
             for finalizeContentId in toFinalize {
                 // applyFinalize
                 self.executeContent(datamodel, finalizeContentId);
             }
-            for invokeId in toForward {
+            for (_, invokeId) in toForward {
                 // When the 'autoforward' attribute is set to true, the SCXML Processor must send an
                 // exact copy of every external event it receives to the invoked process.
                 // All the fields specified in 5.10.1 The Internal Structure of Events must have the
@@ -1711,17 +2771,161 @@ impl Fsm {
                 }
             }
 
+            self.metrics.external_event();
             enabledTransitions = self.selectTransitions(datamodel, &externalEvent);
             if !enabledTransitions.isEmpty() {
                 self.microstep(datamodel, &enabledTransitions.toList());
+                self.record_microstep(
+                    datamodel,
+                    Some(externalEvent.name.clone()),
+                    &enabledTransitions,
+                );
+            } else if let Some(handler) = &self.unhandled_event_handler {
+                let session_id = get_global!(datamodel).session_id;
+                handler(session_id, &externalEvent);
             }
         }
         // End of outer while running loop.  If we get here, we have reached a top-level final state or have been cancelled
+        // The last macrostep may have been interrupted by a final state.
+        self.notify_config_change(datamodel, &mut stableConfiguration);
+        self.metrics.event_loop_finished(loop_start.elapsed());
         self.exitInterpreter(datamodel);
+        // Drops pending evaluations and batches, the callers get an error from their channels.
+        get_global!(datamodel).evaluations = None;
+        get_global!(datamodel).batches = None;
+        // Pending tracer swaps are not installed anymore, the callers get their tracers back.
+        #[cfg(feature = "Trace")]
+        {
+            let swaps = get_global!(datamodel).tracer_swaps.take();
+            for swap in swaps.into_iter().flatten() {
+                let _ = swap.reply.send(swap.tracer);
+            }
+        }
         #[cfg(feature = "Trace_Method")]
         self.tracer.exit_method("mainEventLoop");
     }
 
+    /// Extension to convert the data of an inbound external event via [Datamodel::convert_event_data].\
+    /// If some value can't be converted, the data of the event is left blank and error.execution is raised.
+    fn convert_event_data(&self, datamodel: &mut dyn Datamodel, mut event: Box<Event>) -> Box<Event> {
+        let mut failure = None;
+        if let Some(content) = &event.content {
+            match datamodel.convert_event_data(content) {
+                Ok(converted) => event.content = Some(converted),
+                Err(msg) => failure = Some(msg),
+            }
+        }
+        if let Some(params) = &event.param_values {
+            let mut converted = Vec::with_capacity(params.len());
+            for param in params {
+                match datamodel.convert_event_data(&param.value) {
+                    Ok(value) => converted.push(ParamPair {
+                        name: param.name.clone(),
+                        value,
+                    }),
+                    Err(msg) => {
+                        failure = Some(msg);
+                        break;
+                    }
+                }
+            }
+            event.param_values = Some(converted);
+        }
+        if let Some(msg) = failure {
+            error!("Data of event '{}' can't be converted: {}", event.name, msg);
+            event.content = None;
+            event.param_values = None;
+            datamodel.internal_error_execution_for_event(&event.sendid, &event.invoke_id);
+        }
+        event
+    }
+
+    /// Extension to reply the configuration to the oldest pending batch of [ScxmlSession::fire_batch].
+    fn reply_batch(&mut self, datamodel: &mut dyn Datamodel) {
+        let mut global = get_global!(datamodel);
+        let reply = match &mut global.batches {
+            Some(batches) => batches.pop_front(),
+            None => None,
+        };
+        if let Some(reply) = reply {
+            let configuration = global
+                .configuration
+                .iterator()
+                .map(|sid| self.get_state_by_id(*sid).name.clone())
+                .collect();
+            let _ = reply.send(configuration);
+        }
+    }
+
+    /// Extension to install the tracers of [ScxmlSession::swap_tracer] between two macrosteps.
+    #[cfg(feature = "Trace")]
+    fn swap_pending_tracers(&mut self, datamodel: &mut dyn Datamodel) {
+        let (swaps, session_id) = {
+            let mut global = get_global!(datamodel);
+            match &mut global.tracer_swaps {
+                Some(swaps) => (std::mem::take(swaps), global.session_id),
+                None => return,
+            }
+        };
+        for mut swap in swaps {
+            swap.tracer.set_session_id(session_id);
+            if self.name != DEFAULT_FSM_NAME {
+                swap.tracer.set_session_name(self.name.as_str());
+            }
+            let previous = std::mem::replace(&mut self.tracer, swap.tracer);
+            let _ = swap.reply.send(previous);
+        }
+    }
+
+    /// Extension to evaluate the pending expressions of [ScxmlSession::evaluate] between two macrosteps.
+    fn evaluate_pending(&mut self, datamodel: &mut dyn Datamodel) {
+        let evaluations = match &mut get_global!(datamodel).evaluations {
+            Some(evaluations) => std::mem::take(evaluations),
+            None => return,
+        };
+        for evaluation in evaluations {
+            let mark = get_global!(datamodel).internal_queue_size();
+            let result = if evaluation.side_effects {
+                datamodel.execute(&str_to_source(&evaluation.expression))
+            } else {
+                datamodel.get_by_location(&evaluation.expression)
+            };
+            // Errors are reported to the caller only.
+            get_global!(datamodel).discard_internal_since(mark);
+            let _ = evaluation
+                .reply
+                .send(result.map(|value| value.lock().unwrap().clone()));
+        }
+    }
+
+    /// Extension to send the configuration changes since the last stable configuration.
+    /// See [Fsm::config_change_sender].
+    fn notify_config_change(&self, datamodel: &mut dyn Datamodel, last_configuration: &mut Vec<StateId>) {
+        if let Some(sender) = &self.config_change_sender {
+            let configuration: Vec<StateId> = get_global!(datamodel)
+                .configuration
+                .iterator()
+                .cloned()
+                .collect();
+            let change = ConfigChange {
+                entered: configuration
+                    .iter()
+                    .filter(|sid| !last_configuration.contains(sid))
+                    .map(|sid| self.get_state_by_id(*sid).name.clone())
+                    .collect(),
+                exited: last_configuration
+                    .iter()
+                    .filter(|sid| !configuration.contains(sid))
+                    .map(|sid| self.get_state_by_id(*sid).name.clone())
+                    .collect(),
+            };
+            if !(change.entered.is_empty() && change.exited.is_empty()) {
+                let _ = sender.send(change);
+            }
+            *last_configuration = configuration;
+        }
+    }
+
     /// *W3C says*:
     /// # procedure exitInterpreter()
     /// The purpose of this procedure is to exit the current SCXML process by exiting all active
@@ -1770,7 +2974,7 @@ impl Fsm {
                 for session_id in session_id_list {
                     datamodel.send(
                         SCXML_EVENT_PROCESSOR_SHORT_TYPE,
-                        &Data::String(format!("{}{}", SCXML_TARGET_SESSION_ID_PREFIX, session_id)),
+                        &Data::String(ScxmlTarget::Session(session_id).to_string()),
                         Event::new_simple(EVENT_CANCEL_SESSION),
                     );
                 }
@@ -1795,13 +2999,38 @@ impl Fsm {
         }
     }
 
+    /// Extension to record a microstep, if enabled. See [Fsm::record_transitions].
+    fn record_microstep(
+        &self,
+        datamodel: &mut dyn Datamodel,
+        event: Option<String>,
+        transitions: &OrderedSet<TransitionId>,
+    ) {
+        if !self.record_transitions {
+            return;
+        }
+        let mut global = get_global!(datamodel);
+        let configuration = global
+            .configuration
+            .iterator()
+            .map(|sid| self.get_state_by_id(*sid).name.clone())
+            .collect();
+        if let Some(records) = global.transition_records.as_mut() {
+            records.push(TransitionRecord {
+                event,
+                transitions: transitions.iterator().cloned().collect(),
+                configuration,
+            });
+        }
+    }
+
     /// *W3C says*:
     /// The implementation of returnDoneEvent is platform-dependent, but if this session is the
     /// result of an \<invoke\> in another SCXML session, returnDoneEvent will cause the event
     /// done.invoke.\<id\> to be placed in the external event queue of that session, where \<id\> is
     /// the id generated in that session when the \<invoke\> was executed.
     #[allow(non_snake_case)]
-    fn returnDoneEvent(&mut self, _done_data: &Option<DoneData>, datamodel: &mut dyn Datamodel) {
+    fn returnDoneEvent(&mut self, done_data: &Option<DoneData>, datamodel: &mut dyn Datamodel) {
         let caller_invoke_id;
         let parent_session_id;
         {
@@ -1813,28 +3042,52 @@ impl Fsm {
             None => {
                 // No parent
             }
-            Some(session_id) => {
-                match caller_invoke_id {
-                    None => {
-                        panic!("Internal Error: Caller-Invoke-Id not available but Parent-Session-Id is set.");
-                    }
-                    Some(invoke_id) => {
-                        // TODO: Evaluate done_data, EventType::external ?
-                        let mut event = Event::new(
-                            EVENT_DONE_INVOKE_PREFIX,
-                            &invoke_id,
-                            None,
-                            None,
-                            EventType::external,
-                        );
-                        event.invoke_id = Some(invoke_id);
-                        datamodel.send(
-                            SCXML_EVENT_PROCESSOR_SHORT_TYPE,
-                            &Data::String(format!("{}{}", SCXML_TARGET_SESSION_ID_PREFIX, session_id)),
-                            event,
-                        );
-                    }
+            Some(session_id) => match caller_invoke_id {
+                None => {
+                    panic!("Internal Error: Caller-Invoke-Id not available but Parent-Session-Id is set.");
+                }
+                Some(invoke_id) => {
+                    let (param_values, content) = Self::evaluate_done_data(done_data, datamodel);
+                    let mut event = Event::new(
+                        EVENT_DONE_INVOKE_PREFIX,
+                        &invoke_id,
+                        param_values,
+                        content,
+                        EventType::external,
+                    );
+                    event.invoke_id = Some(invoke_id);
+                    datamodel.send(
+                        SCXML_EVENT_PROCESSOR_SHORT_TYPE,
+                        &Data::String(ScxmlTarget::Session(session_id).to_string()),
+                        event,
+                    );
                 }
+            },
+        }
+    }
+
+    /// Evaluates the \<param\> and \<content\> children of a \<donedata\> element.\
+    /// Evaluation errors are put as "error.execution" on the internal queue by the datamodel.
+    fn evaluate_done_data(
+        done_data: &Option<DoneData>,
+        datamodel: &mut dyn Datamodel,
+    ) -> (Option<Vec<ParamPair>>, Option<Data>) {
+        match done_data {
+            None => (None, None),
+            Some(done_data) => {
+                let mut name_values = Vec::new();
+                datamodel.evaluate_params(&done_data.params, &mut name_values);
+                let content = datamodel
+                    .evaluate_content(&done_data.content)
+                    .map(|data| data.lock().unwrap().clone());
+                (
+                    if name_values.is_empty() {
+                        None
+                    } else {
+                        Some(name_values)
+                    },
+                    content,
+                )
             }
         }
     }
@@ -1871,6 +3124,7 @@ impl Fsm {
         self.tracer.enter_method("selectEventlessTransitions");
 
         let mut enabledTransitions: OrderedSet<TransitionId> = OrderedSet::new();
+        let mut condition_cache = HashMap::new();
         let atomicStates = get_global!(datamodel)
             .configuration
             .toList()
@@ -1896,7 +3150,7 @@ impl Fsm {
                 }
             }
             for ct in condT {
-                if self.conditionMatch(datamodel, ct) {
+                if self.cached_condition_match(datamodel, ct, &mut condition_cache) {
                     enabledTransitions.add(ct);
                     break;
                 }
@@ -1935,6 +3189,7 @@ impl Fsm {
         self.tracer.enter_method("selectTransitions");
 
         let mut enabledTransitions: OrderedSet<TransitionId> = OrderedSet::new();
+        let mut condition_cache = HashMap::new();
         let atomicStates = get_global!(datamodel)
             .configuration
             .toList()
@@ -1960,7 +3215,7 @@ impl Fsm {
                 }
             }
             for ct in condT {
-                if self.conditionMatch(datamodel, ct) {
+                if self.cached_condition_match(datamodel, ct, &mut condition_cache) {
                     enabledTransitions.add(ct);
                     break;
                 }
@@ -2028,6 +3283,7 @@ impl Fsm {
     ///
     ///     return filteredTransitions;
     /// ```
+    /// The exit set of each transition is computed only once per call.
     #[allow(non_snake_case)]
     fn removeConflictingTransitions(
         &self,
@@ -2035,17 +3291,16 @@ impl Fsm {
         enabledTransitions: &OrderedSet<TransitionId>,
     ) -> OrderedSet<TransitionId> {
         let mut filteredTransitions: OrderedSet<TransitionId> = OrderedSet::new();
+        let mut exitSets: HashMap<TransitionId, OrderedSet<StateId>> = HashMap::new();
         //toList sorts the transitions in the order of the states that selected them
         for tid1 in enabledTransitions.toList().iterator() {
             let t1 = self.get_transition_by_id(*tid1);
             let mut t1Preempted = false;
             let mut transitionsToRemove = OrderedSet::new();
+            let t1ExitSet = self.computeExitSet(datamodel, &List::from_array(&[*tid1]));
             let filteredTransitionList = filteredTransitions.toList();
             for tid2 in filteredTransitionList.iterator() {
-                if self
-                    .computeExitSet(datamodel, &List::from_array(&[*tid1]))
-                    .hasIntersection(&self.computeExitSet(datamodel, &List::from_array(&[*tid2])))
-                {
+                if t1ExitSet.hasIntersection(exitSets.get(tid2).unwrap()) {
                     let t2 = self.get_transition_by_id(*tid2);
                     if self.isDescendant(t1.source, t2.source) {
                         transitionsToRemove.add(tid2);
@@ -2060,6 +3315,7 @@ impl Fsm {
                     filteredTransitions.delete(t3);
                 }
                 filteredTransitions.add(*tid1);
+                exitSets.insert(*tid1, t1ExitSet);
             }
         }
         filteredTransitions
@@ -2080,6 +3336,7 @@ impl Fsm {
     fn microstep(&mut self, datamodel: &mut dyn Datamodel, enabledTransitions: &List<TransitionId>) {
         #[cfg(feature = "Trace_Method")]
         self.tracer.enter_method("microstep");
+        self.metrics.microstep(enabledTransitions.size());
         #[cfg(feature = "Debug")]
         if enabledTransitions.size() > 0 {
             if enabledTransitions.size() > 1 {
@@ -2156,26 +3413,23 @@ impl Fsm {
         let statesToExitSorted = statesToExit.sort(&|s1, s2| self.state_exit_order(s1, s2));
         let mut ahistory: HashTable<StateId, OrderedSet<StateId>> = HashTable::new();
 
-        let configStateList = self.set_to_state_list(&get_global!(datamodel).configuration);
+        // Both history types are recorded from the same snapshot of the configuration.
+        let configuration = get_global!(datamodel).configuration.toList();
 
         for sid in statesToExitSorted.iterator() {
             let s = self.get_state_by_id(*sid);
             for hid in s.history.iterator() {
                 let h = self.get_state_by_id(*hid);
-                if h.history_type == HistoryType::Deep {
-                    let stateIdList = self.state_list_to_id_set(
-                        &configStateList
-                            .filter_by(&|s0| -> bool { self.isAtomicState(s0) && self.isDescendant(s0.id, s.id) }),
-                    );
-                    ahistory.put_move(h.id, stateIdList);
+                let historyStates = if h.history_type == HistoryType::Deep {
+                    configuration
+                        .filter_by(&|s0| -> bool { self.isAtomicStateId(s0) && self.isDescendant(*s0, s.id) })
+                        .to_set()
                 } else {
-                    let fl = &get_global!(datamodel)
-                        .configuration
-                        .toList()
+                    configuration
                         .filter_by(&|s0| -> bool { self.get_state_by_id(*s0).parent == s.id })
-                        .to_set();
-                    ahistory.put(h.id, fl);
-                }
+                        .to_set()
+                };
+                ahistory.put_move(h.id, historyStates);
             }
         }
 
@@ -2195,6 +3449,9 @@ impl Fsm {
                 for ec in &s.onexit {
                     exitList.push(*ec);
                 }
+                if self.reverse_onexit_order {
+                    exitList.data.reverse();
+                }
             }
             if !invoke_doc_ids.is_empty() {
                 let mut session_ids = Vec::new();
@@ -2208,9 +3465,16 @@ impl Fsm {
                 }
             }
 
+            let start = self
+                .metrics
+                .state_timing_enabled()
+                .then(std::time::Instant::now);
             for ec in exitList.iterator() {
                 self.executeContent(datamodel, *ec);
             }
+            if let Some(start) = start {
+                self.metrics.state_exited(*sid, start.elapsed());
+            }
 
             get_global!(datamodel).configuration.delete(sid)
         }
@@ -2315,11 +3579,18 @@ impl Fsm {
                 }
             }
 
+            let start = self
+                .metrics
+                .state_timing_enabled()
+                .then(std::time::Instant::now);
             for ct in exe {
                 if ct > 0 {
                     self.executeContent(datamodel, ct);
                 }
             }
+            if let Some(start) = start {
+                self.metrics.state_entered(*s, start.elapsed());
+            }
 
             if self.isFinalStateId(*s) {
                 let state_s = self.get_state_by_id(*s);
@@ -2328,22 +3599,7 @@ impl Fsm {
                     get_global!(datamodel).running = false;
                 } else {
                     let parentS = self.get_state_by_id(parent);
-                    let mut name_values = Vec::new();
-                    let mut content = None;
-                    match &state_s.donedata {
-                        None => {}
-                        Some(done_data) => {
-                            datamodel.evaluate_params(&done_data.params, &mut name_values);
-                            content = datamodel
-                                .evaluate_content(&done_data.content)
-                                .map(|data| data.lock().unwrap().clone());
-                        }
-                    }
-                    let param_values = if name_values.is_empty() {
-                        None
-                    } else {
-                        Some(name_values)
-                    };
+                    let (param_values, content) = Self::evaluate_done_data(&state_s.donedata, datamodel);
 
                     self.enqueue_internal(
                         datamodel,
@@ -2364,6 +3620,7 @@ impl Fsm {
                             .every(&|s: &StateId| -> bool { self.isInFinalState(datamodel, *s) })
                     {
                         let grandparentS = self.get_state_by_id(grandparent);
+                        let grandgrandparent = grandparentS.parent;
                         self.enqueue_internal(
                             datamodel,
                             // TODO: EventType::external ?
@@ -2375,6 +3632,9 @@ impl Fsm {
                                 EventType::external,
                             ),
                         );
+                        if self.terminate_on_parallel_completion && self.isSCXMLElement(grandgrandparent) {
+                            get_global!(datamodel).running = false;
+                        }
                     }
                 }
             }
@@ -2383,11 +3643,20 @@ impl Fsm {
         self.tracer.exit_method("enterStates");
     }
 
+    /// Traces the content of the internal queue and the number of pending external events.\
+    /// Triggered by the external event [EVENT_DEBUG_QUEUES] if trace mode [TraceMode::EVENTS] is enabled.
+    #[cfg(feature = "Debug_Queue")]
+    pub fn trace_queues(&self, datamodel: &mut dyn Datamodel) {
+        let global = get_global!(datamodel);
+        self.tracer
+            .trace_queues(&global.internalQueue, global.externalQueue.size_estimate());
+    }
+
     /// Put an event into the internal queue.
     pub fn enqueue_internal(&mut self, datamodel: &mut dyn Datamodel, event: Event) {
         #[cfg(feature = "Trace_Event")]
         self.tracer.event_internal_send(&event);
-        get_global!(datamodel).internalQueue.enqueue(event);
+        get_global!(datamodel).enqueue_internal(event);
     }
 
     #[allow(non_snake_case)]
@@ -2499,12 +3768,34 @@ impl Fsm {
     fn executeTransitionContent(&mut self, datamodel: &mut dyn Datamodel, enabledTransitions: &List<TransitionId>) {
         for tid in enabledTransitions.iterator() {
             let t = self.get_transition_by_id(*tid);
+            #[cfg(feature = "Trace_State")]
+            self.tracer.trace_transition(t);
             if t.content > 0 {
-                self.executeContent(datamodel, t.content);
+                let content = t.content;
+                // Extension: A panic in the content of one transition (e.g. in a custom action) is handled like
+                // any other failure: "error.execution" is raised and the other transitions are still executed.
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    self.executeContent(datamodel, content);
+                }));
+                if result.is_err() {
+                    self.recover_from_panic(datamodel);
+                }
             }
         }
     }
 
+    /// Extension to continue after a panic during execution of content.\
+    /// Clears the poisoned locks of the global data and raises "error.execution".
+    /// The datamodel is aborted, as the panic may have left its script engine in an undefined state,
+    /// see [Datamodel::abort]. Content that needs no script engine is still executed.
+    fn recover_from_panic(&mut self, datamodel: &mut dyn Datamodel) {
+        error!("Panic during execution of transition content");
+        datamodel.global().clear_poison();
+        get_global!(datamodel).actions.actions.clear_poison();
+        datamodel.abort();
+        datamodel.internal_error_execution();
+    }
+
     /// *W3C says*:
     /// # procedure computeEntrySet(transitions, statesToEnter, statesForDefaultEntry, defaultHistoryContent)
     /// Compute the complete set of states that will be entered as a result of taking 'transitions'.
@@ -2649,6 +3940,28 @@ impl Fsm {
                         defaultHistoryContent,
                     );
                 }
+            } else if self.hasHistoryCycle(sid) {
+                // Would recurse endlessly. To keep the configuration valid, the first
+                // child of the parent is entered instead.
+                error!(
+                    "Default transition of history state '{}' is cyclic",
+                    state.name
+                );
+                get_global!(datamodel).enqueue_internal(Event::error_execution(&None, &None));
+                if let Some(child) = self
+                    .get_state_by_id(state.parent)
+                    .states
+                    .iter()
+                    .find(|child| !self.isHistoryState(**child))
+                {
+                    self.addDescendantStatesToEnter(
+                        datamodel,
+                        *child,
+                        statesToEnter,
+                        statesForDefaultEntry,
+                        defaultHistoryContent,
+                    );
+                }
             } else {
                 // A history state have exactly one transition which specified the default history configuration.
                 let defaultTransition = self.get_transition_by_id(*state.transitions.head());
@@ -2815,6 +4128,9 @@ impl Fsm {
     ///     else:
     ///         return findLCCA([t.source].append(tstates));
     /// ```
+    /// "null" is returned as 0, which is not a valid state id and must not be resolved.
+    /// For targetless transitions findLCCA is never called. computeExitSet skips them and
+    /// computeEntrySet has no effective targets to add ancestors for.
     #[allow(non_snake_case)]
     fn getTransitionDomain(&self, datamodel: &mut dyn Datamodel, t: &Transition) -> StateId {
         #[cfg(feature = "Trace_Method")]
@@ -2880,6 +4196,31 @@ impl Fsm {
         lcca
     }
 
+    /// Extension to detect malformed documents:\
+    /// Returns true if the default transition of the history state leads back to it via
+    /// default transitions of other history states, e.g. if it targets itself.
+    #[allow(non_snake_case)]
+    fn hasHistoryCycle(&self, history: StateId) -> bool {
+        let mut visited: HashSet<StateId> = HashSet::new();
+        let mut pending = vec![history];
+        while let Some(sid) = pending.pop() {
+            if !visited.insert(sid) {
+                continue;
+            }
+            if let Some(tid) = self.get_state_by_id(sid).transitions.iterator().next() {
+                for target in &self.get_transition_by_id(*tid).target {
+                    if *target == history {
+                        return true;
+                    }
+                    if self.isHistoryState(*target) {
+                        pending.push(*target);
+                    }
+                }
+            }
+        }
+        false
+    }
+
     /// *W3C says*:
     /// # function getEffectiveTargetStates(transition)
     /// Returns the states that will be the target when 'transition' is taken, dereferencing any history states.
@@ -2907,6 +4248,10 @@ impl Fsm {
             if self.isHistoryState(*sid) {
                 if get_global!(datamodel).historyValue.has(*sid) {
                     targets.union(get_global!(datamodel).historyValue.get(*sid));
+                } else if self.hasHistoryCycle(*sid) {
+                    // Malformed document, can't be dereferenced. The history state itself is used,
+                    // the error is raised on entry. See addDescendantStatesToEnter.
+                    targets.add(*sid);
                 } else {
                     let s = self.get_state_by_id(*sid);
                     // History states have exactly one "transition"
@@ -2944,7 +4289,8 @@ impl Fsm {
         }
 
         let mut properAncestors: OrderedSet<StateId> = OrderedSet::new();
-        if !self.isDescendant(state2, state1) {
+        // isDescendant is false for equal states, but the result shall be empty for these too.
+        if state2 != state1 && !self.isDescendant(state2, state1) {
             let mut currState = self.get_state_by_id(state1).parent;
             while currState != 0 && currState != state2 {
                 properAncestors.add(currState);
@@ -3038,11 +4384,13 @@ impl Fsm {
     fn invoke(&mut self, datamodel: &mut dyn Datamodel, state_id: StateId, inv: &Invoke) {
         // W3C: if the evaluation of its arguments produces an error, the SCXML Processor must
         // terminate the processing of the element without further action.
+        let error_mark = get_global!(datamodel).internal_queue_size();
 
         let type_name_data = match datamodel.get_expression_alternative_value(&inv.type_name, &inv.type_expr) {
             Ok(value) => value,
             Err(_) => {
                 // Error -> abort
+                Self::abort_invoke(datamodel, error_mark);
                 return;
             }
         };
@@ -3056,6 +4404,7 @@ impl Fsm {
             || (type_name.starts_with(SCXML_INVOKE_TYPE) && type_name.len() <= (SCXML_INVOKE_TYPE.len() + 1)))
         {
             error!("Unsupported <invoke> type {}", type_name);
+            Self::abort_invoke(datamodel, error_mark);
             return;
         }
 
@@ -3083,6 +4432,7 @@ impl Fsm {
         let src = match datamodel.get_expression_alternative_value(&inv.src, &inv.src_expr) {
             Err(_) => {
                 // Error -> Abort
+                Self::abort_invoke(datamodel, error_mark);
                 return;
             }
             Ok(value) => value.lock().unwrap().clone(),
@@ -3092,6 +4442,7 @@ impl Fsm {
             match datamodel.get_by_location(name) {
                 Err(_msg) => {
                     // Error -> Abort
+                    Self::abort_invoke(datamodel, error_mark);
                     return;
                 }
                 Ok(value) => {
@@ -3169,11 +4520,25 @@ impl Fsm {
                     .insert(invokeId, session);
             }
             Err(error) => {
-                error!("Execute of '{}' failed: {}", src, error)
+                // W3C: Errors arising from <invoke> while trying to communicate with the
+                // invoked service are "error.communication". Expression errors are already reported.
+                error!("Execute of '{}' failed: {}", src, error);
+                let mut global = get_global!(datamodel);
+                if global.errors_since(error_mark) == 0 {
+                    global.enqueue_internal(Event::error_communication_for(&None, &Some(invokeId)));
+                }
             }
         }
     }
 
+    /// Puts "error.execution" on the internal queue if the datamodel didn't already report
+    /// an error after the mark.
+    fn abort_invoke(datamodel: &mut dyn Datamodel, error_mark: usize) {
+        if get_global!(datamodel).errors_since(error_mark) == 0 {
+            datamodel.internal_error_execution();
+        }
+    }
+
     #[allow(non_snake_case)]
     fn cancelInvoke(&mut self, datamodel: &mut dyn Datamodel, invoke_id: &InvokeId, session_id: SessionId) {
         #[cfg(feature = "Trace_Method")]
@@ -3181,7 +4546,7 @@ impl Fsm {
         get_global!(datamodel).child_sessions.remove(invoke_id);
         datamodel.send(
             SCXML_EVENT_PROCESSOR_SHORT_TYPE,
-            &Data::String(format!("{}{}", SCXML_TARGET_SESSION_ID_PREFIX, session_id)),
+            &Data::String(ScxmlTarget::Session(session_id).to_string()),
             Event::new_simple(EVENT_CANCEL_SESSION),
         );
         #[cfg(feature = "Trace_Method")]
@@ -3216,21 +4581,23 @@ impl Fsm {
         }
     }
 
-    /// Converts a set of ids to list of references.
-    fn set_to_state_list(&self, state_ids: &OrderedSet<StateId>) -> List<&State> {
-        let mut l = List::new();
-        for sid in state_ids.iterator() {
-            l.push(self.get_state_by_id(*sid));
+    /// Extension to [Fsm::conditionMatch] that uses the cache of the current transition selection
+    /// if [Fsm::cache_conditions] is set.
+    fn cached_condition_match(
+        &mut self,
+        datamodel: &mut dyn Datamodel,
+        tid: TransitionId,
+        cache: &mut HashMap<TransitionId, bool>,
+    ) -> bool {
+        if !self.cache_conditions {
+            return self.conditionMatch(datamodel, tid);
         }
-        l
-    }
-
-    fn state_list_to_id_set(&self, states: &List<&State>) -> OrderedSet<StateId> {
-        let mut l = OrderedSet::new();
-        for state in states.iterator() {
-            l.add(state.id);
+        if let Some(result) = cache.get(&tid) {
+            return *result;
         }
-        l
+        let result = self.conditionMatch(datamodel, tid);
+        cache.insert(tid, result);
+        result
     }
 
     /// Converts a set of Transition-ids to list of references.
@@ -3365,10 +4732,13 @@ pub struct State {
 
     pub history_type: HistoryType,
 
-    /// The script that is executed if the state is entered. See W3c comments for \<onentry\> above.
+    /// The script that is executed if the state is entered. See W3c comments for \<onentry\> above.\
+    /// One entry per \<onentry\> element in document order. The blocks are executed in the order of this list,
+    /// independent of the ids of the content.
     pub onentry: Vec<ExecutableContentId>,
 
-    /// The script that is executed if the state is left. See W3c comments for \<onexit\> above.
+    /// The script that is executed if the state is left. See W3c comments for \<onexit\> above.\
+    /// One entry per \<onexit\> element in document order, executed in the order of this list.
     pub onexit: Vec<ExecutableContentId>,
 
     /// All transitions between sub-states.
@@ -3412,8 +4782,31 @@ impl State {
 }
 
 impl Clone for State {
+    /// Creates a copy of the state. The data of the state is copied and not shared.
     fn clone(&self) -> Self {
-        todo!()
+        State {
+            id: self.id,
+            doc_id: self.doc_id,
+            name: self.name.clone(),
+            initial: self.initial,
+            states: self.states.clone(),
+            is_parallel: self.is_parallel,
+            is_final: self.is_final,
+            history_type: self.history_type,
+            onentry: self.onentry.clone(),
+            onexit: self.onexit.clone(),
+            transitions: self.transitions.clone(),
+            invoke: self.invoke.clone(),
+            history: self.history.clone(),
+            data: self
+                .data
+                .iter()
+                .map(|(name, value)| (name.clone(), create_data_arc(value.lock().unwrap().clone())))
+                .collect(),
+            isFirstEntry: self.isFirstEntry,
+            parent: self.parent,
+            donedata: self.donedata.clone(),
+        }
     }
 }
 
@@ -3458,7 +4851,7 @@ pub fn map_history_type(ts: &String) -> HistoryType {
     }
 }
 
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
 #[repr(u8)]
 pub enum TransitionType {
     Internal,
@@ -3500,13 +4893,17 @@ pub(crate) static SESSION_ID_COUNTER: AtomicU32 = AtomicU32::new(1);
 pub type TransitionId = u32;
 
 /// A state to state transition with references to content that shall be executed with the transition.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Transition {
     pub id: TransitionId,
     pub doc_id: DocumentId,
 
     // TODO: Possibly we need some type to express event ids
+    /// The event descriptors in the normalized form that is used for matching, see [normalize_event_descriptor].
     pub events: Vec<String>,
+    /// The event descriptors as given in the source, e.g. "error.*". Used by writers to re-emit the original
+    /// attribute. Empty if the transition was created without the source, in this case writers use [Transition::events].
+    pub event_descriptors: Vec<String>,
     pub wildcard: bool,
     pub cond: Data,
     pub source: StateId,
@@ -3515,6 +4912,42 @@ pub struct Transition {
     pub content: ExecutableContentId,
 }
 
+/// Strips redundant "." and ".*" suffixes from an event descriptor.
+pub fn normalize_event_descriptor(descriptor: &str) -> String {
+    let mut rt = descriptor;
+    let mut do_it = true;
+    while do_it {
+        do_it = false;
+        match rt.strip_suffix(".*") {
+            None => {}
+            Some(r) => {
+                do_it = true;
+                rt = r
+            }
+        }
+        match rt.strip_suffix(".") {
+            None => {}
+            Some(r) => {
+                do_it = true;
+                rt = r
+            }
+        }
+    }
+    rt.to_string()
+}
+
+/// Read-only view of a transition, as returned by [Fsm::transitions_from].\
+/// All references are resolved to the names of the states.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransitionView {
+    pub id: TransitionId,
+    pub source: String,
+    pub target: Vec<String>,
+    pub events: Vec<String>,
+    pub cond: Option<String>,
+    pub transition_type: String,
+}
+
 impl PartialEq for Transition {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -3522,12 +4955,18 @@ impl PartialEq for Transition {
 }
 
 impl Transition {
+    /// Creates a transition with an id from the process-wide counter.\
+    /// Readers and builders use [Transition::with_id] with ids that are unique per document.
     pub fn new() -> Transition {
-        let idc = ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+        Transition::with_id(ID_COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn with_id(id: TransitionId) -> Transition {
         Transition {
-            id: idc,
+            id,
             doc_id: 0,
             events: vec![],
+            event_descriptors: vec![],
             wildcard: false,
             cond: Data::Null(),
             source: 0,
@@ -3537,6 +4976,30 @@ impl Transition {
         }
     }
 
+    /// Sets the events of the transition from a space separated list of event descriptors.\
+    /// The descriptors are stored as given and in normalized form.
+    pub fn set_event_descriptors(&mut self, descriptors: &str) {
+        self.event_descriptors = descriptors
+            .split_whitespace()
+            .map(|d| d.to_string())
+            .collect();
+        self.events = self
+            .event_descriptors
+            .iter()
+            .map(|d| normalize_event_descriptor(d))
+            .collect();
+        self.wildcard = self.events.contains(&"*".to_string());
+    }
+
+    /// Gets the event descriptors as given in the source, or the normalized events if the source is not known.
+    pub fn source_event_descriptors(&self) -> &Vec<String> {
+        if self.event_descriptors.is_empty() {
+            &self.events
+        } else {
+            &self.event_descriptors
+        }
+    }
+
     #[allow(non_snake_case)]
     /// W3C says:\
     /// An event descriptor matches an event name if its string of tokens is an exact match or a prefix
@@ -3551,7 +5014,7 @@ impl Transition {
     /// since they are token prefixes of exactly the same set of event names.
     ///
     /// Implementation Note:
-    /// Terminating "." and ".*" are already stripped by the parser.
+    /// Uses the normalized [Transition::events], terminating "." and ".*" are already stripped.
     fn nameMatch(&self, name: &str) -> bool {
         if self.wildcard {
             true
@@ -3592,6 +5055,11 @@ lazy_static! {
             RFSM_EXPRESSION_DATAMODEL_LC.to_string(),
             Box::new(RFsmExpressionDatamodelFactory {}),
         );
+        #[cfg(feature = "xpath")]
+        hs.insert(
+            XPATH_DATAMODEL_LC.to_string(),
+            Box::new(XPathDatamodelFactory {}),
+        );
 
         Arc::new(Mutex::new(hs))
     };
@@ -3631,7 +5099,7 @@ impl Display for Fsm {
             "Fsm{{v:{} root:{} states:",
             self.version, self.pseudo_root
         )?;
-        display_state_map(&self.states, f)?;
+        display_state_map(&self.states, self.pseudo_root, f)?;
         display_transition_map(&self.transitions, f)?;
         write!(f, "}}")
     }
@@ -3752,17 +5220,25 @@ mod tests {
     #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
     use std::collections::HashMap;
 
-    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    #[cfg(feature = "xml")]
     use crate::scxml_reader;
 
     #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
     use std::sync::mpsc::Sender;
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    use std::sync::{Arc, Mutex};
 
     #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
-    use crate::test::run_test_manual_with_send;
+    use crate::test::{run_test_manual, run_test_manual_with_send};
 
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    use crate::datamodel::{Datamodel, GlobalDataArc, NULL_DATAMODEL};
     #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
     use crate::fsm::Event;
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    use crate::fsm::{create_datamodel, Fsm, GlobalData, TransitionId};
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    use std::ops::DerefMut;
 
     #[cfg(feature = "ECMAScriptModel")]
     #[cfg(feature = "xml")]
@@ -4135,4 +5611,2590 @@ mod tests {
             "FSM shall terminate with state 'OuterFinal'"
         );
     }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn parallel_transitions_execute_in_document_order() {
+        // Exit in reverse document order, transition content in the document order of the
+        // source states, entry in document order.
+        for run in 0..3 {
+            let fsm = scxml_reader::parse_from_xml(
+                r#"<scxml initial='P' datamodel='ecmascript'>
+                  <datamodel><data id='log' expr="''"/></datamodel>
+                  <parallel id='P'>
+                    <state id='A' initial='A1'>
+                      <state id='A1'>
+                        <onexit><assign location='log' expr="log + 'xA1,'"/></onexit>
+                        <transition event='go' target='A2'><assign location='log' expr="log + 'tA,'"/></transition>
+                      </state>
+                      <state id='A2'>
+                        <onentry><assign location='log' expr="log + 'eA2,'"/></onentry>
+                      </state>
+                    </state>
+                    <state id='B' initial='B1'>
+                      <state id='B1'>
+                        <onexit><assign location='log' expr="log + 'xB1,'"/></onexit>
+                        <transition event='go' target='B2'><assign location='log' expr="log + 'tB,'"/></transition>
+                      </state>
+                      <state id='B2'>
+                        <onentry>
+                          <assign location='log' expr="log + 'eB2'"/>
+                          <raise event='check'/>
+                        </onentry>
+                      </state>
+                    </state>
+                    <transition event='check' cond="log == 'xB1,xA1,tA,tB,eA2,eB2'" target='pass'/>
+                    <transition event='check' target='fail'/>
+                  </parallel>
+                  <final id='pass'/>
+                  <final id='fail'/>
+                </scxml>"#
+                    .to_string(),
+            )
+            .unwrap();
+
+            assert!(
+                run_test_manual_with_send(
+                    "parallel_transitions_execute_in_document_order",
+                    &HashMap::new(),
+                    fsm,
+                    &Vec::new(),
+                    #[cfg(feature = "Trace")]
+                    TraceMode::STATES,
+                    2000,
+                    &vec!["pass".to_string()],
+                    |sender| {
+                        test_send(&sender, Event::new_simple("go"));
+                    },
+                ),
+                "Run {} failed",
+                run
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn unhandled_event_handler_is_called() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='a'>
+              <state id='a'>
+                <transition event='go' target='pass'/>
+              </state>
+              <final id='pass'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        let unhandled = Arc::new(Mutex::new(Vec::new()));
+        let unhandled_clone = unhandled.clone();
+        fsm.unhandled_event_handler = Some(Arc::new(move |_session_id, event: &Event| {
+            unhandled_clone.lock().unwrap().push(event.name.clone());
+        }));
+
+        assert!(run_test_manual_with_send(
+            "unhandled_event_handler_is_called",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+            |sender| {
+                test_send(&sender, Event::new_simple("unknown"));
+                test_send(&sender, Event::new_simple("go"));
+            },
+        ));
+        assert_eq!(*unhandled.lock().unwrap(), vec!["unknown".to_string()]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn done_invoke_contains_done_data() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <invoke type='scxml' id='child'>
+                  <content>
+                    <scxml initial='c0' datamodel='ecmascript'>
+                      <datamodel><data id='x' expr='21'/></datamodel>
+                      <final id='c0'>
+                        <donedata><content expr='x*2'/></donedata>
+                      </final>
+                    </scxml>
+                  </content>
+                </invoke>
+                <transition event='done.invoke.child' cond='_event.data == 42' target='pass'/>
+                <transition event='done.invoke.child' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual(
+            "done_invoke_contains_done_data",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn targetless_transition_keeps_configuration() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel>
+                <data id='entered' expr='0'/>
+                <data id='exited' expr='0'/>
+                <data id='body' expr='0'/>
+              </datamodel>
+              <state id='s0'>
+                <onentry><assign location='entered' expr='entered + 1'/></onentry>
+                <onexit><assign location='exited' expr='exited + 1'/></onexit>
+                <transition event='e'>
+                  <assign location='body' expr='body + 1'/>
+                  <raise event='check'/>
+                </transition>
+                <transition event='check' cond="body == 1 &amp;&amp; entered == 1 &amp;&amp; exited == 0 &amp;&amp; In('s0')" target='pass'/>
+                <transition event='check' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual_with_send(
+            "targetless_transition_keeps_configuration",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+            |sender| {
+                test_send(&sender, Event::new_simple("e"));
+            },
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn set_binding_forces_late_binding() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript' binding='early'>
+              <state id='s0'>
+                <transition cond='x === undefined' target='s1'/>
+                <transition target='fail'/>
+              </state>
+              <state id='s1'>
+                <datamodel><data id='x' expr='1'/></datamodel>
+                <transition cond='x === 1' target='pass'/>
+                <transition target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(fsm.binding(), crate::fsm::BindingType::Early);
+        fsm.set_binding(crate::fsm::BindingType::Late);
+
+        let global = crate::fsm::run_blocking(fsm, Vec::new());
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn malformed_inbound_data_is_blank_and_raises_error() {
+        use crate::datamodel::Data;
+        use crate::fsm::ParamPair;
+
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <transition event='ok' cond="_event.data.value === 'x = 1' &amp;&amp; _event.data.list[1] == 2" target='s1'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <transition event='bad' cond='typeof _event.data === "undefined"' target='s2'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s2'>
+                <transition event='error.execution' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual_with_send(
+            "malformed_inbound_data_is_blank_and_raises_error",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+            |sender| {
+                let mut event = Event::new_simple("ok");
+                // Script source is passed as string and not evaluated.
+                event.param_values = Some(vec![
+                    ParamPair::new("value", &crate::datamodel::str_to_source("x = 1")),
+                    ParamPair::new(
+                        "list",
+                        &Data::Array(vec![
+                            crate::datamodel::create_data_arc(Data::Integer(1)),
+                            crate::datamodel::create_data_arc(Data::Integer(2)),
+                        ]),
+                    ),
+                ]);
+                test_send(&sender, event);
+                let mut event = Event::new_simple("bad");
+                event.content = Some(Data::Error("invalid payload".to_string()));
+                test_send(&sender, event);
+            },
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn targetless_transition_does_not_conflict() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='P' datamodel='ecmascript'>
+              <datamodel><data id='body' expr='0'/></datamodel>
+              <parallel id='P'>
+                <state id='A'>
+                  <transition event='e'><assign location='body' expr='body + 1'/></transition>
+                </state>
+                <state id='B' initial='B1'>
+                  <state id='B1'>
+                    <transition event='e' target='B2'/>
+                  </state>
+                  <state id='B2'>
+                    <onentry><raise event='check'/></onentry>
+                  </state>
+                </state>
+                <transition event='check' cond="body == 1 &amp;&amp; In('A')" target='pass'/>
+                <transition event='check' target='fail'/>
+              </parallel>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual_with_send(
+            "targetless_transition_does_not_conflict",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+            |sender| {
+                test_send(&sender, Event::new_simple("e"));
+            },
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn instances_of_template_run_independently() {
+        let template = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='x' expr='0'/></datamodel>
+              <state id='s0'>
+                <transition event='inc'><assign location='x' expr='x + 1'/></transition>
+                <transition event='check' cond='x == 1' target='pass'/>
+                <transition event='check' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        let mut instance1 = template.instantiate();
+        let instance2 = template.instantiate();
+        assert!(Arc::ptr_eq(
+            &instance1.executableContent,
+            &instance2.executableContent
+        ));
+        // Shared content can't be modified.
+        assert!(instance1.executable_content_mut().is_none());
+
+        for (name, instance) in [("instance1", instance1), ("instance2", instance2)] {
+            assert!(run_test_manual_with_send(
+                name,
+                &HashMap::new(),
+                instance,
+                &Vec::new(),
+                #[cfg(feature = "Trace")]
+                TraceMode::STATES,
+                2000,
+                &vec!["pass".to_string()],
+                |sender| {
+                    test_send(&sender, Event::new_simple("inc"));
+                    test_send(&sender, Event::new_simple("check"));
+                },
+            ));
+        }
+    }
+
+    /// The W3C version of removeConflictingTransitions, without caching of the exit sets.
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    #[allow(non_snake_case)]
+    fn remove_conflicting_transitions_naive(
+        fsm: &Fsm,
+        datamodel: &mut dyn Datamodel,
+        enabledTransitions: &OrderedSet<TransitionId>,
+    ) -> OrderedSet<TransitionId> {
+        let mut filteredTransitions: OrderedSet<TransitionId> = OrderedSet::new();
+        for tid1 in enabledTransitions.toList().iterator() {
+            let t1 = fsm.get_transition_by_id(*tid1);
+            let mut t1Preempted = false;
+            let mut transitionsToRemove = OrderedSet::new();
+            let filteredTransitionList = filteredTransitions.toList();
+            for tid2 in filteredTransitionList.iterator() {
+                if fsm
+                    .computeExitSet(datamodel, &List::from_array(&[*tid1]))
+                    .hasIntersection(&fsm.computeExitSet(datamodel, &List::from_array(&[*tid2])))
+                {
+                    let t2 = fsm.get_transition_by_id(*tid2);
+                    if fsm.isDescendant(t1.source, t2.source) {
+                        transitionsToRemove.add(tid2);
+                    } else {
+                        t1Preempted = true;
+                        break;
+                    }
+                }
+            }
+            if !t1Preempted {
+                for t3 in transitionsToRemove.toList().iterator() {
+                    filteredTransitions.delete(t3);
+                }
+                filteredTransitions.add(*tid1);
+            }
+        }
+        filteredTransitions
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn remove_conflicting_transitions_in_wide_parallel_state() {
+        const REGIONS: usize = 60;
+        let mut xml = String::from("<scxml initial='P'><parallel id='P'>");
+        for r in 0..REGIONS {
+            // Every 7th region leaves the parallel state, which conflicts with all other regions.
+            let target = if r % 7 == 3 {
+                "out".to_string()
+            } else {
+                format!("r{}b", r)
+            };
+            xml.push_str(&format!(
+                "<state id='r{r}' initial='r{r}a'><state id='r{r}a'><transition event='go' target='{target}'/>\
+                 <transition event='go' target='r{r}a'/></state><state id='r{r}b'/></state>"
+            ));
+        }
+        xml.push_str("<transition event='go' target='out'/></parallel><final id='out'/></scxml>");
+        let fsm = scxml_reader::parse_from_xml(xml).unwrap();
+
+        let global_data = GlobalDataArc::new(Mutex::new(GlobalData::new()));
+        let mut datamodel = create_datamodel(NULL_DATAMODEL, global_data, &HashMap::new());
+        let mut enabled = OrderedSet::new();
+        {
+            let mut global = get_global!(datamodel);
+            global
+                .configuration
+                .add(fsm.get_state_by_name(&"P".to_string()).id);
+            for r in 0..REGIONS {
+                global
+                    .configuration
+                    .add(fsm.get_state_by_name(&format!("r{}", r)).id);
+                global
+                    .configuration
+                    .add(fsm.get_state_by_name(&format!("r{}a", r)).id);
+            }
+        }
+        for r in 0..REGIONS {
+            for t in fsm
+                .get_state_by_name(&format!("r{}a", r))
+                .transitions
+                .iterator()
+            {
+                enabled.add(*t);
+            }
+        }
+        for t in fsm
+            .get_state_by_name(&"P".to_string())
+            .transitions
+            .iterator()
+        {
+            enabled.add(*t);
+        }
+
+        let filtered = fsm.removeConflictingTransitions(datamodel.deref_mut(), &enabled);
+        let expected = remove_conflicting_transitions_naive(&fsm, datamodel.deref_mut(), &enabled);
+        assert_eq!(filtered.toList().data, expected.toList().data);
+
+        // Each region keeps its first transition. The transitions that leave the parallel state are
+        // preempted by the regions before them, so these regions keep their second transition.
+        // The transition of the parallel state is preempted too.
+        let survivors: Vec<TransitionId> = (0..REGIONS)
+            .map(|r| {
+                let transitions = &fsm.get_state_by_name(&format!("r{}a", r)).transitions;
+                transitions.data[if r % 7 == 3 { 1 } else { 0 }]
+            })
+            .collect();
+        assert_eq!(filtered.toList().data, survivors);
+        for tid in filtered.iterator() {
+            assert_ne!(
+                fsm.get_transition_by_id(*tid).target,
+                vec![fsm.get_state_by_name(&"out".to_string()).id]
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn invoke_with_srcexpr() {
+        // The child document is found via the include paths.
+        let child_name = format!("rufsm_invoke_child_{}.scxml", std::process::id());
+        let child_path = std::env::temp_dir().join(&child_name);
+        std::fs::write(
+            &child_path,
+            "<scxml initial='f' datamodel='ecmascript'><final id='f'/></scxml>",
+        )
+        .unwrap();
+
+        let fsm = scxml_reader::parse_from_xml(format!(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='child' expr="'{}'"/></datamodel>
+              <state id='s0'>
+                <invoke typeexpr="'scxml'" srcexpr='child' id='c'/>
+                <transition event='done.invoke.c' target='pass'/>
+                <transition event='error.*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#,
+            child_name
+        ))
+        .unwrap();
+
+        let result = run_test_manual(
+            "invoke_with_srcexpr",
+            &HashMap::new(),
+            fsm,
+            &vec![std::env::temp_dir()],
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        );
+        let _ = std::fs::remove_file(&child_path);
+        assert!(result);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn invoke_with_failing_srcexpr_raises_error() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <invoke type='scxml' srcexpr='undefined_variable.path' id='c'/>
+                <transition event='error.execution' target='pass'/>
+                <transition event='done.invoke.c' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual(
+            "invoke_with_failing_srcexpr_raises_error",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn enabled_events_of_configuration() {
+        let fsm = crate::scxml_reader::parse_from_xml(
+            r#"<scxml initial='a'>
+              <state id='p'>
+                <transition event='stop'/>
+                <state id='a'>
+                  <transition event='go error.*' target='b'/>
+                  <transition event='*' target='b'/>
+                </state>
+                <state id='b'>
+                  <transition event='back' target='a'/>
+                </state>
+              </state>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        let mut configuration = OrderedSet::new();
+        configuration.add(fsm.get_state_by_name(&"p".to_string()).id);
+        configuration.add(fsm.get_state_by_name(&"a".to_string()).id);
+
+        let expected: std::collections::HashSet<String> = ["stop", "go", "error"]
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(fsm.enabled_events(&configuration), expected);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn finalize_runs_before_autoforward() {
+        // The child reports a value, the finalize of the parent stores it and sends "ack" to the child.
+        // As the finalize is executed before the event is forwarded, the child gets "ack" before the forwarded
+        // copy of its own event. Only then it answers, so the parent can check the stored value.
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='x' expr='0'/></datamodel>
+              <state id='s0'>
+                <invoke type='scxml' id='child' autoforward='true'>
+                  <content>
+                    <scxml initial='c0' datamodel='ecmascript'>
+                      <state id='c0'>
+                        <onentry>
+                          <send target='#_parent' event='update'><param name='v' expr='42'/></send>
+                        </onentry>
+                        <transition event='ack' target='c1'/>
+                        <transition event='update' target='c2'><send target='#_parent' event='wrongOrder'/></transition>
+                      </state>
+                      <state id='c1'>
+                        <transition event='update' target='c2'><send target='#_parent' event='echo'/></transition>
+                      </state>
+                      <final id='c2'/>
+                    </scxml>
+                  </content>
+                  <finalize>
+                    <if cond="_event.name == 'update'">
+                      <assign location='x' expr='_event.data.v'/>
+                      <send target='#_child' event='ack'/>
+                    </if>
+                  </finalize>
+                </invoke>
+                <transition event='echo' cond='x == 42' target='pass'/>
+                <transition event='echo' target='fail'/>
+                <transition event='wrongOrder' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual(
+            "finalize_runs_before_autoforward",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn history_without_recorded_value_takes_default_transition() {
+        // The parent of the history was never exited, so the default transition is taken
+        // and its content is executed after the onentry of the parent.
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='log' expr='""'/></datamodel>
+              <state id='s0'>
+                <transition event='go' target='h'/>
+              </state>
+              <state id='p' initial='a'>
+                <onentry><assign location='log' expr='log + "p"'/></onentry>
+                <history id='h'>
+                  <transition target='b'><assign location='log' expr='log + "h"'/></transition>
+                </history>
+                <state id='a'/>
+                <state id='b'>
+                  <onentry><assign location='log' expr='log + "b"'/></onentry>
+                </state>
+                <transition event='check' cond="In('b') &amp;&amp; log == 'phb'" target='pass'/>
+                <transition event='check' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual_with_send(
+            "history_without_recorded_value_takes_default_transition",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+            |sender| {
+                test_send(&sender, Event::new_simple("go"));
+                test_send(&sender, Event::new_simple("check"));
+            },
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn history_with_recorded_value_ignores_default_transition() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='defaults' expr='0'/></datamodel>
+              <state id='s0'>
+                <transition event='go' target='h'/>
+              </state>
+              <state id='p' initial='a'>
+                <history id='h'>
+                  <transition target='b'><assign location='defaults' expr='defaults + 1'/></transition>
+                </history>
+                <state id='a'/>
+                <state id='b'><transition event='next' target='c'/></state>
+                <state id='c'/>
+                <transition event='leave' target='away'/>
+                <transition event='check' cond="In('c') &amp;&amp; defaults == 1" target='pass'/>
+                <transition event='check' target='fail'/>
+              </state>
+              <state id='away'>
+                <transition event='back' target='h'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual_with_send(
+            "history_with_recorded_value_ignores_default_transition",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+            |sender| {
+                test_send(&sender, Event::new_simple("go"));
+                test_send(&sender, Event::new_simple("next"));
+                test_send(&sender, Event::new_simple("leave"));
+                test_send(&sender, Event::new_simple("back"));
+                test_send(&sender, Event::new_simple("check"));
+            },
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn deep_history_restores_nested_configuration() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='main' datamodel='ecmascript'>
+              <state id='main' initial='outer'>
+                <state id='outer' initial='a'>
+                  <history id='h' type='deep'><transition target='a'/></history>
+                  <state id='a' initial='a1'>
+                    <state id='a1' initial='a11'>
+                      <state id='a11'><transition event='next' target='a12'/></state>
+                      <state id='a12'/>
+                    </state>
+                    <state id='a2'/>
+                  </state>
+                  <transition event='leave' target='away'/>
+                </state>
+                <state id='away'>
+                  <transition event='back' target='h'/>
+                </state>
+                <transition event='check' cond="In('a12') &amp;&amp; In('a1') &amp;&amp; !In('a11')" target='pass'/>
+                <transition event='check' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual_with_send(
+            "deep_history_restores_nested_configuration",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+            |sender| {
+                test_send(&sender, Event::new_simple("next"));
+                test_send(&sender, Event::new_simple("leave"));
+                test_send(&sender, Event::new_simple("back"));
+                test_send(&sender, Event::new_simple("check"));
+            },
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn event_sinks_from_multiple_threads() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='count' expr='0'/></datamodel>
+              <state id='s0'>
+                <transition event='a b' cond='count == 1' target='pass'/>
+                <transition event='a b'><assign location='count' expr='count + 1'/></transition>
+              </state>
+              <final id='pass'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual_with_send(
+            "event_sinks_from_multiple_threads",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+            |sender| {
+                let sink = crate::fsm::EventSink::new(sender);
+                let sink_a = sink.clone();
+                let sink_b = sink.clone();
+                let a = std::thread::spawn(move || sink_a.send_named("a"));
+                let b = std::thread::spawn(move || sink_b.send(Event::new_simple("b")));
+                assert!(a.join().unwrap().is_ok());
+                assert!(b.join().unwrap().is_ok());
+            },
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn start_fsm_returns_event_sink() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='null'>
+              <state id='s0'>
+                <transition event='go' target='done'/>
+              </state>
+              <final id='done'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let mut executor = crate::fsm_executor::FsmExecutor::new_without_io_processor();
+        let (mut session, sink) = crate::fsm::start_fsm(
+            fsm,
+            crate::actions::ActionWrapper::new(),
+            Box::new(executor.clone()),
+        );
+        assert!(sink.send_named("go").is_ok());
+        // The session only finishes if the event was received.
+        assert!(session.thread.take().unwrap().join().is_ok());
+        executor.shutdown();
+    }
+
+    #[test]
+    fn event_sink_reports_closed_session() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let sink = crate::fsm::EventSink::new(sender);
+        drop(receiver);
+        let r = sink.send_named("lost");
+        assert_eq!(r.err().unwrap().0.name, "lost");
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn targetless_transition_has_no_domain() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            "<scxml initial='s0'><state id='s0'><state id='s01'>\
+               <transition event='a'/>\
+               <transition event='b' type='internal'/>\
+             </state></state></scxml>"
+                .to_string(),
+        )
+        .unwrap();
+
+        let global_data = GlobalDataArc::new(Mutex::new(GlobalData::new()));
+        let mut datamodel = create_datamodel(NULL_DATAMODEL, global_data, &HashMap::new());
+        {
+            let mut global = get_global!(datamodel);
+            global
+                .configuration
+                .add(fsm.get_state_by_name(&"s0".to_string()).id);
+            global
+                .configuration
+                .add(fsm.get_state_by_name(&"s01".to_string()).id);
+        }
+
+        let mut transitions: List<TransitionId> = List::new();
+        for tid in fsm
+            .get_state_by_name(&"s01".to_string())
+            .transitions
+            .iterator()
+        {
+            transitions.push(*tid);
+            let t = fsm.get_transition_by_id(*tid);
+            assert!(t.target.is_empty());
+            assert_eq!(fsm.getTransitionDomain(datamodel.deref_mut(), t), 0);
+        }
+        assert_eq!(transitions.size(), 2);
+
+        assert!(fsm
+            .computeExitSet(datamodel.deref_mut(), &transitions)
+            .isEmpty());
+
+        let mut states_to_enter = OrderedSet::new();
+        let mut states_for_default_entry = OrderedSet::new();
+        let mut default_history_content = crate::fsm::HashTable::new();
+        fsm.computeEntrySet(
+            datamodel.deref_mut(),
+            &transitions,
+            &mut states_to_enter,
+            &mut states_for_default_entry,
+            &mut default_history_content,
+        );
+        assert!(states_to_enter.isEmpty());
+        assert!(states_for_default_entry.isEmpty());
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn concurrent_senders_lose_no_events() {
+        const THREADS: usize = 8;
+        const EVENTS: usize = 200;
+
+        let mut fsm = scxml_reader::parse_from_xml(format!(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='count' expr='0'/></datamodel>
+              <state id='s0'>
+                <transition event='ping' cond='count == {}' target='pass'/>
+                <transition event='ping'><assign location='count' expr='count + 1'/></transition>
+              </state>
+              <final id='pass'/>
+            </scxml>"#,
+            THREADS * EVENTS - 1
+        ))
+        .unwrap();
+        let counter = Arc::new(crate::metrics::MetricsCounter::new());
+        fsm.metrics = Box::new(counter.clone());
+
+        assert!(run_test_manual_with_send(
+            "concurrent_senders_lose_no_events",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::NONE,
+            10000,
+            &vec!["pass".to_string()],
+            |sender| {
+                let producers: Vec<_> = (0..THREADS)
+                    .map(|_| {
+                        let sender = sender.clone();
+                        std::thread::spawn(move || {
+                            for _ in 0..EVENTS {
+                                sender.send(Box::new(Event::new_simple("ping"))).unwrap();
+                            }
+                        })
+                    })
+                    .collect();
+                for producer in producers {
+                    producer.join().unwrap();
+                }
+            },
+        ));
+
+        assert_eq!(
+            counter
+                .external_events
+                .load(std::sync::atomic::Ordering::Relaxed),
+            (THREADS * EVENTS) as u64
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn targetless_transitions_run_content_in_place() {
+        // "a" counts the matching events with a targetless transition, "b" moves on each "tick".
+        // Both transitions are enabled by the same event. As the targetless one has no exit set,
+        // there is no conflict and the configuration of "a" is not touched.
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='p' datamodel='ecmascript'>
+              <datamodel>
+                <data id='count' expr='0'/>
+                <data id='entries' expr='0'/>
+                <data id='exits' expr='0'/>
+              </datamodel>
+              <parallel id='p'>
+                <state id='a'>
+                  <state id='a1'>
+                    <onentry><assign location='entries' expr='entries + 1'/></onentry>
+                    <onexit><assign location='exits' expr='exits + 1'/></onexit>
+                    <transition event='tick' cond='false'><assign location='count' expr='-100'/></transition>
+                    <transition event='tick' cond='count &lt; 3'><assign location='count' expr='count + 1'/></transition>
+                  </state>
+                </state>
+                <state id='b'>
+                  <state id='b1'><transition event='tick' target='b2'/></state>
+                  <state id='b2'><transition event='tick' target='b3'/></state>
+                  <state id='b3'/>
+                </state>
+                <transition event='done' cond="count == 3 &amp;&amp; entries == 1 &amp;&amp; exits == 0 &amp;&amp; In('a1') &amp;&amp; In('b3')" target='pass'/>
+                <transition event='done' target='fail'/>
+              </parallel>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual_with_send(
+            "targetless_transitions_run_content_in_place",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+            |sender| {
+                // The fourth "tick" doesn't match the cond anymore.
+                for _ in 0..4 {
+                    sender.send(Box::new(Event::new_simple("tick"))).unwrap();
+                }
+                sender.send(Box::new(Event::new_simple("done"))).unwrap();
+            },
+        ));
+    }
+
+    #[test]
+    fn cancelled_queue_keeps_pending_events() {
+        let mut queue: crate::fsm::BlockingQueue<Box<crate::fsm::Event>> = crate::fsm::BlockingQueue::default();
+        queue.enqueue(Box::new(crate::fsm::Event::new_simple("e1")));
+        queue
+            .cancelled
+            .store(true, std::sync::atomic::Ordering::Release);
+        assert!(queue
+            .receiver
+            .lock()
+            .unwrap()
+            .recv_unless_cancelled()
+            .is_none());
+
+        queue
+            .cancelled
+            .store(false, std::sync::atomic::Ordering::Release);
+        assert_eq!(queue.dequeue().name, "e1");
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn cancel_stops_blocked_session_immediately() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='waiting' datamodel='null'>
+              <state id='waiting'>
+                <transition event='go' target='done'/>
+              </state>
+              <final id='done'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let mut executor = crate::fsm_executor::FsmExecutor::new_without_io_processor();
+        let session = crate::fsm::start_fsm_with_data_and_finish_mode(
+            fsm,
+            crate::actions::ActionWrapper::new(),
+            Box::new(executor.clone()),
+            &Vec::new(),
+            crate::fsm::FinishMode::KEEP_CONFIGURATION,
+        );
+        // Give the FSM time to block on the external queue.
+        std::thread::sleep(std::time::Duration::from_millis(200));
+
+        let start = std::time::Instant::now();
+        executor.shutdown();
+        let _ = session.thread.unwrap().join();
+        let elapsed = start.elapsed();
+        // Without the cancellation the session would block forever.
+        // The bound is generous to be robust on busy machines.
+        assert!(
+            elapsed < std::time::Duration::from_secs(2),
+            "Stopped after {:?}",
+            elapsed
+        );
+        // "done" was never reached.
+        assert_eq!(
+            session.global_data.lock().unwrap().final_configuration,
+            Some(vec!["waiting".to_string()])
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn panicking_transition_content_does_not_stop_other_regions() {
+        use crate::actions::Action;
+        use crate::datamodel::Data;
+
+        struct PanicAction {}
+        impl Action for PanicAction {
+            fn execute(&self, _arguments: &[Data], _global: &GlobalData) -> Result<Data, String> {
+                panic!("PanicAction called");
+            }
+
+            fn get_copy(&self) -> Box<dyn Action> {
+                Box::new(PanicAction {})
+            }
+        }
+
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='p' datamodel='ecmascript'>
+              <parallel id='p'>
+                <state id='r1'>
+                  <state id='a'>
+                    <transition event='go' target='a2'><script>boom()</script></transition>
+                  </state>
+                  <state id='a2'/>
+                </state>
+                <state id='r2'>
+                  <state id='b'>
+                    <transition event='go' target='b2'><raise event='r2.done'/></transition>
+                  </state>
+                  <state id='b2'/>
+                </state>
+                <transition event='r2.done' target='checking'/>
+                <transition event='check' target='fail'/>
+              </parallel>
+              <state id='checking'>
+                <!-- The panic unwound through the script engine, so no script is evaluated anymore. -->
+                <transition event='check' cond='true' target='fail'/>
+                <transition event='check' target='pass'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let mut actions = crate::actions::ActionWrapper::new();
+        actions.add_action("boom", Box::new(PanicAction {}));
+        let executor = crate::fsm_executor::FsmExecutor::new_without_io_processor();
+        let mut session = crate::fsm::start_fsm_with_data_and_finish_mode(
+            fsm,
+            actions,
+            Box::new(executor),
+            &Vec::new(),
+            crate::fsm::FinishMode::KEEP_CONFIGURATION,
+        );
+        session
+            .sender
+            .send(Box::new(Event::new_simple("go")))
+            .unwrap();
+        session
+            .sender
+            .send(Box::new(Event::new_simple("check")))
+            .unwrap();
+        assert!(session.thread.take().unwrap().join().is_ok());
+        assert_eq!(
+            session.global_data.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn disconnected_external_queue_stops_fsm() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='null'>
+              <state id='s0'>
+                <transition event='go' target='s1'/>
+              </state>
+              <state id='s1'>
+                <transition event='go' target='s0'/>
+              </state>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let queue: crate::fsm::BlockingQueue<Box<Event>> = crate::fsm::BlockingQueue::new();
+        let mut receiver = queue.receiver.clone();
+        queue
+            .sender
+            .send(Box::new(Event::new_simple("go")))
+            .unwrap();
+        // Drops the only sender.
+        drop(queue);
+
+        let global_data = GlobalDataArc::new(Mutex::new(GlobalData::new()));
+        let _ = global_data
+            .lock()
+            .unwrap()
+            .final_configuration
+            .insert(Vec::new());
+        let thread_global = global_data.clone();
+        let thread = std::thread::spawn(move || {
+            let mut datamodel = create_datamodel(NULL_DATAMODEL, thread_global, &HashMap::new());
+            fsm.interpret_with_event_source(datamodel.deref_mut(), &mut receiver);
+        });
+        assert!(thread.join().is_ok());
+        assert_eq!(
+            global_data.lock().unwrap().final_configuration,
+            Some(vec!["s1".to_string()])
+        );
+    }
+
+    #[test]
+    fn session_id_converts_explicitly() {
+        use crate::fsm::SessionId;
+
+        let id = SessionId::from(42u32);
+        assert_eq!(id, SessionId(42));
+        assert_eq!(u32::from(id), 42);
+        assert_eq!(id.to_string(), "42");
+        assert_eq!("42".parse::<SessionId>(), Ok(id));
+        assert!("x42".parse::<SessionId>().is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn concurrent_invokes_of_same_document_are_independent() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='ready' expr='[]'/></datamodel>
+              <state id='s0' initial='waiting'>
+                <invoke id='c1' type='scxml'>
+                  <content>
+                    <scxml initial='c' datamodel='ecmascript'>
+                      <datamodel><data id='count' expr='0'/></datamodel>
+                      <state id='c'>
+                        <onentry><send target='#_parent' event='ready'/></onentry>
+                        <transition event='ping'>
+                          <assign location='count' expr='count + 1'/>
+                          <send target='#_parent' event='pong'><param name='count' expr='count'/></send>
+                        </transition>
+                      </state>
+                    </scxml>
+                  </content>
+                </invoke>
+                <invoke id='c2' type='scxml'>
+                  <content>
+                    <scxml initial='c' datamodel='ecmascript'>
+                      <datamodel><data id='count' expr='0'/></datamodel>
+                      <state id='c'>
+                        <onentry><send target='#_parent' event='ready'/></onentry>
+                        <transition event='ping'>
+                          <assign location='count' expr='count + 1'/>
+                          <send target='#_parent' event='pong'><param name='count' expr='count'/></send>
+                        </transition>
+                      </state>
+                    </scxml>
+                  </content>
+                </invoke>
+                <state id='waiting'>
+                  <transition event='ready'><script>ready.push(_event.invokeid)</script></transition>
+                  <transition cond='ready.length == 2' target='ping1'/>
+                </state>
+                <state id='ping1'>
+                  <onentry><send target='#_c1' event='ping'/></onentry>
+                  <transition event='pong' cond="_event.invokeid == 'c1' &amp;&amp; _event.data.count == 1" target='ping2'/>
+                </state>
+                <state id='ping2'>
+                  <onentry><send target='#_c2' event='ping'/></onentry>
+                  <transition event='pong' cond="_event.invokeid == 'c2' &amp;&amp; _event.data.count == 1" target='s1'/>
+                </state>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s1'>
+                <onentry><send target='#_c1' event='ping'/></onentry>
+                <transition event='error.communication' target='s2'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s2'>
+                <onentry><send target='#_c2' event='ping'/></onentry>
+                <transition event='error.communication' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        // Both invokes are cancelled on exit of "s0", so sending to them fails.
+        assert!(run_test_manual(
+            "concurrent_invokes_of_same_document_are_independent",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            3000u64,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn invokes_are_executed_in_entry_and_document_order() {
+        // The params of the invokes are evaluated when the invoke is executed and record the order.
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='p' datamodel='ecmascript'>
+              <datamodel><data id='order' expr='[]'/></datamodel>
+              <parallel id='p'>
+                <state id='r1' initial='r1a'>
+                  <invoke type='scxml'><param name='x' expr='order.push("r1")'/><content><scxml initial='c'><state id='c'/></scxml></content></invoke>
+                  <state id='r1a'>
+                    <invoke type='scxml'><param name='x' expr='order.push("r1a")'/><content><scxml initial='c'><state id='c'/></scxml></content></invoke>
+                    <transition event='go' target='r1b'/>
+                  </state>
+                  <state id='r1b'>
+                    <invoke type='scxml'><param name='x' expr='order.push("r1b")'/><content><scxml initial='c'><state id='c'/></scxml></content></invoke>
+                  </state>
+                </state>
+                <state id='r2'>
+                  <invoke type='scxml'><param name='x' expr='order.push("r2a")'/><content><scxml initial='c'><state id='c'/></scxml></content></invoke>
+                  <invoke type='scxml'><param name='x' expr='order.push("r2b")'/><content><scxml initial='c'><state id='c'/></scxml></content></invoke>
+                </state>
+                <invoke type='scxml'><param name='x' expr='order.push("p")'/><content><scxml initial='c'><state id='c'/></scxml></content></invoke>
+                <transition event='check' cond='order.join() == "p,r1,r1a,r2a,r2b,r1b"' target='pass'/>
+                <transition event='check' target='fail'/>
+              </parallel>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual_with_send(
+            "invokes_are_executed_in_entry_and_document_order",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            3000u64,
+            &vec!["pass".to_string()],
+            |sender| {
+                test_send(&sender, Event::new_simple("go"));
+                test_send(&sender, Event::new_simple("check"));
+            },
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn autoforward_forwards_delayed_events_after_delay() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='p' datamodel='ecmascript'>
+              <state id='p' initial='s0'>
+                <invoke id='child' type='scxml' autoforward='true'>
+                  <content>
+                    <scxml initial='c0' datamodel='ecmascript'>
+                      <datamodel><data id='seen' expr='false'/></datamodel>
+                      <state id='c0'>
+                        <onentry><send target='#_parent' event='child.ready'/></onentry>
+                        <transition event='tick'>
+                          <assign location='seen' expr='true'/>
+                          <send target='#_parent' event='child.tick'/>
+                        </transition>
+                        <transition event='probe'>
+                          <send target='#_parent' event='child.probe'>
+                            <param name='seen' expr='seen'/>
+                          </send>
+                        </transition>
+                      </state>
+                    </scxml>
+                  </content>
+                </invoke>
+                <state id='s0'>
+                  <onentry>
+                    <send event='tick' delay='300ms'/>
+                  </onentry>
+                  <transition event='child.ready'>
+                    <send event='probe'/>
+                  </transition>
+                  <transition event='probe'/>
+                  <transition event='child.probe' cond='_event.data.seen == false' target='s1'/>
+                </state>
+                <state id='s1'>
+                  <transition event='tick'/>
+                  <transition event='child.tick' target='pass'/>
+                </state>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>
+            "#
+            .to_string(),
+        )
+        .unwrap();
+
+        let start = std::time::Instant::now();
+        assert!(run_test_manual(
+            "autoforward_forwards_delayed_events_after_delay",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            3000u64,
+            &vec!["pass".to_string()],
+        ));
+        assert!(start.elapsed() >= std::time::Duration::from_millis(300));
+    }
+
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    #[derive(Debug, Default)]
+    struct MockIOProcessor {
+        queues: crate::event_io_processor::ExternalQueueContainer,
+        sent: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    impl crate::event_io_processor::EventIOProcessor for MockIOProcessor {
+        fn get_location(&self, id: crate::fsm::SessionId) -> String {
+            format!("mock://{}", id)
+        }
+
+        fn get_types(&self) -> &[&str] {
+            &["mock"]
+        }
+
+        fn get_external_queues(&mut self) -> &mut crate::event_io_processor::ExternalQueueContainer {
+            &mut self.queues
+        }
+
+        fn get_copy(&self) -> Box<dyn crate::event_io_processor::EventIOProcessor> {
+            Box::new(MockIOProcessor {
+                queues: self.queues.clone(),
+                sent: self.sent.clone(),
+            })
+        }
+
+        fn send(&mut self, global: &crate::datamodel::GlobalDataArc, target: &str, event: Event) -> bool {
+            self.sent
+                .lock()
+                .unwrap()
+                .push(format!("{}:{}", target, event.name));
+            // Answers through the queue that was bound by add_fsm.
+            let session_id = global.lock().unwrap().session_id;
+            match self.queues.fsms.get(&session_id) {
+                Some(sender) => sender
+                    .send(Box::new(Event::new_simple("mock.reply")))
+                    .is_ok(),
+                None => false,
+            }
+        }
+
+        fn shutdown(&mut self) {
+            self.queues.shutdown();
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn send_reaches_io_processor_of_session() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry>
+                  <send type='mock' target='peer' event='hello'/>
+                  <send event='timeout' delay='2s'/>
+                </onentry>
+                <transition event='mock.reply'
+                  cond="_ioprocessors['mock'].location == 'mock://' + _sessionid" target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        fsm.add_io_processor(Box::new(MockIOProcessor {
+            queues: Default::default(),
+            sent: sent.clone(),
+        }));
+
+        assert!(run_test_manual(
+            "send_reaches_io_processor_of_session",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            3000,
+            &vec!["pass".to_string()],
+        ));
+        assert_eq!(*sent.lock().unwrap(), vec!["peer:hello".to_string()]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn display_labels_pseudo_root_as_document_root() {
+        use crate::fsm::DOCUMENT_ROOT_LABEL;
+
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0'>
+              <state id='s0'><transition event='go' target='s1'/></state>
+              <state id='s1'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let root = fsm.pseudo_root;
+        let s0 = fsm.get_state_by_name(&"s0".to_string()).id;
+        let output = fsm.to_string();
+        assert!(output.contains(&format!("{{#{} {} states:", root, DOCUMENT_ROOT_LABEL)));
+        assert!(!output.contains(&format!("{{#{} states:", root)));
+        assert!(output.contains(&format!("{{#{} states:", s0)));
+        assert_eq!(output, format!("{:?}", fsm));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn fire_batch_returns_configuration_after_last_event() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'><transition event='a' target='s1'/></state>
+              <state id='s1'><transition event='b' target='s2'/></state>
+              <state id='s2'>
+                <transition event='c' target='p'/>
+              </state>
+              <parallel id='p'>
+                <state id='r1'/>
+                <state id='r2'/>
+                <transition event='done' target='end'/>
+              </parallel>
+              <final id='end'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let mut executor = crate::fsm_executor::FsmExecutor::new_without_io_processor();
+        let mut session = crate::fsm::start_fsm_with_data_and_finish_mode(
+            fsm,
+            crate::actions::ActionWrapper::new(),
+            Box::new(executor.clone()),
+            &Vec::new(),
+            crate::fsm::FinishMode::KEEP_CONFIGURATION,
+        );
+        let thread = session.thread.take().unwrap();
+
+        let configuration = session.fire_batch(vec![
+            Event::new_simple("a"),
+            Event::new_simple("b"),
+            Event::new_simple("c"),
+        ]);
+        assert_eq!(
+            configuration,
+            Ok(vec!["p".to_string(), "r1".to_string(), "r2".to_string()])
+        );
+
+        // The FSM finishes during this batch, so no configuration can be returned.
+        assert!(session.fire_batch(vec![Event::new_simple("done")]).is_err());
+        assert!(session.fire_batch(Vec::new()).is_err());
+
+        executor.shutdown();
+        let _ = thread.join();
+    }
+
+    #[test]
+    #[cfg(all(feature = "Trace_State", feature = "xml"))]
+    fn swap_tracer_of_running_session() {
+        use crate::tracer::{RecordingTracer, TraceRecord};
+
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='null'>
+              <state id='s0'><transition event='a' target='s1'/></state>
+              <state id='s1'><transition event='b' target='s2'/></state>
+              <state id='s2'><transition event='c' target='end'/></state>
+              <final id='end'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let mut executor = crate::fsm_executor::FsmExecutor::new_without_io_processor();
+        let mut session = crate::fsm::start_fsm_with_data_and_finish_mode(
+            fsm,
+            crate::actions::ActionWrapper::new(),
+            Box::new(executor.clone()),
+            &Vec::new(),
+            crate::fsm::FinishMode::KEEP_CONFIGURATION,
+        );
+        let thread = session.thread.take().unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+
+        assert!(session.fire_batch(vec![Event::new_simple("a")]).is_ok());
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let previous = session
+            .swap_tracer(Box::new(RecordingTracer::new(records.clone())))
+            .recv_timeout(timeout)
+            .unwrap();
+        assert!(session.fire_batch(vec![Event::new_simple("b")]).is_ok());
+        let recording = session.swap_tracer(previous).recv_timeout(timeout).unwrap();
+        assert!(format!("{:?}", recording).starts_with("RecordingTracer"));
+        assert!(session.fire_batch(vec![Event::new_simple("c")]).is_err());
+        let _ = thread.join();
+
+        let states: Vec<TraceRecord> = records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| matches!(r, TraceRecord::Enter(_) | TraceRecord::Exit(_)))
+            .cloned()
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                TraceRecord::Exit("s1".to_string()),
+                TraceRecord::Enter("s2".to_string())
+            ]
+        );
+
+        // The finished session returns the tracer.
+        let records = Arc::new(Mutex::new(Vec::new()));
+        assert!(session
+            .swap_tracer(Box::new(RecordingTracer::new(records)))
+            .recv_timeout(timeout)
+            .is_ok());
+        executor.shutdown();
+    }
+
+    #[test]
+    #[cfg(all(feature = "Trace", feature = "xml"))]
+    fn swap_tracer_pending_at_exit_returns_tracer() {
+        use crate::fsm::TracerSwap;
+        use crate::tracer::RecordingTracer;
+
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='null'>
+              <state id='s0'><transition event='a' target='end'/></state>
+              <final id='end'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let mut executor = crate::fsm_executor::FsmExecutor::new_without_io_processor();
+        let mut session = crate::fsm::start_fsm_with_data_and_finish_mode(
+            fsm,
+            crate::actions::ActionWrapper::new(),
+            Box::new(executor.clone()),
+            &Vec::new(),
+            crate::fsm::FinishMode::KEEP_CONFIGURATION,
+        );
+        let thread = session.thread.take().unwrap();
+
+        // Queues a swap the same way as "swap_tracer", but the FSM finishes before it handles the swap.
+        let (reply, receiver) = std::sync::mpsc::channel();
+        session
+            .global_data
+            .lock()
+            .unwrap()
+            .tracer_swaps
+            .as_mut()
+            .unwrap()
+            .push(TracerSwap {
+                tracer: Box::new(RecordingTracer::new(Arc::new(Mutex::new(Vec::new())))),
+                reply,
+            });
+        session
+            .sender
+            .send(Box::new(Event::new_simple("a")))
+            .unwrap();
+        let _ = thread.join();
+
+        let tracer = receiver
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .unwrap();
+        assert!(format!("{:?}", tracer).starts_with("RecordingTracer"));
+        executor.shutdown();
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn pending_sends_lists_scheduled_sends() {
+        use crate::event_io_processor::scxml_event_io_processor::ScxmlEventIOProcessor;
+        use std::time::Duration;
+
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry>
+                  <send id='later' event='late' delay='20s'/>
+                  <send id='sooner' event='soon' delay='10s'/>
+                  <send id='cancelled' event='never' delay='15s'/>
+                  <cancel sendid='cancelled'/>
+                </onentry>
+                <transition event='soon late' target='fail'/>
+              </state>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        fsm.add_io_processor(Box::new(ScxmlEventIOProcessor::new()));
+        let mut executor = crate::fsm_executor::FsmExecutor::new_without_io_processor();
+        let mut session = crate::fsm::start_fsm_with_data_and_finish_mode(
+            fsm,
+            crate::actions::ActionWrapper::new(),
+            Box::new(executor.clone()),
+            &Vec::new(),
+            crate::fsm::FinishMode::KEEP_CONFIGURATION,
+        );
+        let thread = session.thread.take().unwrap();
+
+        // The onentry content was executed at the end of the batch.
+        assert_eq!(session.fire_batch(Vec::new()), Ok(vec!["s0".to_string()]));
+        let pending = session.pending_sends();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].send_id, Some("sooner".to_string()));
+        assert_eq!(pending[0].event, "soon");
+        assert_eq!(pending[1].send_id, Some("later".to_string()));
+        assert_eq!(pending[1].event, "late");
+        let remaining = pending[0].remaining();
+        assert!(remaining > Duration::from_secs(5) && remaining <= Duration::from_secs(10));
+        let remaining = pending[1].remaining();
+        assert!(remaining > Duration::from_secs(15) && remaining <= Duration::from_secs(20));
+
+        executor.shutdown();
+        let _ = thread.join();
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn evaluate_reads_data_of_running_session() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='count' expr='0'/></datamodel>
+              <state id='s0'>
+                <transition event='inc' type='internal'><assign location='count' expr='count + 1'/></transition>
+                <transition event='error.execution' target='fail'/>
+              </state>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let mut executor = crate::fsm_executor::FsmExecutor::new_without_io_processor();
+        let mut session = crate::fsm::start_fsm_with_data_and_finish_mode(
+            fsm,
+            crate::actions::ActionWrapper::new(),
+            Box::new(executor.clone()),
+            &Vec::new(),
+            crate::fsm::FinishMode::KEEP_CONFIGURATION,
+        );
+        let thread = session.thread.take().unwrap();
+        let evaluate = |expression: &str, side_effects: bool| {
+            session
+                .evaluate(expression, side_effects)
+                .recv_timeout(std::time::Duration::from_secs(5))
+                .unwrap()
+        };
+
+        session
+            .sender
+            .send(Box::new(Event::new_simple("inc")))
+            .unwrap();
+        session
+            .sender
+            .send(Box::new(Event::new_simple("inc")))
+            .unwrap();
+        assert_eq!(
+            evaluate("count", false),
+            Ok(crate::datamodel::Data::Integer(2))
+        );
+        assert_eq!(
+            evaluate("count * 10", false),
+            Ok(crate::datamodel::Data::Integer(20))
+        );
+        assert!(evaluate("unknown.member", false).is_err());
+
+        // Marked as side-effecting, the evaluation may change the data model.
+        assert!(evaluate("count = 5", true).is_ok());
+        session
+            .sender
+            .send(Box::new(Event::new_simple("inc")))
+            .unwrap();
+        assert_eq!(
+            evaluate("count", false),
+            Ok(crate::datamodel::Data::Integer(6))
+        );
+
+        executor.shutdown();
+        let _ = thread.join();
+        // The failed evaluation didn't raise an error in the FSM.
+        assert_eq!(
+            session.global_data.lock().unwrap().final_configuration,
+            Some(vec!["s0".to_string()])
+        );
+        assert!(evaluate("count", false).is_err());
+    }
+
+    /// Creates a chart that evaluates each expression as condition, as value of \<log\> and as value of \<assign\>.
+    /// Each evaluation has to raise "error.execution" and has to abort the block, otherwise the chart ends in "fail".
+    #[cfg(all(
+        any(feature = "ECMAScriptModel", feature = "RfsmExpressionModel"),
+        feature = "xml"
+    ))]
+    fn failing_expressions_chart(datamodel: &str, expressions: &[&str]) -> Box<crate::fsm::Fsm> {
+        let mut states = String::new();
+        for (idx, expression) in expressions.iter().enumerate() {
+            let next = if idx + 1 < expressions.len() {
+                format!("cond{}", idx + 1)
+            } else {
+                "pass".to_string()
+            };
+            states.push_str(&format!(
+                r#"<state id='cond{idx}'>
+                  <onentry><raise event='check'/></onentry>
+                  <transition event='check' cond="{expression}" target='fail'/>
+                  <transition event='check'/>
+                  <transition event='error.execution' target='log{idx}'/>
+                  <transition event='*' target='fail'/>
+                </state>
+                <state id='log{idx}'>
+                  <onentry><log expr="{expression}"/><raise event='not_reached'/></onentry>
+                  <transition event='error.execution' target='assign{idx}'/>
+                  <transition event='*' target='fail'/>
+                </state>
+                <state id='assign{idx}'>
+                  <onentry><assign location='result' expr="{expression}"/><raise event='not_reached'/></onentry>
+                  <transition event='error.execution' target='{next}'/>
+                  <transition event='*' target='fail'/>
+                </state>"#
+            ));
+        }
+        crate::scxml_reader::parse_from_xml(format!(
+            r#"<scxml initial='cond0' datamodel='{datamodel}'>
+              <datamodel><data id='result'/></datamodel>
+              {states}
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn expression_errors_raise_error_execution_ecma() {
+        let fsm = failing_expressions_chart(
+            "ecmascript",
+            &["1n / 0n", "undefinedVariable", "null.member"],
+        );
+        let global = crate::fsm::run_blocking(fsm, Vec::new());
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "RfsmExpressionModel", feature = "xml"))]
+    fn expression_errors_raise_error_execution_rfsm_expression() {
+        let fsm = failing_expressions_chart(
+            "rfsm-expression",
+            &["1 / 0", "5 % 0", "undefinedVariable", "'a' - 1"],
+        );
+        let global = crate::fsm::run_blocking(fsm, Vec::new());
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn run_blocking_processes_events_on_calling_thread() {
+        let fsm = crate::scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <transition event='a' target='s1'/>
+              </state>
+              <state id='s1'>
+                <onentry><send event='self'/></onentry>
+                <transition event='self' target='s2'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <state id='s2'>
+                <transition event='b' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        let global = crate::fsm::run_blocking(fsm, vec![Event::new_simple("a"), Event::new_simple("b")]);
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn records_executed_transitions() {
+        let mut fsm = crate::scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <transition event='a' target='s1'/>
+              </state>
+              <state id='s1'>
+                <transition target='s2'/>
+              </state>
+              <state id='s2'>
+                <transition event='b' target='pass'/>
+              </state>
+              <final id='pass'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        fsm.record_transitions = true;
+        let t_a = fsm.transitions_from("s0")[0].id;
+        let t_s1 = fsm.transitions_from("s1")[0].id;
+        let t_b = fsm.transitions_from("s2")[0].id;
+
+        let global = crate::fsm::run_blocking(fsm, vec![Event::new_simple("a"), Event::new_simple("b")]);
+        let global = global.lock().unwrap();
+        assert_eq!(
+            global.recorded_transitions(),
+            &[
+                crate::fsm::TransitionRecord {
+                    event: Some("a".to_string()),
+                    transitions: vec![t_a],
+                    configuration: vec!["s1".to_string()],
+                },
+                crate::fsm::TransitionRecord {
+                    event: None,
+                    transitions: vec![t_s1],
+                    configuration: vec!["s2".to_string()],
+                },
+                crate::fsm::TransitionRecord {
+                    event: Some("b".to_string()),
+                    transitions: vec![t_b],
+                    configuration: vec!["pass".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn cyclic_history_default_raises_error() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <transition target='h'/>
+              </state>
+              <state id='p'>
+                <history id='h'><transition target='h'/></history>
+                <state id='p1'/>
+                <transition event='error.execution' target='pass'/>
+              </state>
+              <final id='pass'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual(
+            "cyclic_history_default_raises_error",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn config_changes_are_sent_per_macrostep() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <transition event='go' target='a'/>
+              </state>
+              <state id='a'>
+                <transition target='b'/>
+              </state>
+              <state id='b'>
+                <transition event='stop' target='pass'/>
+              </state>
+              <final id='pass'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        fsm.config_change_sender = Some(sender);
+
+        crate::fsm::run_blocking(
+            fsm,
+            vec![
+                Event::new_simple("go"),
+                Event::new_simple("unknown"),
+                Event::new_simple("stop"),
+            ],
+        );
+
+        let change = |entered: &[&str], exited: &[&str]| crate::fsm::ConfigChange {
+            entered: entered.iter().map(|s| s.to_string()).collect(),
+            exited: exited.iter().map(|s| s.to_string()).collect(),
+        };
+        // "a" is only active inside a macrostep, "unknown" changes nothing.
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                change(&["s0"], &[]),
+                change(&["b"], &["s0"]),
+                change(&["pass"], &["b"]),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn invoke_idlocation_stores_generated_id() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='child'/></datamodel>
+              <state id='s0'>
+                <invoke idlocation='child'>
+                  <content>
+                    <scxml initial='c' datamodel='ecmascript'><final id='c'/></scxml>
+                  </content>
+                </invoke>
+                <transition event='done.invoke' cond='child.indexOf("s0.") == 0 &amp;&amp; _event.name == "done.invoke." + child &amp;&amp; _event.invokeid == child' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        assert!(run_test_manual(
+            "invoke_idlocation_stores_generated_id",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn interpret_from_enters_deep_configuration() {
+        let source = r#"<scxml initial='idle' datamodel='ecmascript'>
+              <state id='idle'>
+                <transition event='done' target='fail'/>
+              </state>
+              <parallel id='work'>
+                <state id='r1'>
+                  <state id='r1a'/>
+                  <state id='r1b'>
+                    <transition event='done' target='pass'/>
+                  </state>
+                </state>
+                <state id='r2'>
+                  <state id='r2a'/>
+                </state>
+              </parallel>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#;
+
+        let mut fsm = scxml_reader::parse_from_xml(source.to_string()).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        fsm.config_change_sender = Some(sender);
+        let global_data = GlobalDataArc::new(Mutex::new(GlobalData::new()));
+        let mut datamodel = create_datamodel("ecmascript", global_data.clone(), &HashMap::new());
+        global_data
+            .lock()
+            .unwrap()
+            .externalQueue
+            .enqueue(Box::new(Event::new_simple("done")));
+
+        assert!(fsm.interpret_from(datamodel.deref_mut(), &["r1b"]).is_ok());
+
+        let changes: Vec<crate::fsm::ConfigChange> = receiver.try_iter().collect();
+        assert_eq!(changes[0].entered, vec!["work", "r1", "r1b", "r2", "r2a"]);
+        assert_eq!(changes[1].entered, vec!["pass"]);
+
+        let mut fsm = scxml_reader::parse_from_xml(source.to_string()).unwrap();
+        assert!(fsm.validate_configuration(&["work", "r1b", "r2a"]).is_ok());
+        // Two children of the same compound state.
+        assert!(fsm.validate_configuration(&["r1a", "r1b"]).is_err());
+        assert!(fsm.validate_configuration(&["idle", "r2a"]).is_err());
+        assert!(fsm.validate_configuration(&["nowhere"]).is_err());
+
+        let mut fsm = scxml_reader::parse_from_xml(source.to_string()).unwrap();
+        let mut datamodel = create_datamodel(
+            "ecmascript",
+            GlobalDataArc::new(Mutex::new(GlobalData::new())),
+            &HashMap::new(),
+        );
+        assert!(fsm
+            .interpret_from(datamodel.deref_mut(), &["r1a", "r1b"])
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn is_legal_configuration_checks_compound_and_parallel_states() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='idle'>
+              <state id='idle'/>
+              <parallel id='work'>
+                <state id='r1'>
+                  <state id='r1a'/>
+                  <state id='r1b'/>
+                  <history id='h1'/>
+                </state>
+                <state id='r2'>
+                  <state id='r2a'/>
+                </state>
+              </parallel>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let ids = |names: &[&str]| -> Vec<crate::fsm::StateId> {
+            names
+                .iter()
+                .map(|name| fsm.get_state_by_name(&name.to_string()).id)
+                .collect()
+        };
+
+        assert!(fsm.is_legal_configuration(&ids(&["idle"])).is_ok());
+        assert!(fsm
+            .is_legal_configuration(&ids(&["work", "r1", "r1b", "r2", "r2a"]))
+            .is_ok());
+
+        // Compound state with two active children
+        let err = fsm
+            .is_legal_configuration(&ids(&["work", "r1", "r1a", "r1b", "r2", "r2a"]))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "State 'r1' has 2 active children, but needs exactly one"
+        );
+
+        // Parallel state with a missing region
+        let err = fsm
+            .is_legal_configuration(&ids(&["work", "r1", "r1a"]))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "Not all regions of the parallel state 'work' are active"
+        );
+
+        assert!(fsm.is_legal_configuration(&ids(&["r1a"])).is_err());
+        assert!(fsm
+            .is_legal_configuration(&ids(&["idle", "work", "r1", "r1a", "r2", "r2a"]))
+            .is_err());
+        assert!(fsm
+            .is_legal_configuration(&ids(&["work", "r1", "h1", "r2", "r2a"]))
+            .is_err());
+        assert!(fsm.is_legal_configuration(&[]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn scxml_initial_attribute_selects_start_state() {
+        let run = |initial: &str| -> Option<Vec<String>> {
+            let fsm = scxml_reader::parse_from_xml(format!(
+                r#"<scxml {} datamodel='null'>
+                  <state id='A'><transition target='left_A'/></state>
+                  <state id='B'>
+                    <state id='B1'><transition target='left_B1'/></state>
+                    <state id='B2'><transition target='left_B2'/></state>
+                  </state>
+                  <final id='left_A'/>
+                  <final id='left_B1'/>
+                  <final id='left_B2'/>
+                </scxml>"#,
+                initial
+            ))
+            .unwrap();
+            crate::fsm::run_blocking(fsm, Vec::new())
+                .lock()
+                .unwrap()
+                .final_configuration
+                .clone()
+        };
+        let left = |name: &str| Some(vec![name.to_string()]);
+
+        // Default is the first child in document order.
+        assert_eq!(run(""), left("left_A"));
+        assert_eq!(run("initial='B'"), left("left_B1"));
+        assert_eq!(run("initial='B2'"), left("left_B2"));
+        assert_eq!(run("initial='A'"), left("left_A"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn proper_ancestors_edge_cases() {
+        let fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='a'>
+              <state id='a'>
+                <state id='b'>
+                  <state id='c'>
+                    <state id='d'/>
+                  </state>
+                </state>
+              </state>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let id = |name: &str| fsm.get_state_by_name(&name.to_string()).id;
+        let names = |state1: &str, state2: crate::fsm::StateId| -> Vec<String> {
+            fsm.getProperAncestors(id(state1), state2)
+                .iterator()
+                .map(|sid| fsm.get_state_by_id(*sid).name.clone())
+                .collect()
+        };
+        let root_name = fsm.get_state_by_id(fsm.pseudo_root).name.clone();
+
+        // No state2: all ancestors, including the document root.
+        assert_eq!(names("d", 0), vec!["c", "b", "a", root_name.as_str()]);
+        assert_eq!(names("d", id("a")), vec!["c", "b"]);
+        // state2 is the parent
+        assert!(names("d", id("c")).is_empty());
+        // state2 is equal to state1
+        assert!(names("c", id("c")).is_empty());
+        // state2 is a (deep) descendant
+        assert!(names("b", id("c")).is_empty());
+        assert!(names("a", id("d")).is_empty());
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn entry_and_exit_blocks_run_in_document_order() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel><data id='trace' expr='""'/></datamodel>
+              <state id='s0'>
+                <onentry><assign location='trace' expr='trace + "a"'/></onentry>
+                <onentry><assign location='trace' expr='trace + "b"'/></onentry>
+                <onexit><assign location='trace' expr='trace + "c"'/></onexit>
+                <onexit><assign location='trace' expr='trace + "d"'/></onexit>
+                <transition target='s1'/>
+              </state>
+              <state id='s1'>
+                <transition cond='trace == "abcd"' target='pass'/>
+                <transition target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        // Give the first blocks the higher content ids, e.g. as after an include.
+        let sid = fsm.get_state_by_name(&"s0".to_string()).id;
+        for exit in [false, true] {
+            let blocks = if exit {
+                fsm.get_state_by_id(sid).onexit.clone()
+            } else {
+                fsm.get_state_by_id(sid).onentry.clone()
+            };
+            let (first, second) = (blocks[0], blocks[1]);
+            assert!(first < second);
+            let content = fsm.executable_content_mut().unwrap();
+            let first_content = content.remove(&first).unwrap();
+            let second_content = content.insert(second, first_content).unwrap();
+            content.insert(first, second_content);
+            let state = fsm.get_state_by_id_mut(sid);
+            if exit {
+                state.onexit = vec![second, first];
+            } else {
+                state.onentry = vec![second, first];
+            }
+        }
+
+        assert!(run_test_manual(
+            "entry_and_exit_blocks_run_in_document_order",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn cached_conditions_are_evaluated_once_per_selection() {
+        let source = r#"<scxml initial='p' datamodel='ecmascript'>
+              <datamodel><data id='evals' expr='0'/></datamodel>
+              <parallel id='p'>
+                <transition event='go' cond='++evals &lt; 0' target='fail'/>
+                <transition event='go' target='check'/>
+                <state id='r1'/>
+                <state id='r2'/>
+                <state id='r3'/>
+                <state id='r4'/>
+                <state id='r5'/>
+              </parallel>
+              <state id='check'>
+                <transition cond='evals == 1' target='once'/>
+                <transition cond='evals == 5' target='five'/>
+                <transition target='fail'/>
+              </state>
+              <final id='once'/>
+              <final id='five'/>
+              <final id='fail'/>
+            </scxml>"#;
+
+        let run = |cache_conditions: bool| {
+            let mut fsm = scxml_reader::parse_from_xml(source.to_string()).unwrap();
+            fsm.record_transitions = true;
+            fsm.cache_conditions = cache_conditions;
+            let global = crate::fsm::run_blocking(fsm, vec![Event::new_simple("go")]);
+            let global = global.lock().unwrap();
+            (
+                global.recorded_transitions().to_vec(),
+                global.final_configuration.clone().unwrap(),
+            )
+        };
+        let (records, configuration) = run(false);
+        let (cached_records, cached_configuration) = run(true);
+
+        // The shared transition of "p" is examined for each of the five regions.
+        assert_eq!(configuration, vec!["five".to_string()]);
+        assert_eq!(cached_configuration, vec!["once".to_string()]);
+        // Same transitions are selected, besides the final one that checks the number of evaluations.
+        assert_eq!(records.len(), 2);
+        assert_eq!(cached_records[0].transitions, records[0].transitions);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn reverse_onexit_order_if_enabled() {
+        let exit_order = |reverse: bool| -> Vec<String> {
+            let mut fsm = scxml_reader::parse_from_xml(
+                r#"<scxml initial='s0' datamodel='ecmascript'>
+                  <state id='s0' initial='s1'>
+                    <onentry><log expr='"enter connection"'/></onentry>
+                    <onentry><log expr='"enter session"'/></onentry>
+                    <onexit><log expr='"exit 1"'/></onexit>
+                    <onexit><log expr='"exit 2"'/></onexit>
+                    <state id='s1'>
+                      <onexit><log expr='"exit child"'/></onexit>
+                      <transition event='done' target='end'/>
+                    </state>
+                  </state>
+                  <final id='end'/>
+                </scxml>"#
+                    .to_string(),
+            )
+            .unwrap();
+            fsm.reverse_onexit_order = reverse;
+            let (sender, receiver) = std::sync::mpsc::channel::<String>();
+            fsm.log_sink = Some(Arc::new(sender));
+            crate::fsm::run_blocking(fsm, vec![Event::new_simple("done")]);
+            receiver
+                .try_iter()
+                .filter(|message| message.starts_with("exit"))
+                .collect()
+        };
+
+        assert_eq!(exit_order(false), vec!["exit child", "exit 1", "exit 2"]);
+        // Only the handlers of each state are reversed, the states are still exited from the inside out.
+        assert_eq!(exit_order(true), vec!["exit child", "exit 2", "exit 1"]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+    fn completed_top_level_parallel_terminates_if_enabled() {
+        let run = |terminate: bool| {
+            let mut fsm = scxml_reader::parse_from_xml(
+                r#"<scxml initial='p' datamodel='ecmascript'>
+                  <parallel id='p'>
+                    <state id='r1'>
+                      <state id='a'><transition event='e1' target='af'/></state>
+                      <final id='af'/>
+                    </state>
+                    <state id='r2'>
+                      <state id='b'><transition event='e2' target='bf'/></state>
+                      <final id='bf'/>
+                    </state>
+                    <transition event='e3' target='other'/>
+                  </parallel>
+                  <state id='other'/>
+                </scxml>"#
+                    .to_string(),
+            )
+            .unwrap();
+            fsm.record_transitions = true;
+            fsm.terminate_on_parallel_completion = terminate;
+            let global = crate::fsm::run_blocking(
+                fsm,
+                vec![
+                    Event::new_simple("e1"),
+                    Event::new_simple("e2"),
+                    Event::new_simple("e3"),
+                ],
+            );
+            let global = global.lock().unwrap();
+            (
+                global.recorded_transitions().len(),
+                global.final_configuration.clone().unwrap(),
+            )
+        };
+
+        let (transitions, mut configuration) = run(true);
+        assert_eq!(transitions, 2);
+        configuration.sort();
+        assert_eq!(configuration, vec!["af", "bf", "p", "r1", "r2"]);
+
+        // W3C behaviour: The FSM keeps running.
+        let (transitions, configuration) = run(false);
+        assert_eq!(transitions, 3);
+        assert_eq!(configuration, vec!["other"]);
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn initial_configuration_is_computed_without_running() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='null'>
+              <state id='s0' initial='s01'>
+                <state id='s00'/>
+                <state id='s01'>
+                  <initial><transition target='s011'/></initial>
+                  <state id='s010'/>
+                  <state id='s011'/>
+                </state>
+              </state>
+              <state id='s1'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(fsm.initial_configuration(), vec!["s0", "s01", "s011"]);
+
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml datamodel='null'>
+              <parallel id='p'>
+                <state id='r1'>
+                  <state id='a1'/>
+                  <state id='a2'/>
+                </state>
+                <state id='r2' initial='b2'>
+                  <state id='b1'/>
+                  <state id='b2'/>
+                </state>
+                <parallel id='r3'>
+                  <state id='c'/>
+                  <state id='d'/>
+                </parallel>
+              </parallel>
+              <state id='other'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            fsm.initial_configuration(),
+            vec!["p", "r1", "a1", "r2", "b2", "r3", "c", "d"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn transitions_from_resolves_names() {
+        let fsm = crate::scxml_reader::parse_from_xml(
+            r#"<scxml initial='A'>
+              <state id='A'>
+                <transition event='go go.now' cond="In('A')" target='B C' type='internal'/>
+                <transition event='stop' target='C'/>
+              </state>
+              <parallel id='P'>
+                <state id='B'/>
+                <state id='C'/>
+              </parallel>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        let transitions = fsm.transitions_from("A");
+        assert_eq!(transitions.len(), 2);
+
+        let t = &transitions[0];
+        assert_eq!(t.source, "A");
+        assert_eq!(t.events, vec!["go".to_string(), "go.now".to_string()]);
+        assert_eq!(t.target, vec!["B".to_string(), "C".to_string()]);
+        assert_eq!(t.cond, Some("In('A')".to_string()));
+        assert_eq!(t.transition_type, "internal");
+
+        assert_eq!(transitions[1].events, vec!["stop".to_string()]);
+        assert_eq!(transitions[1].cond, None);
+        assert_eq!(transitions[1].transition_type, "external");
+
+        let to_c = fsm.transitions_to("C");
+        assert_eq!(to_c.len(), 2);
+        assert_eq!(to_c[0], transitions[0]);
+
+        assert!(fsm.transitions_from("B").is_empty());
+        assert!(fsm.transitions_from("unknown").is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn invokes_of_lists_invoke_descriptors() {
+        let fsm = crate::scxml_reader::parse_from_xml(
+            r#"<scxml initial='A'>
+              <state id='A'>
+                <invoke id='first' type='scxml' src='first.scxml' autoforward='true'/>
+                <invoke id='second' type='scxml' src='second.scxml'/>
+              </state>
+              <state id='B'>
+                <invoke id='third' type='scxml' src='third.scxml'/>
+              </state>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        let invokes = fsm.invokes_of("A");
+        assert_eq!(invokes.len(), 2);
+        assert_eq!(invokes[0].src.to_string(), "first.scxml");
+        assert!(invokes[0].autoforward);
+        assert_eq!(invokes[1].src.to_string(), "second.scxml");
+        assert!(!invokes[1].autoforward);
+        assert!(fsm.invokes_of("unknown").is_empty());
+
+        let all: Vec<&str> = fsm
+            .all_invokes()
+            .iter()
+            .map(|invoke| invoke.invoke_id.as_str())
+            .collect();
+        assert_eq!(all, vec!["first", "second", "third"]);
+    }
+
+    #[test]
+    #[cfg(feature = "Debug_Queue")]
+    fn trace_queues_reports_pending_events() {
+        use crate::datamodel::{create_global_data_arc, NullDatamodel};
+        use crate::fsm::{Event, Fsm};
+        use crate::tracer::CallbackTracer;
+        use std::sync::{Arc, Mutex};
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let messages_copy = messages.clone();
+        let mut fsm = Fsm::new();
+        fsm.tracer = Box::new(CallbackTracer::new(Arc::new(
+            move |_session_id, _mode, msg| {
+                messages_copy.lock().unwrap().push(msg.to_string());
+            },
+        )));
+
+        let mut datamodel = NullDatamodel::new(create_global_data_arc());
+        {
+            let mut global = datamodel.global.lock().unwrap();
+            global.enqueue_internal(Event::new_simple("i1"));
+            global.enqueue_internal(Event::error_execution(&None, &None));
+            global
+                .externalQueue
+                .enqueue(Box::new(Event::new_simple("e1")));
+            global
+                .externalQueue
+                .enqueue(Box::new(Event::new_simple("e2")));
+        }
+
+        // Nothing is dumped as long as events are not traced.
+        fsm.trace_queues(&mut datamodel);
+        assert!(messages.lock().unwrap().is_empty());
+
+        fsm.tracer.enable_trace(TraceMode::EVENTS);
+        fsm.trace_queues(&mut datamodel);
+
+        assert_eq!(
+            *messages.lock().unwrap(),
+            vec![
+                "Internal Queue: 2 Events".to_string(),
+                " i1 (external)".to_string(),
+                " error.execution (platform)".to_string(),
+                "External Queue: 2 Events".to_string(),
+            ]
+        );
+
+        // Counting shall not change the content or order of the external queue.
+        let mut global = datamodel.global.lock().unwrap();
+        assert_eq!(global.internalQueue.size(), 2);
+        assert_eq!(global.externalQueue.dequeue().name, "e1");
+        assert_eq!(global.externalQueue.dequeue().name, "e2");
+    }
 }
@@ -0,0 +1,430 @@
+//! Builder to create a FSM programmatically, without a SCXML document.\
+//! The builder creates the same structures as the SCXML reader. Elements are opened and
+//! closed in the same nesting as in a document, e.g.:
+//! ```ignore
+//! let fsm = FsmBuilder::new()
+//!     .state("off")
+//!     .transition("toggle", "on")
+//!     .end()
+//!     .state("on")
+//!     .transition("toggle", "off")
+//!     .end()
+//!     .build()?;
+//! ```
+
+use std::collections::HashSet;
+
+use crate::datamodel::{create_data_arc, Data, SourceCode};
+use crate::executable_content::ExecutableContent;
+use crate::fsm::{
+    BindingType, DocumentId, ExecutableContentId, Fsm, State, StateId, Transition, TransitionId, TransitionType,
+};
+
+pub struct FsmBuilder {
+    fsm: Box<Fsm>,
+    /// The currently open states. The first entry is the pseudo root.
+    stack: Vec<StateId>,
+    /// The last added transition, target of [FsmBuilder::cond], [FsmBuilder::transition_type]
+    /// and [FsmBuilder::content].
+    current_transition: TransitionId,
+    /// States that were declared. Other states were only referenced by some target.
+    declared: HashSet<StateId>,
+    doc_id_count: DocumentId,
+    id_count: u32,
+    /// Last id of transitions and executable content.
+    content_id_count: u32,
+    source_id_count: usize,
+    /// The first error. Reported by [FsmBuilder::build].
+    error: Option<String>,
+}
+
+impl Default for FsmBuilder {
+    fn default() -> Self {
+        FsmBuilder::new()
+    }
+}
+
+impl FsmBuilder {
+    pub fn new() -> FsmBuilder {
+        let mut builder = FsmBuilder {
+            fsm: Box::new(Fsm::new()),
+            stack: Vec::new(),
+            current_transition: 0,
+            declared: HashSet::new(),
+            doc_id_count: 0,
+            id_count: 0,
+            content_id_count: 0,
+            source_id_count: 0,
+            error: None,
+        };
+        let root_name = builder.generate_name();
+        let root = builder.declare_state(&root_name, 0);
+        builder.fsm.pseudo_root = root;
+        builder.stack.push(root);
+        builder
+    }
+
+    /// Sets the name of the FSM, same as the "name" attribute of \<scxml\>.
+    pub fn name(mut self, name: &str) -> Self {
+        self.fsm.name = name.to_string();
+        self
+    }
+
+    /// Sets the datamodel, same as the "datamodel" attribute of \<scxml\>.
+    pub fn datamodel(mut self, datamodel: &str) -> Self {
+        self.fsm.datamodel = datamodel.to_string();
+        self
+    }
+
+    /// Sets the data binding, same as the "binding" attribute of \<scxml\>.
+    pub fn binding(mut self, binding: BindingType) -> Self {
+        self.fsm.binding = binding;
+        self
+    }
+
+    /// Opens a new \<state\> inside the current state. Needs to be closed by [FsmBuilder::end].
+    pub fn state(mut self, name: &str) -> Self {
+        let parent = self.current_state();
+        let sid = self.declare_state(name, parent);
+        self.stack.push(sid);
+        self
+    }
+
+    /// Opens a new \<parallel\> inside the current state. Needs to be closed by [FsmBuilder::end].
+    pub fn parallel(mut self, name: &str) -> Self {
+        let parent = self.current_state();
+        let sid = self.declare_state(name, parent);
+        self.fsm.get_state_by_id_mut(sid).is_parallel = true;
+        self.stack.push(sid);
+        self
+    }
+
+    /// Opens a new \<final\> inside the current state. Needs to be closed by [FsmBuilder::end].
+    pub fn final_state(mut self, name: &str) -> Self {
+        let parent = self.current_state();
+        let sid = self.declare_state(name, parent);
+        self.fsm.get_state_by_id_mut(sid).is_final = true;
+        self.stack.push(sid);
+        self
+    }
+
+    /// Closes the current state.\
+    /// W3C: If not specified, the default initial state is the first child state in document order.
+    pub fn end(mut self) -> Self {
+        if self.stack.len() > 1 {
+            let sid = self.stack.pop().unwrap();
+            self.set_default_initial(sid);
+        } else {
+            self.set_error("end() without open state".to_string());
+        }
+        self
+    }
+
+    /// Sets the initial states of the current state, same as the "initial" attribute.\
+    /// The targets are a space separated list of state names.
+    pub fn initial(mut self, targets: &str) -> Self {
+        let sid = self.current_state();
+        let mut t = Transition::with_id(self.next_id());
+        t.doc_id = self.next_doc_id();
+        t.transition_type = TransitionType::Internal;
+        t.source = sid;
+        t.target = self.resolve_targets(targets);
+        self.fsm.get_state_by_id_mut(sid).initial = t.id;
+        self.fsm.transitions.insert(t.id, t);
+        self
+    }
+
+    /// Adds a transition to the current state.\
+    /// "events" is a space separated list of event descriptors, empty for an eventless transition.
+    /// "targets" is a space separated list of state names, empty for a targetless transition.
+    pub fn transition(mut self, events: &str, targets: &str) -> Self {
+        let sid = self.current_state();
+        if sid == self.fsm.pseudo_root {
+            self.set_error(format!("Transition '{}' outside of a state", events));
+            return self;
+        }
+        let mut t = Transition::with_id(self.next_id());
+        t.doc_id = self.next_doc_id();
+        t.set_event_descriptors(events);
+        t.source = sid;
+        t.target = self.resolve_targets(targets);
+        self.current_transition = t.id;
+        self.fsm.get_state_by_id_mut(sid).transitions.push(t.id);
+        self.fsm.transitions.insert(t.id, t);
+        self
+    }
+
+    /// Sets the condition of the last added transition.
+    pub fn cond(mut self, expr: &str) -> Self {
+        let source = self.create_source(expr);
+        if let Some(t) = self.get_current_transition() {
+            t.cond = source;
+        }
+        self
+    }
+
+    /// Sets the type of the last added transition.
+    pub fn transition_type(mut self, transition_type: TransitionType) -> Self {
+        if let Some(t) = self.get_current_transition() {
+            t.transition_type = transition_type;
+        }
+        self
+    }
+
+    /// Sets the executable content of the last added transition.
+    pub fn content(mut self, content: Vec<Box<dyn ExecutableContent>>) -> Self {
+        let ec_id = self.add_executable_content(content);
+        if let Some(t) = self.get_current_transition() {
+            t.content = ec_id;
+        }
+        self
+    }
+
+    /// Adds an \<onentry\> block to the current state.
+    pub fn on_entry(mut self, content: Vec<Box<dyn ExecutableContent>>) -> Self {
+        let ec_id = self.add_executable_content(content);
+        let sid = self.current_state();
+        self.fsm.get_state_by_id_mut(sid).onentry.push(ec_id);
+        self
+    }
+
+    /// Adds an \<onexit\> block to the current state.
+    pub fn on_exit(mut self, content: Vec<Box<dyn ExecutableContent>>) -> Self {
+        let ec_id = self.add_executable_content(content);
+        let sid = self.current_state();
+        self.fsm.get_state_by_id_mut(sid).onexit.push(ec_id);
+        self
+    }
+
+    /// Adds a \<data\> element to the current state.
+    pub fn data(mut self, id: &str, expr: &str) -> Self {
+        let source = self.create_source(expr);
+        let sid = self.current_state();
+        self.fsm
+            .get_state_by_id_mut(sid)
+            .data
+            .insert(id.to_string(), create_data_arc(source));
+        self
+    }
+
+    /// Finishes the FSM.\
+    /// Fails if some state is still open, some target references an undeclared state or if any
+    /// other call failed before.
+    pub fn build(mut self) -> Result<Box<Fsm>, String> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.stack.len() > 1 {
+            return Err(format!(
+                "State '{}' not closed",
+                self.fsm.get_state_by_id(self.current_state()).name
+            ));
+        }
+        if let Some(state) = self
+            .fsm
+            .states
+            .iter()
+            .find(|s| !self.declared.contains(&s.id))
+        {
+            return Err(format!("Unknown state '{}'", state.name));
+        }
+        let root = self.fsm.pseudo_root;
+        self.set_default_initial(root);
+        Ok(self.fsm)
+    }
+
+    fn current_state(&self) -> StateId {
+        *self.stack.last().unwrap()
+    }
+
+    fn get_current_transition(&mut self) -> Option<&mut Transition> {
+        if self.current_transition == 0 {
+            self.set_error("No transition".to_string());
+            None
+        } else {
+            Some(self.fsm.get_transition_by_id_mut(self.current_transition))
+        }
+    }
+
+    fn set_error(&mut self, error: String) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    fn next_doc_id(&mut self) -> DocumentId {
+        self.doc_id_count += 1;
+        self.doc_id_count
+    }
+
+    fn next_id(&mut self) -> u32 {
+        self.content_id_count += 1;
+        self.content_id_count
+    }
+
+    fn generate_name(&mut self) -> String {
+        self.id_count += 1;
+        format!("__id{}", self.id_count)
+    }
+
+    fn create_source(&mut self, src: &str) -> Data {
+        self.source_id_count += 1;
+        Data::Source(SourceCode::new(src, self.source_id_count))
+    }
+
+    fn get_or_create_state(&mut self, name: &str) -> StateId {
+        match self.fsm.statesNames.get(name) {
+            Some(sid) => *sid,
+            None => {
+                let mut s = State::new(name);
+                s.id = (self.fsm.states.len() + 1) as StateId;
+                let sid = s.id;
+                self.fsm.statesNames.insert(s.name.clone(), sid);
+                self.fsm.states.push(s);
+                sid
+            }
+        }
+    }
+
+    fn declare_state(&mut self, name: &str, parent: StateId) -> StateId {
+        let sid = self.get_or_create_state(name);
+        if !self.declared.insert(sid) {
+            self.set_error(format!("State '{}' declared twice", name));
+        }
+        let doc_id = self.next_doc_id();
+        let state = self.fsm.get_state_by_id_mut(sid);
+        state.doc_id = doc_id;
+        if parent != 0 {
+            state.parent = parent;
+            self.fsm.get_state_by_id_mut(parent).states.push(sid);
+        }
+        sid
+    }
+
+    fn resolve_targets(&mut self, targets: &str) -> Vec<StateId> {
+        targets
+            .split_ascii_whitespace()
+            .map(|target| self.get_or_create_state(target))
+            .collect()
+    }
+
+    fn add_executable_content(&mut self, content: Vec<Box<dyn ExecutableContent>>) -> ExecutableContentId {
+        let ec_id = self.next_id();
+        self.fsm
+            .executable_content_mut()
+            .expect("The builder has exclusive executable content")
+            .insert(ec_id, content);
+        ec_id
+    }
+
+    fn set_default_initial(&mut self, sid: StateId) {
+        let state = self.fsm.get_state_by_id(sid);
+        if state.initial == 0 && !state.states.is_empty() {
+            let transition_id = self.next_id();
+            let state = self.fsm.get_state_by_id_mut(sid);
+            if let Some(first_state) = state.states.first() {
+                let mut t = Transition::with_id(transition_id);
+                state.initial = t.id;
+                t.source = state.id;
+                t.target.push(*first_state);
+                self.fsm.transitions.insert(t.id, t);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ECMAScriptModel")]
+    use crate::datamodel::str_to_source;
+    #[cfg(feature = "ECMAScriptModel")]
+    use crate::executable_content::Assign;
+    #[cfg(feature = "ECMAScriptModel")]
+    use crate::fsm::Event;
+    use crate::fsm_builder::FsmBuilder;
+    #[cfg(all(feature = "ECMAScriptModel", feature = "Trace"))]
+    use crate::tracer::TraceMode;
+    #[cfg(feature = "ECMAScriptModel")]
+    use std::collections::HashMap;
+
+    #[test]
+    fn build_document_order() {
+        let fsm = FsmBuilder::new()
+            .state("a")
+            .state("a1")
+            .end()
+            .end()
+            .state("b")
+            .end()
+            .build()
+            .unwrap();
+
+        let doc_ids: Vec<u32> = ["a", "a1", "b"]
+            .iter()
+            .map(|name| fsm.get_state_by_name(&name.to_string()).doc_id)
+            .collect();
+        assert!(doc_ids[0] < doc_ids[1] && doc_ids[1] < doc_ids[2]);
+
+        let a = fsm.get_state_by_name(&"a".to_string());
+        assert_eq!(a.states, vec![fsm.get_state_by_name(&"a1".to_string()).id]);
+        assert_eq!(
+            fsm.get_transition_by_id(fsm.get_state_by_id(fsm.pseudo_root).initial)
+                .target,
+            vec![a.id]
+        );
+    }
+
+    #[test]
+    fn build_fails_for_unknown_or_open_states() {
+        assert!(FsmBuilder::new()
+            .state("a")
+            .transition("go", "b")
+            .end()
+            .build()
+            .is_err());
+        assert!(FsmBuilder::new().state("a").build().is_err());
+        assert!(FsmBuilder::new().end().build().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "ECMAScriptModel")]
+    fn build_and_run_toggle() {
+        let mut count = Assign::new();
+        count.location = str_to_source("count");
+        count.expr = str_to_source("count + 1");
+
+        let fsm = FsmBuilder::new()
+            .name("Toggle")
+            .datamodel("ecmascript")
+            .data("count", "0")
+            .state("off")
+            .transition("toggle", "on")
+            .end()
+            .state("on")
+            .on_entry(vec![Box::new(count)])
+            .transition("toggle", "off")
+            .transition("stop", "done")
+            .cond("count == 2")
+            .end()
+            .final_state("done")
+            .end()
+            .build()
+            .unwrap();
+
+        assert!(crate::test::run_test_manual_with_send(
+            "build_and_run_toggle",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["done".to_string()],
+            |sender| {
+                for name in ["toggle", "toggle", "toggle", "stop"] {
+                    let _ = sender.send(Box::new(Event::new_simple(name)));
+                }
+            },
+        ));
+    }
+}
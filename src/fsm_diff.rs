@@ -0,0 +1,358 @@
+//! Structural comparison of two FSMs, e.g. to review changes of a SCXML document.\
+//! States are matched by name, transitions by source state, events and targets.
+//! Transitions with the same key are matched in document order.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+
+use crate::executable_content::{ExecutableContent, ExecutableContentTracer, TYPE_NAMES};
+use crate::fsm::{ExecutableContentId, Fsm, HistoryType, State, StateId, Transition};
+
+/// Identifies a transition in both FSMs.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TransitionKey {
+    pub source: String,
+    pub events: Vec<String>,
+    pub target: Vec<String>,
+}
+
+impl Display for TransitionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -[{}]-> {}",
+            self.source,
+            self.events.join(" "),
+            self.target.join(" ")
+        )
+    }
+}
+
+/// Result of [fsm_diff]. All lists are sorted by name or key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FsmDiff {
+    pub added_states: Vec<String>,
+    pub removed_states: Vec<String>,
+    /// States that exist in both FSMs, with the names of the changed aspects
+    /// ("type", "parent", "initial", "onentry", "onexit", "data").
+    pub modified_states: Vec<(String, Vec<&'static str>)>,
+    pub added_transitions: Vec<TransitionKey>,
+    pub removed_transitions: Vec<TransitionKey>,
+    /// Transitions that exist in both FSMs, with the names of the changed aspects ("cond", "type", "content").
+    pub modified_transitions: Vec<(TransitionKey, Vec<&'static str>)>,
+    /// True if the global \<script\> differs.
+    pub script_changed: bool,
+}
+
+impl FsmDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_states.is_empty()
+            && self.removed_states.is_empty()
+            && self.modified_states.is_empty()
+            && self.added_transitions.is_empty()
+            && self.removed_transitions.is_empty()
+            && self.modified_transitions.is_empty()
+            && !self.script_changed
+    }
+}
+
+/// Prints one line per change, prefixed by "+" (added), "-" (removed) or "~" (modified).
+impl Display for FsmDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for name in &self.added_states {
+            writeln!(f, "+ state {}", name)?;
+        }
+        for name in &self.removed_states {
+            writeln!(f, "- state {}", name)?;
+        }
+        for (name, aspects) in &self.modified_states {
+            writeln!(f, "~ state {}: {}", name, aspects.join(", "))?;
+        }
+        for key in &self.added_transitions {
+            writeln!(f, "+ transition {}", key)?;
+        }
+        for key in &self.removed_transitions {
+            writeln!(f, "- transition {}", key)?;
+        }
+        for (key, aspects) in &self.modified_transitions {
+            writeln!(f, "~ transition {}: {}", key, aspects.join(", "))?;
+        }
+        if self.script_changed {
+            writeln!(f, "~ script")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the structure of two FSMs. "a" is the old, "b" the new version.
+pub fn fsm_diff(a: &Fsm, b: &Fsm) -> FsmDiff {
+    let mut diff = FsmDiff::default();
+
+    let states_a = states_by_name(a);
+    let states_b = states_by_name(b);
+
+    for (name, state_a) in &states_a {
+        match states_b.get(name) {
+            None => diff.removed_states.push(name.to_string()),
+            Some(state_b) => {
+                let aspects = state_changes(a, state_a, b, state_b);
+                if !aspects.is_empty() {
+                    diff.modified_states.push((name.to_string(), aspects));
+                }
+            }
+        }
+    }
+    for name in states_b.keys() {
+        if !states_a.contains_key(name) {
+            diff.added_states.push(name.to_string());
+        }
+    }
+
+    let transitions_a = transitions_by_key(a);
+    let mut transitions_b = transitions_by_key(b);
+    for (key, list_a) in transitions_a {
+        let list_b = transitions_b.remove(&key).unwrap_or_default();
+        for (idx, transition_a) in list_a.iter().enumerate() {
+            match list_b.get(idx) {
+                None => diff.removed_transitions.push(key.clone()),
+                Some(transition_b) => {
+                    let aspects = transition_changes(a, transition_a, b, transition_b);
+                    if !aspects.is_empty() {
+                        diff.modified_transitions.push((key.clone(), aspects));
+                    }
+                }
+            }
+        }
+        for _ in list_a.len()..list_b.len() {
+            diff.added_transitions.push(key.clone());
+        }
+    }
+    for (key, list_b) in transitions_b {
+        for _ in list_b {
+            diff.added_transitions.push(key.clone());
+        }
+    }
+    diff.added_transitions.sort();
+
+    diff.script_changed = content_to_string(a, a.script) != content_to_string(b, b.script);
+    diff
+}
+
+fn states_by_name(fsm: &Fsm) -> BTreeMap<&str, &State> {
+    fsm.states
+        .iter()
+        .filter(|state| state.id != fsm.pseudo_root)
+        .map(|state| (state.name.as_str(), state))
+        .collect()
+}
+
+fn state_names(fsm: &Fsm, ids: &[StateId]) -> Vec<String> {
+    ids.iter()
+        .map(|sid| fsm.get_state_by_id(*sid).name.clone())
+        .collect()
+}
+
+/// Gets the transitions of all states (besides initial transitions), grouped by key and in document order.
+fn transitions_by_key(fsm: &Fsm) -> BTreeMap<TransitionKey, Vec<&Transition>> {
+    let mut map: BTreeMap<TransitionKey, Vec<&Transition>> = BTreeMap::new();
+    let mut transitions: Vec<&Transition> = fsm.transitions.values().collect();
+    transitions.sort_by_key(|transition| transition.doc_id);
+    let initial: Vec<_> = fsm.states.iter().map(|state| state.initial).collect();
+    for transition in transitions {
+        if initial.contains(&transition.id) {
+            continue;
+        }
+        let key = TransitionKey {
+            source: fsm.get_state_by_id(transition.source).name.clone(),
+            events: transition.events.clone(),
+            target: state_names(fsm, &transition.target),
+        };
+        map.entry(key).or_default().push(transition);
+    }
+    map
+}
+
+fn state_type(state: &State) -> &'static str {
+    if state.history_type != HistoryType::None {
+        "history"
+    } else if state.is_final {
+        "final"
+    } else if state.is_parallel {
+        "parallel"
+    } else {
+        "state"
+    }
+}
+
+fn state_changes(a: &Fsm, state_a: &State, b: &Fsm, state_b: &State) -> Vec<&'static str> {
+    let mut aspects = Vec::new();
+    if state_type(state_a) != state_type(state_b) || state_a.history_type != state_b.history_type {
+        aspects.push("type");
+    }
+    let parent_name = |fsm: &Fsm, state: &State| {
+        if state.parent == fsm.pseudo_root {
+            String::new()
+        } else {
+            fsm.get_state_by_id(state.parent).name.clone()
+        }
+    };
+    if parent_name(a, state_a) != parent_name(b, state_b) {
+        aspects.push("parent");
+    }
+    let initial_target = |fsm: &Fsm, state: &State| {
+        if state.initial == 0 {
+            Vec::new()
+        } else {
+            state_names(fsm, &fsm.get_transition_by_id(state.initial).target)
+        }
+    };
+    if initial_target(a, state_a) != initial_target(b, state_b) {
+        aspects.push("initial");
+    }
+    let contents_to_string = |fsm: &Fsm, ids: &[ExecutableContentId]| {
+        ids.iter()
+            .map(|id| content_to_string(fsm, *id))
+            .collect::<Vec<String>>()
+    };
+    if contents_to_string(a, &state_a.onentry) != contents_to_string(b, &state_b.onentry) {
+        aspects.push("onentry");
+    }
+    if contents_to_string(a, &state_a.onexit) != contents_to_string(b, &state_b.onexit) {
+        aspects.push("onexit");
+    }
+    let data_to_string = |state: &State| {
+        let mut data: Vec<String> = state
+            .data
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value.lock().unwrap()))
+            .collect();
+        data.sort();
+        data
+    };
+    if data_to_string(state_a) != data_to_string(state_b) {
+        aspects.push("data");
+    }
+    aspects
+}
+
+fn transition_changes(a: &Fsm, transition_a: &Transition, b: &Fsm, transition_b: &Transition) -> Vec<&'static str> {
+    let mut aspects = Vec::new();
+    if transition_a.cond.to_string() != transition_b.cond.to_string() {
+        aspects.push("cond");
+    }
+    if transition_a.transition_type != transition_b.transition_type {
+        aspects.push("type");
+    }
+    if content_to_string(a, transition_a.content) != content_to_string(b, transition_b.content) {
+        aspects.push("content");
+    }
+    aspects
+}
+
+/// Prints the executable content to a string, used to compare it.
+fn content_to_string(fsm: &Fsm, content_id: ExecutableContentId) -> String {
+    let mut printer = ContentPrinter::default();
+    printer.print_sub_content("", fsm, content_id);
+    printer.buffer
+}
+
+#[derive(Default)]
+struct ContentPrinter {
+    buffer: String,
+}
+
+impl ExecutableContentTracer for ContentPrinter {
+    fn print_name_and_attributes(&mut self, ec: &dyn ExecutableContent, attrs: &[(&str, &String)]) {
+        let attrs: HashMap<&str, &String> = attrs.iter().cloned().collect();
+        let mut names: Vec<&&str> = attrs.keys().collect();
+        names.sort();
+        self.buffer.push_str(TYPE_NAMES[ec.get_type() as usize]);
+        self.buffer.push('[');
+        for name in names {
+            self.buffer
+                .push_str(format!("{}:{};", name, attrs.get(*name).unwrap()).as_str());
+        }
+        self.buffer.push(']');
+    }
+
+    fn print_sub_content(&mut self, name: &str, fsm: &Fsm, content_id: ExecutableContentId) {
+        self.buffer.push_str(name);
+        self.buffer.push('{');
+        if let Some(vec) = fsm.executableContent.get(&content_id) {
+            for ec in vec {
+                ec.trace(self, fsm);
+            }
+        }
+        self.buffer.push('}');
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "xml")]
+mod tests {
+    use crate::fsm_diff::{fsm_diff, TransitionKey};
+    use crate::scxml_reader;
+
+    #[test]
+    fn reports_added_transition_and_removed_state() {
+        let a = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry><log expr='"hello"'/></onentry>
+                <transition event='go' target='s1'/>
+              </state>
+              <state id='s1'>
+                <transition event='next' target='s2'/>
+              </state>
+              <state id='s2'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let b = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <onentry><log expr='"hello"'/></onentry>
+                <transition event='go' target='s1'/>
+                <transition event='back' target='s0'/>
+              </state>
+              <state id='s1'>
+                <onentry><log expr='"s1"'/></onentry>
+              </state>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+
+        let diff = fsm_diff(&a, &b);
+        assert!(diff.added_states.is_empty());
+        assert_eq!(diff.removed_states, vec!["s2".to_string()]);
+        assert_eq!(
+            diff.modified_states,
+            vec![("s1".to_string(), vec!["onentry"])]
+        );
+        assert_eq!(
+            diff.added_transitions,
+            vec![TransitionKey {
+                source: "s0".to_string(),
+                events: vec!["back".to_string()],
+                target: vec!["s0".to_string()],
+            }]
+        );
+        assert_eq!(
+            diff.removed_transitions,
+            vec![TransitionKey {
+                source: "s1".to_string(),
+                events: vec!["next".to_string()],
+                target: vec!["s2".to_string()],
+            }]
+        );
+        assert!(diff.modified_transitions.is_empty());
+        assert_eq!(
+            diff.to_string(),
+            "- state s2\n~ state s1: onentry\n+ transition s0 -[back]-> s0\n- transition s1 -[next]-> s2\n"
+        );
+
+        assert!(fsm_diff(&a, &a).is_empty());
+    }
+}
@@ -166,9 +166,13 @@ impl FsmExecutor {
         }
     }
 
-    /// Shutdown of all FSMs and IO-Processors.
+    /// Shutdown of all FSMs and IO-Processors.\
+    /// Running sessions are cancelled immediately, see [ScxmlSession::cancel].
     pub fn shutdown(&mut self) {
         let mut guard = self.state.lock().unwrap();
+        for session in guard.sessions.values() {
+            session.cancel();
+        }
         while !guard.processors.is_empty() {
             if let Some(pp) = guard.processors.pop() {
                 pp.lock().unwrap().shutdown();
@@ -235,7 +239,7 @@ impl FsmExecutor {
         }
 
         #[cfg(all(not(feature = "xml"), not(feature = "serializer")))]
-        let sm = Ok(Box::new(Fsm::new()));
+        let mut sm: Result<Box<Fsm>, String> = Ok(Box::new(Fsm::new()));
 
         if let Ok(ref mut fsm) = &mut sm {
             fsm.file = Some(uri.to_string());
@@ -314,12 +318,11 @@ impl FsmExecutor {
         )
     }
 
-    /// Sends some event to a session.
+    /// Sends some event to a session.\
+    /// If the session doesn't exist, the event is returned as error, same as for a terminated session.
     pub fn send_to_session(&self, session_id: SessionId, event: Event) -> Result<(), SendError<Box<Event>>> {
         match self.get_session_sender(session_id) {
-            None => {
-                todo!("Handling of unknown session")
-            }
+            None => Err(SendError(Box::new(event))),
             Some(sender) => sender.send(Box::new(event)),
         }
     }
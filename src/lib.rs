@@ -8,9 +8,15 @@ extern crate core;
 
 pub mod executable_content;
 pub mod fsm;
+pub mod fsm_builder;
+pub mod fsm_diff;
 pub mod fsm_executor;
 #[cfg(feature = "xml")]
+pub mod scxml_conformance;
+#[cfg(feature = "xml")]
 pub mod scxml_reader;
+#[cfg(feature = "xml")]
+pub mod scxml_writer;
 
 #[cfg(feature = "serializer")]
 pub mod serializer;
@@ -23,7 +29,9 @@ pub mod remote_tracer;
 
 pub mod actions;
 pub mod common;
+pub mod compact_fsm;
 pub mod datamodel;
 pub mod event_io_processor;
 pub mod expression_engine;
+pub mod metrics;
 pub mod test;
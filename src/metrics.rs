@@ -0,0 +1,279 @@
+//! Hook to collect runtime metrics of a FSM.\
+//! The FSM calls the methods of [Metrics] while it runs. The default [NoMetrics] does nothing.
+//! [MetricsCounter] counts the calls and can be shared with the application via an Arc:
+//! ```ignore
+//! let counter = Arc::new(MetricsCounter::new());
+//! fsm.metrics = Box::new(counter.clone());
+//! ```
+//! [StateTimings] measures the time spent in the \<onentry\> and \<onexit\> content of each state.
+
+use crate::fsm::StateId;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Callbacks for the FSM. All methods have no-op defaults.
+pub trait Metrics: Send + Debug {
+    /// Called at the end of each macrostep.
+    fn macrostep(&self) {}
+
+    /// Called for each microstep with the number of transitions that are taken.
+    fn microstep(&self, _transitions: usize) {}
+
+    /// Called for each event taken from the internal queue.
+    fn internal_event(&self) {}
+
+    /// Called for each event taken from the external queue.
+    fn external_event(&self) {}
+
+    /// Called if the main event loop ends, with the time spent in the loop,
+    /// including the time waiting for external events.
+    fn event_loop_finished(&self, _duration: Duration) {}
+
+    /// If true, the FSM measures the time spent in the \<onentry\> and \<onexit\> content of states
+    /// and calls [Metrics::state_entered] and [Metrics::state_exited].\
+    /// Checked once per state, so it costs nothing else if false.
+    fn state_timing_enabled(&self) -> bool {
+        false
+    }
+
+    /// Called after a state was entered, with the time spent in its entry content.
+    fn state_entered(&self, _state: StateId, _duration: Duration) {}
+
+    /// Called after a state was exited, with the time spent in its exit content.
+    fn state_exited(&self, _state: StateId, _duration: Duration) {}
+}
+
+/// Metrics that do nothing. Used by default.
+#[derive(Debug, Default)]
+pub struct NoMetrics {}
+
+impl Metrics for NoMetrics {}
+
+/// Metrics that count all calls.
+#[derive(Debug, Default)]
+pub struct MetricsCounter {
+    pub macrosteps: AtomicU64,
+    pub microsteps: AtomicU64,
+    pub transitions: AtomicU64,
+    pub internal_events: AtomicU64,
+    pub external_events: AtomicU64,
+    /// Time spent in the main event loop in microseconds.
+    pub event_loop_micros: AtomicU64,
+}
+
+impl MetricsCounter {
+    pub fn new() -> MetricsCounter {
+        MetricsCounter::default()
+    }
+}
+
+impl Metrics for MetricsCounter {
+    fn macrostep(&self) {
+        self.macrosteps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn microstep(&self, transitions: usize) {
+        self.microsteps.fetch_add(1, Ordering::Relaxed);
+        self.transitions
+            .fetch_add(transitions as u64, Ordering::Relaxed);
+    }
+
+    fn internal_event(&self) {
+        self.internal_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn external_event(&self) {
+        self.external_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn event_loop_finished(&self, duration: Duration) {
+        self.event_loop_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Accumulated entry and exit times of a state.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StateTiming {
+    pub entries: u64,
+    pub entry_time: Duration,
+    pub exits: u64,
+    pub exit_time: Duration,
+}
+
+/// Metrics that aggregate the entry and exit times per state.
+#[derive(Debug, Default)]
+pub struct StateTimings {
+    pub timings: Mutex<HashMap<StateId, StateTiming>>,
+}
+
+impl StateTimings {
+    pub fn new() -> StateTimings {
+        StateTimings::default()
+    }
+
+    /// Gets the timing of a state. Default if the state was never entered.
+    pub fn get(&self, state: StateId) -> StateTiming {
+        self.timings
+            .lock()
+            .unwrap()
+            .get(&state)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl Metrics for StateTimings {
+    fn state_timing_enabled(&self) -> bool {
+        true
+    }
+
+    fn state_entered(&self, state: StateId, duration: Duration) {
+        let mut timings = self.timings.lock().unwrap();
+        let timing = timings.entry(state).or_default();
+        timing.entries += 1;
+        timing.entry_time += duration;
+    }
+
+    fn state_exited(&self, state: StateId, duration: Duration) {
+        let mut timings = self.timings.lock().unwrap();
+        let timing = timings.entry(state).or_default();
+        timing.exits += 1;
+        timing.exit_time += duration;
+    }
+}
+
+/// Allows to share metrics between the FSM and the application.
+impl<T: Metrics + Sync> Metrics for Arc<T> {
+    fn macrostep(&self) {
+        self.as_ref().macrostep()
+    }
+
+    fn microstep(&self, transitions: usize) {
+        self.as_ref().microstep(transitions)
+    }
+
+    fn internal_event(&self) {
+        self.as_ref().internal_event()
+    }
+
+    fn external_event(&self) {
+        self.as_ref().external_event()
+    }
+
+    fn event_loop_finished(&self, duration: Duration) {
+        self.as_ref().event_loop_finished(duration)
+    }
+
+    fn state_timing_enabled(&self) -> bool {
+        self.as_ref().state_timing_enabled()
+    }
+
+    fn state_entered(&self, state: StateId, duration: Duration) {
+        self.as_ref().state_entered(state, duration)
+    }
+
+    fn state_exited(&self, state: StateId, duration: Duration) {
+        self.as_ref().state_exited(state, duration)
+    }
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "ECMAScriptModel", feature = "xml"))]
+mod tests {
+    use crate::fsm::Event;
+    use crate::metrics::{MetricsCounter, StateTimings};
+    use crate::scxml_reader;
+    #[cfg(feature = "Trace")]
+    use crate::tracer::TraceMode;
+    use std::collections::HashMap;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    #[test]
+    fn metrics_count_steps_and_events() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <state id='s0'>
+                <transition event='go' target='s1'/>
+              </state>
+              <state id='s1'>
+                <transition target='s2'><raise event='inner'/></transition>
+              </state>
+              <state id='s2'>
+                <transition event='inner' target='pass'/>
+              </state>
+              <final id='pass'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let counter = Arc::new(MetricsCounter::new());
+        fsm.metrics = Box::new(counter.clone());
+
+        assert!(crate::test::run_test_manual_with_send(
+            "metrics_count_steps_and_events",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+            |sender| {
+                let _ = sender.send(Box::new(Event::new_simple("go")));
+            },
+        ));
+
+        // "go", the eventless transition and "inner".
+        assert_eq!(counter.microsteps.load(Ordering::Relaxed), 3);
+        assert_eq!(counter.transitions.load(Ordering::Relaxed), 3);
+        assert_eq!(counter.internal_events.load(Ordering::Relaxed), 1);
+        assert_eq!(counter.external_events.load(Ordering::Relaxed), 1);
+        // Initial macrostep and the one after "go".
+        assert_eq!(counter.macrosteps.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn state_timings_measure_entry_and_exit_content() {
+        let mut fsm = scxml_reader::parse_from_xml(
+            r#"<scxml initial='slow' datamodel='ecmascript'>
+              <state id='slow'>
+                <onentry><script>var x = 0; for (var i = 0; i &lt; 20000; i++) { x += i; }</script></onentry>
+                <onexit><log expr='"leaving"'/></onexit>
+                <transition target='fast'/>
+              </state>
+              <state id='fast'>
+                <transition target='pass'/>
+              </state>
+              <final id='pass'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let slow = fsm.get_state_by_name(&"slow".to_string()).id;
+        let fast = fsm.get_state_by_name(&"fast".to_string()).id;
+        let timings = Arc::new(StateTimings::new());
+        fsm.metrics = Box::new(timings.clone());
+
+        assert!(crate::test::run_test_manual(
+            "state_timings_measure_entry_and_exit_content",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            TraceMode::STATES,
+            5000,
+            &vec!["pass".to_string()],
+        ));
+
+        let slow_timing = timings.get(slow);
+        assert_eq!(slow_timing.entries, 1);
+        assert_eq!(slow_timing.exits, 1);
+        assert!(!slow_timing.entry_time.is_zero());
+        assert!(slow_timing.entry_time > timings.get(fast).entry_time);
+    }
+}
@@ -0,0 +1,387 @@
+//! Expands the "conf" namespace of the W3C conformance tests (IRP) into standard SCXML.\
+//! The test documents (".txml") are written datamodel-agnostic. They use helper elements and attributes
+//! of the namespace "http://www.w3.org/2005/scxml-conformance" that are mapped to the ECMAScript datamodel,
+//! as the "confEcma.xsl" transformation of the test suite does.\
+//! See [W3C Test README](test/w3c/README.md).
+
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+/// Namespace of the helper elements and attributes.
+pub const CONF_NAMESPACE: &str = "http://www.w3.org/2005/scxml-conformance";
+
+const CONF_PREFIX: &str = "conf:";
+
+/// Replaces all "conf:" elements and attributes of the document by standard SCXML for the
+/// ECMAScript datamodel. Other content is copied unchanged.\
+/// Returns an error if the document can't be read or contains an unknown "conf:" element or attribute.
+pub fn expand_conf(txml: &str) -> Result<String, String> {
+    let mut reader = Reader::from_str(txml);
+    let mut writer = Writer::new(Vec::new());
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Error at position {}: {:?}", reader.buffer_position(), e))?;
+        match event {
+            Event::Eof => break,
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                match name.strip_prefix(CONF_PREFIX) {
+                    Some(conf_name) => {
+                        // The content of helper elements is ignored.
+                        let element = ConfElement::new(conf_name, &e)?;
+                        reader
+                            .read_to_end(e.name())
+                            .map_err(|err| format!("<{}>: {:?}", name, err))?;
+                        element.write(&mut writer)?;
+                    }
+                    None => write_event(&mut writer, Event::Start(expand_attributes(&e)?))?,
+                }
+            }
+            Event::Empty(e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                match name.strip_prefix(CONF_PREFIX) {
+                    Some(conf_name) => ConfElement::new(conf_name, &e)?.write(&mut writer)?,
+                    None => write_event(&mut writer, Event::Empty(expand_attributes(&e)?))?,
+                }
+            }
+            other => write_event(&mut writer, other)?,
+        }
+    }
+    String::from_utf8(writer.into_inner()).map_err(|e| e.to_string())
+}
+
+fn write_event(writer: &mut Writer<Vec<u8>>, event: Event) -> Result<(), String> {
+    writer.write_event(event).map_err(|e| e.to_string())
+}
+
+/// Gets the value of an attribute of a helper element.
+fn get_attribute(e: &BytesStart, name: &str) -> Result<String, String> {
+    match e.try_get_attribute(name) {
+        Ok(Some(attr)) => attr
+            .unescape_value()
+            .map(|v| v.to_string())
+            .map_err(|err| err.to_string()),
+        _ => Err(format!(
+            "<{}>: missing attribute '{}'",
+            String::from_utf8_lossy(e.name().as_ref()),
+            name
+        )),
+    }
+}
+
+/// Copies the element and replaces the "conf:" attributes. The declaration of the namespace is removed.
+fn expand_attributes(e: &BytesStart) -> Result<BytesStart<'static>, String> {
+    let mut expanded = BytesStart::new(String::from_utf8_lossy(e.name().as_ref()).to_string());
+    for attr in e.attributes() {
+        let attr = attr.map_err(|err| err.to_string())?;
+        let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+        let value = attr
+            .unescape_value()
+            .map_err(|err| err.to_string())?
+            .to_string();
+        if key.starts_with("xmlns:") && value == CONF_NAMESPACE {
+            continue;
+        }
+        match key.strip_prefix(CONF_PREFIX) {
+            Some(conf_name) => {
+                let (name, value) = expand_attribute(conf_name, &value)
+                    .ok_or_else(|| format!("Unknown conformance attribute '{}'", key))?;
+                expanded.push_attribute((name, value.as_str()));
+            }
+            None => expanded.push_attribute((key.as_str(), value.as_str())),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Maps a "conf:" attribute to the standard attribute and value.
+fn expand_attribute(name: &str, value: &str) -> Option<(&'static str, String)> {
+    let var = format!("Var{}", value);
+    Some(match name {
+        // Success and failure criteria.
+        "targetpass" => ("target", "pass".to_string()),
+        "targetfail" => ("target", "fail".to_string()),
+        "datamodel" => ("datamodel", "ecmascript".to_string()),
+
+        // Names of variables.
+        "id" => ("id", var),
+        "name" => ("name", var),
+        "location" => ("location", var),
+        "invalidLocation" => ("location", "foo.bar.baz".to_string()),
+        "systemVarLocation" => ("location", value.to_string()),
+        "idlocation" => ("idlocation", var),
+        "namelist" => ("namelist", var),
+        "invalidNamelist" => ("namelist", "\"foo".to_string()),
+        "arrayVar" | "arrayTextVar" => ("array", var),
+        "illegalArray" => ("array", "7".to_string()),
+        "item" => ("item", var),
+        "illegalItem" => ("item", "'continue'".to_string()),
+        "index" => ("index", var),
+
+        // Value expressions.
+        "expr" | "systemVarExpr" => ("expr", value.to_string()),
+        "quoteExpr" => ("expr", format!("'{}'", value)),
+        "varExpr" | "varChildExpr" => ("expr", var),
+        "varNonexistentStruct" => ("expr", format!("{}.bar", var)),
+        "illegalExpr" => ("expr", "return".to_string()),
+        "invalidSessionID" | "invalidSendTypeExpr" => ("expr", "27".to_string()),
+        "eventName" => ("expr", "_event.name".to_string()),
+        "eventType" => ("expr", "_event.type".to_string()),
+        "eventSendid" => ("expr", "_event.sendid".to_string()),
+        "eventRaw" => ("expr", "_event.raw".to_string()),
+        "eventField" => ("expr", format!("_event.{}", value)),
+        "eventDataFieldValue" | "eventDataParamValue" => ("expr", format!("_event.data.{}", value)),
+        "eventDataNamelistValue" => ("expr", format!("_event.data.{}", var)),
+        "scxmlEventIOLocation" => (
+            "expr",
+            "_ioprocessors['http://www.w3.org/TR/scxml/#SCXMLEventProcessor'].location".to_string(),
+        ),
+
+        // Attributes of <send>, <invoke> and <cancel>.
+        "targetVar" | "targetExpr" => ("targetexpr", var),
+        "eventExpr" => ("eventexpr", var),
+        "typeExpr" => ("typeexpr", var),
+        "delayFromVar" => ("delayexpr", var),
+        "delay" => ("delayexpr", format!("'{}s'", value)),
+        "sendIDExpr" => ("sendidexpr", var),
+        "srcExpr" => ("srcexpr", var),
+        "illegalTarget" => ("target", "baz".to_string()),
+        "unreachableTarget" => ("target", "#_scxml_foo".to_string()),
+        "invalidSendType" => ("type", "27".to_string()),
+
+        // Conditions.
+        "idVal" | "namelistIdVal" | "idSystemVarVal" => {
+            let (id, op, rest) = split_comparison(value)?;
+            ("cond", format!("Var{} {} {}", id, op, rest))
+        }
+        "varIdVal" | "compareIDVal" => {
+            let (id, op, rest) = split_comparison(value)?;
+            ("cond", format!("Var{} {} Var{}", id, op, rest))
+        }
+        "idQuoteVal" => {
+            let (id, op, rest) = split_comparison(value)?;
+            ("cond", format!("Var{} {} '{}'", id, op, rest))
+        }
+        "eventvarVal" => {
+            let (id, op, rest) = split_comparison(value)?;
+            ("cond", format!("_event.data.Var{} {} {}", id, op, rest))
+        }
+        "VarEqVar" => {
+            let (id1, id2) = split_pair(value)?;
+            ("cond", format!("Var{} == Var{}", id1, id2))
+        }
+        "VarEqVarStruct" => {
+            let (id1, id2) = split_pair(value)?;
+            (
+                "cond",
+                format!("JSON.stringify(Var{}) == JSON.stringify(Var{})", id1, id2),
+            )
+        }
+        "varPrefix" => {
+            let (id1, id2) = split_pair(value)?;
+            ("cond", format!("Var{}.indexOf(Var{}) == 0", id2, id1))
+        }
+        "idSomeVal" => ("cond", format!("{} == 123", var)),
+        "eventNameVal" => ("cond", format!("_event.name == '{}'", value)),
+        "eventdataVal" => ("cond", format!("_event.data == {}", value)),
+        "eventdataSomeVal" => ("cond", "_event.data == 123".to_string()),
+        "emptyEventData" => ("cond", "typeof _event.data === 'undefined'".to_string()),
+        "nameVarVal" => ("cond", format!("_name == '{}'", value)),
+        "inState" => ("cond", format!("In('{}')", value)),
+        "nonBoolean" => ("cond", "return".to_string()),
+        "isBound" => ("cond", format!("typeof {} !== 'undefined'", var)),
+        "unboundVar" | "noValue" => ("cond", format!("{} === undefined", var)),
+        "systemVarIsBound" => ("cond", format!("typeof {} !== 'undefined'", value)),
+        "true" => ("cond", "true".to_string()),
+        "false" => ("cond", "false".to_string()),
+        "eventFieldsAreBound" => (
+            "cond",
+            [
+                "name",
+                "type",
+                "sendid",
+                "origin",
+                "origintype",
+                "invokeid",
+                "data",
+            ]
+            .iter()
+            .map(|field| format!("'{}' in _event", field))
+            .collect::<Vec<String>>()
+            .join(" && "),
+        ),
+        "eventDataHasField" => ("cond", format!("'{}' in _event.data", value)),
+        "eventFieldHasNoValue" => ("cond", format!("_event.{} == null", value)),
+        "eventLanguageMatchesDatamodel" => ("cond", "_event.language == 'ecmascript'".to_string()),
+        "originTypeEq" => ("cond", format!("_event.origintype == '{}'", value)),
+        _ => return None,
+    })
+}
+
+/// Splits comparisons like "1=2" or "3<=Var4" into id, ECMAScript operator and the rest.
+fn split_comparison(value: &str) -> Option<(&str, &str, &str)> {
+    let value = value.trim();
+    let id_end = value.find(|c: char| !c.is_ascii_digit())?;
+    if id_end == 0 {
+        return None;
+    }
+    let rest = &value[id_end..];
+    let op_end = rest
+        .find(|c: char| !matches!(c, '=' | '<' | '>'))
+        .unwrap_or(rest.len());
+    let op = match &rest[..op_end] {
+        "=" | "==" => "==",
+        op @ ("<" | ">" | "<=" | ">=") => op,
+        _ => return None,
+    };
+    Some((&value[..id_end], op, rest[op_end..].trim()))
+}
+
+/// Splits pairs of ids, like "1 2" or "1,2".
+fn split_pair(value: &str) -> Option<(&str, &str)> {
+    let mut ids = value
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|id| !id.is_empty());
+    match (ids.next(), ids.next()) {
+        (Some(id1), Some(id2)) => Some((id1, id2)),
+        _ => None,
+    }
+}
+
+/// Replacement of a "conf:" element.
+enum ConfElement {
+    /// An element with attributes and optional text content.
+    Element(&'static str, Vec<(&'static str, String)>, Option<String>),
+    /// A final state that logs the outcome.
+    Final(&'static str),
+    Text(&'static str),
+}
+
+impl ConfElement {
+    fn new(name: &str, e: &BytesStart) -> Result<ConfElement, String> {
+        Ok(match name {
+            "pass" => ConfElement::Final("pass"),
+            "fail" => ConfElement::Final("fail"),
+            "incrementID" => {
+                let var = format!("Var{}", get_attribute(e, "id")?);
+                ConfElement::Element(
+                    "assign",
+                    vec![("location", var.clone()), ("expr", format!("{} + 1", var))],
+                    None,
+                )
+            }
+            "concatVars" | "sumVars" => {
+                let var1 = format!("Var{}", get_attribute(e, "id1")?);
+                let var2 = format!("Var{}", get_attribute(e, "id2")?);
+                ConfElement::Element(
+                    "assign",
+                    vec![
+                        ("location", var1.clone()),
+                        ("expr", format!("{} + {}", var1, var2)),
+                    ],
+                    None,
+                )
+            }
+            "extendArray" => {
+                let var = format!("Var{}", get_attribute(e, "id")?);
+                ConfElement::Element(
+                    "assign",
+                    vec![
+                        ("location", var.clone()),
+                        ("expr", format!("[].concat({}, [4])", var)),
+                    ],
+                    None,
+                )
+            }
+            "sendToSender" => ConfElement::Element(
+                "send",
+                vec![
+                    ("event", get_attribute(e, "name")?),
+                    ("targetexpr", "_event.origin".to_string()),
+                    ("typeexpr", "_event.origintype".to_string()),
+                ],
+                None,
+            ),
+            "script" => ConfElement::Element("script", Vec::new(), Some("var Var1 = 1".to_string())),
+            "illegalContent" => ConfElement::Element("content", Vec::new(), Some("xyz".to_string())),
+            "contentFoo" => ConfElement::Element("content", Vec::new(), Some("foo".to_string())),
+            "someInlineVal" => ConfElement::Text("123"),
+            "array123" => ConfElement::Text("[1,2,3]"),
+            _ => {
+                return Err(format!(
+                    "Unknown conformance element <{}{}>",
+                    CONF_PREFIX, name
+                ))
+            }
+        })
+    }
+
+    fn write(&self, writer: &mut Writer<Vec<u8>>) -> Result<(), String> {
+        match self {
+            ConfElement::Element(name, attributes, text) => {
+                let mut start = BytesStart::new(*name);
+                for (key, value) in attributes {
+                    start.push_attribute((*key, value.as_str()));
+                }
+                match text {
+                    None => write_event(writer, Event::Empty(start)),
+                    Some(text) => {
+                        write_event(writer, Event::Start(start))?;
+                        write_event(writer, Event::Text(BytesText::new(text)))?;
+                        write_event(writer, Event::End(BytesEnd::new(*name)))
+                    }
+                }
+            }
+            ConfElement::Final(id) => {
+                let mut final_state = BytesStart::new("final");
+                final_state.push_attribute(("id", *id));
+                let mut log = BytesStart::new("log");
+                log.push_attribute(("label", "Outcome"));
+                log.push_attribute(("expr", format!("'{}'", id).as_str()));
+                write_event(writer, Event::Start(final_state))?;
+                write_event(writer, Event::Start(BytesStart::new("onentry")))?;
+                write_event(writer, Event::Empty(log))?;
+                write_event(writer, Event::End(BytesEnd::new("onentry")))?;
+                write_event(writer, Event::End(BytesEnd::new("final")))
+            }
+            ConfElement::Text(text) => write_event(writer, Event::Text(BytesText::new(text))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scxml_conformance::expand_conf;
+
+    #[test]
+    fn expands_conf_namespace() {
+        let txml = r#"<scxml initial="s0" version="1.0" conf:datamodel="" xmlns="http://www.w3.org/2005/07/scxml" xmlns:conf="http://www.w3.org/2005/scxml-conformance">
+<datamodel><data conf:id="1" conf:expr="0"/><data conf:id="2"><conf:someInlineVal/></data></datamodel>
+<state id="s0">
+<onentry><conf:incrementID id="1"/><send event="timeout" conf:delay="1"/></onentry>
+<transition conf:idVal="1=1" conf:targetpass=""/>
+<transition conf:VarEqVar="1 2" event="*" conf:targetfail=""/>
+</state>
+<conf:pass/>
+<conf:fail/>
+</scxml>"#;
+        let expected = r#"<scxml initial="s0" version="1.0" datamodel="ecmascript" xmlns="http://www.w3.org/2005/07/scxml">
+<datamodel><data id="Var1" expr="0"/><data id="Var2">123</data></datamodel>
+<state id="s0">
+<onentry><assign location="Var1" expr="Var1 + 1"/><send event="timeout" delayexpr="&apos;1s&apos;"/></onentry>
+<transition cond="Var1 == 1" target="pass"/>
+<transition cond="Var1 == Var2" event="*" target="fail"/>
+</state>
+<final id="pass"><onentry><log label="Outcome" expr="&apos;pass&apos;"/></onentry></final>
+<final id="fail"><onentry><log label="Outcome" expr="&apos;fail&apos;"/></onentry></final>
+</scxml>"#;
+        assert_eq!(expand_conf(txml).unwrap(), expected);
+
+        let fsm = crate::scxml_reader::parse_from_xml(expand_conf(txml).unwrap()).unwrap();
+        assert_eq!(fsm.datamodel, "ecmascript");
+
+        assert!(expand_conf("<scxml><conf:unknown/></scxml>").is_err());
+        assert!(expand_conf("<scxml conf:unknown=''/>").is_err());
+    }
+}
@@ -9,38 +9,60 @@ use std::path::{Path, PathBuf};
 #[cfg(feature = "Debug_Reader")]
 use crate::common::debug;
 
+use std::panic::AssertUnwindSafe;
 use std::str::FromStr;
-use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{env, mem, str, string::String};
 
 use crate::common::info;
+use crate::common::warn;
 use crate::common::ArgOption;
-use crate::datamodel::{create_data_arc, Data, SourceCode};
+use crate::datamodel::{create_data_arc, Data, SourceCode, SourceId};
+use lazy_static::lazy_static;
+use quick_xml::errors::IllFormedError;
 use quick_xml::events::attributes::Attributes;
 use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader;
 use url::Url;
 
+use crate::event_io_processor::scxml_event_io_processor::is_internal_target;
 use crate::executable_content::{
     get_opt_executable_content_as, get_safe_executable_content_as, parse_duration_to_milliseconds, Assign, Cancel,
     ExecutableContent, Expression, ForEach, If, Log, Raise, SendParameters,
 };
 use crate::fsm::push_param;
-#[cfg(feature = "Debug_Reader")]
-use crate::fsm::vec_to_string;
 use crate::fsm::{
-    map_history_type, map_transition_type, BindingType, DoneData, ExecutableContentId, Fsm, HistoryType, Invoke,
-    Parameter, State, StateId, Transition, TransitionId, TransitionType, ID_COUNTER,
+    map_history_type, map_transition_type, BindingType, DocumentId, DoneData, ExecutableContentId,
+    ExecutableContentMap, Fsm, HistoryType, Invoke, List, Parameter, State, StateId, Transition, TransitionId,
+    TransitionType,
 };
 
 use crate::fsm::CommonContent;
 
 pub type AttributeMap = HashMap<String, String>;
-pub type XReader<'a> = Reader<&'a [u8]>;
 
-static DOC_ID_COUNTER: AtomicU32 = AtomicU32::new(1);
-static SOURCE_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
+/// Generates the names of states without "id".\
+/// Gets the number of the include the state is defined in (0 for the main document, includes are numbered in
+/// document order starting at 1) and the number of the anonymous state inside this document (starting at 1).
+pub type NameGenerator = fn(include: usize, count: u32) -> String;
+
+/// The default [NameGenerator]: "__id\<count\>" in the main document, "__inc\<include\>_id\<count\>" in
+/// included documents.\
+/// As each document is counted separately, the names don't change if anonymous states are added to some
+/// other document.
+pub fn default_name_generator(include: usize, count: u32) -> String {
+    if include == 0 {
+        format!("__id{}", count)
+    } else {
+        format!("__inc{}_id{}", include, count)
+    }
+}
+
+/// Maximal nesting depth of \<include\> elements.
+pub const MAX_INCLUDE_DEPTH: usize = 32;
+
+pub type XReader<'a> = Reader<&'a [u8]>;
 
 pub static INCLUDE_PATH_ARGUMENT_OPTION: ArgOption = ArgOption {
     name: "includePaths",
@@ -87,6 +109,8 @@ pub const ATTR_DATAMODEL: &str = "datamodel";
 pub const TAG_DATAMODEL: &str = "datamodel";
 pub const TAG_DATA: &str = "data";
 pub const TAG_VERSION: &str = "version";
+/// The only SCXML version supported.
+pub const SCXML_VERSION: &str = "1.0";
 pub const TAG_INITIAL: &str = "initial";
 pub const ATTR_ID: &str = "id";
 
@@ -236,6 +260,273 @@ pub const ATTR_EXPR: &str = "expr";
 
 pub const NS_XINCLUDE: &str = "http://www.w3.org/2001/XInclude";
 
+/// Elements known by the reader.
+const KNOWN_TAGS: [&str; 27] = [
+    TAG_SCXML,
+    TAG_DATAMODEL,
+    TAG_DATA,
+    TAG_INITIAL,
+    TAG_STATE,
+    TAG_HISTORY,
+    TAG_PARALLEL,
+    TAG_FINAL,
+    TAG_TRANSITION,
+    TAG_ON_ENTRY,
+    TAG_ON_EXIT,
+    TAG_INVOKE,
+    TAG_FINALIZE,
+    TAG_DONEDATA,
+    TAG_INCLUDE,
+    TAG_RAISE,
+    TAG_SEND,
+    TAG_PARAM,
+    TAG_CONTENT,
+    TAG_LOG,
+    TAG_SCRIPT,
+    TAG_ASSIGN,
+    TAG_IF,
+    TAG_FOR_EACH,
+    TAG_CANCEL,
+    TAG_ELSE,
+    TAG_ELSEIF,
+];
+
+/// Attributes known by the reader.
+const KNOWN_ATTRIBUTES: [&str; 31] = [
+    ATTR_NAME,
+    ATTR_BINDING,
+    ATTR_DATAMODEL,
+    TAG_VERSION,
+    ATTR_ID,
+    ATTR_INITIAL,
+    ATTR_COND,
+    TAG_EVENT,
+    TAG_TYPE,
+    ATTR_SRCEXPR,
+    ATTR_AUTOFORWARD,
+    TAG_HREF,
+    ATTR_PARSE,
+    ATTR_XPOINTER,
+    ATTR_EVENTEXPR,
+    ATTR_TARGET,
+    ATTR_TARGETEXPR,
+    ATTR_TYPEEXPR,
+    ATTR_IDLOCATION,
+    ATTR_DELAY,
+    ATTR_DELAYEXPR,
+    ATTR_NAMELIST,
+    ATTR_SRC,
+    ATTR_LOCATION,
+    ATTR_ARRAY,
+    ATTR_ITEM,
+    ATTR_INDEX,
+    ATTR_SENDIDEXPR,
+    ATTR_SENDID,
+    ATTR_LABEL,
+    ATTR_EXPR,
+];
+
+/// Resolves and loads external resources that are referenced by a document,
+/// e.g. by \<data src\>, \<script src\> or XInclude.\
+/// Embedders can provide their own implementation to parse documents without access to
+/// file system or network.
+pub trait ResourceResolver: Send {
+    /// Loads the resource.
+    /// # Arguments
+    /// * `uri`           - The URI or path as given in the document.
+    /// * `current_file`  - The file of the current document, base for relative paths.
+    /// * `include_paths` - Additional paths to search for relative paths.
+    ///
+    /// Returns the resolved file (used as base for further relative references) and the content.
+    fn load(&self, uri: &str, current_file: &Path, include_paths: &[PathBuf]) -> Result<(PathBuf, String), String>;
+}
+
+/// Handler for a custom URI scheme. Gets the URI as given in the document and returns the content.
+pub type UriSchemeHandler = Arc<dyn Fn(&str) -> Result<String, String> + Send + Sync>;
+
+lazy_static! {
+    static ref uri_scheme_handlers: Mutex<HashMap<String, UriSchemeHandler>> = Mutex::new(HashMap::new());
+}
+
+/// Registers a handler for a URI scheme, e.g. "mem" for "mem://foo".\
+/// The scheme is case-insensitive. Resources with a registered scheme are loaded by the handler,
+/// independent of the [ResourceResolver] of the reader. A handler for "file" or "http" replaces the default.
+pub fn register_uri_scheme(scheme: &str, handler: UriSchemeHandler) {
+    uri_scheme_handlers
+        .lock()
+        .unwrap()
+        .insert(scheme.to_lowercase(), handler);
+}
+
+/// Removes the handler for a URI scheme, see [register_uri_scheme].\
+/// Returns the removed handler.
+pub fn unregister_uri_scheme(scheme: &str) -> Option<UriSchemeHandler> {
+    uri_scheme_handlers
+        .lock()
+        .unwrap()
+        .remove(&scheme.to_lowercase())
+}
+
+fn get_uri_scheme_handler(uri: &str) -> Option<UriSchemeHandler> {
+    match Url::parse(uri) {
+        Ok(url) => uri_scheme_handlers
+            .lock()
+            .unwrap()
+            .get(&url.scheme().to_ascii_lowercase())
+            .cloned(),
+        Err(_) => None,
+    }
+}
+
+/// Default timeout to download a resource via http.
+pub const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default resolver. Loads resources from file system or via http.
+pub struct DefaultResourceResolver {
+    /// Timeout to download a resource via http, see [DEFAULT_FETCH_TIMEOUT].
+    pub timeout: Duration,
+}
+
+impl Default for DefaultResourceResolver {
+    fn default() -> Self {
+        DefaultResourceResolver::new()
+    }
+}
+
+impl DefaultResourceResolver {
+    pub fn new() -> DefaultResourceResolver {
+        DefaultResourceResolver::with_timeout(DEFAULT_FETCH_TIMEOUT)
+    }
+
+    pub fn with_timeout(timeout: Duration) -> DefaultResourceResolver {
+        DefaultResourceResolver { timeout }
+    }
+
+    /// Try to resolve the file name relative to the current file or include paths.
+    fn get_resolved_path(ps: &str, current_file: &Path, include_paths: &[PathBuf]) -> Result<PathBuf, String> {
+        let mut ps = ps.to_string();
+        while ps.starts_with('\\') || ps.starts_with('/') {
+            ps.remove(0);
+        }
+        let src = Path::new(&ps);
+
+        let to_current = {
+            let parent = current_file.parent();
+            match parent {
+                Some(parent_path) => {
+                    let pp = parent_path.join(src);
+                    pp.to_owned()
+                }
+                None => src.to_owned(),
+            }
+        };
+        if to_current.exists() {
+            return Ok(to_current);
+        } else {
+            for ip in include_paths {
+                let rp = ip.join(src);
+                if rp.exists() {
+                    return Ok(rp);
+                }
+            }
+        }
+        Err(format!("Can not resolve {:?}", ps))
+    }
+
+    fn read_from_relative_path(
+        path: &str,
+        current_file: &Path,
+        include_paths: &[PathBuf],
+    ) -> Result<(PathBuf, String), String> {
+        let file_src = Self::get_resolved_path(path, current_file, include_paths)?;
+        match File::open(file_src.clone()) {
+            Ok(mut file) => {
+                let mut buf = String::with_capacity(file.metadata().unwrap().len() as usize);
+                match file.read_to_string(&mut buf) {
+                    Ok(_r) => Ok((file_src, buf)),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            Err(e) => Err(format!(
+                "Failed to load relative path {:?}: {}",
+                file_src, e
+            )),
+        }
+    }
+}
+
+impl ResourceResolver for DefaultResourceResolver {
+    fn load(&self, uri: &str, current_file: &Path, include_paths: &[PathBuf]) -> Result<(PathBuf, String), String> {
+        let url_result = Url::parse(uri);
+        match url_result {
+            Ok(url) => match url.scheme().to_ascii_lowercase().as_str() {
+                "file" => Self::read_from_relative_path(url.path(), current_file, include_paths),
+                &_ => {
+                    #[cfg(feature = "Debug_Reader")]
+                    debug!("read from URL {}", url);
+                    let resp = ureq::AgentBuilder::new()
+                        .timeout(self.timeout)
+                        .build()
+                        .get(uri)
+                        .call();
+                    match resp {
+                        Ok(r) => match r.status() {
+                            200..=299 => match r.into_string() {
+                                Ok(content) => Ok((current_file.to_path_buf(), content)),
+                                Err(err) => Err(format!("Failed to load from {}. {}", uri, err)),
+                            },
+                            _ => Err(format!(
+                                "Failed to load from {}. Status {} {}",
+                                uri,
+                                r.status(),
+                                r.status_text()
+                            )),
+                        },
+                        Err(e) => Err(format!("Failed to download {}. {}", uri, e)),
+                    }
+                }
+            },
+            Err(_e) => {
+                #[cfg(feature = "Debug_Reader")]
+                debug!(
+                    "{} is not a URI ({}). Try loading as relative path...",
+                    uri, _e
+                );
+                Self::read_from_relative_path(uri, current_file, include_paths)
+            }
+        }
+    }
+}
+
+/// Resolver that serves resources from memory, e.g. for tests or sandboxed environments.\
+/// The URI or path is used as given in the document, nothing is loaded from file system or network.
+#[derive(Default)]
+pub struct MapResourceResolver {
+    pub resources: HashMap<String, String>,
+}
+
+impl MapResourceResolver {
+    pub fn new() -> MapResourceResolver {
+        MapResourceResolver {
+            resources: HashMap::new(),
+        }
+    }
+
+    /// Adds a resource.
+    pub fn add(&mut self, uri: &str, content: &str) {
+        self.resources.insert(uri.to_string(), content.to_string());
+    }
+}
+
+impl ResourceResolver for MapResourceResolver {
+    fn load(&self, uri: &str, current_file: &Path, _include_paths: &[PathBuf]) -> Result<(PathBuf, String), String> {
+        match self.resources.get(uri) {
+            Some(content) => Ok((current_file.to_path_buf(), content.clone())),
+            None => Err(format!("Resource '{}' not available", uri)),
+        }
+    }
+}
+
 struct ReaderStackItem {
     current_state: StateId,
     current_transition: TransitionId,
@@ -255,7 +546,13 @@ impl ReaderStackItem {
 struct ReaderState {
     // True if reader in inside an scxml element
     in_scxml: bool,
-    id_count: i32,
+    // Number of anonymous states in the current document.
+    id_count: u32,
+    // Number of the current include, 0 for the main document.
+    current_include: usize,
+    // Number of includes read so far.
+    include_count: usize,
+    name_generator: NameGenerator,
     file: PathBuf,
     content: String,
 
@@ -267,6 +564,25 @@ struct ReaderState {
     executable_content_stack: Vec<(ExecutableContentId, &'static str)>,
     current_executable_content: ExecutableContentId,
     include_paths: Vec<PathBuf>,
+    resolver: Box<dyn ResourceResolver>,
+    // If true, deviations from the W3C recommendation are reported as errors instead of warnings.
+    strict: bool,
+    // If true, common malformations are repaired. See [parse_from_xml_tolerant].
+    tolerant: bool,
+    // Repairs done in tolerant mode.
+    diagnostics: Vec<String>,
+    // Last document id. Document ids are assigned per document, starting at 1.
+    doc_id_count: DocumentId,
+    // Last id of transitions and executable content regions, also assigned per document.
+    content_id_count: u32,
+    // Last id of source code, also assigned per document.
+    source_id_count: usize,
+    // If true, sources get no id and are not cached by the datamodels. See [reparse_state].
+    uncached_sources: bool,
+    // Targets of transitions (and "initial" attributes) by name. Resolved after all states are declared.
+    pending_targets: Vec<(TransitionId, String)>,
+    // Documents that are currently included, to detect cycles.
+    include_stack: Vec<String>,
 }
 
 impl ReaderState {
@@ -274,6 +590,15 @@ impl ReaderState {
         ReaderState {
             in_scxml: false,
             id_count: 0,
+            current_include: 0,
+            include_count: 0,
+            name_generator: default_name_generator,
+            pending_targets: Vec::new(),
+            include_stack: Vec::new(),
+            doc_id_count: 0,
+            content_id_count: 0,
+            source_id_count: 0,
+            uncached_sources: false,
             stack: vec![],
             executable_content_stack: vec![],
             current_executable_content: 0,
@@ -286,6 +611,10 @@ impl ReaderState {
             file: Path::new("Buffer").to_path_buf(),
             content: "".to_string(),
             include_paths: Vec::new(),
+            resolver: Box::new(DefaultResourceResolver::new()),
+            strict: false,
+            tolerant: false,
+            diagnostics: Vec::new(),
         }
     }
 
@@ -294,17 +623,21 @@ impl ReaderState {
     fn process_file(&mut self, file: &Path) -> Result<&str, String> {
         self.file.clone_from(&file.to_path_buf());
         match File::open(self.file.clone()) {
-            Ok(mut f) => {
-                self.content.clear();
-                match f.read_to_string(&mut self.content) {
-                    Ok(_len) => self.process(),
-                    Err(e) => Err(format!("Failed to read {:?}. {}", self.file, e)),
-                }
-            }
+            Ok(mut f) => self.process_reader(&mut f),
             Err(e) => Err(format!("Failed to open {:?}. {}", self.file, e)),
         }
     }
 
+    /// Process a XML stream.
+    /// The stream is read completely into the temporary buffer.
+    fn process_reader(&mut self, source: &mut dyn Read) -> Result<&str, String> {
+        self.content.clear();
+        match source.read_to_string(&mut self.content) {
+            Ok(_len) => self.process(),
+            Err(e) => Err(format!("Failed to read {:?}. {}", self.file, e)),
+        }
+    }
+
     /// Process all events from current content
     fn process(&mut self) -> Result<&str, String> {
         #[cfg(feature = "Debug_Reader")]
@@ -316,10 +649,19 @@ impl ReaderState {
         let ct = self.content.clone();
         let mut reader = Reader::from_str(ct.as_str());
         reader.config_mut().trim_text(true);
+        if self.tolerant {
+            // Mismatched end-tags are handled in end_element.
+            reader.config_mut().check_end_names = false;
+        }
 
         let mut txt = Vec::new();
         loop {
             match reader.read_event() {
+                Err(quick_xml::Error::IllFormed(IllFormedError::UnmatchedEndTag(name))) if self.tolerant => {
+                    // The reader has no open element left, e.g. because of previous stray end-tags.
+                    let name = self.tolerant_tag_name(&name);
+                    self.end_element(&name);
+                }
                 Err(e) => {
                     #[cfg(feature = "Debug_Reader")]
 
@@ -330,17 +672,36 @@ impl ReaderState {
                         e
                     ));
                 }
-                Ok(Event::Eof) => break,
+                Ok(Event::Eof) => {
+                    if self.tolerant {
+                        self.close_open_elements();
+                    }
+                    break;
+                }
                 Ok(Event::Start(e)) => {
-                    self.start_element(&mut reader, &e, true);
+                    if let Err(err) = self.start_element(&mut reader, &e, true) {
+                        return Err(format!(
+                            "Error at position {}: {}",
+                            reader.buffer_position(),
+                            err
+                        ));
+                    }
                 }
                 Ok(Event::End(e)) => {
-                    self.end_element(str::from_utf8(e.local_name().as_ref()).unwrap());
+                    let name = self.tolerant_tag_name(str::from_utf8(e.local_name().as_ref()).unwrap());
+                    self.end_element(&name);
                 }
                 Ok(Event::Empty(e)) => {
                     // Element without content.
-                    self.start_element(&mut reader, &e, false);
-                    self.end_element(str::from_utf8(e.local_name().as_ref()).unwrap());
+                    if let Err(err) = self.start_element(&mut reader, &e, false) {
+                        return Err(format!(
+                            "Error at position {}: {}",
+                            reader.buffer_position(),
+                            err
+                        ));
+                    }
+                    let name = self.tolerant_tag_name(str::from_utf8(e.local_name().as_ref()).unwrap());
+                    self.end_element(&name);
                 }
                 Ok(Event::Text(e)) => txt.push(e.unescape().unwrap().into_owned()),
                 Ok(Event::Comment(_e)) => {
@@ -355,6 +716,10 @@ impl ReaderState {
                 }
             }
         }
+        if self.current_include == 0 {
+            // Includes are resolved with the including document.
+            self.resolve_targets()?;
+        }
         #[cfg(feature = "Debug_Reader")]
 
         debug!("<<< {:?}", self.file);
@@ -373,23 +738,45 @@ impl ReaderState {
         }
     }
 
+    fn next_doc_id(&mut self) -> DocumentId {
+        self.doc_id_count = self
+            .doc_id_count
+            .checked_add(1)
+            .unwrap_or_else(|| panic!("Document ids exhausted"));
+        self.doc_id_count
+    }
+
+    /// Next id for a transition or an executable content region.
+    fn next_id(&mut self) -> u32 {
+        self.content_id_count = self
+            .content_id_count
+            .checked_add(1)
+            .unwrap_or_else(|| panic!("Transition and content ids exhausted"));
+        self.content_id_count
+    }
+
     fn generate_name(&mut self) -> String {
         self.id_count += 1;
-        format!("__id{}", self.id_count)
+        (self.name_generator)(self.current_include, self.id_count)
+    }
+
+    fn next_source_id(&mut self) -> SourceId {
+        if self.uncached_sources {
+            0
+        } else {
+            self.source_id_count += 1;
+            self.source_id_count
+        }
     }
 
     fn create_source(&mut self, src: &str) -> Data {
-        Data::Source(SourceCode::new(
-            src,
-            SOURCE_ID_COUNTER.fetch_add(1, Ordering::Relaxed),
-        ))
+        let source_id = self.next_source_id();
+        Data::Source(SourceCode::new(src, source_id))
     }
 
     fn create_source_moved(&mut self, src: String) -> Data {
-        Data::Source(SourceCode::new_move(
-            src,
-            SOURCE_ID_COUNTER.fetch_add(1, Ordering::Relaxed),
-        ))
+        let source_id = self.next_source_id();
+        Data::Source(SourceCode::new_move(src, source_id))
     }
 
     fn parse_location_expressions(&mut self, location_expr: &str, targets: &mut Vec<String>) {
@@ -398,10 +785,35 @@ impl ReaderState {
         });
     }
 
-    fn parse_state_specification(&mut self, target_name: &str, targets: &mut Vec<StateId>) {
+    /// Stores the target names of a transition. The names are resolved by [ReaderState::resolve_targets],
+    /// as the states may be declared after the transition.
+    fn parse_state_specification(&mut self, target_name: &str, transition: TransitionId) {
         target_name
             .split_ascii_whitespace()
-            .for_each(|target| targets.push(self.get_or_create_state(target, false)));
+            .for_each(|target| self.pending_targets.push((transition, target.to_string())));
+    }
+
+    /// Resolves the target names of all transitions, after the document was read.
+    /// Returns an error for names of states that are not declared.
+    fn resolve_targets(&mut self) -> Result<(), String> {
+        for (transition_id, target_name) in mem::take(&mut self.pending_targets) {
+            let target = match self.fsm.statesNames.get(&target_name) {
+                Some(sid) => *sid,
+                None => {
+                    let source = self.fsm.get_transition_by_id(transition_id).source;
+                    return Err(format!(
+                        "Unknown target state '{}' of transition in state '{}'",
+                        target_name,
+                        self.fsm.get_state_by_id(source).name
+                    ));
+                }
+            };
+            self.fsm
+                .get_transition_by_id_mut(transition_id)
+                .target
+                .push(target);
+        }
+        Ok(())
     }
 
     fn parse_boolean(&mut self, value: &Option<&String>, default: bool) -> bool {
@@ -453,25 +865,32 @@ impl ReaderState {
         } else {
             self.executable_content_stack.clear();
         }
-        self.current_executable_content = ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+        self.current_executable_content = self.next_id();
         #[cfg(feature = "Debug_Reader")]
 
         debug!(
             " start executable content region #{}",
             self.current_executable_content
         );
-        self.fsm
-            .executableContent
-            .insert(self.current_executable_content, Vec::new());
+        let ec_id = self.current_executable_content;
+        self.executable_content().insert(ec_id, Vec::new());
         self.current_executable_content
     }
 
+    /// The executable content of the FSM under construction.\
+    /// Content is only shared by [Fsm::instantiate], the reader has exclusive access.
+    fn executable_content(&mut self) -> &mut ExecutableContentMap {
+        self.fsm
+            .executable_content_mut()
+            .expect("Executable content of the reader is shared")
+    }
+
     /// Get the last entry for the current content region.
     fn get_last_executable_content_entry_for_region(
         &mut self,
         ec_id: ExecutableContentId,
     ) -> Option<&mut dyn ExecutableContent> {
-        let v = self.fsm.executableContent.get_mut(&ec_id);
+        let v = self.executable_content().get_mut(&ec_id);
         match v {
             Some(vc) => Some(vc.last_mut().unwrap().as_mut()),
             None => None,
@@ -523,11 +942,8 @@ impl ReaderState {
                 ec.get_type(),
                 self.current_executable_content
             );
-            self.fsm
-                .executableContent
-                .get_mut(&self.current_executable_content)
-                .unwrap()
-                .push(ec);
+            let ec_id = self.current_executable_content;
+            self.executable_content().get_mut(&ec_id).unwrap().push(ec);
         }
     }
 
@@ -605,30 +1021,25 @@ impl ReaderState {
             None => initial = 0,
             Some(id_refs) => {
                 // Create initial-transition with the initial states
-                let mut t = Transition::new();
-                t.doc_id = DOC_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+                let mut t = Transition::with_id(self.next_id());
+                t.doc_id = self.next_doc_id();
                 t.transition_type = TransitionType::Internal;
                 t.source = id;
                 initial = t.id;
-                self.parse_state_specification(id_refs, &mut t.target);
+                self.parse_state_specification(id_refs, t.id);
                 #[cfg(feature = "Debug_Reader")]
 
-                debug!(
-                    " {}#{}.initial = {} -> {}",
-                    sname,
-                    id,
-                    initial,
-                    vec_to_string(&t.target)
-                );
+                debug!(" {}#{}.initial = {} -> {}", sname, id, initial, id_refs);
                 self.fsm.transitions.insert(t.id, t);
             }
         }
 
+        let doc_id = self.next_doc_id();
         let state = self.get_state_by_id_mut(id);
         if initial != 0 {
             state.initial = initial;
         }
-        state.doc_id = DOC_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+        state.doc_id = doc_id;
 
         if parent != 0 {
             state.parent = parent;
@@ -667,59 +1078,17 @@ impl ReaderState {
         attr.unwrap()
     }
 
-    fn read_from_uri(&mut self, uri: &String) -> Result<String, String> {
-        let url_result = Url::parse(uri);
-        match url_result {
-            Ok(url) => match url.scheme().to_ascii_lowercase().as_str() {
-                "file" => self.read_from_relative_path(url.path()),
-                &_ => {
-                    #[cfg(feature = "Debug_Reader")]
-                    debug!("read from URL {}", url);
-                    let resp = ureq::get(uri).call();
-                    match resp {
-                        Ok(r) => match r.status() {
-                            200..=299 => match r.into_string() {
-                                Ok(content) => Ok(content),
-                                Err(err) => Err(format!("Failed to load from {}. {}", uri, err)),
-                            },
-                            _ => Err(format!(
-                                "Failed to load from {}. Status {} {}",
-                                uri,
-                                r.status(),
-                                r.status_text()
-                            )),
-                        },
-                        Err(e) => Err(format!("Failed to download {}. {}", uri, e)),
-                    }
-                }
-            },
-            Err(_e) => {
-                #[cfg(feature = "Debug_Reader")]
-                debug!(
-                    "{} is not a URI ({}). Try loading as relative path...",
-                    uri, _e
-                );
-                self.read_from_relative_path(uri.as_str())
-            }
-        }
-    }
-
-    fn read_from_relative_path(&mut self, path: &str) -> Result<String, String> {
-        let file_src = self.get_resolved_path(path)?;
-        self.file.clone_from(&file_src);
-        match File::open(file_src.clone()) {
-            Ok(mut file) => {
-                let mut buf = String::with_capacity(file.metadata().unwrap().len() as usize);
-                match file.read_to_string(&mut buf) {
-                    Ok(_r) => Ok(buf),
-                    Err(e) => Err(e.to_string()),
-                }
-            }
-            Err(e) => Err(format!(
-                "Failed to load relative path {:?}: {}",
-                file_src, e
-            )),
+    /// Loads an external resource via a registered scheme handler or the resolver.
+    /// For local files, the resolved file is used as base for further relative references.
+    fn read_from_uri(&mut self, uri: &str) -> Result<String, String> {
+        if let Some(handler) = get_uri_scheme_handler(uri) {
+            #[cfg(feature = "Debug_Reader")]
+            debug!("read {} via scheme handler", uri);
+            return handler(uri);
         }
+        let (file, content) = self.resolver.load(uri, &self.file, &self.include_paths)?;
+        self.file = file;
+        Ok(content)
     }
 
     /// A new "parallel" element started
@@ -813,6 +1182,8 @@ impl ReaderState {
             // at the time specified by the 'binding' attribute of \<scxml\> and must assign it as
             // the value of the data element
 
+            // A resource that can't be loaded (or not in time) is no legal data value. The item is
+            // left blank and error.execution is raised by the datamodel, the other items are initialized.
             match self.read_from_uri(src.unwrap()) {
                 Ok(source) => {
                     #[cfg(feature = "Debug_Reader")]
@@ -821,7 +1192,12 @@ impl ReaderState {
                     source
                 }
                 Err(e) => {
-                    panic!("Can't read data source '{}'. {}", src.unwrap(), e);
+                    let msg = format!("Can't read data source '{}'. {}", src.unwrap(), e);
+                    warn!("{}", msg);
+                    self.get_current_state()
+                        .data
+                        .insert(id.to_string(), create_data_arc(Data::Error(msg)));
+                    return;
                 }
             }
         } else if expr.is_some() {
@@ -908,7 +1284,7 @@ impl ReaderState {
         self.get_current_state().invoke.last_mut().finalize = ec_id;
     }
 
-    fn start_transition(&mut self, attr: &AttributeMap) {
+    fn start_transition(&mut self, attr: &AttributeMap) -> Result<(), String> {
         let parent_tag = self
             .verify_parent_tag(
                 TAG_TRANSITION,
@@ -916,42 +1292,29 @@ impl ReaderState {
             )
             .to_string();
 
-        let mut t = Transition::new();
-        t.doc_id = DOC_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+        // W3C: A conformant SCXML document must not specify "cond" or "event" attributes on this element.
+        // (for the transition of <initial>).
+        // These transitions are always taken, a condition would never be evaluated.
+        if parent_tag.eq(TAG_INITIAL) {
+            for attribute in [ATTR_COND, TAG_EVENT] {
+                if attr.contains_key(attribute) {
+                    return Err(format!(
+                        "<{}> inside <{}> must not have attribute '{}'",
+                        TAG_TRANSITION, parent_tag, attribute
+                    ));
+                }
+            }
+        }
+
+        let mut t = Transition::with_id(self.next_id());
+        t.doc_id = self.next_doc_id();
 
         // Start script.
         self.start_executable_content_region(false, TAG_TRANSITION);
 
         let event = attr.get(TAG_EVENT);
-        if event.is_some() {
-            t.events = event
-                .unwrap()
-                .split_whitespace()
-                .map(|s| {
-                    // Strip redundant "." and ".*" suffix
-                    let mut rt = s;
-                    let mut do_it = true;
-                    while do_it {
-                        do_it = false;
-                        match rt.strip_suffix(".*") {
-                            None => {}
-                            Some(r) => {
-                                do_it = true;
-                                rt = r
-                            }
-                        }
-                        match rt.strip_suffix(".") {
-                            None => {}
-                            Some(r) => {
-                                do_it = true;
-                                rt = r
-                            }
-                        }
-                    }
-                    rt.to_string()
-                })
-                .collect();
-            t.wildcard = t.events.contains(&"*".to_string());
+        if let Some(event) = event {
+            t.set_event_descriptors(event);
         }
 
         let cond = attr.get(ATTR_COND);
@@ -963,7 +1326,7 @@ impl ReaderState {
         match target {
             None => (),
             Some(target_name) => {
-                self.parse_state_specification(target_name, &mut t.target);
+                self.parse_state_specification(target_name, t.id);
             }
         }
 
@@ -988,6 +1351,7 @@ impl ReaderState {
         t.source = state.id;
         self.current.current_transition = t.id;
         self.fsm.transitions.insert(t.id, t);
+        Ok(())
     }
 
     fn end_transition(&mut self) {
@@ -997,7 +1361,7 @@ impl ReaderState {
         trans.content = ec_id;
     }
 
-    fn start_script(&mut self, attr: &AttributeMap, reader: &mut XReader, has_content: bool) {
+    fn start_script(&mut self, attr: &AttributeMap, reader: &mut XReader, has_content: bool) -> Result<(), String> {
         let at_root = self.get_parent_tag().eq(TAG_SCXML);
 
         if !at_root {
@@ -1034,30 +1398,30 @@ impl ReaderState {
                     s.content = self.create_source_moved(source);
                 }
                 Err(e) => {
-                    panic!("Can't read script '{}'. {}", file_src, e);
+                    return Err(format!("Can't read script '{}'. {}", file_src, e));
                 }
             }
         }
 
+        // Keep the script verbatim, whitespace and line breaks may be significant (e.g. for "//" comments).
         let script_text = if has_content {
-            self.read_content(TAG_SCRIPT, reader)
+            self.read_raw_content(TAG_SCRIPT, reader)
         } else {
             String::new()
         };
 
-        let src = script_text.trim();
-
-        if !src.is_empty() {
+        if !script_text.trim().is_empty() {
             if !s.content.is_empty() {
                 panic!("<script> with 'src' attribute shall not have content.")
             }
-            s.content = self.create_source_moved(src.to_string());
+            s.content = self.create_source_moved(script_text);
         }
 
         self.add_executable_content(Box::new(s));
         if at_root {
             self.fsm.script = self.end_executable_content_region(TAG_SCRIPT);
         }
+        Ok(())
     }
 
     fn start_for_each(&mut self, attr: &AttributeMap) {
@@ -1283,7 +1647,7 @@ impl ReaderState {
         }
     }
 
-    fn start_send(&mut self, attr: &AttributeMap) {
+    fn start_send(&mut self, attr: &AttributeMap) -> Result<(), String> {
         self.verify_parent_tag(
             TAG_SEND,
             &[
@@ -1366,23 +1730,23 @@ impl ReaderState {
             }
             send_params.delay_expr = self.create_source(delay_expr_attr_value);
         } else if delay_attr.is_some() {
-            if (!delay_attr.unwrap().is_empty()) && type_attr.is_some() && type_attr.unwrap().eq(TARGET_INTERNAL) {
-                panic!(
+            if (!delay_attr.unwrap().is_empty()) && target.is_some_and(|t| is_internal_target(t)) {
+                return Err(format!(
                     "{}: {} with {} {} is not possible",
                     TAG_SEND,
                     ATTR_DELAY,
                     ATTR_TARGET,
-                    type_attr.unwrap()
-                );
+                    target.unwrap()
+                ));
             }
             let delayms = parse_duration_to_milliseconds(delay_attr.unwrap());
             if delayms < 0 {
-                panic!(
+                return Err(format!(
                     "{}: {} with illegal value '{}'",
                     TAG_SEND,
                     ATTR_DELAY,
                     delay_attr.unwrap()
-                );
+                ));
             } else {
                 send_params.delay_ms = delayms as u64;
             }
@@ -1395,19 +1759,23 @@ impl ReaderState {
             .parent_state_name
             .clone_from(&self.get_current_state().name);
         self.add_executable_content(Box::new(send_params));
+        Ok(())
     }
 
     /// Reads the content until an end-tag is encountered.
     fn read_content(&mut self, tag: &str, reader: &mut XReader) -> String {
+        self.read_raw_content(tag, reader).trim().to_string()
+    }
+
+    /// Reads the content of the current element verbatim, without trimming whitespace.
+    fn read_raw_content(&mut self, tag: &str, reader: &mut XReader) -> String {
         let start = BytesStart::new(tag.to_string());
         let end = start.to_end().into_owned();
 
         let mut buf = Vec::new();
         let content = match reader.read_to_end_into(end.name(), &mut buf) {
             Ok(span) => {
-                let r = self.content[(span.start as usize)..(span.end as usize)]
-                    .trim()
-                    .to_string();
+                let r = self.content[(span.start as usize)..(span.end as usize)].to_string();
                 #[cfg(feature = "Debug_Reader")]
                 debug!("{} content {} - {}: {}", tag, span.start, span.end, r);
                 r
@@ -1558,10 +1926,11 @@ impl ReaderState {
         );
         let label = attr.get(ATTR_LABEL);
         let expr = attr.get(ATTR_EXPR);
-        if let Some(exprs) = expr {
-            let expression = self.create_source(exprs.as_str());
-            self.add_executable_content(Box::new(Log::new(&label, expression)));
-        }
+        let expression = match expr {
+            Some(exprs) => self.create_source(exprs.as_str()),
+            None => Data::None(),
+        };
+        self.add_executable_content(Box::new(Log::new(&label, expression)));
     }
 
     fn start_assign(&mut self, attr: &AttributeMap, reader: &mut XReader, has_content: bool) {
@@ -1628,7 +1997,7 @@ impl ReaderState {
         self.add_executable_content(Box::new(raise));
     }
 
-    fn start_scxml(&mut self, attr: &AttributeMap) {
+    fn start_scxml(&mut self, attr: &AttributeMap) -> Result<(), String> {
         if self.in_scxml {
             panic!("Only one <{}> allowed", TAG_SCXML);
         }
@@ -1662,12 +2031,24 @@ impl ReaderState {
         }
         let version = attr.get(TAG_VERSION);
         if let Some(version_value) = version {
-            self.fsm.version.clone_from(version_value);
             #[cfg(feature = "Debug_Reader")]
             debug!(" scxml.version = {}", version_value);
+            // W3C: The value must be "1.0".
+            if version_value.trim() != SCXML_VERSION {
+                let msg = format!(
+                    "<{}>: unsupported {} '{}', expected '{}'",
+                    TAG_SCXML, TAG_VERSION, version_value, SCXML_VERSION
+                );
+                if self.strict {
+                    return Err(msg);
+                }
+                warn!("{}", msg);
+            }
+            self.fsm.version.clone_from(version_value);
         }
         self.fsm.pseudo_root = self.get_or_create_state_with_attributes(attr, false, 0);
         self.current.current_state = self.fsm.pseudo_root;
+        Ok(())
     }
 
     fn end_scxml(&mut self) {
@@ -1675,6 +2056,11 @@ impl ReaderState {
     }
 
     fn set_default_initial(&mut self, id: StateId) {
+        let needs_initial = {
+            let state = self.fsm.get_state_by_id(id);
+            state.initial == 0 && !state.states.is_empty()
+        };
+        let transition_id = if needs_initial { self.next_id() } else { 0 };
         let state = self.get_state_by_id_mut(id);
         if state.initial == 0 {
             //  W3C: If not specified, the default initial state is the first child state in document order.
@@ -1683,7 +2069,10 @@ impl ReaderState {
                     // No states at all
                 }
                 Some(first_state) => {
-                    let mut t = Transition::new();
+                    let mut t = Transition::with_id(transition_id);
+                    // As for the "initial" attribute. Otherwise, the domain of the initial transition of
+                    // the document would be outside the document and the <scxml> element would be entered as state.
+                    t.transition_type = TransitionType::Internal;
                     state.initial = t.id;
                     t.source = state.id;
                     t.target.push(*first_state);
@@ -1698,22 +2087,27 @@ impl ReaderState {
         self.set_default_initial(self.current.current_state);
     }
 
-    fn start_element(&mut self, reader: &mut XReader, e: &BytesStart, has_content: bool) {
+    fn start_element(&mut self, reader: &mut XReader, e: &BytesStart, has_content: bool) -> Result<(), String> {
         let n = e.local_name();
-        let name = str::from_utf8(n.as_ref()).unwrap();
+        let tag_name = self.tolerant_tag_name(str::from_utf8(n.as_ref()).unwrap());
+        let name = tag_name.as_str();
         self.push(name);
 
         #[cfg(feature = "Debug_Reader")]
         debug!("Start Element {}", name);
 
-        let attr = &decode_attributes(reader, &mut e.attributes());
+        let mut attr_map = decode_attributes(reader, &mut e.attributes());
+        if self.tolerant {
+            attr_map = self.tolerant_attributes(name, attr_map);
+        }
+        let attr = &attr_map;
 
         match name {
             TAG_INCLUDE => {
-                self.include(attr);
+                self.include(attr)?;
             }
             TAG_SCXML => {
-                self.start_scxml(attr);
+                self.start_scxml(attr)?;
             }
             TAG_DATAMODEL => {
                 self.start_datamodel();
@@ -1743,7 +2137,7 @@ impl ReaderState {
                 self.start_invoke(attr);
             }
             TAG_TRANSITION => {
-                self.start_transition(attr);
+                self.start_transition(attr)?;
             }
             TAG_FINALIZE => {
                 self.start_finalize(attr);
@@ -1755,13 +2149,13 @@ impl ReaderState {
                 self.start_on_exit(attr);
             }
             TAG_SCRIPT => {
-                self.start_script(attr, reader, has_content);
+                self.start_script(attr, reader, has_content)?;
             }
             TAG_RAISE => {
                 self.start_raise(attr);
             }
             TAG_SEND => {
-                self.start_send(attr);
+                self.start_send(attr)?;
             }
             TAG_PARAM => {
                 self.start_param(attr);
@@ -1795,77 +2189,165 @@ impl ReaderState {
                 debug!("Ignored tag {}", name)
             }
         }
+        Ok(())
     }
 
-    /// Try to resolve the file name relative to the current file or include paths.
-    fn get_resolved_path(&self, ps: &str) -> Result<PathBuf, String> {
-        let mut ps = ps.to_string();
-        while ps.starts_with('\\') || ps.starts_with('/') {
-            ps.remove(0);
-        }
-        let src = Path::new(&ps);
-
-        let to_current = {
-            let parent = self.file.parent();
-            match parent {
-                Some(parent_path) => {
-                    let pp = parent_path.join(src);
-                    pp.to_owned()
-                }
-                None => src.to_owned(),
-            }
-        };
-        if to_current.exists() {
-            return Ok(to_current);
-        } else {
-            for ip in &self.include_paths {
-                let rp = ip.join(src);
-                if rp.exists() {
-                    return Ok(rp);
-                }
-            }
-        }
-        Err(format!("Can not resolve {:?}", ps))
-    }
-
-    /// Handle a XInclude include element.
-    /// See https://www.w3.org/TR/xinclude/
-    /// Only parse="text" and "href" with a relative path are supported, also no "xpointer" etc.
-    fn include(&mut self, attr: &AttributeMap) {
-        let href = Self::get_required_attr(TAG_INCLUDE, TAG_HREF, attr);
-        let parse = attr.get(ATTR_PARSE);
-        if parse.is_none() || parse.unwrap().ne("text") {
-            panic!("{}: only {}='text' is supported", TAG_INCLUDE, ATTR_PARSE)
-        }
-        let xpointer = attr.get(ATTR_XPOINTER);
-        if xpointer.is_some() {
-            panic!("{}: {} is not supported", TAG_INCLUDE, ATTR_XPOINTER)
+    /// Handle a XInclude include element.
+    /// See https://www.w3.org/TR/xinclude/
+    /// Only parse="text" and "href" are supported, also no "xpointer" etc.
+    /// The "href" is loaded via the [ResourceResolver].\
+    /// Returns an error if a document includes itself (directly or via other includes)
+    /// or if includes are nested deeper than [MAX_INCLUDE_DEPTH].
+    fn include(&mut self, attr: &AttributeMap) -> Result<(), String> {
+        let href = Self::get_required_attr(TAG_INCLUDE, TAG_HREF, attr);
+        let parse = attr.get(ATTR_PARSE);
+        if parse.is_none() || parse.unwrap().ne("text") {
+            panic!("{}: only {}='text' is supported", TAG_INCLUDE, ATTR_PARSE)
+        }
+        let xpointer = attr.get(ATTR_XPOINTER);
+        if xpointer.is_some() {
+            panic!("{}: {} is not supported", TAG_INCLUDE, ATTR_XPOINTER)
         }
 
         // remove "include" from parent-stack as long as we read the content.
         self.pop();
 
-        match self.get_resolved_path(href.as_str()) {
-            Ok(src) => {
-                let org_file = mem::take(&mut self.file);
-                let org_content = mem::take(&mut self.content);
-                let rs = self.process_file(&src);
-                if rs.is_err() {
-                    panic!("Failed to read {:?}. {}", src, rs.err().unwrap());
+        let org_file = self.file.clone();
+        match self.read_from_uri(href.as_str()) {
+            Ok(content) => {
+                // Resources that are not loaded from files are identified by the reference.
+                let include_key = if self.file != org_file {
+                    self.file.to_string_lossy().to_string()
+                } else {
+                    href.clone()
+                };
+                if self.include_stack.contains(&include_key) {
+                    return Err(format!(
+                        "Include cycle detected: {} -> {}",
+                        self.include_stack.join(" -> "),
+                        include_key
+                    ));
+                }
+                if self.include_stack.len() >= MAX_INCLUDE_DEPTH {
+                    return Err(format!(
+                        "Includes are nested deeper than {} levels at '{}'",
+                        MAX_INCLUDE_DEPTH, include_key
+                    ));
+                }
+                self.include_stack.push(include_key);
+                let org_content = mem::replace(&mut self.content, content);
+                // Anonymous states are named per document.
+                self.include_count += 1;
+                let org_include = mem::replace(&mut self.current_include, self.include_count);
+                let org_id_count = mem::replace(&mut self.id_count, 0);
+                if let Err(err) = self.process().map(|_| ()) {
+                    return Err(format!("Failed to read {:?}. {}", self.file, err));
                 }
-                self.file = org_file;
+                self.include_stack.pop();
+                self.current_include = org_include;
+                self.id_count = org_id_count;
                 self.content = org_content;
             }
             Err(err) => {
                 panic!("Can resolve path {}: {}", href, err);
             }
         }
+        self.file = org_file;
 
         self.push(TAG_INCLUDE);
+        Ok(())
+    }
+
+    /// Tolerant mode: Maps element names that differ only in case to the known SCXML element.
+    fn tolerant_tag_name(&mut self, name: &str) -> String {
+        if self.tolerant && !KNOWN_TAGS.contains(&name) {
+            if let Some(known) = KNOWN_TAGS.iter().find(|t| t.eq_ignore_ascii_case(name)) {
+                self.diagnostics
+                    .push(format!("Element <{}> read as <{}>", name, known));
+                return known.to_string();
+            }
+        }
+        name.to_string()
+    }
+
+    /// Tolerant mode: Fixes the case of known attribute names, trims the values
+    /// and removes unknown attributes.
+    fn tolerant_attributes(&mut self, tag: &str, attr: AttributeMap) -> AttributeMap {
+        let mut result = AttributeMap::new();
+        for (key, value) in attr {
+            // Namespace declarations and attributes of other namespaces are kept as they are.
+            if key.contains(':') || key.eq("xmlns") || !KNOWN_TAGS.contains(&tag) {
+                result.insert(key, value);
+                continue;
+            }
+            let name = if KNOWN_ATTRIBUTES.contains(&key.as_str()) {
+                key
+            } else {
+                match KNOWN_ATTRIBUTES
+                    .iter()
+                    .find(|a| a.eq_ignore_ascii_case(key.as_str()))
+                {
+                    Some(known) => {
+                        self.diagnostics.push(format!(
+                            "Attribute '{}' of <{}> read as '{}'",
+                            key, tag, known
+                        ));
+                        known.to_string()
+                    }
+                    None => {
+                        self.diagnostics
+                            .push(format!("Unknown attribute '{}' of <{}> ignored", key, tag));
+                        continue;
+                    }
+                }
+            };
+            let trimmed = value.trim();
+            if trimmed.len() != value.len() {
+                self.diagnostics.push(format!(
+                    "Whitespace around value of attribute '{}' of <{}> removed",
+                    name, tag
+                ));
+                result.insert(name, trimmed.to_string());
+            } else {
+                result.insert(name, value);
+            }
+        }
+        result
+    }
+
+    /// Tolerant mode: Closes all elements that are still open at the end of the document.
+    fn close_open_elements(&mut self) {
+        while !self.stack.is_empty() {
+            let open_tag = self.current.current_tag.clone();
+            self.diagnostics
+                .push(format!("Missing end-tag </{}> added", open_tag));
+            self.end_element(&open_tag);
+        }
     }
 
     /// Called from SAX handler if some end-tag was read.
     fn end_element(&mut self, name: &str) {
+        if self.tolerant && !self.current.current_tag.eq(name) {
+            // The first item on stack is the document itself.
+            if self
+                .stack
+                .iter()
+                .skip(1)
+                .any(|item| item.current_tag.eq(name))
+            {
+                // Close the elements that are missing their end-tag.
+                while !self.current.current_tag.eq(name) {
+                    let open_tag = self.current.current_tag.clone();
+                    self.diagnostics
+                        .push(format!("Missing end-tag </{}> added", open_tag));
+                    self.end_element(&open_tag);
+                }
+            } else {
+                self.diagnostics
+                    .push(format!("End-tag </{}> without start-tag ignored", name));
+                return;
+            }
+        }
         if !self.current.current_tag.eq(name) {
             panic!(
                 "Illegal end-tag {:?}, expected {:?}",
@@ -1974,6 +2456,17 @@ pub fn parse_from_uri(uri: String, include_paths: &[PathBuf]) -> Result<Box<Fsm>
     }
 }
 
+/// Reads the FSM from a stream, e.g. stdin or a network connection.
+pub fn parse_from_reader(mut source: impl Read) -> Result<Box<Fsm>, String> {
+    let mut rs = ReaderState::new();
+    rs.file = Path::new("Stream").to_path_buf();
+    let r = rs.process_reader(&mut source);
+    match r {
+        Ok(_m) => Ok(rs.fsm),
+        Err(e) => Err(e),
+    }
+}
+
 /// Reads the FSM from a XML String
 pub fn parse_from_xml(xml: String) -> Result<Box<Fsm>, String> {
     let mut rs = ReaderState::new();
@@ -1985,6 +2478,84 @@ pub fn parse_from_xml(xml: String) -> Result<Box<Fsm>, String> {
     }
 }
 
+/// Reads the FSM from a XML String in strict mode.
+/// Deviations that are only reported as warnings by [parse_from_xml] are errors.
+pub fn parse_from_xml_strict(xml: String) -> Result<Box<Fsm>, String> {
+    let mut rs = ReaderState::new();
+    rs.strict = true;
+    rs.content = xml;
+    let r = rs.process();
+    match r {
+        Ok(_m) => Ok(rs.fsm),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads the FSM from a XML String in tolerant mode, for (e.g. generated) documents that are slightly malformed.\
+/// Returns the FSM and a list of diagnostics, describing the repairs that were done:
+/// + Element and attribute names that differ only in case from a known SCXML name are read as the known name.
+/// + Unknown attributes of SCXML elements are ignored.
+/// + Whitespace around attribute values is removed.
+/// + A missing end-tag is added if an end-tag of an enclosing element is found, or at the end of the document.
+/// + End-tags without matching start-tag are ignored.
+///
+/// All other errors, including violations of the SCXML schema, are still reported as error.
+/// Panics of the reader are caught and also reported as error.\
+/// This mode is independent of [parse_from_xml_strict] and should not be used to check conformance.
+pub fn parse_from_xml_tolerant(xml: String) -> Result<(Box<Fsm>, Vec<String>), String> {
+    let r = std::panic::catch_unwind(move || {
+        let mut rs = ReaderState::new();
+        rs.tolerant = true;
+        rs.content = xml;
+        let r = rs.process().map(|_| ());
+        match r {
+            Ok(_) => Ok((rs.fsm, rs.diagnostics)),
+            Err(e) => Err(e),
+        }
+    });
+    match r {
+        Ok(result) => result,
+        Err(panic) => Err(match panic.downcast_ref::<String>() {
+            Some(msg) => msg.clone(),
+            None => match panic.downcast_ref::<&str>() {
+                Some(msg) => msg.to_string(),
+                None => "Reader failed".to_string(),
+            },
+        }),
+    }
+}
+
+/// Reads the FSM from a XML String.
+/// External resources are loaded via the given resolver.
+pub fn parse_from_xml_with_resolver(xml: String, resolver: Box<dyn ResourceResolver>) -> Result<Box<Fsm>, String> {
+    let mut rs = ReaderState::new();
+    rs.resolver = resolver;
+    rs.content = xml;
+    let r = rs.process();
+    match r {
+        Ok(_m) => Ok(rs.fsm),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads the FSM from a XML String, like [parse_from_xml_with_resolver].
+/// States without "id" are named by the given generator.
+pub fn parse_from_xml_with_name_generator(
+    xml: String,
+    resolver: Box<dyn ResourceResolver>,
+    name_generator: NameGenerator,
+) -> Result<Box<Fsm>, String> {
+    let mut rs = ReaderState::new();
+    rs.resolver = resolver;
+    rs.name_generator = name_generator;
+    rs.content = xml;
+    let r = rs.process();
+    match r {
+        Ok(_m) => Ok(rs.fsm),
+        Err(e) => Err(e),
+    }
+}
+
 /// Reads the FSM from a XML String
 pub fn parse_from_xml_with_includes(xml: String, include_paths: &[PathBuf]) -> Result<Box<Fsm>, String> {
     let mut rs = ReaderState::new();
@@ -1997,6 +2568,196 @@ pub fn parse_from_xml_with_includes(xml: String, include_paths: &[PathBuf]) -> R
     }
 }
 
+/// Replaces the content of one state with the given XML element, e.g. to apply edits of an editor without
+/// reading the whole document again.\
+/// The element has to be the \<state\>, \<parallel\> or \<final\> element of the state, with the name of the state as "id".
+/// The transitions, \<onentry\>, \<onexit\>, \<invoke\>, \<datamodel\> and \<donedata\> elements and the "initial"
+/// attribute of the state are replaced by the content of the element. All other states and transitions are kept.\
+/// Child states can't be replaced this way: the element must not contain states, the existing child states are kept.\
+/// Targets are resolved against the states of the FSM. On error the FSM is not modified.\
+/// Scripts of the new content get no source id, so they are not cached by the datamodels.
+pub fn reparse_state(fsm: &mut Fsm, state_name: &str, xml: &str) -> Result<(), String> {
+    let sid = match fsm.statesNames.get(state_name) {
+        Some(sid) if *sid != fsm.pseudo_root => *sid,
+        _ => return Err(format!("Unknown state '{}'", state_name)),
+    };
+    if fsm.executable_content_mut().is_none() {
+        return Err("Executable content is shared with other instances".to_string());
+    }
+    let state = fsm.get_state_by_id(sid);
+    let tag = if state.history_type != HistoryType::None {
+        return Err(format!("History state '{}' can't be reparsed", state_name));
+    } else if state.is_parallel {
+        TAG_PARALLEL
+    } else if state.is_final {
+        TAG_FINAL
+    } else {
+        TAG_STATE
+    };
+    let parent_tag = if state.parent == fsm.pseudo_root {
+        TAG_SCXML
+    } else if fsm.get_state_by_id(state.parent).is_parallel {
+        TAG_PARALLEL
+    } else {
+        TAG_STATE
+    };
+    verify_state_element(xml, tag, state_name)?;
+
+    // Content of the state that is removed if the new content was read successfully.
+    let mut old_content: Vec<ExecutableContentId> = Vec::new();
+    old_content.extend(state.onentry.iter());
+    old_content.extend(state.onexit.iter());
+    old_content.extend(state.invoke.iterator().map(|invoke| invoke.finalize));
+    let mut old_transitions: Vec<TransitionId> = state.transitions.iterator().copied().collect();
+    if state.initial != 0 {
+        old_transitions.push(state.initial);
+    }
+    old_content.extend(
+        old_transitions
+            .iter()
+            .map(|tid| fsm.get_transition_by_id(*tid).content),
+    );
+    let doc_id = state.doc_id;
+    let parent = state.parent;
+
+    let content_id_count = fsm
+        .transitions
+        .keys()
+        .chain(fsm.executableContent.keys())
+        .max()
+        .copied()
+        .unwrap_or(0);
+    let doc_id_count = fsm
+        .states
+        .iter()
+        .map(|state| {
+            state
+                .invoke
+                .iterator()
+                .map(|invoke| invoke.doc_id)
+                .max()
+                .unwrap_or(0)
+                .max(state.doc_id)
+        })
+        .chain(fsm.transitions.values().map(|t| t.doc_id))
+        .max()
+        .unwrap_or(0);
+
+    let states_backup = fsm.states.clone();
+    let transitions_backup = fsm.transitions.clone();
+    let names_backup = fsm.statesNames.clone();
+
+    let state = fsm.get_state_by_id_mut(sid);
+    state.transitions = List::new();
+    state.initial = 0;
+    state.onentry.clear();
+    state.onexit.clear();
+    state.invoke = List::new();
+    state.data.clear();
+    state.donedata = None;
+
+    let mut rs = ReaderState::new();
+    rs.fsm = Box::new(mem::take(fsm));
+    rs.in_scxml = true;
+    rs.uncached_sources = true;
+    rs.content_id_count = content_id_count;
+    rs.doc_id_count = doc_id_count;
+    rs.current.current_state = parent;
+    rs.current.current_tag = parent_tag.to_string();
+    rs.content = xml.to_string();
+
+    let mut result = match std::panic::catch_unwind(AssertUnwindSafe(|| rs.process().map(|_| ()))) {
+        Ok(result) => result,
+        Err(panic) => Err(match panic.downcast_ref::<String>() {
+            Some(msg) => msg.clone(),
+            None => match panic.downcast_ref::<&str>() {
+                Some(msg) => msg.to_string(),
+                None => "Reader failed".to_string(),
+            },
+        }),
+    };
+    if result.is_ok() && rs.fsm.states.len() != states_backup.len() {
+        result = Err(format!(
+            "<{}> '{}' must not contain states",
+            tag, state_name
+        ));
+    }
+
+    let mut new_fsm = rs.fsm;
+    match result {
+        Ok(_) => {
+            new_fsm.get_state_by_id_mut(sid).doc_id = doc_id;
+            for tid in old_transitions {
+                new_fsm.transitions.remove(&tid);
+            }
+            let executable_content = new_fsm.executable_content_mut().unwrap();
+            while let Some(content_id) = old_content.pop() {
+                if let Some(block) = executable_content.remove(&content_id) {
+                    for ec in block {
+                        old_content.extend(ec.describe().blocks.iter().map(|(_, id)| *id));
+                    }
+                }
+            }
+        }
+        Err(_) => {
+            new_fsm.states = states_backup;
+            new_fsm.transitions = transitions_backup;
+            new_fsm.statesNames = names_backup;
+            new_fsm
+                .executable_content_mut()
+                .unwrap()
+                .retain(|id, _| *id <= content_id_count);
+        }
+    }
+    *fsm = *new_fsm;
+    result
+}
+
+/// Checks that the document consists of exactly one element with the given tag and id.
+fn verify_state_element(xml: &str, tag: &str, id: &str) -> Result<(), String> {
+    let mut reader = Reader::from_str(xml);
+    let mut depth = 0usize;
+    let mut elements = 0usize;
+    loop {
+        let (e, has_content) = match reader.read_event() {
+            Err(e) => {
+                return Err(format!(
+                    "Error at position {}: {:?}",
+                    reader.buffer_position(),
+                    e
+                ))
+            }
+            Ok(Event::Eof) => break,
+            Ok(Event::Start(e)) => (e, true),
+            Ok(Event::Empty(e)) => (e, false),
+            Ok(Event::End(_)) => {
+                depth -= 1;
+                continue;
+            }
+            Ok(_) => continue,
+        };
+        if depth == 0 {
+            elements += 1;
+            let has_id = e
+                .try_get_attribute(ATTR_ID)
+                .ok()
+                .flatten()
+                .and_then(|a| a.unescape_value().ok().map(|v| v == id))
+                .unwrap_or(false);
+            if elements > 1 || e.local_name().as_ref() != tag.as_bytes() || !has_id {
+                return Err(format!("Expected one <{}> element with id '{}'", tag, id));
+            }
+        }
+        if has_content {
+            depth += 1;
+        }
+    }
+    if elements == 0 {
+        return Err(format!("Expected one <{}> element with id '{}'", tag, id));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::common::debug;
@@ -2014,8 +2775,11 @@ mod tests {
     #[test]
     #[should_panic]
     fn script_with_src_and_content_should_panic() {
-        let _r = crate::scxml_reader::parse_from_xml("<scxml initial='Main'><state id='Main'>\
-    <initial><transition><script src='xml/example/script.js'>println();</script></transition></initial></state></scxml>".to_string());
+        let _r = crate::scxml_reader::parse_from_xml(
+            "<scxml initial='Main'><state id='Main'>\
+    <initial><transition><script src='test/script.js'>println();</script></transition></initial></state></scxml>"
+                .to_string(),
+        );
     }
 
     #[test]
@@ -2044,6 +2808,491 @@ mod tests {
         assert!(b);
     }
 
+    #[test]
+    fn script_with_src_from_resolver() {
+        let mut resolver = crate::scxml_reader::MapResourceResolver::new();
+        resolver.add("lib/script.js", "var fromResolver = 42;");
+        let r = crate::scxml_reader::parse_from_xml_with_resolver(
+            "<scxml initial='Main'><script src='lib/script.js'/><state id='Main'/></scxml>".to_string(),
+            Box::new(resolver),
+        );
+        assert!(r.is_ok());
+
+        let fsm = r.unwrap();
+        assert_ne!(fsm.script, 0);
+        let script = format!("{:?}", fsm.executableContent.get(&fsm.script).unwrap());
+        assert!(script.contains("var fromResolver = 42;"), "{}", script);
+    }
+
+    #[test]
+    #[cfg(feature = "ECMAScriptModel")]
+    fn failing_data_src_leaves_item_blank() {
+        let mut resolver = crate::scxml_reader::MapResourceResolver::new();
+        resolver.add("ok.json", "42");
+        let fsm = crate::scxml_reader::parse_from_xml_with_resolver(
+            r#"<scxml initial='s0' datamodel='ecmascript'>
+              <datamodel>
+                <data id='a' src='ok.json'/>
+                <data id='b' src='missing.json'/>
+                <data id='c' expr='3'/>
+              </datamodel>
+              <state id='s0'>
+                <transition event='error.execution' cond='a == 42 &amp;&amp; b === undefined &amp;&amp; c == 3' target='pass'/>
+                <transition event='*' target='fail'/>
+              </state>
+              <final id='pass'/>
+              <final id='fail'/>
+            </scxml>"#
+                .to_string(),
+            Box::new(resolver),
+        )
+        .unwrap();
+
+        let global = crate::fsm::run_blocking(fsm, Vec::new());
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+    }
+
+    const TOLERANT_BASE: &str = r#"<scxml initial="main" version="1.0">
+  <state id="main" initial="a">
+    <state id="a">
+      <transition event="go" target="b"/>
+      <onentry><log expr="'a'"/></onentry>
+    </state>
+    <state id="b">
+      <transition event="back" target="a"/>
+      <onexit><log expr="'b'"/></onexit>
+    </state>
+    <transition event="done" target="end"/>
+    <onentry><raise event="started"/></onentry>
+  </state>
+  <final id="end"/>
+</scxml>"#;
+
+    /// Describes the structure of the FSM by names.
+    fn tolerant_structure(fsm: &crate::fsm::Fsm) -> Vec<String> {
+        let mut r: Vec<String> = fsm
+            .states
+            .iter()
+            .filter(|s| s.id != fsm.pseudo_root)
+            .map(|s| {
+                let transitions: Vec<String> = s
+                    .transitions
+                    .iterator()
+                    .map(|tid| {
+                        let t = fsm.get_transition_by_id(*tid);
+                        format!(
+                            "{:?}->{:?}",
+                            t.events,
+                            t.target
+                                .iter()
+                                .map(|sid| fsm.get_state_by_id(*sid).name.clone())
+                                .collect::<Vec<String>>()
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{} parent={} final={} onentry={} onexit={} {:?}",
+                    s.name,
+                    if s.parent == 0 {
+                        ""
+                    } else {
+                        fsm.get_state_by_id(s.parent).name.as_str()
+                    },
+                    s.is_final,
+                    s.onentry.len(),
+                    s.onexit.len(),
+                    transitions
+                )
+            })
+            .collect();
+        r.sort();
+        r
+    }
+
+    #[test]
+    fn tolerant_mode_repairs_common_errors() {
+        let (base, diagnostics) = crate::scxml_reader::parse_from_xml_tolerant(TOLERANT_BASE.to_string()).unwrap();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let expected = tolerant_structure(&base);
+
+        for (xml, diagnostic) in [
+            (
+                TOLERANT_BASE.replace("<state id=\"b\">", "<State ID=\"b\">"),
+                "Element <State> read as <state>",
+            ),
+            (
+                TOLERANT_BASE.replace("id=\"b\"", "id=\" b \""),
+                "Whitespace around value of attribute 'id' of <state> removed",
+            ),
+            (
+                TOLERANT_BASE.replace("<final id=\"end\"/>", "<final id=\"end\" color=\"red\"/>"),
+                "Unknown attribute 'color' of <final> ignored",
+            ),
+            (
+                TOLERANT_BASE.replace("</onexit>", ""),
+                "Missing end-tag </onexit> added",
+            ),
+            (
+                TOLERANT_BASE.replace("</scxml>", ""),
+                "Missing end-tag </scxml> added",
+            ),
+            (
+                TOLERANT_BASE.replace("<final id=\"end\"/>", "</transition><final id=\"end\"/>"),
+                "End-tag </transition> without start-tag ignored",
+            ),
+        ] {
+            let (fsm, diagnostics) = crate::scxml_reader::parse_from_xml_tolerant(xml.clone()).unwrap();
+            assert_eq!(tolerant_structure(&fsm), expected, "{}", xml);
+            assert!(
+                diagnostics.contains(&diagnostic.to_string()),
+                "{:?}",
+                diagnostics
+            );
+        }
+
+        // Normal mode is not affected.
+        assert!(crate::scxml_reader::parse_from_xml(TOLERANT_BASE.replace("</onexit>", "")).is_err());
+
+        // Errors that can't be repaired.
+        assert!(crate::scxml_reader::parse_from_xml_tolerant("<scxml><transition/></scxml>".to_string()).is_err());
+        assert!(crate::scxml_reader::parse_from_xml_tolerant("<scxml><state id='a'".to_string()).is_err());
+    }
+
+    /// Simple deterministic pseudo random generator for the fuzz test.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) as usize) % bound
+        }
+    }
+
+    /// Positions of the start-tags and names.
+    fn start_tags(xml: &str) -> Vec<(usize, String)> {
+        xml.match_indices('<')
+            .filter_map(|(pos, _)| {
+                let name: String = xml[pos + 1..]
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphabetic())
+                    .collect();
+                if name.is_empty() {
+                    None
+                } else {
+                    Some((pos + 1 + name.len(), name))
+                }
+            })
+            .collect()
+    }
+
+    /// Applies a random, repairable malformation.
+    fn mutate(xml: &str, rnd: &mut Lcg) -> String {
+        match rnd.next(5) {
+            0 => {
+                // Change case of an element.
+                let tags = start_tags(xml);
+                let (_, name) = &tags[rnd.next(tags.len())];
+                let mut upper = name.clone();
+                upper.replace_range(0..1, &name[0..1].to_uppercase());
+                xml.replace(&format!("<{}", name), &format!("<{}", upper))
+                    .replace(&format!("</{}>", name), &format!("</{}>", upper))
+            }
+            1 => {
+                // Whitespace around an attribute value.
+                let values: Vec<usize> = xml.match_indices("=\"").map(|(pos, _)| pos + 2).collect();
+                let pos = values[rnd.next(values.len())];
+                let end = pos + xml[pos..].find('"').unwrap();
+                format!("{}  {}\t{}", &xml[..pos], &xml[pos..end], &xml[end..])
+            }
+            2 => {
+                // Unknown attribute.
+                let tags = start_tags(xml);
+                let (pos, _) = tags[rnd.next(tags.len())];
+                format!(
+                    "{} unknown{}=\"x\"{}",
+                    &xml[..pos],
+                    rnd.next(100),
+                    &xml[pos..]
+                )
+            }
+            3 => {
+                // Drop an end-tag that is directly followed by another end-tag.
+                let candidates: Vec<(usize, usize)> = xml
+                    .match_indices("</")
+                    .filter_map(|(pos, _)| {
+                        let end = pos + xml[pos..].find('>').unwrap() + 1;
+                        if xml[end..].trim_start().starts_with("</") || xml[end..].trim().is_empty() {
+                            Some((pos, end))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                if candidates.is_empty() {
+                    return xml.to_string();
+                }
+                let (pos, end) = candidates[rnd.next(candidates.len())];
+                format!("{}{}", &xml[..pos], &xml[end..])
+            }
+            _ => {
+                // Stray end-tag between two elements.
+                let positions: Vec<usize> = xml.match_indices(">\n").map(|(pos, _)| pos + 1).collect();
+                if positions.is_empty() {
+                    return xml.to_string();
+                }
+                let pos = positions[rnd.next(positions.len())];
+                format!("{}</stray{}>{}", &xml[..pos], rnd.next(100), &xml[pos..])
+            }
+        }
+    }
+
+    #[test]
+    fn tolerant_mode_fuzz() {
+        let (base, _) = crate::scxml_reader::parse_from_xml_tolerant(TOLERANT_BASE.to_string()).unwrap();
+        let expected = tolerant_structure(&base);
+
+        let mut rnd = Lcg(4711);
+        for round in 0..300 {
+            let mut xml = TOLERANT_BASE.to_string();
+            for _ in 0..(1 + rnd.next(3)) {
+                xml = mutate(&xml, &mut rnd);
+            }
+            match crate::scxml_reader::parse_from_xml_tolerant(xml.clone()) {
+                Ok((fsm, diagnostics)) => {
+                    assert_eq!(
+                        tolerant_structure(&fsm),
+                        expected,
+                        "Round {}:\n{}",
+                        round,
+                        xml
+                    );
+                    if xml != TOLERANT_BASE {
+                        assert!(!diagnostics.is_empty(), "Round {}:\n{}", round, xml);
+                    }
+                }
+                Err(err) => panic!("Round {} failed with '{}':\n{}", round, err, xml),
+            }
+        }
+    }
+
+    #[test]
+    fn scxml_version() {
+        let xml = |version: &str| {
+            format!(
+                "<scxml version='{}' initial='Main'><state id='Main'/></scxml>",
+                version
+            )
+        };
+
+        let fsm = crate::scxml_reader::parse_from_xml_strict(xml("1.0")).unwrap();
+        assert_eq!(fsm.version, "1.0");
+
+        // Only a warning in normal mode.
+        let fsm = crate::scxml_reader::parse_from_xml(xml("2.0")).unwrap();
+        assert_eq!(fsm.version, "2.0");
+
+        let r = crate::scxml_reader::parse_from_xml_strict(xml("2.0"));
+        assert!(r.is_err());
+        assert!(r.err().unwrap().contains("2.0"));
+    }
+
+    #[test]
+    #[cfg(feature = "ECMAScriptModel")]
+    fn script_with_comments_is_kept_verbatim() {
+        let script = "\n  var x = 1; // first\n  // x = 100;\n  x = x + 1;\n";
+        let fsm = crate::scxml_reader::parse_from_xml(format!(
+            "<scxml initial='s0' datamodel='ecmascript'><script>{}</script>\
+             <state id='s0'><transition cond='x == 2' target='pass'/><transition target='fail'/></state>\
+             <final id='pass'/><final id='fail'/></scxml>",
+            script
+        ))
+        .unwrap();
+        let content = format!("{:?}", fsm.executableContent.get(&fsm.script).unwrap());
+        assert!(content.contains(script), "{}", content);
+
+        assert!(crate::test::run_test_manual(
+            "script_with_comments_is_kept_verbatim",
+            &std::collections::HashMap::new(),
+            fsm,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            crate::tracer::TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    fn ids_are_assigned_per_document() {
+        let ids = |xml: &str| {
+            let fsm = crate::scxml_reader::parse_from_xml(xml.to_string()).unwrap();
+            let mut state_ids: Vec<(String, u32)> = fsm
+                .states
+                .iter()
+                .map(|s| (s.name.clone(), s.doc_id))
+                .collect();
+            state_ids.sort();
+            let mut transition_ids: Vec<(u32, u32)> = fsm.transitions.values().map(|t| (t.id, t.doc_id)).collect();
+            transition_ids.sort();
+            (state_ids, transition_ids)
+        };
+
+        let xml = "<scxml initial='a'><state id='a'><transition event='e' target='b'/></state><final id='b'/></scxml>";
+        let first = ids(xml);
+        // Parse something else in between, ids of a new document shall not depend on it.
+        ids("<scxml><state id='x'><state id='y'/></state></scxml>");
+        let second = ids(xml);
+        assert_eq!(first, second);
+
+        // Different documents start from the same base.
+        let root_doc_id = |xml: &str| {
+            let fsm = crate::scxml_reader::parse_from_xml(xml.to_string()).unwrap();
+            fsm.get_state_by_id(fsm.pseudo_root).doc_id
+        };
+        assert_eq!(
+            root_doc_id(xml),
+            root_doc_id("<scxml initial='x'><state id='x'/></scxml>")
+        );
+        assert_eq!(root_doc_id("<scxml><state id='x'/></scxml>"), 1);
+    }
+
+    #[test]
+    fn script_with_src_from_custom_scheme() {
+        /// Removes the scheme again, even if the test fails.
+        struct SchemeGuard(&'static str);
+        impl Drop for SchemeGuard {
+            fn drop(&mut self) {
+                crate::scxml_reader::unregister_uri_scheme(self.0);
+            }
+        }
+
+        crate::scxml_reader::register_uri_scheme(
+            "mem",
+            std::sync::Arc::new(|uri: &str| match uri {
+                "mem://foo" => Ok("var fromMem = 1;".to_string()),
+                _ => Err(format!("{} not found", uri)),
+            }),
+        );
+        let _guard = SchemeGuard("mem");
+        let fsm = crate::scxml_reader::parse_from_xml(
+            "<scxml initial='Main'><script src='mem://foo'/><state id='Main'/></scxml>".to_string(),
+        )
+        .unwrap();
+        let script = format!("{:?}", fsm.executableContent.get(&fsm.script).unwrap());
+        assert!(script.contains("var fromMem = 1;"), "{}", script);
+
+        let r = crate::scxml_reader::parse_from_xml(
+            "<scxml initial='Main'><script src='MEM://bar'/><state id='Main'/></scxml>".to_string(),
+        );
+        let err = r.err().unwrap();
+        assert!(err.contains("MEM://bar not found"), "{}", err);
+    }
+
+    #[test]
+    fn xinclude_from_resolver() {
+        let mut resolver = crate::scxml_reader::MapResourceResolver::new();
+        resolver.add("sub.scxml", "<state id='Sub'/>");
+        let fsm = crate::scxml_reader::parse_from_xml_with_resolver(
+            "<scxml initial='Main'><state id='Main'><include href='sub.scxml' parse='text'/></state></scxml>"
+                .to_string(),
+            Box::new(resolver),
+        )
+        .unwrap();
+        let main = fsm.get_state_by_name(&"Main".to_string());
+        assert_eq!(main.states.len(), 1);
+        assert_eq!(fsm.get_state_by_id(main.states[0]).name, "Sub");
+    }
+
+    #[test]
+    fn anonymous_states_in_includes_get_distinct_stable_names() {
+        let parse = |main: &str| {
+            let mut resolver = crate::scxml_reader::MapResourceResolver::new();
+            resolver.add("a.scxml", "<state><state id='A'/></state>");
+            resolver.add("b.scxml", "<state><state id='B'/></state>");
+            crate::scxml_reader::parse_from_xml_with_resolver(main.to_string(), Box::new(resolver)).unwrap()
+        };
+        let parent_name = |fsm: &crate::fsm::Fsm, name: &str| {
+            let state = fsm.get_state_by_name(&name.to_string());
+            fsm.get_state_by_id(state.parent).name.clone()
+        };
+
+        let fsm = parse(
+            "<scxml initial='Main'><state id='Main'>\
+             <include href='a.scxml' parse='text'/><include href='b.scxml' parse='text'/></state></scxml>",
+        );
+        assert_eq!(parent_name(&fsm, "A"), "__inc1_id1");
+        assert_eq!(parent_name(&fsm, "B"), "__inc2_id1");
+
+        // An additional anonymous state in the main document doesn't change the names in the includes.
+        let fsm = parse(
+            "<scxml initial='Main'><state id='Main'><state/>\
+             <include href='a.scxml' parse='text'/><include href='b.scxml' parse='text'/></state></scxml>",
+        );
+        assert_eq!(parent_name(&fsm, "A"), "__inc1_id1");
+        assert_eq!(parent_name(&fsm, "B"), "__inc2_id1");
+        assert!(fsm.states.iter().any(|state| state.name == "__id1"));
+
+        let mut resolver = crate::scxml_reader::MapResourceResolver::new();
+        resolver.add("a.scxml", "<state><state id='A'/></state>");
+        let fsm = crate::scxml_reader::parse_from_xml_with_name_generator(
+            "<scxml initial='Main'><state id='Main'><include href='a.scxml' parse='text'/></state></scxml>".to_string(),
+            Box::new(resolver),
+            |include, count| format!("anonymous_{}_{}", include, count),
+        )
+        .unwrap();
+        assert_eq!(parent_name(&fsm, "A"), "anonymous_1_1");
+    }
+
+    #[test]
+    fn xinclude_cycle_is_an_error() {
+        let mut resolver = crate::scxml_reader::MapResourceResolver::new();
+        resolver.add(
+            "a.scxml",
+            "<state id='A'><include href='b.scxml' parse='text'/></state>",
+        );
+        resolver.add(
+            "b.scxml",
+            "<state id='B'><include href='a.scxml' parse='text'/></state>",
+        );
+        let r = crate::scxml_reader::parse_from_xml_with_resolver(
+            "<scxml initial='A'><include href='a.scxml' parse='text'/></scxml>".to_string(),
+            Box::new(resolver),
+        );
+        assert!(r.is_err(), "Shall result in error");
+        assert!(r
+            .err()
+            .unwrap()
+            .contains("Include cycle detected: a.scxml -> b.scxml -> a.scxml"));
+
+        // Chains of distinct documents are limited in depth.
+        let mut resolver = crate::scxml_reader::MapResourceResolver::new();
+        for level in 0..=crate::scxml_reader::MAX_INCLUDE_DEPTH {
+            resolver.add(
+                format!("{}.scxml", level).as_str(),
+                format!(
+                    "<state id='S{}'><include href='{}.scxml' parse='text'/></state>",
+                    level,
+                    level + 1
+                )
+                .as_str(),
+            );
+        }
+        let r = crate::scxml_reader::parse_from_xml_with_resolver(
+            "<scxml initial='S0'><include href='0.scxml' parse='text'/></scxml>".to_string(),
+            Box::new(resolver),
+        );
+        assert!(r
+            .err()
+            .unwrap()
+            .contains("Includes are nested deeper than 32 levels"));
+    }
+
     #[test]
     fn initial_attribute() {
         let _r = crate::scxml_reader::parse_from_xml(
@@ -2122,10 +3371,225 @@ mod tests {
         );
     }
 
+    #[test]
+    fn send_with_illegal_delay_shall_fail() {
+        let r = crate::scxml_reader::parse_from_xml(
+            "<scxml><state><onentry><send event='e' delay='abc'/></onentry></state></scxml>".to_string(),
+        );
+        assert!(r.is_err(), "Shall result in error");
+        assert!(r.err().unwrap().contains("illegal value 'abc'"));
+
+        let r = crate::scxml_reader::parse_from_xml(
+            "<scxml><state><onentry><send event='e' delay='1.5s'/></onentry></state></scxml>".to_string(),
+        );
+        assert!(r.is_ok());
+    }
+
     #[test]
     fn assign_without_expr_and_content() {
         let _r = crate::scxml_reader::parse_from_xml(
             "<scxml><state><transition><assign location='x'>123</assign></transition></state></scxml>".to_string(),
         );
     }
+
+    #[test]
+    fn parse_from_reader_equals_string_variant() {
+        let xml = "<scxml initial='s0' name='stream'><state id='s0'>\
+            <onentry><log expr='\"hello\"'/></onentry>\
+            <transition event='go' target='s1'/></state><final id='s1'/></scxml>";
+
+        let from_string = crate::scxml_reader::parse_from_xml(xml.to_string()).unwrap();
+        let from_reader =
+            crate::scxml_reader::parse_from_reader(std::io::Cursor::new(xml.as_bytes().to_vec())).unwrap();
+
+        assert_eq!(from_reader.name, "stream");
+        assert_eq!(from_reader.states.len(), from_string.states.len());
+        assert!(crate::fsm_diff::fsm_diff(&from_string, &from_reader).is_empty());
+
+        let r = crate::scxml_reader::parse_from_reader(std::io::Cursor::new(vec![0xffu8, 0xfe]));
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn unknown_target_is_an_error() {
+        let r = crate::scxml_reader::parse_from_xml(
+            "<scxml initial='s0'><state id='s0'><transition event='go' target='s1 sl'/></state>\
+             <state id='s1'/></scxml>"
+                .to_string(),
+        );
+        assert!(r.is_err(), "Shall result in error");
+        assert!(r
+            .err()
+            .unwrap()
+            .contains("Unknown target state 'sl' of transition in state 's0'"));
+
+        let r = crate::scxml_reader::parse_from_xml("<scxml initial='s2'><state id='s0'/></scxml>".to_string());
+        assert!(r.is_err(), "Unknown initial state shall result in error");
+    }
+
+    #[test]
+    fn targets_are_resolved_after_all_states_are_declared() {
+        let fsm = crate::scxml_reader::parse_from_xml(
+            "<scxml initial='p'><parallel id='p'>\
+             <transition event='go' target=' a2\n\tb2 '/>\
+             <state id='a'><state id='a1'/><state id='a2'/></state>\
+             <state id='b'><state id='b1'/><state id='b2'/></state>\
+             </parallel></scxml>"
+                .to_string(),
+        )
+        .unwrap();
+        let names = |ids: &Vec<crate::fsm::StateId>| {
+            ids.iter()
+                .map(|sid| fsm.get_state_by_id(*sid).name.clone())
+                .collect::<Vec<String>>()
+        };
+        let p = fsm.get_state_by_name(&"p".to_string());
+        let go = fsm.get_transition_by_id(*p.transitions.iterator().next().unwrap());
+        assert_eq!(names(&go.target), vec!["a2".to_string(), "b2".to_string()]);
+        // No placeholder states are created.
+        assert!(fsm.states.iter().all(|state| state.doc_id != 0));
+    }
+
+    #[test]
+    #[cfg(feature = "ECMAScriptModel")]
+    fn initial_transition_is_unconditional() {
+        let fsm = crate::scxml_reader::parse_from_xml(
+            "<scxml initial='s' datamodel='ecmascript'><state id='s'>\
+             <initial><transition target='s2'><raise event='init'/></transition></initial>\
+             <state id='s1'/><state id='s2'><transition event='init' target='pass'/></state>\
+             </state><final id='pass'/></scxml>"
+                .to_string(),
+        )
+        .unwrap();
+        let global = crate::fsm::run_blocking(fsm, Vec::new());
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["pass".to_string()])
+        );
+    }
+
+    #[test]
+    fn cond_or_event_of_initial_transition_is_an_error() {
+        for (parent, attribute) in [
+            (
+                "<initial><transition cond='false' target='s1'/></initial>",
+                "cond",
+            ),
+            (
+                "<initial><transition event='go' target='s1'/></initial>",
+                "event",
+            ),
+        ] {
+            let r = crate::scxml_reader::parse_from_xml(format!(
+                "<scxml initial='s'><state id='s'>{}<state id='s1'/></state></scxml>",
+                parent
+            ));
+            let err = r.err().unwrap();
+            assert!(
+                err.contains(&format!("must not have attribute '{}'", attribute)),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn cond_of_history_transition_is_accepted() {
+        let r = crate::scxml_reader::parse_from_xml(
+            "<scxml initial='s'><state id='s'><history id='h'><transition cond='true' target='s1'/></history>\
+             <state id='s1'/></state></scxml>"
+                .to_string(),
+        );
+        assert!(r.is_ok(), "{:?}", r.err());
+    }
+
+    #[test]
+    fn reparse_state_replaces_only_the_content_of_the_state() {
+        let xml = "<scxml initial='a' datamodel='null'>\
+            <state id='a'><onentry><log label='a'/></onentry><transition event='go' target='b'/></state>\
+            <state id='b'><onentry><log label='b'/></onentry><transition event='next' target='a'/></state>\
+            <final id='c'/></scxml>";
+        let original = crate::scxml_reader::parse_from_xml(xml.to_string()).unwrap();
+        let mut fsm = crate::scxml_reader::parse_from_xml(xml.to_string()).unwrap();
+
+        crate::scxml_reader::reparse_state(
+            &mut fsm,
+            "b",
+            "<state id='b'><onexit><if cond='true'><log label='b'/></if></onexit>\
+             <transition event='next' target='c'/></state>",
+        )
+        .unwrap();
+
+        let diff = crate::fsm_diff::fsm_diff(&original, &fsm);
+        assert_eq!(
+            diff.to_string(),
+            "~ state b: onentry, onexit\n\
+             + transition b -[next]-> c\n\
+             - transition b -[next]-> a\n"
+        );
+        assert_eq!(
+            fsm.transitions_from("a")
+                .iter()
+                .map(|t| t.target.clone())
+                .collect::<Vec<_>>(),
+            vec![vec!["b".to_string()]]
+        );
+        // The old content of "b" is removed, only the content of "a" and the new content of "b" is left.
+        let mut blocks: Vec<String> = fsm
+            .executableContent
+            .values()
+            .map(|block| {
+                block
+                    .iter()
+                    .map(|ec| ec.describe().type_name)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect();
+        blocks.sort();
+        // The transitions have empty blocks.
+        assert_eq!(blocks, vec!["", "", "if", "log", "log"]);
+
+        let global = crate::fsm::run_blocking(
+            fsm,
+            [
+                crate::fsm::Event::new_simple("go"),
+                crate::fsm::Event::new_simple("next"),
+            ],
+        );
+        assert_eq!(
+            global.lock().unwrap().final_configuration,
+            Some(vec!["c".to_string()])
+        );
+    }
+
+    #[test]
+    fn reparse_state_errors_keep_the_fsm() {
+        let xml = "<scxml initial='a' datamodel='null'>\
+            <state id='a'><transition event='go' target='b'/></state>\
+            <state id='b'><onentry><log label='b'/></onentry></state></scxml>";
+        let original = crate::scxml_reader::parse_from_xml(xml.to_string()).unwrap();
+        let mut fsm = crate::scxml_reader::parse_from_xml(xml.to_string()).unwrap();
+
+        for (state, element) in [
+            ("x", "<state id='x'/>"),
+            ("b", "<state id='a'/>"),
+            ("b", "<parallel id='b'/>"),
+            ("b", "<state id='b'/><state id='b'/>"),
+            ("b", "<state id='b'><state id='b1'/></state>"),
+            (
+                "b",
+                "<state id='b'><onentry><log label='x'/></onentry><transition target='unknown'/></state>",
+            ),
+            ("b", "<state id='b'><onentry><raise/></onentry></state>"),
+        ] {
+            let r = crate::scxml_reader::reparse_state(&mut fsm, state, element);
+            assert!(r.is_err(), "{} shall fail", element);
+            assert!(crate::fsm_diff::fsm_diff(&original, &fsm).is_empty());
+            assert_eq!(
+                fsm.executableContent.len(),
+                original.executableContent.len()
+            );
+        }
+    }
 }
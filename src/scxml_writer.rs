@@ -0,0 +1,784 @@
+//! Writes a FSM as SCXML document.\
+//! Counterpart of the [scxml_reader](crate::scxml_reader). Useful for tools that modify FSMs
+//! programmatically and need to store them as SCXML again.\
+//! The document is reconstructed from the FSM model, so formatting, comments and
+//! \<data\> children (written as 'expr') are not preserved.
+
+use crate::datamodel::Data;
+use crate::executable_content::{
+    Assign, Cancel, ExecutableContent, Expression, ForEach, If, Log, Raise, Script, SendParameters, TYPE_ASSIGN,
+    TYPE_CANCEL, TYPE_EXPRESSION, TYPE_FOREACH, TYPE_IF, TYPE_LOG, TYPE_RAISE, TYPE_SCRIPT, TYPE_SEND,
+};
+use crate::fsm::{
+    BindingType, CommonContent, DoneData, ExecutableContentId, Fsm, HistoryType, Invoke, Parameter, State, StateId,
+    TransitionId, TransitionType,
+};
+use crate::scxml_reader::{
+    ATTR_ARRAY, ATTR_AUTOFORWARD, ATTR_BINDING, ATTR_COND, ATTR_DATAMODEL, ATTR_DELAY, ATTR_DELAYEXPR, ATTR_EVENT,
+    ATTR_EVENTEXPR, ATTR_EXPR, ATTR_ID, ATTR_IDLOCATION, ATTR_INDEX, ATTR_INITIAL, ATTR_ITEM, ATTR_LABEL,
+    ATTR_LOCATION, ATTR_NAME, ATTR_NAMELIST, ATTR_SENDID, ATTR_SENDIDEXPR, ATTR_SRC, ATTR_SRCEXPR, ATTR_TARGET,
+    ATTR_TARGETEXPR, ATTR_TYPE, ATTR_TYPEEXPR, TAG_ASSIGN, TAG_CANCEL, TAG_CONTENT, TAG_DATA, TAG_DATAMODEL,
+    TAG_DONEDATA, TAG_ELSE, TAG_ELSEIF, TAG_EVENT, TAG_FINAL, TAG_FINALIZE, TAG_FOR_EACH, TAG_HISTORY, TAG_IF,
+    TAG_INITIAL, TAG_INVOKE, TAG_LOG, TAG_ON_ENTRY, TAG_ON_EXIT, TAG_PARALLEL, TAG_PARAM, TAG_RAISE, TAG_SCRIPT,
+    TAG_SCXML, TAG_SEND, TAG_STATE, TAG_TRANSITION, TAG_TYPE, TAG_VERSION,
+};
+
+pub const SCXML_NAMESPACE: &str = "http://www.w3.org/2005/07/scxml";
+
+const INDENT: &str = "  ";
+
+/// Writes the FSM as SCXML document.
+pub fn to_scxml(fsm: &Fsm) -> String {
+    let mut writer = ScxmlWriter::new(fsm);
+    writer.write_fsm();
+    writer.buffer
+}
+
+fn get_executable_content_as<T: 'static>(ec: &dyn ExecutableContent) -> &T {
+    let va = ec.as_any();
+    va.downcast_ref::<T>()
+        .unwrap_or_else(|| panic!("Failed to cast executable content"))
+}
+
+/// Gets the source of an attribute value or None if the value was not specified.
+fn source_of(data: &Data) -> Option<String> {
+    match data {
+        Data::None() | Data::Null() => None,
+        Data::Source(src) => {
+            if src.is_empty() {
+                None
+            } else {
+                Some(src.source.clone())
+            }
+        }
+        other => Some(other.to_string()),
+    }
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+struct ScxmlWriter<'a> {
+    fsm: &'a Fsm,
+    buffer: String,
+    level: usize,
+}
+
+impl<'a> ScxmlWriter<'a> {
+    fn new(fsm: &'a Fsm) -> ScxmlWriter<'a> {
+        ScxmlWriter {
+            fsm,
+            buffer: String::with_capacity(4096),
+            level: 0,
+        }
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.level {
+            self.buffer.push_str(INDENT);
+        }
+    }
+
+    /// Writes a start tag. Attributes without value are skipped.\
+    /// If "empty" is true, the element is closed immediately.
+    fn start_tag(&mut self, tag: &str, attributes: &[(&str, Option<String>)], empty: bool) {
+        self.indent();
+        self.buffer.push('<');
+        self.buffer.push_str(tag);
+        for (name, value) in attributes {
+            if let Some(v) = value {
+                self.buffer
+                    .push_str(&format!(" {}=\"{}\"", name, escape_attribute(v)));
+            }
+        }
+        if empty {
+            self.buffer.push_str("/>\n");
+        } else {
+            self.buffer.push_str(">\n");
+            self.level += 1;
+        }
+    }
+
+    fn end_tag(&mut self, tag: &str) {
+        self.level -= 1;
+        self.indent();
+        self.buffer.push_str(&format!("</{}>\n", tag));
+    }
+
+    /// Writes an element with verbatim (already XML-encoded) text content.
+    fn text_element(&mut self, tag: &str, text: &str) {
+        self.indent();
+        self.buffer
+            .push_str(&format!("<{}>{}</{}>\n", tag, text, tag));
+    }
+
+    fn state_names(&self, states: &[StateId]) -> String {
+        states
+            .iter()
+            .map(|sid| self.fsm.get_state_by_id(*sid).name.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    fn has_content(&self, content_id: ExecutableContentId) -> bool {
+        content_id != 0
+            && self
+                .fsm
+                .executableContent
+                .get(&content_id)
+                .is_some_and(|c| !c.is_empty())
+    }
+
+    fn write_fsm(&mut self) {
+        let root = self.fsm.get_state_by_id(self.fsm.pseudo_root);
+
+        let initial = if root.initial != 0 {
+            let t = self.fsm.get_transition_by_id(root.initial);
+            // Default initial transitions (without document id) are not part of the document.
+            if t.doc_id != 0 {
+                Some(self.state_names(&t.target))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+        let name = if self.fsm.name.is_empty() {
+            None
+        } else {
+            Some(self.fsm.name.clone())
+        };
+
+        self.start_tag(
+            TAG_SCXML,
+            &[
+                ("xmlns", Some(SCXML_NAMESPACE.to_string())),
+                (TAG_VERSION, Some(self.fsm.version.clone())),
+                (ATTR_NAME, name),
+                (ATTR_DATAMODEL, Some(self.fsm.datamodel.clone())),
+                (
+                    ATTR_BINDING,
+                    if self.fsm.binding == BindingType::Late {
+                        Some("late".to_string())
+                    } else {
+                        None
+                    },
+                ),
+                (ATTR_INITIAL, initial),
+            ],
+            false,
+        );
+        self.write_datamodel(root);
+        if self.has_content(self.fsm.script) {
+            self.write_executable_content(self.fsm.script);
+        }
+        for sid in &root.states {
+            self.write_state(self.fsm.get_state_by_id(*sid));
+        }
+        self.end_tag(TAG_SCXML);
+    }
+
+    fn write_datamodel(&mut self, state: &State) {
+        if state.data.is_empty() {
+            return;
+        }
+        self.start_tag(TAG_DATAMODEL, &[], false);
+        // Data is held in a map, write it in a stable order.
+        let mut names: Vec<&String> = state.data.keys().collect();
+        names.sort();
+        for name in names {
+            let expr = source_of(&state.data.get(name).unwrap().lock().unwrap());
+            self.start_tag(
+                TAG_DATA,
+                &[(ATTR_ID, Some(name.clone())), (ATTR_EXPR, expr)],
+                true,
+            );
+        }
+        self.end_tag(TAG_DATAMODEL);
+    }
+
+    fn write_state(&mut self, state: &State) {
+        let tag = if state.is_final {
+            TAG_FINAL
+        } else if state.is_parallel {
+            TAG_PARALLEL
+        } else {
+            TAG_STATE
+        };
+
+        let mut initial_attribute = None;
+        let mut initial_transition = None;
+        if state.initial != 0 {
+            let t = self.fsm.get_transition_by_id(state.initial);
+            if t.doc_id != 0 {
+                if self.has_content(t.content) {
+                    initial_transition = Some(state.initial);
+                } else {
+                    initial_attribute = Some(self.state_names(&t.target));
+                }
+            }
+        }
+
+        let empty = initial_transition.is_none()
+            && state.data.is_empty()
+            && state.onentry.is_empty()
+            && state.onexit.is_empty()
+            && state.transitions.size() == 0
+            && state.history.size() == 0
+            && state.states.is_empty()
+            && state.invoke.size() == 0
+            && state.donedata.is_none();
+
+        self.start_tag(
+            tag,
+            &[
+                (ATTR_ID, Some(state.name.clone())),
+                (ATTR_INITIAL, initial_attribute),
+            ],
+            empty,
+        );
+        if empty {
+            return;
+        }
+
+        if let Some(tid) = initial_transition {
+            self.start_tag(TAG_INITIAL, &[], false);
+            self.write_transition(tid);
+            self.end_tag(TAG_INITIAL);
+        }
+        self.write_datamodel(state);
+        for ec_id in &state.onentry {
+            self.write_executable_content_block(TAG_ON_ENTRY, *ec_id);
+        }
+        for ec_id in &state.onexit {
+            self.write_executable_content_block(TAG_ON_EXIT, *ec_id);
+        }
+        for tid in state.transitions.iterator() {
+            self.write_transition(*tid);
+        }
+        for hid in state.history.iterator() {
+            self.write_history(self.fsm.get_state_by_id(*hid));
+        }
+        for sid in &state.states {
+            self.write_state(self.fsm.get_state_by_id(*sid));
+        }
+        for invoke in state.invoke.iterator() {
+            self.write_invoke(invoke);
+        }
+        if let Some(done_data) = &state.donedata {
+            self.write_done_data(done_data);
+        }
+        self.end_tag(tag);
+    }
+
+    fn write_history(&mut self, state: &State) {
+        let history_type = match state.history_type {
+            HistoryType::Deep => Some("deep".to_string()),
+            HistoryType::Shallow | HistoryType::None => None,
+        };
+        let empty = state.transitions.size() == 0;
+        self.start_tag(
+            TAG_HISTORY,
+            &[
+                (ATTR_ID, Some(state.name.clone())),
+                (TAG_TYPE, history_type),
+            ],
+            empty,
+        );
+        if !empty {
+            for tid in state.transitions.iterator() {
+                self.write_transition(*tid);
+            }
+            self.end_tag(TAG_HISTORY);
+        }
+    }
+
+    fn write_transition(&mut self, tid: TransitionId) {
+        let t = self.fsm.get_transition_by_id(tid);
+        let events = if t.events.is_empty() {
+            None
+        } else {
+            Some(t.source_event_descriptors().join(" "))
+        };
+        let target = if t.target.is_empty() {
+            None
+        } else {
+            Some(self.state_names(&t.target))
+        };
+        let transition_type = if t.transition_type == TransitionType::Internal {
+            Some("internal".to_string())
+        } else {
+            None
+        };
+        let has_content = self.has_content(t.content);
+        self.start_tag(
+            TAG_TRANSITION,
+            &[
+                (TAG_EVENT, events),
+                (ATTR_COND, source_of(&t.cond)),
+                (ATTR_TARGET, target),
+                (TAG_TYPE, transition_type),
+            ],
+            !has_content,
+        );
+        if has_content {
+            self.write_executable_content(t.content);
+            self.end_tag(TAG_TRANSITION);
+        }
+    }
+
+    fn write_invoke(&mut self, invoke: &Invoke) {
+        let id = if invoke.invoke_id.is_empty() {
+            None
+        } else {
+            Some(invoke.invoke_id.clone())
+        };
+        let id_location = if invoke.external_id_location.is_empty() {
+            None
+        } else {
+            Some(invoke.external_id_location.clone())
+        };
+        let has_finalize = self.has_content(invoke.finalize);
+        let empty = invoke.params.is_none() && invoke.content.is_none() && !has_finalize;
+        self.start_tag(
+            TAG_INVOKE,
+            &[
+                (ATTR_ID, id),
+                (ATTR_IDLOCATION, id_location),
+                (ATTR_TYPE, source_of(&invoke.type_name)),
+                (ATTR_TYPEEXPR, source_of(&invoke.type_expr)),
+                (ATTR_SRC, source_of(&invoke.src)),
+                (ATTR_SRCEXPR, source_of(&invoke.src_expr)),
+                (ATTR_NAMELIST, Self::name_list(&invoke.name_list)),
+                (
+                    ATTR_AUTOFORWARD,
+                    if invoke.autoforward {
+                        Some("true".to_string())
+                    } else {
+                        None
+                    },
+                ),
+            ],
+            empty,
+        );
+        if !empty {
+            self.write_params(&invoke.params);
+            if let Some(content) = &invoke.content {
+                self.write_common_content(content);
+            }
+            if has_finalize {
+                self.write_executable_content_block(TAG_FINALIZE, invoke.finalize);
+            }
+            self.end_tag(TAG_INVOKE);
+        }
+    }
+
+    fn write_done_data(&mut self, done_data: &DoneData) {
+        self.start_tag(TAG_DONEDATA, &[], false);
+        if let Some(content) = &done_data.content {
+            self.write_common_content(content);
+        }
+        self.write_params(&done_data.params);
+        self.end_tag(TAG_DONEDATA);
+    }
+
+    fn write_common_content(&mut self, content: &CommonContent) {
+        match &content.content {
+            Some(text) => {
+                // The reader keeps the content as it is in the document, so no encoding is needed.
+                self.text_element(TAG_CONTENT, text);
+            }
+            None => {
+                self.start_tag(
+                    TAG_CONTENT,
+                    &[(ATTR_EXPR, content.content_expr.clone())],
+                    true,
+                );
+            }
+        }
+    }
+
+    fn write_params(&mut self, params: &Option<Vec<Parameter>>) {
+        if let Some(params) = params {
+            for param in params {
+                let expr = if param.expr.is_empty() {
+                    None
+                } else {
+                    Some(param.expr.clone())
+                };
+                let location = if param.location.is_empty() {
+                    None
+                } else {
+                    Some(param.location.clone())
+                };
+                self.start_tag(
+                    TAG_PARAM,
+                    &[
+                        (ATTR_NAME, Some(param.name.clone())),
+                        (ATTR_EXPR, expr),
+                        (ATTR_LOCATION, location),
+                    ],
+                    true,
+                );
+            }
+        }
+    }
+
+    fn name_list(names: &[String]) -> Option<String> {
+        if names.is_empty() {
+            None
+        } else {
+            Some(names.join(" "))
+        }
+    }
+
+    /// Writes a block like \<onentry\> with the content of the given region.
+    fn write_executable_content_block(&mut self, tag: &str, content_id: ExecutableContentId) {
+        if self.has_content(content_id) {
+            self.start_tag(tag, &[], false);
+            self.write_executable_content(content_id);
+            self.end_tag(tag);
+        } else {
+            self.start_tag(tag, &[], true);
+        }
+    }
+
+    /// Writes all elements of an executable content region.
+    fn write_executable_content(&mut self, content_id: ExecutableContentId) {
+        if let Some(content) = self.fsm.executableContent.get(&content_id) {
+            for ec in content {
+                self.write_executable_content_element(ec.as_ref());
+            }
+        }
+    }
+
+    fn write_executable_content_element(&mut self, ec: &dyn ExecutableContent) {
+        match ec.get_type() {
+            TYPE_IF => self.write_if(get_executable_content_as::<If>(ec)),
+            TYPE_EXPRESSION => {
+                let expression = get_executable_content_as::<Expression>(ec);
+                // Script source is taken verbatim from the document by the reader.
+                self.text_element(TAG_SCRIPT, &expression.content.to_string());
+            }
+            TYPE_SCRIPT => {
+                for content_id in &get_executable_content_as::<Script>(ec).content {
+                    self.write_executable_content(*content_id);
+                }
+            }
+            TYPE_LOG => {
+                let log = get_executable_content_as::<Log>(ec);
+                let label = if log.label.is_empty() {
+                    None
+                } else {
+                    Some(log.label.clone())
+                };
+                self.start_tag(
+                    TAG_LOG,
+                    &[(ATTR_LABEL, label), (ATTR_EXPR, source_of(&log.expression))],
+                    true,
+                );
+            }
+            TYPE_FOREACH => {
+                let for_each = get_executable_content_as::<ForEach>(ec);
+                let index = if for_each.index.is_empty() {
+                    None
+                } else {
+                    Some(for_each.index.clone())
+                };
+                let has_content = self.has_content(for_each.content);
+                self.start_tag(
+                    TAG_FOR_EACH,
+                    &[
+                        (ATTR_ARRAY, source_of(&for_each.array)),
+                        (ATTR_ITEM, Some(for_each.item.clone())),
+                        (ATTR_INDEX, index),
+                    ],
+                    !has_content,
+                );
+                if has_content {
+                    self.write_executable_content(for_each.content);
+                    self.end_tag(TAG_FOR_EACH);
+                }
+            }
+            TYPE_SEND => self.write_send(get_executable_content_as::<SendParameters>(ec)),
+            TYPE_RAISE => {
+                let raise = get_executable_content_as::<Raise>(ec);
+                self.start_tag(TAG_RAISE, &[(ATTR_EVENT, Some(raise.event.clone()))], true);
+            }
+            TYPE_CANCEL => {
+                let cancel = get_executable_content_as::<Cancel>(ec);
+                let send_id = if cancel.send_id.is_empty() {
+                    None
+                } else {
+                    Some(cancel.send_id.clone())
+                };
+                self.start_tag(
+                    TAG_CANCEL,
+                    &[
+                        (ATTR_SENDID, send_id),
+                        (ATTR_SENDIDEXPR, source_of(&cancel.send_id_expr)),
+                    ],
+                    true,
+                );
+            }
+            TYPE_ASSIGN => {
+                let assign = get_executable_content_as::<Assign>(ec);
+                self.start_tag(
+                    TAG_ASSIGN,
+                    &[
+                        (ATTR_LOCATION, source_of(&assign.location)),
+                        (ATTR_EXPR, source_of(&assign.expr)),
+                    ],
+                    true,
+                );
+            }
+            ut => {
+                panic!("Unknown Executable Content: {}", ut)
+            }
+        }
+    }
+
+    fn write_if(&mut self, ec_if: &If) {
+        self.start_tag(TAG_IF, &[(ATTR_COND, source_of(&ec_if.condition))], false);
+        self.write_executable_content(ec_if.content);
+
+        let mut else_content = ec_if.else_content;
+        while else_content != 0 {
+            // An "else" region with only an "if" is written as "elseif".
+            let else_if = match self.fsm.executableContent.get(&else_content) {
+                Some(content) if content.len() == 1 && content[0].get_type() == TYPE_IF => {
+                    Some(get_executable_content_as::<If>(content[0].as_ref()))
+                }
+                _ => None,
+            };
+            match else_if {
+                Some(else_if) => {
+                    self.start_tag(
+                        TAG_ELSEIF,
+                        &[(ATTR_COND, source_of(&else_if.condition))],
+                        true,
+                    );
+                    self.write_executable_content(else_if.content);
+                    else_content = else_if.else_content;
+                }
+                None => {
+                    self.start_tag(TAG_ELSE, &[], true);
+                    self.write_executable_content(else_content);
+                    else_content = 0;
+                }
+            }
+        }
+        self.end_tag(TAG_IF);
+    }
+
+    fn write_send(&mut self, send: &SendParameters) {
+        let id = if send.name.is_empty() {
+            None
+        } else {
+            Some(send.name.clone())
+        };
+        let id_location = if send.name_location.is_empty() {
+            None
+        } else {
+            Some(send.name_location.clone())
+        };
+        let delay = if send.delay_ms > 0 {
+            Some(format!("{}ms", send.delay_ms))
+        } else {
+            None
+        };
+        let empty = send.params.is_none() && send.content.is_none();
+        self.start_tag(
+            TAG_SEND,
+            &[
+                (ATTR_ID, id),
+                (ATTR_IDLOCATION, id_location),
+                (ATTR_EVENT, source_of(&send.event)),
+                (ATTR_EVENTEXPR, source_of(&send.event_expr)),
+                (ATTR_TARGET, source_of(&send.target)),
+                (ATTR_TARGETEXPR, source_of(&send.target_expr)),
+                (ATTR_TYPE, source_of(&send.type_value)),
+                (ATTR_TYPEEXPR, source_of(&send.type_expr)),
+                (ATTR_DELAY, delay),
+                (ATTR_DELAYEXPR, source_of(&send.delay_expr)),
+                (ATTR_NAMELIST, Self::name_list(&send.name_list)),
+            ],
+            empty,
+        );
+        if !empty {
+            self.write_params(&send.params);
+            if let Some(content) = &send.content {
+                self.write_common_content(content);
+            }
+            self.end_tag(TAG_SEND);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fsm::Fsm;
+    use crate::scxml_reader::parse_from_xml;
+    use crate::scxml_writer::to_scxml;
+
+    const CHART: &str = r#"<scxml initial="main" datamodel="ecmascript" name="roundtrip" version="1.0">
+  <datamodel><data id="count" expr="0"/><data id="items" expr="[1,2,3]"/></datamodel>
+  <script>var sum = 0;</script>
+  <state id="main">
+    <initial><transition target="work"><log label="init" expr="'start'"/></transition></initial>
+    <onexit><assign location="count" expr="count + 1"/></onexit>
+    <history id="hist" type="deep"><transition target="work"/></history>
+    <state id="work">
+      <onentry>
+        <foreach array="items" item="item" index="i"><assign location="sum" expr="sum + item"/></foreach>
+        <if cond="sum == 6"><raise event="summed"/><elseif cond="sum &lt; 6"/><raise event="low"/><else/><raise event="high"/></if>
+        <send event="later" delay="1s" id="later_id"><param name="p" expr="1"/></send>
+        <cancel sendid="later_id"/>
+      </onentry>
+      <transition event="summed" target="par" type="internal"/>
+      <transition event="low high" target="fail"/>
+    </state>
+    <parallel id="par">
+      <state id="a"><transition cond="In('b')" target="done"/></state>
+      <state id="b"/>
+    </parallel>
+    <state id="unused">
+      <invoke type="scxml" id="child" autoforward="true">
+        <content><scxml><final id="f"/></scxml></content>
+        <finalize><log expr="'fin'"/></finalize>
+      </invoke>
+    </state>
+    <final id="done"><donedata><param name="result" expr="sum"/></donedata></final>
+    <transition event="done.state.main" cond="count == 0" target="pass"/>
+  </state>
+  <final id="pass"/>
+  <final id="fail"/>
+</scxml>"#;
+
+    /// Describes the structure of the FSM, independent of internal ids.
+    fn describe(fsm: &Fsm) -> Vec<String> {
+        let names = |ids: &[u32]| {
+            ids.iter()
+                .map(|id| fsm.get_state_by_id(*id).name.clone())
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+        let content_size = |id: u32| fsm.executableContent.get(&id).map_or(0, |c| c.len());
+        let mut lines = Vec::new();
+        for state in &fsm.states {
+            if state.id == fsm.pseudo_root {
+                continue;
+            }
+            let mut line = format!(
+                "{} parent={} parallel={} final={} history={:?} states=[{}] onentry={:?} onexit={:?} invokes={} donedata={}",
+                state.name,
+                if state.parent == fsm.pseudo_root {
+                    String::new()
+                } else {
+                    fsm.get_state_by_id(state.parent).name.clone()
+                },
+                state.is_parallel,
+                state.is_final,
+                state.history_type,
+                names(&state.states),
+                state.onentry.iter().map(|id| content_size(*id)).collect::<Vec<usize>>(),
+                state.onexit.iter().map(|id| content_size(*id)).collect::<Vec<usize>>(),
+                state.invoke.size(),
+                state.donedata.is_some(),
+            );
+            if state.initial != 0 {
+                let t = fsm.get_transition_by_id(state.initial);
+                line.push_str(&format!(
+                    " initial=[{}] {}",
+                    names(&t.target),
+                    content_size(t.content)
+                ));
+            }
+            for tid in state.transitions.iterator() {
+                let t = fsm.get_transition_by_id(*tid);
+                line.push_str(&format!(
+                    " | {:?} cond={} target=[{}] {:?} content={}",
+                    t.events,
+                    t.cond,
+                    names(&t.target),
+                    t.transition_type,
+                    content_size(t.content)
+                ));
+            }
+            lines.push(line);
+        }
+        lines.sort();
+        lines
+    }
+
+    #[test]
+    fn round_trip() {
+        let fsm = parse_from_xml(CHART.to_string()).unwrap();
+        let xml = to_scxml(&fsm);
+        let fsm2 = parse_from_xml(xml.clone()).unwrap();
+
+        assert_eq!(describe(&fsm), describe(&fsm2));
+        assert_eq!(fsm.name, fsm2.name);
+        assert_eq!(fsm.datamodel, fsm2.datamodel);
+        // Writing the re-read FSM shall give the same document.
+        assert_eq!(xml, to_scxml(&fsm2));
+
+        for expected in [
+            "<elseif cond=\"sum &lt; 6\"/>",
+            "<else/>",
+            "<send id=\"later_id\" event=\"later\" delay=\"1000ms\">",
+            "<foreach array=\"items\" item=\"item\" index=\"i\">",
+            "<history id=\"hist\" type=\"deep\">",
+            "<invoke id=\"child\" type=\"scxml\" autoforward=\"true\">",
+            "<content><scxml><final id=\"f\"/></scxml></content>",
+        ] {
+            assert!(
+                xml.contains(expected),
+                "'{}' missing in:\n{}",
+                expected,
+                xml
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "ECMAScriptModel")]
+    fn round_trip_runs() {
+        let fsm = parse_from_xml(CHART.to_string()).unwrap();
+        let fsm2 = parse_from_xml(to_scxml(&fsm)).unwrap();
+        assert!(crate::test::run_test_manual(
+            "round_trip_runs",
+            &std::collections::HashMap::new(),
+            fsm2,
+            &Vec::new(),
+            #[cfg(feature = "Trace")]
+            crate::tracer::TraceMode::STATES,
+            2000,
+            &vec!["pass".to_string()],
+        ));
+    }
+
+    #[test]
+    fn original_event_descriptors_are_written() {
+        let fsm = parse_from_xml(
+            "<scxml initial='s'><state id='s'><transition event='error.* done. go' target='f'/></state><final id='f'/></scxml>"
+                .to_string(),
+        )
+        .unwrap();
+        let t = fsm
+            .transitions
+            .values()
+            .find(|t| !t.events.is_empty())
+            .unwrap();
+        assert_eq!(t.events, vec!["error", "done", "go"]);
+        assert_eq!(t.event_descriptors, vec!["error.*", "done.", "go"]);
+
+        let xml = to_scxml(&fsm);
+        assert!(xml.contains("event=\"error.* done. go\""), "{}", xml);
+    }
+}
@@ -14,8 +14,8 @@ use crate::executable_content::{
     Assign, Cancel, ExecutableContent, Expression, ForEach, If, Log, Raise, Script, SendParameters,
 };
 use crate::fsm::{
-    BindingType, CommonContent, DocumentId, DoneData, ExecutableContentId, Fsm, HistoryType, Invoke, Parameter, State,
-    StateId, Transition, TransitionId, TransitionType,
+    normalize_event_descriptor, BindingType, CommonContent, DocumentId, DoneData, ExecutableContentId, Fsm,
+    HistoryType, Invoke, Parameter, State, StateId, Transition, TransitionId, TransitionType,
 };
 use crate::serializer::default_protocol_definitions::{
     FSM_PROTOCOL_FLAG_DATA, FSM_PROTOCOL_FLAG_DONE_DATA, FSM_PROTOCOL_FLAG_HISTORY,
@@ -74,7 +74,9 @@ where
                 for _idx2 in 0..content_len {
                     content.push(self.read_executable_content());
                 }
-                fsm.executableContent.insert(content_id, content);
+                fsm.executable_content_mut()
+                    .expect("New FSM has exclusive executable content")
+                    .insert(content_id, content);
             }
 
             let end = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
@@ -212,8 +214,13 @@ where
 
         let events_len = self.reader.read_usize();
         for _idx in 0..events_len {
-            transition.events.push(self.reader.read_string())
+            transition.event_descriptors.push(self.reader.read_string())
         }
+        transition.events = transition
+            .event_descriptors
+            .iter()
+            .map(|e| normalize_event_descriptor(e))
+            .collect();
 
         let flags = self.reader.read_u8();
 
@@ -423,6 +430,7 @@ where
 }
 
 #[cfg(test)]
+#[cfg(feature = "xml")]
 mod tests {
     pub const FSM_SRC: &str = r###"
 <?xml version="1.0" encoding="UTF-8"?>
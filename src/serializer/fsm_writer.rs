@@ -68,7 +68,7 @@ where
         }
 
         self.writer.write_usize(fsm.executableContent.len());
-        for (content_id, content) in &fsm.executableContent {
+        for (content_id, content) in fsm.executableContent.iter() {
             self.write_executable_content_id(*content_id);
             self.writer.write_usize(content.len());
             for executable_content in content {
@@ -181,8 +181,9 @@ where
         for t in &transition.target {
             self.write_state_id(*t);
         }
-        self.writer.write_usize(transition.events.len());
-        for e in &transition.events {
+        let events = transition.source_event_descriptors();
+        self.writer.write_usize(events.len());
+        for e in events {
             self.writer.write_str(e);
         }
         self.writer.write_u8(
@@ -1,7 +1,17 @@
 //! Functions for automated test of FSMs.
 
 use std::collections::HashMap;
+#[cfg(any(
+    feature = "serializer",
+    feature = "yaml-config",
+    feature = "json-config"
+))]
 use std::fs::File;
+#[cfg(any(
+    feature = "serializer",
+    feature = "yaml-config",
+    feature = "json-config"
+))]
 use std::io::BufReader;
 #[cfg(feature = "yaml-config")]
 use std::io::Read;
@@ -23,6 +33,8 @@ use crate::fsm;
 use crate::fsm::{Event, FinishMode, Fsm};
 use crate::fsm_executor::FsmExecutor;
 #[cfg(feature = "xml")]
+use crate::scxml_conformance::expand_conf;
+#[cfg(feature = "xml")]
 use crate::scxml_reader;
 #[cfg(feature = "serializer")]
 use crate::serializer::default_protocol_reader::DefaultProtocolReader;
@@ -75,6 +87,14 @@ pub fn load_fsm(file_path: &str, include_paths: &[PathBuf]) -> Result<Box<Fsm>,
     if extension.eq_ignore_ascii_case("scxml") || extension.eq_ignore_ascii_case("xml") {
         return scxml_reader::parse_from_uri(file_path.to_string(), include_paths);
     }
+    #[cfg(feature = "xml")]
+    if extension.eq_ignore_ascii_case("txml") {
+        // W3C conformance test, uses the "conf" namespace.
+        return match std::fs::read_to_string(file_path) {
+            Ok(txml) => scxml_reader::parse_from_xml_with_includes(expand_conf(&txml)?, include_paths),
+            Err(err) => Err(format!("Failed to read {}. {}", file_path, err)),
+        };
+    }
     #[cfg(feature = "serializer")]
     if extension.eq_ignore_ascii_case("rfsm") {
         return match File::open(file_path) {
@@ -10,7 +10,7 @@ use std::sync::{Arc, Mutex};
 
 use crate::common::ArgOption;
 use crate::fsm;
-use crate::fsm::{Event, OrderedSet, State};
+use crate::fsm::{Event, OrderedSet, SessionId, State, Transition, TransitionId};
 
 /// Trace mode for FSM Tracer.
 #[derive(Debug, Clone, PartialEq, Copy, Hash, Eq)]
@@ -50,23 +50,66 @@ impl TraceMode {
     }
 }
 
+/// Log target for traces of [TraceMode::METHODS].
+pub const TRACE_TARGET_METHODS: &str = "scxml::method";
+/// Log target for traces of [TraceMode::STATES].
+pub const TRACE_TARGET_STATES: &str = "scxml::state";
+/// Log target for traces of [TraceMode::EVENTS].
+pub const TRACE_TARGET_EVENTS: &str = "scxml::event";
+/// Log target for traces of [TraceMode::ARGUMENTS].
+pub const TRACE_TARGET_ARGUMENTS: &str = "scxml::argument";
+/// Log target for traces of [TraceMode::RESULTS].
+pub const TRACE_TARGET_RESULTS: &str = "scxml::result";
+/// Log target for traces without a specific scope.
+pub const TRACE_TARGET_OTHER: &str = "scxml::trace";
+
+impl TraceMode {
+    /// The log target used by [DefaultTracer] for traces of this scope.
+    pub fn log_target(&self) -> &'static str {
+        match self {
+            TraceMode::METHODS => TRACE_TARGET_METHODS,
+            TraceMode::STATES => TRACE_TARGET_STATES,
+            TraceMode::EVENTS => TRACE_TARGET_EVENTS,
+            TraceMode::ARGUMENTS => TRACE_TARGET_ARGUMENTS,
+            TraceMode::RESULTS => TRACE_TARGET_RESULTS,
+            TraceMode::ALL | TraceMode::NONE => TRACE_TARGET_OTHER,
+        }
+    }
+
+    /// The log level used by [DefaultTracer] for traces of this scope.\
+    /// States and events are logged with "info", the more verbose scopes with "debug".
+    pub fn log_level(&self) -> log::Level {
+        match self {
+            TraceMode::METHODS | TraceMode::ARGUMENTS | TraceMode::RESULTS => log::Level::Debug,
+            _ => log::Level::Info,
+        }
+    }
+
+    /// The log target and level used by [DefaultTracer] for traces of this scope.
+    pub fn log_target_and_level(&self) -> (&'static str, log::Level) {
+        (self.log_target(), self.log_level())
+    }
+}
+
 impl Display for TraceMode {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         Debug::fmt(self, f)
     }
 }
 
+/// Case-insensitive. The singular forms ("state", "event", ...) are accepted as aliases.
 impl FromStr for TraceMode {
     type Err = ();
 
     fn from_str(input: &str) -> Result<TraceMode, Self::Err> {
-        match input.to_lowercase().as_str() {
-            "methods" => Ok(TraceMode::METHODS),
-            "states" => Ok(TraceMode::STATES),
-            "events" => Ok(TraceMode::EVENTS),
-            "arguments" => Ok(TraceMode::ARGUMENTS),
-            "results" => Ok(TraceMode::RESULTS),
+        match input.trim().to_lowercase().as_str() {
+            "methods" | "method" => Ok(TraceMode::METHODS),
+            "states" | "state" => Ok(TraceMode::STATES),
+            "events" | "event" => Ok(TraceMode::EVENTS),
+            "arguments" | "argument" => Ok(TraceMode::ARGUMENTS),
+            "results" | "result" => Ok(TraceMode::RESULTS),
             "all" => Ok(TraceMode::ALL),
+            "none" => Ok(TraceMode::NONE),
             _ => Err(()),
         }
     }
@@ -80,6 +123,19 @@ pub trait Tracer: Send + Debug {
     /// Method with a textual representation of the trace-event.
     fn trace(&self, msg: &str);
 
+    /// Called by the default methods below with the scope the message belongs to.
+    /// The default implementation calls [Tracer::trace]. Implementations that need the
+    /// scope (e.g. to map it to some log level) can override this.
+    fn trace_with_mode(&self, _mode: TraceMode, msg: &str) {
+        self.trace(msg);
+    }
+
+    /// Called by FSM before the interpretation starts with the id of the traced session.
+    fn set_session_id(&mut self, _session_id: SessionId) {}
+
+    /// Called by FSM before the interpretation starts with the name of the traced FSM.
+    fn set_session_name(&mut self, _name: &str) {}
+
     /// Enter a sub-scope, e.g. by increase the log indentation.
     fn enter(&self);
 
@@ -98,7 +154,7 @@ pub trait Tracer: Send + Debug {
     /// Called by FSM if a method is entered
     fn enter_method(&self, what: &str) {
         if self.is_trace(TraceMode::METHODS) {
-            self.trace(format!(">>> {}", what).as_str());
+            self.trace_with_mode(TraceMode::METHODS, format!(">>> {}", what).as_str());
             self.enter();
         }
     }
@@ -107,21 +163,25 @@ pub trait Tracer: Send + Debug {
     fn exit_method(&self, what: &str) {
         if self.is_trace(TraceMode::METHODS) {
             self.leave();
-            self.trace(format!("<<< {}", what).as_str());
+            self.trace_with_mode(TraceMode::METHODS, format!("<<< {}", what).as_str());
         }
     }
 
     /// Called by FSM if an internal event is sent
     fn event_internal_send(&self, what: &Event) {
         if self.is_trace(TraceMode::EVENTS) {
-            self.trace(format!("Send Internal Event: {} #{:?}", what.name, what.invoke_id).as_str());
+            self.trace_with_mode(
+                TraceMode::EVENTS,
+                format!("Send Internal Event: {} #{:?}", what.name, what.invoke_id).as_str(),
+            );
         }
     }
 
     /// Called by FSM if an internal event is received
     fn event_internal_received(&self, what: &Event) {
         if self.is_trace(TraceMode::EVENTS) {
-            self.trace(
+            self.trace_with_mode(
+                TraceMode::EVENTS,
                 format!(
                     "Received Internal Event: {}, invokeId {:?}, content {:?}, param {:?}",
                     what.name, what.invoke_id, what.content, what.param_values
@@ -134,7 +194,10 @@ pub trait Tracer: Send + Debug {
     /// Called by FSM if an external event is send
     fn event_external_send(&self, what: &Event) {
         if self.is_trace(TraceMode::EVENTS) {
-            self.trace(format!("Send External Event: {} #{:?}", what.name, what.invoke_id).as_str());
+            self.trace_with_mode(
+                TraceMode::EVENTS,
+                format!("Send External Event: {} #{:?}", what.name, what.invoke_id).as_str(),
+            );
         }
     }
 
@@ -144,15 +207,16 @@ pub trait Tracer: Send + Debug {
             let p = what.name.as_str().split('.').collect::<Vec<&str>>();
             if p.len() == 3 {
                 match TraceMode::from_str(p.get(1).unwrap()) {
-                    Ok(t) => match *p.get(2).unwrap() {
-                        "on" | "ON" | "On" => {
+                    Ok(t) => match p.get(2).unwrap().to_lowercase().as_str() {
+                        "on" => {
                             self.enable_trace(t);
                         }
-                        "off" | "OFF" | "Off" => {
+                        "off" => {
                             self.disable_trace(t);
                         }
                         _ => {
-                            self.trace(
+                            self.trace_with_mode(
+                                TraceMode::EVENTS,
                                 format!(
                                     "Trace event '{}' with illegal flag '{}'. Use 'On' or 'Off'.",
                                     what.name,
@@ -163,7 +227,8 @@ pub trait Tracer: Send + Debug {
                         }
                     },
                     Err(_e) => {
-                        self.trace(
+                        self.trace_with_mode(
+                            TraceMode::EVENTS,
                             format!(
                                 "Trace event '{}' has unknown trace flag '{}'",
                                 what.name,
@@ -176,7 +241,8 @@ pub trait Tracer: Send + Debug {
             }
         }
         if self.is_trace(TraceMode::EVENTS) {
-            self.trace(
+            self.trace_with_mode(
+                TraceMode::EVENTS,
                 format!(
                     "Received External Event: {} #{:?}",
                     what.name, what.invoke_id
@@ -190,9 +256,12 @@ pub trait Tracer: Send + Debug {
     fn trace_state(&self, what: &str, s: &State) {
         if self.is_trace(TraceMode::STATES) {
             if s.name.is_empty() {
-                self.trace(format!("{} #{}", what, s.id).as_str());
+                self.trace_with_mode(TraceMode::STATES, format!("{} #{}", what, s.id).as_str());
             } else {
-                self.trace(format!("{} <{}> #{}", what, &s.name, s.id).as_str());
+                self.trace_with_mode(
+                    TraceMode::STATES,
+                    format!("{} <{}> #{}", what, &s.name, s.id).as_str(),
+                );
             }
         }
     }
@@ -207,17 +276,26 @@ pub trait Tracer: Send + Debug {
         self.trace_state("Exit", s);
     }
 
+    /// Called by FSM for each transition that is taken, before the content of the transition is executed.
+    fn trace_transition(&self, _transition: &Transition) {}
+
     /// Called by FSM for input arguments in methods.
     fn trace_argument(&self, what: &str, d: &dyn Display) {
         if self.is_trace(TraceMode::ARGUMENTS) {
-            self.trace(format!("Argument:{}={}", what, d).as_str());
+            self.trace_with_mode(
+                TraceMode::ARGUMENTS,
+                format!("Argument:{}={}", what, d).as_str(),
+            );
         }
     }
 
     /// Called by FSM for results in methods.
     fn trace_result(&self, what: &str, d: &dyn Display) {
         if self.is_trace(TraceMode::RESULTS) {
-            self.trace(format!("Result:{}={}", what, d).as_str());
+            self.trace_with_mode(
+                TraceMode::RESULTS,
+                format!("Result:{}={}", what, d).as_str(),
+            );
         }
     }
 
@@ -231,13 +309,55 @@ pub trait Tracer: Send + Debug {
         self.trace(format!("{}=({})", what, fsm::vec_to_string(&l.data)).as_str());
     }
 
+    /// Called by FSM to dump the event queues. See [fsm::Fsm::trace_queues].\
+    /// Does nothing if [TraceMode::EVENTS] is not enabled.
+    #[cfg(feature = "Debug_Queue")]
+    fn trace_queues(&self, internal_queue: &fsm::Queue<Event>, external_queue_size: usize) {
+        if !self.is_trace(TraceMode::EVENTS) {
+            return;
+        }
+        self.trace_with_mode(
+            TraceMode::EVENTS,
+            format!("Internal Queue: {} Events", internal_queue.size()).as_str(),
+        );
+        for event in internal_queue.iterator() {
+            self.trace_with_mode(
+                TraceMode::EVENTS,
+                format!(" {} ({})", event.name, event.etype.name()).as_str(),
+            );
+        }
+        self.trace_with_mode(
+            TraceMode::EVENTS,
+            format!("External Queue: {} Events", external_queue_size).as_str(),
+        );
+    }
+
     /// Get trace mode
     fn trace_mode(&self) -> TraceMode;
 }
 
 impl Tracer for DefaultTracer {
     fn trace(&self, msg: &str) {
-        info!("{}{}", DefaultTracer::get_prefix(), msg);
+        self.trace_with_mode(TraceMode::ALL, msg);
+    }
+
+    /// If the build uses "EnvLog" and a logger is installed, the message is logged with the target and
+    /// level of the scope, see [TraceMode::log_target_and_level]. Otherwise it is printed via [info].
+    fn trace_with_mode(&self, mode: TraceMode, msg: &str) {
+        if cfg!(feature = "EnvLog") && log::max_level() != log::LevelFilter::Off {
+            let (target, level) = mode.log_target_and_level();
+            log::log!(target: target, level, "{}", self.format_line(msg));
+        } else {
+            info!("{}", self.format_line(msg));
+        }
+    }
+
+    fn set_session_id(&mut self, session_id: SessionId) {
+        self.session_id = Some(session_id);
+    }
+
+    fn set_session_name(&mut self, name: &str) {
+        self.session_name = Some(name.to_string());
     }
 
     fn enter(&self) {
@@ -284,6 +404,10 @@ impl Tracer for DefaultTracer {
 #[derive(Debug)]
 pub struct DefaultTracer {
     pub trace_flags: HashSet<TraceMode>,
+    /// Session of the traced FSM, if known.
+    pub session_id: Option<SessionId>,
+    /// Name of the traced FSM, if known. If set, each line is prefixed with "\<name\>#session\>".
+    pub session_name: Option<String>,
 }
 
 impl Default for DefaultTracer {
@@ -296,6 +420,26 @@ impl DefaultTracer {
     pub fn new() -> DefaultTracer {
         DefaultTracer {
             trace_flags: HashSet::new(),
+            session_id: None,
+            session_name: None,
+        }
+    }
+
+    /// Creates the line that is logged for a message, including the session context and the
+    /// current indentation.
+    pub fn format_line(&self, msg: &str) -> String {
+        match &self.session_name {
+            None => format!("{}{}", DefaultTracer::get_prefix(), msg),
+            Some(name) => match self.session_id {
+                None => format!("<{}> {}{}", name, DefaultTracer::get_prefix(), msg),
+                Some(session_id) => format!(
+                    "<{}>#{}> {}{}",
+                    name,
+                    session_id,
+                    DefaultTracer::get_prefix(),
+                    msg
+                ),
+            },
         }
     }
 
@@ -315,6 +459,83 @@ thread_local! {
    static TRACE_PREFIX: RefCell<String> = RefCell::new("".to_string());
 }
 
+/// Callback used by [CallbackTracer].\
+/// Called with the session of the traced FSM, the scope of the message and the message itself.
+pub type TraceCallback = Arc<dyn Fn(SessionId, TraceMode, &str) + Send + Sync>;
+
+/// Tracer that forwards all trace-messages to a callback.\
+/// Can be used by host applications to route traces into their own logging.
+/// Messages that are not created by one of the scoped default methods (e.g. [Tracer::trace_id_vec])
+/// are reported with [TraceMode::ALL].
+pub struct CallbackTracer {
+    pub trace_flags: HashSet<TraceMode>,
+    pub session_id: SessionId,
+    callback: TraceCallback,
+}
+
+impl CallbackTracer {
+    pub fn new(callback: TraceCallback) -> CallbackTracer {
+        CallbackTracer {
+            trace_flags: HashSet::new(),
+            session_id: SessionId(0),
+            callback,
+        }
+    }
+}
+
+impl Debug for CallbackTracer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackTracer")
+            .field("trace_flags", &self.trace_flags)
+            .field("session_id", &self.session_id)
+            .finish()
+    }
+}
+
+impl Tracer for CallbackTracer {
+    fn trace(&self, msg: &str) {
+        (self.callback)(self.session_id, TraceMode::ALL, msg);
+    }
+
+    fn trace_with_mode(&self, mode: TraceMode, msg: &str) {
+        (self.callback)(self.session_id, mode, msg);
+    }
+
+    fn set_session_id(&mut self, session_id: SessionId) {
+        self.session_id = session_id;
+    }
+
+    fn enter(&self) {}
+
+    fn leave(&self) {}
+
+    fn enable_trace(&mut self, flag: TraceMode) {
+        self.trace_flags.insert(flag);
+    }
+
+    fn disable_trace(&mut self, flag: TraceMode) {
+        self.trace_flags.remove(&flag);
+    }
+
+    fn is_trace(&self, flag: TraceMode) -> bool {
+        self.trace_flags.contains(&flag) || self.trace_flags.contains(&TraceMode::ALL)
+    }
+
+    fn trace_mode(&self) -> TraceMode {
+        if self.is_trace(TraceMode::ALL) {
+            TraceMode::ALL
+        } else if self.is_trace(TraceMode::EVENTS) {
+            TraceMode::EVENTS
+        } else if self.is_trace(TraceMode::STATES) {
+            TraceMode::STATES
+        } else if self.is_trace(TraceMode::METHODS) {
+            TraceMode::METHODS
+        } else {
+            TraceMode::NONE
+        }
+    }
+}
+
 pub trait TracerFactory: Send {
     fn create(&mut self) -> Box<dyn Tracer>;
 }
@@ -339,6 +560,143 @@ impl TracerFactory for DefaultTracerFactory {
     }
 }
 
+/// Factory for [CallbackTracer]. All created tracers share the same callback.
+pub struct CallbackTracerFactory {
+    callback: TraceCallback,
+}
+
+impl CallbackTracerFactory {
+    pub fn new(callback: TraceCallback) -> CallbackTracerFactory {
+        CallbackTracerFactory { callback }
+    }
+}
+
+impl TracerFactory for CallbackTracerFactory {
+    fn create(&mut self) -> Box<dyn Tracer> {
+        Box::new(CallbackTracer::new(self.callback.clone()))
+    }
+}
+
+/// A record of [RecordingTracer].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceRecord {
+    /// A state was entered.
+    Enter(String),
+    /// A state was exited.
+    Exit(String),
+    /// An internal or external event was received.
+    Event(String),
+    /// A transition was taken.
+    Transition(TransitionId),
+}
+
+/// Tracer that records entered and exited states, received events and taken transitions, e.g. to assert on
+/// them in tests.\
+/// The records are recorded independent of the trace mode, other messages are ignored.
+/// States are only recorded with feature "Trace_State", events only with "Trace_Event".
+#[derive(Debug)]
+pub struct RecordingTracer {
+    pub trace_flags: HashSet<TraceMode>,
+    records: Arc<Mutex<Vec<TraceRecord>>>,
+}
+
+impl RecordingTracer {
+    /// Creates a tracer that adds the records to the given list.
+    pub fn new(records: Arc<Mutex<Vec<TraceRecord>>>) -> RecordingTracer {
+        RecordingTracer {
+            trace_flags: HashSet::new(),
+            records,
+        }
+    }
+
+    /// Gets a copy of the current records.
+    pub fn records(&self) -> Vec<TraceRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    fn record(&self, record: TraceRecord) {
+        self.records.lock().unwrap().push(record);
+    }
+}
+
+impl Tracer for RecordingTracer {
+    fn trace(&self, _msg: &str) {}
+
+    fn enter(&self) {}
+
+    fn leave(&self) {}
+
+    fn enable_trace(&mut self, flag: TraceMode) {
+        self.trace_flags.insert(flag);
+    }
+
+    fn disable_trace(&mut self, flag: TraceMode) {
+        self.trace_flags.remove(&flag);
+    }
+
+    fn is_trace(&self, flag: TraceMode) -> bool {
+        self.trace_flags.contains(&flag) || self.trace_flags.contains(&TraceMode::ALL)
+    }
+
+    fn event_internal_received(&self, what: &Event) {
+        self.record(TraceRecord::Event(what.name.clone()));
+    }
+
+    fn event_external_received(&mut self, what: &Event) {
+        self.record(TraceRecord::Event(what.name.clone()));
+    }
+
+    fn trace_enter_state(&self, s: &State) {
+        self.record(TraceRecord::Enter(s.name.clone()));
+    }
+
+    fn trace_exit_state(&self, s: &State) {
+        self.record(TraceRecord::Exit(s.name.clone()));
+    }
+
+    fn trace_transition(&self, transition: &Transition) {
+        self.record(TraceRecord::Transition(transition.id));
+    }
+
+    fn trace_mode(&self) -> TraceMode {
+        if self.is_trace(TraceMode::ALL) {
+            TraceMode::ALL
+        } else if self.is_trace(TraceMode::EVENTS) {
+            TraceMode::EVENTS
+        } else if self.is_trace(TraceMode::STATES) {
+            TraceMode::STATES
+        } else if self.is_trace(TraceMode::METHODS) {
+            TraceMode::METHODS
+        } else {
+            TraceMode::NONE
+        }
+    }
+}
+
+/// Factory for [RecordingTracer]. All created tracers add to the same list of records.\
+/// Install it via [set_tracer_factory] to record all FSMs that are created afterwards.
+#[derive(Default)]
+pub struct RecordingTracerFactory {
+    records: Arc<Mutex<Vec<TraceRecord>>>,
+}
+
+impl RecordingTracerFactory {
+    pub fn new() -> RecordingTracerFactory {
+        RecordingTracerFactory::default()
+    }
+
+    /// The records of all created tracers.
+    pub fn records(&self) -> Arc<Mutex<Vec<TraceRecord>>> {
+        self.records.clone()
+    }
+}
+
+impl TracerFactory for RecordingTracerFactory {
+    fn create(&mut self) -> Box<dyn Tracer> {
+        Box::new(RecordingTracer::new(self.records.clone()))
+    }
+}
+
 lazy_static! {
     static ref tracer_factory_arc: Arc<Mutex<Box<dyn TracerFactory>>> =
         Arc::new(Mutex::new(Box::new(DefaultTracerFactory::new())));
@@ -350,3 +708,323 @@ pub fn set_tracer_factory(tracer_factory: Box<dyn TracerFactory>) {
 pub fn create_tracer() -> Box<dyn Tracer> {
     tracer_factory_arc.lock().unwrap().create()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::fsm::Event;
+    use crate::fsm::SessionId;
+    #[cfg(feature = "xml")]
+    use crate::tracer::CallbackTracer;
+    use crate::tracer::{CallbackTracerFactory, DefaultTracer, TraceMode, Tracer, TracerFactory, TRACE_TARGET_EVENTS};
+    #[cfg(feature = "xml")]
+    use std::collections::HashMap;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn callback_tracer_respects_trace_mode() {
+        let calls: Arc<Mutex<Vec<(SessionId, TraceMode, String)>>> = Arc::new(Mutex::new(Vec::new()));
+        let calls_copy = calls.clone();
+        let mut factory = CallbackTracerFactory::new(Arc::new(move |session_id, mode, msg| {
+            calls_copy
+                .lock()
+                .unwrap()
+                .push((session_id, mode, msg.to_string()));
+        }));
+
+        let mut tracer = factory.create();
+        tracer.set_session_id(SessionId(7));
+        tracer.enable_trace(TraceMode::STATES);
+
+        tracer.enter_method("ignored");
+        tracer.trace_result("ignored", &"x");
+
+        let state = crate::fsm::State::new("A");
+        tracer.trace_enter_state(&state);
+        tracer.trace_exit_state(&state);
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            calls[0],
+            (
+                SessionId(7),
+                TraceMode::STATES,
+                format!("Enter <A> #{}", state.id)
+            )
+        );
+        assert_eq!(
+            calls[1],
+            (
+                SessionId(7),
+                TraceMode::STATES,
+                format!("Exit <A> #{}", state.id)
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn callback_tracer_receives_state_notifications() {
+        let calls: Arc<Mutex<Vec<(TraceMode, String)>>> = Arc::new(Mutex::new(Vec::new()));
+        let calls_copy = calls.clone();
+
+        let mut fsm = crate::scxml_reader::parse_from_xml(
+            "<scxml initial='A'><state id='A'><transition target='B'/></state><final id='B'/></scxml>".to_string(),
+        )
+        .unwrap();
+        fsm.tracer = Box::new(CallbackTracer::new(Arc::new(
+            move |_session_id, mode, msg| {
+                calls_copy.lock().unwrap().push((mode, msg.to_string()));
+            },
+        )));
+
+        assert!(crate::test::run_test_manual_with_send(
+            "callback_tracer_receives_state_notifications",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            TraceMode::STATES,
+            2000,
+            &vec!["B".to_string()],
+            |_sender| {},
+        ));
+
+        let calls = calls.lock().unwrap();
+        assert!(calls.iter().all(|(mode, _)| *mode == TraceMode::STATES));
+        let messages: Vec<&str> = calls
+            .iter()
+            .map(|(_, msg)| msg.split(" #").next().unwrap())
+            .collect();
+        assert_eq!(messages, vec!["Enter <A>", "Exit <A>", "Enter <B>"]);
+    }
+
+    /// Records the lines a [DefaultTracer] would log.
+    #[cfg(feature = "xml")]
+    #[derive(Debug)]
+    struct LineRecordingTracer {
+        inner: DefaultTracer,
+        lines: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[cfg(feature = "xml")]
+    impl Tracer for LineRecordingTracer {
+        fn trace(&self, msg: &str) {
+            self.lines.lock().unwrap().push(self.inner.format_line(msg));
+        }
+
+        fn set_session_id(&mut self, session_id: SessionId) {
+            self.inner.set_session_id(session_id);
+        }
+
+        fn set_session_name(&mut self, name: &str) {
+            self.inner.set_session_name(name);
+        }
+
+        fn enter(&self) {}
+
+        fn leave(&self) {}
+
+        fn enable_trace(&mut self, flag: TraceMode) {
+            self.inner.enable_trace(flag);
+        }
+
+        fn disable_trace(&mut self, flag: TraceMode) {
+            self.inner.disable_trace(flag);
+        }
+
+        fn is_trace(&self, flag: TraceMode) -> bool {
+            self.inner.is_trace(flag)
+        }
+
+        fn trace_mode(&self) -> TraceMode {
+            self.inner.trace_mode()
+        }
+    }
+
+    #[test]
+    fn default_tracer_prefixes_session() {
+        let mut tracer = DefaultTracer::new();
+        assert_eq!(tracer.format_line("msg"), "msg");
+        tracer.set_session_id(SessionId(3));
+        assert_eq!(tracer.format_line("msg"), "msg");
+        tracer.set_session_name("Main");
+        assert_eq!(tracer.format_line("msg"), "<Main>#3> msg");
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn named_fsm_traces_contain_name() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+
+        let mut fsm = crate::scxml_reader::parse_from_xml(
+            "<scxml name='Main' initial='A'><state id='A'><transition target='B'/></state><final id='B'/></scxml>"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(fsm.name(), "Main");
+        fsm.tracer = Box::new(LineRecordingTracer {
+            inner: DefaultTracer::new(),
+            lines: lines.clone(),
+        });
+
+        assert!(crate::test::run_test_manual_with_send(
+            "named_fsm_traces_contain_name",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            TraceMode::STATES,
+            2000,
+            &vec!["B".to_string()],
+            |_sender| {},
+        ));
+
+        let lines = lines.lock().unwrap();
+        assert!(!lines.is_empty());
+        assert!(lines.iter().all(|line| line.starts_with("<Main>#")));
+    }
+
+    #[test]
+    #[cfg(feature = "xml")]
+    fn unnamed_fsm_traces_have_no_prefix() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+
+        let mut fsm = crate::scxml_reader::parse_from_xml(
+            "<scxml initial='A'><state id='A'><transition target='B'/></state><final id='B'/></scxml>".to_string(),
+        )
+        .unwrap();
+        fsm.tracer = Box::new(LineRecordingTracer {
+            inner: DefaultTracer::new(),
+            lines: lines.clone(),
+        });
+
+        assert!(crate::test::run_test_manual_with_send(
+            "unnamed_fsm_traces_have_no_prefix",
+            &HashMap::new(),
+            fsm,
+            &Vec::new(),
+            TraceMode::STATES,
+            2000,
+            &vec!["B".to_string()],
+            |_sender| {},
+        ));
+
+        let lines = lines.lock().unwrap();
+        assert!(!lines.is_empty());
+        assert!(lines.iter().all(|line| !line.starts_with('<')));
+    }
+
+    #[test]
+    fn default_tracer_uses_log_targets() {
+        assert_eq!(
+            TraceMode::EVENTS.log_target_and_level(),
+            (TRACE_TARGET_EVENTS, log::Level::Info)
+        );
+        assert_eq!(
+            TraceMode::STATES.log_target_and_level(),
+            (crate::tracer::TRACE_TARGET_STATES, log::Level::Info)
+        );
+        assert_eq!(
+            TraceMode::METHODS.log_target_and_level(),
+            (crate::tracer::TRACE_TARGET_METHODS, log::Level::Debug)
+        );
+        assert_eq!(
+            TraceMode::ARGUMENTS.log_target_and_level(),
+            (crate::tracer::TRACE_TARGET_ARGUMENTS, log::Level::Debug)
+        );
+        assert_eq!(
+            TraceMode::RESULTS.log_target_and_level(),
+            (crate::tracer::TRACE_TARGET_RESULTS, log::Level::Debug)
+        );
+        assert_eq!(
+            TraceMode::ALL.log_target_and_level(),
+            (crate::tracer::TRACE_TARGET_OTHER, log::Level::Info)
+        );
+    }
+
+    #[test]
+    fn trace_mode_accepts_aliases_and_any_case() {
+        assert_eq!(TraceMode::from_str("states"), Ok(TraceMode::STATES));
+        assert_eq!(TraceMode::from_str("State"), Ok(TraceMode::STATES));
+        assert_eq!(TraceMode::from_str("EVENT"), Ok(TraceMode::EVENTS));
+        assert_eq!(TraceMode::from_str("Method"), Ok(TraceMode::METHODS));
+        assert_eq!(TraceMode::from_str("argument"), Ok(TraceMode::ARGUMENTS));
+        assert_eq!(TraceMode::from_str("rESULT"), Ok(TraceMode::RESULTS));
+        assert_eq!(TraceMode::from_str("All"), Ok(TraceMode::ALL));
+        assert_eq!(TraceMode::from_str("stat"), Err(()));
+
+        // Display and FromStr round-trip.
+        for mode in [
+            TraceMode::METHODS,
+            TraceMode::STATES,
+            TraceMode::EVENTS,
+            TraceMode::ARGUMENTS,
+            TraceMode::RESULTS,
+            TraceMode::ALL,
+            TraceMode::NONE,
+        ] {
+            assert_eq!(TraceMode::from_str(&mode.to_string()), Ok(mode));
+        }
+
+        let mut tracer = DefaultTracer::new();
+        tracer.event_external_received(&Event::new_simple("trace.State.On"));
+        assert!(tracer.is_trace(TraceMode::STATES));
+        tracer.event_external_received(&Event::new_simple("trace.state.oFF"));
+        assert!(!tracer.is_trace(TraceMode::STATES));
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "Trace_State",
+        feature = "Trace_Event",
+        feature = "ECMAScriptModel",
+        feature = "xml"
+    ))]
+    fn recording_tracer_records_enter_and_exit_sequence() {
+        use crate::tracer::{RecordingTracerFactory, TraceRecord};
+
+        let mut fsm = crate::scxml_reader::parse_from_xml(
+            r#"<scxml initial='a' datamodel='ecmascript'>
+              <state id='a'>
+                <transition event='go' target='b'/>
+              </state>
+              <state id='b'>
+                <state id='b1'/>
+                <transition event='stop' target='done'/>
+              </state>
+              <final id='done'/>
+            </scxml>"#
+                .to_string(),
+        )
+        .unwrap();
+        let go = *fsm.get_state_by_name(&"a".to_string()).transitions.head();
+        let stop = *fsm.get_state_by_name(&"b".to_string()).transitions.head();
+        let mut factory = RecordingTracerFactory::new();
+        fsm.tracer = factory.create();
+
+        crate::fsm::run_blocking(
+            fsm,
+            vec![Event::new_simple("go"), Event::new_simple("stop")],
+        );
+
+        let records = factory.records().lock().unwrap().clone();
+        let enter = |name: &str| TraceRecord::Enter(name.to_string());
+        let exit = |name: &str| TraceRecord::Exit(name.to_string());
+        assert_eq!(
+            records,
+            vec![
+                enter("a"),
+                TraceRecord::Event("go".to_string()),
+                exit("a"),
+                TraceRecord::Transition(go),
+                enter("b"),
+                enter("b1"),
+                TraceRecord::Event("stop".to_string()),
+                exit("b1"),
+                exit("b"),
+                TraceRecord::Transition(stop),
+                enter("done"),
+            ]
+        );
+    }
+}
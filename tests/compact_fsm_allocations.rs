@@ -0,0 +1,69 @@
+//! Compares the heap allocations of a compact FSM template with those of a parsed FSM.\
+//! The allocations are counted by a global allocator, so this file contains exactly one test
+//! and no other test can allocate concurrently.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rufsm::fsm_builder::FsmBuilder;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static DEALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        DEALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Number of allocations that are not freed yet.
+fn live_allocations() -> usize {
+    ALLOCATIONS.load(Ordering::SeqCst) - DEALLOCATIONS.load(Ordering::SeqCst)
+}
+
+/// Number of allocations that are held by the result of `f`.
+fn allocations_of<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = live_allocations();
+    let value = f();
+    let after = live_allocations();
+    (value, after - before)
+}
+
+#[test]
+fn compact_fsm_needs_less_allocations() {
+    const STATES: usize = 200;
+
+    let mut builder = FsmBuilder::new().name("allocations").datamodel("null");
+    for s in 0..STATES {
+        let next = (s + 1) % STATES;
+        builder = builder
+            .state(format!("s{}", s).as_str())
+            .transition("next", format!("s{}", next).as_str())
+            .transition("skip error.*", format!("s{}", (s + 2) % STATES).as_str())
+            .end();
+    }
+    let fsm = builder.build().unwrap();
+
+    // The executable content is shared by both, so it is not counted.
+    let (parsed, parsed_allocations) = allocations_of(|| fsm.instantiate());
+    let (compact, compact_allocations) = allocations_of(|| fsm.compile());
+
+    assert_eq!(compact.state_count(), parsed.states.len());
+    assert!(
+        compact_allocations * 4 < parsed_allocations,
+        "compact {} vs parsed {}",
+        compact_allocations,
+        parsed_allocations
+    );
+}
@@ -0,0 +1,131 @@
+//! Runs a trivial chart end to end with the features the test is built with.\
+//! The CI builds and runs these tests for several feature sets, to detect code that only
+//! compiles with some combination of features:
+//! ```text
+//! cargo test --no-default-features --test feature_matrix
+//! cargo test --no-default-features --features RfsmExpressionModel,serializer --test feature_matrix
+//! cargo test --all-features --test feature_matrix
+//! ```
+
+use std::collections::HashMap;
+
+use rufsm::fsm::{Event, Fsm};
+use rufsm::fsm_builder::FsmBuilder;
+use rufsm::test::run_test_manual_with_send;
+#[cfg(feature = "Trace")]
+use rufsm::tracer::TraceMode;
+
+fn run(test_name: &str, fsm: Box<Fsm>, events: &[&str], expected_final_configuration: &str) -> bool {
+    run_test_manual_with_send(
+        test_name,
+        &HashMap::new(),
+        fsm,
+        &Vec::new(),
+        #[cfg(feature = "Trace")]
+        TraceMode::STATES,
+        2000,
+        &vec![expected_final_configuration.to_string()],
+        |sender| {
+            for name in events {
+                let _ = sender.send(Box::new(Event::new_simple(name)));
+            }
+        },
+    )
+}
+
+/// Needs no feature at all.
+#[test]
+fn null_datamodel_chart_runs() {
+    let fsm = FsmBuilder::new()
+        .datamodel("null")
+        .state("idle")
+        .transition("start", "busy")
+        .end()
+        .state("busy")
+        .transition("stop", "done")
+        .end()
+        .final_state("done")
+        .end()
+        .build()
+        .unwrap();
+
+    assert!(run(
+        "null_datamodel_chart_runs",
+        fsm,
+        &["start", "stop"],
+        "done"
+    ));
+}
+
+#[cfg(feature = "RfsmExpressionModel")]
+#[test]
+fn expression_datamodel_chart_runs() {
+    let fsm = FsmBuilder::new()
+        .datamodel("rfsm-expression")
+        .data("count", "2")
+        .state("idle")
+        .transition("start", "done")
+        .cond("count == 2")
+        .transition("start", "failed")
+        .end()
+        .final_state("done")
+        .end()
+        .final_state("failed")
+        .end()
+        .build()
+        .unwrap();
+
+    assert!(run(
+        "expression_datamodel_chart_runs",
+        fsm,
+        &["start"],
+        "done"
+    ));
+}
+
+/// Reads a document, writes it in the binary format and runs the FSM that is read back.
+#[cfg(all(
+    feature = "ECMAScriptModel",
+    feature = "RfsmExpressionModel",
+    feature = "xml",
+    feature = "serializer",
+    feature = "Trace"
+))]
+#[test]
+fn full_feature_chart_runs() {
+    use rufsm::scxml_reader;
+    use rufsm::serializer::default_protocol_reader::DefaultProtocolReader;
+    use rufsm::serializer::default_protocol_writer::DefaultProtocolWriter;
+    use rufsm::serializer::fsm_reader::FsmReader;
+    use rufsm::serializer::fsm_writer::FsmWriter;
+
+    let fsm = scxml_reader::parse_from_xml(
+        r#"<scxml initial='idle' datamodel='ecmascript'>
+          <datamodel><data id='count' expr='0'/></datamodel>
+          <state id='idle'>
+            <transition event='tick'><assign location='count' expr='count + 1'/></transition>
+            <transition event='stop' cond='count === 2' target='done'/>
+            <transition event='stop' target='failed'/>
+          </state>
+          <final id='done'/>
+          <final id='failed'/>
+        </scxml>"#
+            .to_string(),
+    )
+    .unwrap();
+
+    let mut writer: FsmWriter<Vec<u8>> = FsmWriter::new(Box::new(DefaultProtocolWriter::new(Vec::new())));
+    writer.write(&fsm);
+    writer.close();
+    let buffer = writer.get_writer().clone();
+    let fsm = FsmReader::new(Box::new(DefaultProtocolReader::new(buffer.as_slice())))
+        .read()
+        .unwrap();
+
+    assert!(run(
+        "full_feature_chart_runs",
+        fsm,
+        &["tick", "tick", "stop"],
+        "done"
+    ));
+}
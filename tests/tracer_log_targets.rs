@@ -0,0 +1,59 @@
+//! Routes the traces of a [DefaultTracer] through a logger that only accepts the event target,
+//! as "RUST_LOG=scxml::event=debug" would do.\
+//! The logger is global, so this file contains exactly one test.
+#![cfg(feature = "EnvLog")]
+
+use std::sync::Mutex;
+
+use rufsm::fsm::{Event, State};
+use rufsm::tracer::{DefaultTracer, TraceMode, Tracer, TRACE_TARGET_EVENTS};
+
+struct EventTargetLogger {
+    records: Mutex<Vec<(String, log::Level, String)>>,
+}
+
+impl log::Log for EventTargetLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target() == TRACE_TARGET_EVENTS && metadata.level() <= log::Level::Debug
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            self.records.lock().unwrap().push((
+                record.target().to_string(),
+                record.level(),
+                record.args().to_string(),
+            ));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: EventTargetLogger = EventTargetLogger {
+    records: Mutex::new(Vec::new()),
+};
+
+#[test]
+fn default_tracer_traces_can_be_filtered_by_target() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let mut tracer = DefaultTracer::new();
+    tracer.enable_trace(TraceMode::ALL);
+    tracer.enter_method("MethodOfLogTargetTest");
+    tracer.trace_enter_state(&State::new("StateOfLogTargetTest"));
+    tracer.event_internal_send(&Event::new_simple("EventOfLogTargetTest"));
+
+    let records = LOGGER.records.lock().unwrap();
+    assert!(!records.is_empty());
+    assert!(records
+        .iter()
+        .all(|(target, level, _)| target == TRACE_TARGET_EVENTS && *level == log::Level::Info));
+    assert!(records
+        .iter()
+        .any(|(_, _, msg)| msg.contains("EventOfLogTargetTest")));
+    assert!(!records
+        .iter()
+        .any(|(_, _, msg)| msg.contains("StateOfLogTargetTest") || msg.contains("MethodOfLogTargetTest")));
+}